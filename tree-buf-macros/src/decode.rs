@@ -1,5 +1,5 @@
 use {
-    crate::utils::{canonical_ident, get_named_fields, NamedField},
+    crate::utils::{canonical_name, enum_schema, get_named_fields, has_attribute, integer_variants, struct_schema, NamedField},
     proc_macro2::TokenStream,
     quote::ToTokens,
     syn::{Data, DataEnum, DataStruct, DeriveInput, Fields, FieldsUnnamed},
@@ -8,6 +8,7 @@ use {
 pub fn impl_decode_macro(ast: &DeriveInput) -> TokenStream {
     match &ast.data {
         Data::Struct(data_struct) => impl_struct_decode(ast, data_struct),
+        Data::Enum(data_enum) if has_attribute(&ast.attrs, "integer") => impl_integer_enum_decode(ast, data_enum),
         Data::Enum(data_enum) => impl_enum_decode(ast, data_enum),
         Data::Union(_) => panic!("Unions are not supported by tree-buf"),
     }
@@ -16,15 +17,35 @@ pub fn impl_decode_macro(ast: &DeriveInput) -> TokenStream {
 fn impl_struct_decode(ast: &DeriveInput, data_struct: &DataStruct) -> TokenStream {
     let fields = get_named_fields(data_struct);
     let name = &ast.ident;
+    let array_decoder_name = format_ident!("{}TreeBufDecoderArray", name);
 
-    let inits = fields
+    // A flattened field is decoded from the same fields as the struct containing it, so
+    // it is decoded here rather than in parallel with the others.
+    let root_inits = fields
         .iter()
-        .map(|NamedField { ident, canon_str, .. }| {
-            quote! {
-                let #ident = fields.remove(#canon_str).unwrap_or_default();
+        .map(|NamedField { ident, canon_str, ty, flatten }| {
+            if *flatten {
+                quote! {
+                    let #ident = <#ty as ::tree_buf::internal::DecodableFields>::decode_root_fields(fields, options);
+                }
+            } else {
+                quote! {
+                    let #ident = fields.remove(#canon_str).unwrap_or_default();
+                }
             }
         })
         .collect::<Vec<_>>();
+    let array_inits = fields.iter().map(|NamedField { ident, canon_str, ty, flatten }| {
+        if *flatten {
+            quote! {
+                let #ident = <#ty as ::tree_buf::internal::DecodableFields>::new_array_fields(fields, options);
+            }
+        } else {
+            quote! {
+                let #ident = fields.remove(#canon_str).unwrap_or_default();
+            }
+        }
+    });
     let unwraps = fields
         .iter()
         .map(|NamedField { ident, .. }| {
@@ -34,12 +55,39 @@ fn impl_struct_decode(ast: &DeriveInput, data_struct: &DataStruct) -> TokenStrea
         })
         .collect::<Vec<_>>();
 
+    let into_fields = fields
+        .iter()
+        .map(|NamedField { ident, .. }| {
+            let into_ident = format_ident!("tree_buf_into_{}", ident);
+            quote! {
+                #ident: #into_ident,
+            }
+        })
+        .collect::<Vec<_>>();
+    let checks = fields
+        .iter()
+        .map(|NamedField { ident, flatten, .. }| {
+            if *flatten {
+                let into_ident = format_ident!("tree_buf_into_{}", ident);
+                quote! {
+                    *#into_ident = #ident?;
+                }
+            } else {
+                quote! {
+                    #ident?;
+                }
+            }
+        })
+        .collect::<Vec<_>>();
+
     let mut parallel_lhs = quote! {};
     let mut decodes_parallel_rhs = quote! {};
+    let mut decode_intos_parallel_rhs = quote! {};
     let mut news_parallel_rhs = quote! {};
     let mut is_first = true;
 
-    for NamedField { ident, ty, .. } in fields.iter() {
+    for NamedField { ident, ty, .. } in fields.iter().filter(|field| !field.flatten) {
+        let into_ident = format_ident!("tree_buf_into_{}", ident);
         if is_first {
             is_first = false;
             parallel_lhs = quote! { #ident };
@@ -49,6 +97,13 @@ fn impl_struct_decode(ast: &DeriveInput, data_struct: &DataStruct) -> TokenStrea
                     options,
                 )
             };
+            decode_intos_parallel_rhs = quote! {
+                <#ty as ::tree_buf::internal::Decodable>::decode_into(
+                    #ident,
+                    options,
+                    #into_ident,
+                )
+            };
             news_parallel_rhs = quote! {
                 ::tree_buf::internal::DecoderArray::new(#ident, options)
             };
@@ -64,6 +119,17 @@ fn impl_struct_decode(ast: &DeriveInput, data_struct: &DataStruct) -> TokenStrea
                     options
                 )
             };
+            decode_intos_parallel_rhs = quote! {
+                ::tree_buf::internal::parallel(
+                    || <#ty as ::tree_buf::internal::Decodable>::decode_into(
+                        #ident,
+                        options,
+                        #into_ident,
+                    ),
+                    || #decode_intos_parallel_rhs,
+                    options
+                )
+            };
             news_parallel_rhs = quote! {
                 ::tree_buf::internal::parallel(
                     || ::tree_buf::internal::DecoderArray::new(#ident, options),
@@ -87,33 +153,84 @@ fn impl_struct_decode(ast: &DeriveInput, data_struct: &DataStruct) -> TokenStrea
         }
     });
 
+    let decode_next_intos = fields.iter().map(|NamedField { ident, .. }| {
+        quote! {
+            // Overly verbose because of `?` requiring `From` See also ec4fa3ba-def5-44eb-9065-e80b59530af6
+            if let Err(e) = self.#ident.decode_next_into(&mut into.#ident) { return Err(e.into()); }
+        }
+    });
+
+    // When every field is flattened, there is nothing to decode in parallel.
+    let (decodes_parallel, decode_intos_parallel, news_parallel) = if parallel_lhs.is_empty() {
+        (quote! {}, quote! {}, quote! {})
+    } else {
+        (
+            quote! { let #parallel_lhs = #decodes_parallel_rhs; },
+            quote! { let #parallel_lhs = #decode_intos_parallel_rhs; },
+            quote! { let #parallel_lhs = #news_parallel_rhs; },
+        )
+    };
+
     let decode = quote! {
         let mut fields = match sticks {
             ::tree_buf::internal::DynRootBranch::Object { fields } => fields,
             _ => return Err(::tree_buf::DecodeError::SchemaMismatch),
         };
+        <Self as ::tree_buf::internal::DecodableFields>::decode_root_fields(&mut fields, options)
+    };
+    let decode_into = quote! {
+        fn decode_into(sticks: ::tree_buf::internal::DynRootBranch<'_>, options: &impl ::tree_buf::experimental::options::DecodeOptions, into: &mut Self) -> Result<(), ::tree_buf::DecodeError> {
+            let mut fields = match sticks {
+                ::tree_buf::internal::DynRootBranch::Object { fields } => fields,
+                _ => return Err(::tree_buf::DecodeError::SchemaMismatch),
+            };
+            let fields = &mut fields;
 
-        #(#inits)*
+            #(#root_inits)*
 
-        let #parallel_lhs = #decodes_parallel_rhs;
+            // Borrow each field separately so that they can be decoded in parallel.
+            let Self { #(#into_fields)* } = into;
 
-        Ok(Self {
-            #(#unwraps)*
-        })
+            #decode_intos_parallel
+
+            #(#checks)*
+            Ok(())
+        }
     };
     let new = quote! {
         let mut fields = match sticks {
             ::tree_buf::internal::DynArrayBranch::Object { fields } => fields,
             _ => return Err(::tree_buf::DecodeError::SchemaMismatch),
         };
+        <#name as ::tree_buf::internal::DecodableFields>::new_array_fields(&mut fields, options)
+    };
+    let decode_fields = quote! {
+        impl ::tree_buf::internal::DecodableFields for #name {
+            fn decode_root_fields<'a>(
+                fields: &mut ::std::collections::HashMap<::tree_buf::internal::Ident<'a>, ::tree_buf::internal::DynRootBranch<'a>>,
+                options: &impl ::tree_buf::experimental::options::DecodeOptions,
+            ) -> Result<Self, ::tree_buf::DecodeError> {
+                #(#root_inits)*
+
+                #decodes_parallel
+
+                Ok(Self {
+                    #(#unwraps)*
+                })
+            }
+            fn new_array_fields<'a>(
+                fields: &mut ::std::collections::HashMap<::tree_buf::internal::Ident<'a>, ::tree_buf::internal::DynArrayBranch<'a>>,
+                options: &impl ::tree_buf::experimental::options::DecodeOptions,
+            ) -> Result<Self::DecoderArray, ::tree_buf::DecodeError> {
+                #(#array_inits)*
 
-        #(#inits)*
-
-        let #parallel_lhs = #news_parallel_rhs;
+                #news_parallel
 
-        Ok(Self {
-            #(#unwraps)*
-        })
+                Ok(#array_decoder_name {
+                    #(#unwraps)*
+                })
+            }
+        }
     };
 
     let decode_next = quote! {
@@ -122,15 +239,36 @@ fn impl_struct_decode(ast: &DeriveInput, data_struct: &DataStruct) -> TokenStrea
         })
     };
 
-    fill_decode_skeleton(ast, decode, array_fields, new, decode_next)
+    let decode_next_into = quote! {
+        fn decode_next_into(&mut self, into: &mut Self::Decode) -> ::std::result::Result<(), Self::Error> {
+            #(#decode_next_intos)*
+            Ok(())
+        }
+    };
+
+    let schema = struct_schema(ast, &fields, quote! { ::tree_buf::internal::Decodable }, format_ident!("decode_schema"));
+    let root_methods = quote! {
+        #decode_into
+        fn decode_schema() -> ::tree_buf::experimental::compat::TypeSchema {
+            #schema
+        }
+    };
+
+    let mut result = fill_decode_skeleton(ast, decode, root_methods, array_fields, new, decode_next, decode_next_into);
+    result.extend(decode_fields);
+    result
 }
 
+/// root_methods and decode_next_into are whole methods. root_methods has decode_schema, and may
+/// have decode_into. decode_next_into may be empty to use the default.
 fn fill_decode_skeleton<A: ToTokens>(
     ast: &DeriveInput,
     decode: impl ToTokens,
+    root_methods: impl ToTokens,
     array_fields: impl Iterator<Item = A>,
     new: impl ToTokens,
     decode_next: impl ToTokens,
+    decode_next_into: impl ToTokens,
 ) -> TokenStream {
     let name = &ast.ident;
     let vis = &ast.vis;
@@ -145,6 +283,7 @@ fn fill_decode_skeleton<A: ToTokens>(
                 //let _profile_guard = ::tree_buf::internal::firestorm::start_guard(::tree_buf::internal::firestorm::FmtStr::Str3(::std::any::type_name::<Self>(), "::", "decode"));
                 #decode
             }
+            #root_methods
         }
 
         #[allow(non_snake_case)]
@@ -165,6 +304,7 @@ fn fill_decode_skeleton<A: ToTokens>(
             fn decode_next(&mut self) -> ::std::result::Result<Self::Decode, Self::Error> {
                 #decode_next
             }
+            #decode_next_into
         }
     }
 }
@@ -184,10 +324,18 @@ fn impl_enum_decode(ast: &DeriveInput, data_enum: &DataEnum) -> TokenStream {
     let mut new_parallel_rhs = quote! { ::tree_buf::internal::DecoderArray::new(tree_buf_discriminant, options) };
 
     let mut root_matches = Vec::new();
+    let mut other = None;
 
     for variant in data_enum.variants.iter() {
         let variant_ident = &variant.ident;
-        let discriminant = canonical_ident(variant_ident);
+        let discriminant = canonical_name(variant_ident, &variant.attrs);
+
+        if has_attribute(&variant.attrs, "other") {
+            if other.is_some() {
+                panic!("Only one variant may be #[tree_buf(other)]");
+            }
+            other = Some(variant);
+        }
 
         match &variant.fields {
             Fields::Unit => {
@@ -271,6 +419,55 @@ fn impl_enum_decode(ast: &DeriveInput, data_enum: &DataEnum) -> TokenStream {
         }
     }
 
+    // The other variant decodes as itself when it was written, and also stands in
+    // for any variant in the file which this enum doesn't have.
+    let (root_other, new_other, decode_next_other) = match other.map(|variant| (&variant.ident, &variant.fields)) {
+        None => (
+            quote! { _ => { return Err(::tree_buf::DecodeError::SchemaMismatch); }, },
+            quote! { _ => { return Err(::tree_buf::DecodeError::SchemaMismatch); } },
+            quote! {},
+        ),
+        Some((variant_ident, Fields::Unit)) => {
+            array_fields.push(quote! {
+                tree_buf_other: Vec<u64>
+            });
+            new_inits.push(quote! {
+                let mut tree_buf_other = Vec::new();
+            });
+            new_unpacks.push(quote! { tree_buf_other, });
+            (
+                quote! { _ => Self::#variant_ident, },
+                quote! { _ => { tree_buf_other.push(index as u64); } },
+                quote! {
+                    if self.tree_buf_other.contains(&discriminant) {
+                        return Ok(#ident::#variant_ident);
+                    }
+                },
+            )
+        }
+        Some((variant_ident, Fields::Unnamed(FieldsUnnamed { unnamed, .. }))) if unnamed.len() == 1 => {
+            array_fields.push(quote! {
+                tree_buf_other: Vec<(u64, ::std::string::String)>
+            });
+            new_inits.push(quote! {
+                let mut tree_buf_other = Vec::new();
+            });
+            new_unpacks.push(quote! { tree_buf_other, });
+            (
+                quote! { unknown => Self::#variant_ident(unknown.to_owned()), },
+                quote! { unknown => { tree_buf_other.push((index as u64, unknown.to_owned())); } },
+                quote! {
+                    for (d, unknown) in self.tree_buf_other.iter() {
+                        if *d == discriminant {
+                            return Ok(#ident::#variant_ident(unknown.clone()));
+                        }
+                    }
+                },
+            )
+        }
+        Some(_) => panic!("The #[tree_buf(other)] variant must be a unit variant, or capture the unknown variant's name as a String"),
+    };
+
     let decode = quote! {
         // If this is an enum,
         if let ::tree_buf::internal::DynRootBranch::Enum { discriminant, value } = sticks {
@@ -279,7 +476,7 @@ fn impl_enum_decode(ast: &DeriveInput, data_enum: &DataEnum) -> TokenStream {
                 // matches the expected data.
                 match discriminant {
                     #(#root_matches)*
-                    _ => { return Err(::tree_buf::DecodeError::SchemaMismatch); },
+                    #root_other
                 }
             )
         } else {
@@ -298,7 +495,7 @@ fn impl_enum_decode(ast: &DeriveInput, data_enum: &DataEnum) -> TokenStream {
                     let ::tree_buf::internal::ArrayEnumVariant { ident, data } = variant;
                     match ident {
                         #(#new_matches),*
-                        _ => { return Err(::tree_buf::DecodeError::SchemaMismatch); }
+                        #new_other
                     }
                 }
 
@@ -309,8 +506,8 @@ fn impl_enum_decode(ast: &DeriveInput, data_enum: &DataEnum) -> TokenStream {
                     #(#new_unpacks)*
                 };
 
-                // FIXME: Need to verify that the range of tree_buf_discriminant does
-                // not go beyond the number of variants listed (this would indicate a corrupt file)
+                // The range of tree_buf_discriminant is not verified here. Instead,
+                // decode_next returns an error for a discriminant that matches no variant.
                 // See also: fb0a3c86-23be-4d4a-9dbf-9c83ae6e2f0f
                 Ok(result)
             }
@@ -323,10 +520,69 @@ fn impl_enum_decode(ast: &DeriveInput, data_enum: &DataEnum) -> TokenStream {
     let decode_next = quote! {
         let discriminant = ::tree_buf::internal::InfallibleDecoderArray::decode_next_infallible(&mut self.tree_buf_discriminant);
         #(#decode_nexts)*
+        #decode_next_other
 
+        // The discriminant is not one of the variants in the file, so the file is corrupt.
         // See also: fb0a3c86-23be-4d4a-9dbf-9c83ae6e2f0f
-        todo!("Make this unreachable by verifying range");
+        Err(::tree_buf::DecodeError::InvalidFormat)
+    };
+
+    let schema = enum_schema(ast, data_enum, quote! { ::tree_buf::internal::Decodable }, format_ident!("decode_schema"));
+    let root_methods = quote! {
+        fn decode_schema() -> ::tree_buf::experimental::compat::TypeSchema {
+            #schema
+        }
+    };
+
+    // Enums use the default decode_into, since a variant's fields can't be re-used by another variant.
+    fill_decode_skeleton(ast, decode, root_methods, array_fields.iter(), new, decode_next, quote! {})
+}
+
+/// Reads each value from its discriminant, in a column of integers
+fn impl_integer_enum_decode(ast: &DeriveInput, data_enum: &DataEnum) -> TokenStream {
+    let name = &ast.ident;
+    let variants = integer_variants(data_enum);
+    let other = data_enum.variants.iter().find(|variant| has_attribute(&variant.attrs, "other"));
+    let matches = variants.iter().map(|variant| {
+        quote! {
+            if value == #name::#variant as u64 {
+                return Ok(#name::#variant);
+            }
+        }
+    });
+    let fallback = match other {
+        Some(variant) => {
+            let variant = &variant.ident;
+            quote! { Ok(#name::#variant) }
+        }
+        None => quote! { Err(::tree_buf::DecodeError::SchemaMismatch) },
+    };
+    let from_discriminant = quote! {
+        #(#matches)*
+        #fallback
+    };
+
+    let decode = quote! {
+        let value: u64 = ::tree_buf::internal::Decodable::decode(sticks, options)?;
+        #from_discriminant
+    };
+    let array_fields = std::iter::once(quote! {
+        tree_buf_values: <u64 as ::tree_buf::internal::Decodable>::DecoderArray
+    });
+    let new = quote! {
+        Ok(Self {
+            tree_buf_values: ::tree_buf::internal::DecoderArray::new(sticks, options)?,
+        })
+    };
+    let decode_next = quote! {
+        let value = ::tree_buf::internal::InfallibleDecoderArray::decode_next_infallible(&mut self.tree_buf_values);
+        #from_discriminant
+    };
+    let root_methods = quote! {
+        fn decode_schema() -> ::tree_buf::experimental::compat::TypeSchema {
+            ::tree_buf::experimental::compat::TypeSchema::Integer(64)
+        }
     };
 
-    fill_decode_skeleton(ast, decode, array_fields.iter(), new, decode_next)
+    fill_decode_skeleton(ast, decode, root_methods, array_fields, new, decode_next, quote! {})
 }
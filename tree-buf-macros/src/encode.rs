@@ -1,6 +1,6 @@
 use {
-    crate::utils::{canonical_ident, get_named_fields, NamedField},
-    proc_macro2::{Ident, Span, TokenStream},
+    crate::utils::{canonical_name, enum_schema, get_named_fields, has_attribute, integer_variants, struct_schema, NamedField},
+    proc_macro2::TokenStream,
     quote::ToTokens,
     syn::{Data, DataEnum, DataStruct, DeriveInput, Fields, FieldsUnnamed},
 };
@@ -8,6 +8,7 @@ use {
 pub fn impl_encode_macro(ast: &DeriveInput) -> TokenStream {
     match &ast.data {
         Data::Struct(data_struct) => impl_struct_encode(ast, data_struct),
+        Data::Enum(data_enum) if has_attribute(&ast.attrs, "integer") => impl_integer_enum_encode(ast, data_enum),
         Data::Enum(data_enum) => impl_enum_encode(ast, data_enum),
         Data::Union(_) => panic!("Unions are not supported by tree-buf"),
     }
@@ -15,11 +16,19 @@ pub fn impl_encode_macro(ast: &DeriveInput) -> TokenStream {
 
 fn impl_struct_encode(ast: &DeriveInput, data_struct: &DataStruct) -> TokenStream {
     let fields = get_named_fields(data_struct);
+    let name = &ast.ident;
+    let array_encoder_name = format_ident!("{}TreeBufEncoderArray", name);
 
-    let encoders = fields.iter().map(|NamedField { ident, canon_str, .. }| {
-        quote! {
-            ::tree_buf::internal::encode_ident(#canon_str, stream);
-            stream.encode_with_id(|stream| self.#ident.encode_root(stream));
+    let encoders = fields.iter().map(|NamedField { ident, canon_str, flatten, .. }| {
+        if *flatten {
+            quote! {
+                ::tree_buf::internal::EncodableFields::encode_root_fields(&self.#ident, stream);
+            }
+        } else {
+            quote! {
+                ::tree_buf::internal::encode_ident(#canon_str, stream);
+                stream.encode_field(#canon_str, |stream| stream.encode_with_id(|stream| ::tree_buf::internal::Encodable::encode_root(&self.#ident, stream)));
+            }
         }
     });
 
@@ -35,31 +44,84 @@ fn impl_struct_encode(ast: &DeriveInput, data_struct: &DataStruct) -> TokenStrea
         }
     });
 
-    let flushers = fields.iter().map(|NamedField { ident, canon_str, ty }| {
-        quote! {
-            ::tree_buf::internal::encode_ident(#canon_str, stream);
-            let o = self.#ident;
-            stream.encode_with_id(|stream| ::tree_buf::internal::EncoderArray::<#ty>::flush(o, stream));
+    let field_bindings: Vec<_> = fields
+        .iter()
+        .map(|NamedField { ident, .. }| {
+            let binding = format_ident!("tree_buf_{}", ident);
+            quote! {
+                #ident: #binding,
+            }
+        })
+        .collect();
+
+    let field_flushes: Vec<_> = fields
+        .iter()
+        .map(|NamedField { ident, canon_str, ty, .. }| {
+            let binding = format_ident!("tree_buf_{}", ident);
+            quote! {
+                ::tree_buf::internal::encode_ident(#canon_str, stream);
+                stream.encode_field(#canon_str, |stream| stream.encode_with_id(|stream| ::tree_buf::internal::EncoderArray::<#ty>::flush(#binding, stream)));
+            }
+        })
+        .collect();
+
+    let sequential_flushers = fields.iter().zip(field_flushes.iter()).map(|(NamedField { ident, ty, flatten, .. }, field_flush)| {
+        let binding = format_ident!("tree_buf_{}", ident);
+        if *flatten {
+            quote! {
+                <#ty as ::tree_buf::internal::EncodableFields>::flush_fields_in_order(#binding, stream);
+            }
+        } else {
+            quote! { #field_flush }
         }
     });
 
-    let num_fields = fields.len();
-
-    // See also: fadaec14-35ad-4dc1-b6dc-6106ab811669
-    let (prefix, suffix) = match num_fields {
-        0..=8 => (quote! {}, Ident::new(format!("Obj{}", num_fields).as_str(), Span::call_site())),
-        _ => (
+    // The fields are independent, so they may be flushed in parallel. See also encode_fields
+    let flushers = fields.iter().zip(field_flushes.iter()).map(|(NamedField { ident, ty, flatten, .. }, field_flush)| {
+        let binding = format_ident!("tree_buf_{}", ident);
+        if *flatten {
             quote! {
-                ::tree_buf::internal::encodings::varint::encode_prefix_varint(#num_fields as u64 - 9, stream.bytes);
-            },
-            Ident::new("ObjN", Span::call_site()),
-        ),
+                fields.extend(<#ty as ::tree_buf::internal::EncodableFields>::flush_fields(#binding));
+            }
+        } else {
+            quote! {
+                fields.push(Box::new(move |stream: &mut ::tree_buf::internal::EncoderStream<'_, O>| {
+                    #field_flush
+                }));
+            }
+        }
+    });
+
+    let plain_names = fields.iter().filter(|field| !field.flatten).map(|field| field.canon_str.as_str());
+    let flattened_names = fields.iter().filter(|field| field.flatten).map(|NamedField { ty, .. }| {
+        quote! { &<#ty as ::tree_buf::internal::EncodableFields>::FIELD_NAMES }
+    });
+
+    // Names of plain fields are checked by get_named_fields, so only flattening may repeat one.
+    // The check is a constant, so that a clash is a compile error.
+    let check_names = if fields.iter().any(|field| field.flatten) {
+        quote! {
+            const _: () = ::tree_buf::internal::check_field_names(&<#name as ::tree_buf::internal::EncodableFields>::FIELD_NAMES);
+        }
+    } else {
+        quote! {}
     };
 
+    let num_plain_fields = fields.iter().filter(|field| !field.flatten).count();
+    let flattened_counts = fields.iter().filter(|field| field.flatten).map(|NamedField { ty, .. }| {
+        quote! { + <#ty as ::tree_buf::internal::EncodableFields>::FIELD_COUNT }
+    });
+
     let flush = quote! {
-        #prefix
-        #(#flushers)*
-        ::tree_buf::internal::ArrayTypeId::#suffix
+        let num_fields = <#name as ::tree_buf::internal::EncodableFields>::FIELD_COUNT;
+        let id = ::tree_buf::internal::encode_array_object(num_fields, stream);
+        // Boxing each field is only worth it when they may be flushed in parallel
+        if stream.options.parallel() {
+            ::tree_buf::internal::encode_fields(stream, <#name as ::tree_buf::internal::EncodableFields>::flush_fields(self));
+        } else {
+            <#name as ::tree_buf::internal::EncodableFields>::flush_fields_in_order(self, stream);
+        }
+        id
     };
 
     let buffer = quote! {
@@ -67,12 +129,68 @@ fn impl_struct_encode(ast: &DeriveInput, data_struct: &DataStruct) -> TokenStrea
     };
 
     let encode_root = quote! {
-        #prefix
-        #(#encoders)*
-        ::tree_buf::internal::RootTypeId::#suffix
+        let id = ::tree_buf::internal::encode_root_object(<Self as ::tree_buf::internal::EncodableFields>::FIELD_COUNT, stream);
+        ::tree_buf::internal::EncodableFields::encode_root_fields(self, stream);
+        id
     };
 
-    fill_encode_skeleton(ast, array_fields, buffer, flush, encode_root)
+    let schema = struct_schema(ast, &fields, quote! { ::tree_buf::internal::Encodable }, format_ident!("encode_schema"));
+
+    let mut result = fill_encode_skeleton(ast, array_fields, buffer, flush, encode_root, schema);
+    result.extend(quote! {
+        impl ::tree_buf::internal::EncodableFields for #name {
+            const FIELD_COUNT: usize = #num_plain_fields #(#flattened_counts)*;
+            const FIELD_NAMES: ::tree_buf::internal::FieldNames = ::tree_buf::internal::FieldNames {
+                names: &[#(#plain_names),*],
+                flattened: &[#(#flattened_names),*],
+            };
+            fn encode_root_fields<O: ::tree_buf::experimental::options::EncodeOptions>(&self, stream: &mut ::tree_buf::internal::EncoderStream<'_, O>) {
+                #(#encoders)*
+            }
+            fn flush_fields_in_order<O: ::tree_buf::experimental::options::EncodeOptions>(array: #array_encoder_name, stream: &mut ::tree_buf::internal::EncoderStream<'_, O>) {
+                let #array_encoder_name { #(#field_bindings)* } = array;
+                #(#sequential_flushers)*
+            }
+            fn flush_fields<'a, O: ::tree_buf::experimental::options::EncodeOptions>(array: #array_encoder_name) -> Vec<::tree_buf::internal::FieldEncoder<'a, O>> {
+                let #array_encoder_name { #(#field_bindings)* } = array;
+                let mut fields: Vec<::tree_buf::internal::FieldEncoder<'a, O>> = Vec::new();
+                #(#flushers)*
+                fields
+            }
+        }
+        #check_names
+    });
+    result
+}
+
+/// Writes each value as its discriminant, in a column of u64
+fn impl_integer_enum_encode(ast: &DeriveInput, data_enum: &DataEnum) -> TokenStream {
+    let name = &ast.ident;
+    let discriminants = integer_variants(data_enum).into_iter().map(|variant| {
+        quote! {
+            #name::#variant => #name::#variant as u64,
+        }
+    });
+    let discriminant = quote! {
+        match value {
+            #(#discriminants)*
+        }
+    };
+
+    let array_fields = std::iter::once(quote! { tree_buf_values: Vec<u64> });
+    let buffer = quote! {
+        self.tree_buf_values.push(#discriminant);
+    };
+    let flush = quote! {
+        ::tree_buf::internal::EncoderArray::<u64>::flush(self.tree_buf_values, stream)
+    };
+    let encode_root = quote! {
+        let value = self;
+        ::tree_buf::internal::Encodable::encode_root(&#discriminant, stream)
+    };
+    let schema = quote! { ::tree_buf::experimental::compat::TypeSchema::Integer(64) };
+
+    fill_encode_skeleton(ast, array_fields, buffer, flush, encode_root, schema)
 }
 
 fn fill_encode_skeleton<A: ToTokens>(
@@ -81,6 +199,7 @@ fn fill_encode_skeleton<A: ToTokens>(
     buffer: impl ToTokens,
     flush: impl ToTokens,
     encode_root: impl ToTokens,
+    schema: impl ToTokens,
 ) -> TokenStream {
     let name = &ast.ident;
     let vis = &ast.vis;
@@ -111,6 +230,9 @@ fn fill_encode_skeleton<A: ToTokens>(
                 //let _profile_guard = ::tree_buf::internal::firestorm::start_guard(::tree_buf::internal::firestorm::FmtStr::Str3(::std::any::type_name::<Self>(), "::", "flush"));
                 #encode_root
             }
+            fn encode_schema() -> ::tree_buf::experimental::compat::TypeSchema {
+                #schema
+            }
         }
     }
 }
@@ -135,7 +257,7 @@ fn impl_enum_encode(ast: &DeriveInput, data_enum: &DataEnum) -> TokenStream {
 
     for variant in data_enum.variants.iter() {
         let variant_ident = &variant.ident;
-        let discriminant = canonical_ident(variant_ident);
+        let discriminant = canonical_name(variant_ident, &variant.attrs);
 
         match &variant.fields {
             Fields::Unit => {
@@ -187,7 +309,7 @@ fn impl_enum_encode(ast: &DeriveInput, data_enum: &DataEnum) -> TokenStream {
                         root_matches.push(quote! {
                             #ident::#variant_ident(_0) => {
                                 ::tree_buf::internal::encode_ident(#discriminant, stream);
-                                stream.encode_with_id(|stream| _0.encode_root(stream));
+                                stream.encode_field(#discriminant, |stream| stream.encode_with_id(|stream| _0.encode_root(stream)));
                             }
                         });
                         array_fields.push(quote! {
@@ -214,7 +336,7 @@ fn impl_enum_encode(ast: &DeriveInput, data_enum: &DataEnum) -> TokenStream {
                             if matches {
                                 let mut buffer = self.#variant_ident.take().unwrap().1;
                                 ::tree_buf::internal::encode_ident(#discriminant, stream);
-                                stream.encode_with_id(|stream| ::tree_buf::internal::EncoderArray::<#ty>:: flush(buffer, stream));
+                                stream.encode_field(#discriminant, |stream| stream.encode_with_id(|stream| ::tree_buf::internal::EncoderArray::<#ty>:: flush(buffer, stream)));
                                 continue;
                             }
                         });
@@ -249,5 +371,7 @@ fn impl_enum_encode(ast: &DeriveInput, data_enum: &DataEnum) -> TokenStream {
         ::tree_buf::internal::ArrayTypeId::Enum
     };
 
-    fill_encode_skeleton(ast, array_fields.iter(), buffer, flush, encode_root)
+    let schema = enum_schema(ast, data_enum, quote! { ::tree_buf::internal::Encodable }, format_ident!("encode_schema"));
+
+    fill_encode_skeleton(ast, array_fields.iter(), buffer, flush, encode_root, schema)
 }
@@ -15,14 +15,14 @@ use {
 
 
 
-#[proc_macro_derive(Encode)]
+#[proc_macro_derive(Encode, attributes(tree_buf))]
 pub fn encode_macro_derive(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
     let ast = parse_macro_input!(input as DeriveInput);
     let output = impl_encode_macro(&ast);
     proc_macro::TokenStream::from(output)
 }
 
-#[proc_macro_derive(Decode)]
+#[proc_macro_derive(Decode, attributes(tree_buf))]
 pub fn decode_macro_derive(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
     let ast = parse_macro_input!(input as DeriveInput);
     let output = impl_decode_macro(&ast);
@@ -1,7 +1,7 @@
 use {
     inflector::cases::camelcase::to_camel_case,
-    proc_macro2::Ident,
-    syn::{DataStruct, Fields, Type},
+    proc_macro2::{Ident, TokenStream},
+    syn::{ext::IdentExt, Attribute, DataEnum, DataStruct, DeriveInput, Fields, Lit, Meta, NestedMeta, Type},
 };
 
 // TODO: If we limit ourselves to ASCII characters, then it's possible to just do the same as prefix-varint and have a tag bit to save binary size
@@ -12,15 +12,86 @@ use {
 // TODO: Ensure that leading separators are preserved?
 // TODO: Unfortunately, the current method is quite inadequate. Consider a language with no case. Consider a letter 'q' having
 // neither uppercase nor lowercase. qq vs q_q is different. But, in this encoding they are the same.
-pub fn canonical_ident(ident: &Ident) -> String {
-    let ident_str = format!("{}", ident);
+fn canonical_ident(ident: &Ident) -> String {
+    // Eg: r#type is written as type
+    let ident_str = format!("{}", ident.unraw());
     to_camel_case(&ident_str)
 }
 
+/// The name written for a field or variant, which is either given by #[tree_buf(rename = "...")]
+/// or the canonical form of its identifier.
+pub fn canonical_name(ident: &Ident, attrs: &[Attribute]) -> String {
+    rename(attrs).unwrap_or_else(|| canonical_ident(ident))
+}
+
+const ATTRIBUTES: &[&str] = &["other", "flatten", "integer", "rename"];
+
+/// The #[tree_buf(...)] attributes, as parsed.
+fn tree_buf_attributes(attrs: &[Attribute]) -> Vec<NestedMeta> {
+    let mut result = Vec::new();
+    for attr in attrs.iter().filter(|attr| attr.path.is_ident("tree_buf")) {
+        let list = match attr.parse_meta() {
+            Ok(Meta::List(list)) => list,
+            _ => panic!("Expected #[tree_buf(...)]"),
+        };
+        for nested in list.nested.into_iter() {
+            let known = match &nested {
+                NestedMeta::Meta(Meta::Path(path)) => path,
+                NestedMeta::Meta(Meta::NameValue(name_value)) => &name_value.path,
+                _ => panic!("Unsupported tree_buf attribute. Expected one of {:?}", ATTRIBUTES),
+            };
+            if !ATTRIBUTES.iter().any(|name| known.is_ident(name)) {
+                panic!("Unsupported tree_buf attribute. Expected one of {:?}", ATTRIBUTES);
+            }
+            result.push(nested);
+        }
+    }
+    result
+}
+
+/// Whether there is a #[tree_buf(name)] attribute
+pub fn has_attribute(attrs: &[Attribute], name: &str) -> bool {
+    tree_buf_attributes(attrs).iter().any(|nested| match nested {
+        NestedMeta::Meta(Meta::Path(path)) => path.is_ident(name),
+        _ => false,
+    })
+}
+
+/// The name from #[tree_buf(rename = "name")], which is written as is. This allows any name in
+/// the file, including those which aren't valid identifiers, like self.
+fn rename(attrs: &[Attribute]) -> Option<String> {
+    let mut result = None;
+    for nested in tree_buf_attributes(attrs) {
+        match nested {
+            NestedMeta::Meta(Meta::NameValue(name_value)) if name_value.path.is_ident("rename") => match name_value.lit {
+                Lit::Str(name) => result = Some(name.value()),
+                _ => panic!("Expected #[tree_buf(rename = \"name\")]"),
+            },
+            NestedMeta::Meta(Meta::NameValue(_)) => panic!("Expected #[tree_buf(rename = \"name\")]"),
+            _ => {}
+        }
+    }
+    result
+}
+
+/// The variants of an enum with #[tree_buf(integer)], which are written as their discriminants.
+pub fn integer_variants(data_enum: &DataEnum) -> Vec<&Ident> {
+    data_enum
+        .variants
+        .iter()
+        .map(|variant| match variant.fields {
+            Fields::Unit => &variant.ident,
+            _ => panic!("#[tree_buf(integer)] requires that every variant is a unit variant"),
+        })
+        .collect()
+}
+
 pub struct NamedField<'a> {
     pub ident: &'a Ident,
     pub ty: &'a Type,
     pub canon_str: String,
+    /// Whether the field has #[tree_buf(flatten)], writing its fields in place of its own.
+    pub flatten: bool,
 }
 pub type NamedFields<'a> = Vec<NamedField<'a>>;
 
@@ -31,7 +102,7 @@ pub fn get_named_fields(data_struct: &DataStruct) -> NamedFields {
         _ => panic!("The struct must have named fields"),
     };
 
-    fields_named
+    let fields: NamedFields = fields_named
         .named
         .iter()
         .map(|field| {
@@ -39,8 +110,73 @@ pub fn get_named_fields(data_struct: &DataStruct) -> NamedFields {
             NamedField {
                 ident: field.ident.as_ref().unwrap(),
                 ty: &field.ty,
-                canon_str: canonical_ident(&ident),
+                canon_str: canonical_name(&ident, &field.attrs),
+                flatten: has_attribute(&field.attrs, "flatten"),
             }
         })
-        .collect()
+        .collect();
+
+    // Eg: foo_bar and fooBar. Clashes with the fields of flattened structs are checked when encoding.
+    let plain: Vec<_> = fields.iter().filter(|field| !field.flatten).collect();
+    for (i, field) in plain.iter().enumerate() {
+        if let Some(other) = plain[..i].iter().find(|other| other.canon_str == field.canon_str) {
+            panic!("The fields {} and {} would both be written as {}", other.ident, field.ident, field.canon_str);
+        }
+    }
+
+    fields
+}
+
+/// The body of encode_schema() or decode_schema() for a struct, where trait_name is Encodable or Decodable.
+pub fn struct_schema(ast: &DeriveInput, fields: &NamedFields, trait_name: TokenStream, schema_fn: Ident) -> TokenStream {
+    let name = ast.ident.to_string();
+    let pushes = fields.iter().map(|NamedField { ty, canon_str, flatten, .. }| {
+        if *flatten {
+            quote! {
+                ::tree_buf::internal::flatten_schema(&mut fields, <#ty as #trait_name>::#schema_fn());
+            }
+        } else {
+            quote! {
+                fields.push((#canon_str, <#ty as #trait_name>::#schema_fn()));
+            }
+        }
+    });
+    quote! {
+        let mut fields = Vec::new();
+        #(#pushes)*
+        ::tree_buf::experimental::compat::TypeSchema::Struct { name: #name, fields }
+    }
+}
+
+/// Like struct_schema, for an enum.
+pub fn enum_schema(ast: &DeriveInput, data_enum: &DataEnum, trait_name: TokenStream, schema_fn: Ident) -> TokenStream {
+    let name = ast.ident.to_string();
+    let variants = data_enum.variants.iter().map(|variant| {
+        let discriminant = canonical_name(&variant.ident, &variant.attrs);
+        let data = match &variant.fields {
+            Fields::Unnamed(unnamed) if unnamed.unnamed.len() == 1 => {
+                let ty = &unnamed.unnamed[0].ty;
+                quote! { <#ty as #trait_name>::#schema_fn() }
+            }
+            // Other kinds of variant are rejected elsewhere in the macro
+            _ => quote! { ::tree_buf::experimental::compat::TypeSchema::Ignore },
+        };
+        quote! {
+            (#discriminant, #data)
+        }
+    });
+    let other = match data_enum.variants.iter().find(|variant| has_attribute(&variant.attrs, "other")) {
+        Some(variant) => {
+            let discriminant = canonical_name(&variant.ident, &variant.attrs);
+            quote! { Some(#discriminant) }
+        }
+        None => quote! { None },
+    };
+    quote! {
+        ::tree_buf::experimental::compat::TypeSchema::Enum {
+            name: #name,
+            variants: vec![#(#variants),*],
+            other: #other,
+        }
+    }
 }
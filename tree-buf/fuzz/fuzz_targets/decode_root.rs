@@ -0,0 +1,9 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+use tree_buf::experimental::stats::size_breakdown;
+use tree_buf::internal::{decode_root, DecodeOptionsDefault};
+
+fuzz_target!(|data: &[u8]| {
+    let _ = decode_root(data, &DecodeOptionsDefault);
+    let _ = size_breakdown(data);
+});
@@ -0,0 +1,24 @@
+#![no_main]
+//! Decodes a single array branch, which is usually one of the integer, float, or string encodings,
+//! as each of the types which might reasonably be stored that way.
+use libfuzzer_sys::fuzz_target;
+use tree_buf::Blob;
+use tree_buf_fuzz::{decode_limited, Array, File, Root};
+
+fuzz_target!(|input: (u16, Array)| {
+    let (len, array) = input;
+    let file = File {
+        checksums: false,
+        root: Root::ArrayN(len, array),
+    };
+    let bytes = file.write();
+
+    let _ = decode_limited::<Vec<u64>>(&bytes);
+    let _ = decode_limited::<Vec<u8>>(&bytes);
+    let _ = decode_limited::<Vec<f64>>(&bytes);
+    let _ = decode_limited::<Vec<f32>>(&bytes);
+    let _ = decode_limited::<Vec<bool>>(&bytes);
+    let _ = decode_limited::<Vec<String>>(&bytes);
+    let _ = decode_limited::<Vec<Blob>>(&bytes);
+    let _ = decode_limited::<Vec<Option<u32>>>(&bytes);
+});
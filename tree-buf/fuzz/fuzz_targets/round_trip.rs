@@ -0,0 +1,43 @@
+#![no_main]
+//! Every value should decode to itself, whichever encodings are chosen for it.
+use arbitrary::Arbitrary;
+use libfuzzer_sys::fuzz_target;
+use std::collections::HashMap;
+use tree_buf::prelude::*;
+use tree_buf::Blob;
+
+#[derive(Arbitrary, Encode, Decode, Debug, PartialEq, Clone)]
+struct Values {
+    u64s: Vec<u64>,
+    u8s: Vec<u8>,
+    bools: Vec<bool>,
+    strings: Vec<String>,
+    nullable: Vec<Option<u32>>,
+    nested: Vec<Vec<u16>>,
+    map: HashMap<String, u32>,
+    tuples: Vec<(u32, bool)>,
+    blobs: Vec<Vec<u8>>,
+}
+
+#[derive(Arbitrary, Debug)]
+struct Floats {
+    f64s: Vec<f64>,
+    f32s: Vec<f32>,
+}
+
+fuzz_target!(|input: (Values, Floats)| {
+    let (values, floats) = input;
+
+    assert_eq!(decode::<Values>(&encode(&values)), Ok(values.clone()));
+
+    let blobs: Vec<Blob> = values.blobs.into_iter().map(Blob).collect();
+    assert_eq!(decode::<Vec<Blob>>(&encode(&blobs)), Ok(blobs));
+
+    // NaN != NaN, and NaN payloads are not preserved. See the FIXME in encode_root for floats.
+    let decoded = decode::<Vec<f64>>(&encode(&floats.f64s)).unwrap();
+    assert!(decoded.iter().zip(&floats.f64s).all(|(a, b)| a.to_bits() == b.to_bits() || (a.is_nan() && b.is_nan())));
+    assert_eq!(decoded.len(), floats.f64s.len());
+    let decoded = decode::<Vec<f32>>(&encode(&floats.f32s)).unwrap();
+    assert!(decoded.iter().zip(&floats.f32s).all(|(a, b)| a.to_bits() == b.to_bits() || (a.is_nan() && b.is_nan())));
+    assert_eq!(decoded.len(), floats.f32s.len());
+});
@@ -0,0 +1,10 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+use tree_buf::internal::{decode_root, DecodeOptionsDefault};
+use tree_buf_fuzz::File;
+
+fuzz_target!(|file: File| {
+    let bytes = file.write();
+    let _ = decode_root(&bytes, &DecodeOptionsDefault);
+    tree_buf_fuzz::decode_all_types(&bytes);
+});
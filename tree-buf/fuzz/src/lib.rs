@@ -0,0 +1,551 @@
+//! Shared code for the fuzz targets.
+//!
+//! The structure-aware targets generate a model of a Tree-Buf file with `arbitrary`, then
+//! write out the bytes for it. The writer follows the format closely enough that most files
+//! parse, but puts no constraints on what goes in the branches. So, the files are valid-but-unusual:
+//! Eg: an RLE whose runs are floats, or an array whose len is longer than its values.
+
+use arbitrary::Arbitrary;
+use std::collections::HashMap;
+use tree_buf::internal::encodings::varint::{encode_prefix_varint, encode_suffix_varint};
+use tree_buf::internal::{ArrayTypeId, RootTypeId};
+use tree_buf::prelude::*;
+use tree_buf::experimental::options;
+use tree_buf::{decode_options, Blob, Decodable, DecodeError};
+
+#[derive(Arbitrary, Debug)]
+pub enum Root {
+    Void,
+    Bool(bool),
+    Zero,
+    One,
+    NegOne,
+    Int { signed: bool, width: u8, value: u64 },
+    F32(f32),
+    F64(f64),
+    NaN,
+    Str(String),
+    Blob(Codec, Vec<u8>),
+    Array0,
+    Array1(Box<Root>),
+    ArrayN(u16, Array),
+    Map0,
+    Map1(Box<Root>, Box<Root>),
+    MapN(u16, Array, Array),
+    Tuple(Box<Root>, Box<Root>, Vec<Root>),
+    Obj(Vec<(String, Root)>),
+    Enum(String, Box<Root>),
+    Tagged(u64, u64, Box<Root>),
+}
+
+#[derive(Arbitrary, Debug)]
+pub enum Array {
+    Void,
+    Nullable(Box<Array>, Box<Array>),
+    ArrayVar(Box<Array>, Box<Array>),
+    ArrayFixed(u16, Box<Array>),
+    Map(Box<Array>, Box<Array>, Box<Array>),
+    Ints(Ints),
+    DeltaZig64(Vec<u64>),
+    PackedBool(Vec<u8>),
+    RleBool(bool, Box<Array>),
+    F32(Vec<f32>),
+    F64(Vec<f64>),
+    Gorilla(Vec<u8>),
+    Zfp32(Vec<u8>),
+    Zfp64(Vec<u8>),
+    Utf8(Vec<String>),
+    BrotliUtf8(Vec<u8>, Box<Array>),
+    PrefixUtf8(Box<Array>, Box<Array>),
+    FsstUtf8(Vec<u8>, Box<Array>),
+    HexUtf8(Vec<u8>, Box<Array>),
+    DecimalUtf8(Box<Array>, Box<Array>),
+    Blob(Codec, Vec<u8>, Box<Array>),
+    Enum(Box<Array>, Vec<(String, Array)>),
+    Rle(Box<Array>, Box<Array>),
+    Dictionary(Box<Array>, Box<Array>),
+    Tuple(Box<Array>, Box<Array>, Vec<Array>),
+    Obj(Vec<(String, Array)>),
+    /// A codec id, which no codec is registered for when decoding, and its bytes
+    Extension(u64, Vec<u8>),
+    Tagged(u64, u64, Box<Array>),
+}
+
+/// Integers are usually written as valid values, but sometimes as arbitrary bytes.
+#[derive(Arbitrary, Debug)]
+pub enum Ints {
+    PrefixVarInt(Vec<u64>),
+    DeltaZig(Vec<u32>),
+    U8(Vec<u8>),
+    Raw(IntEncoding, Vec<u8>),
+}
+
+#[derive(Arbitrary, Debug, Clone, Copy)]
+pub enum IntEncoding {
+    PrefixVarInt,
+    Simple16,
+    U8,
+    DeltaZig,
+}
+
+#[derive(Arbitrary, Debug, Clone, Copy)]
+pub enum Codec {
+    Raw,
+    Lz4,
+    Brotli,
+}
+
+#[derive(Arbitrary, Debug)]
+pub struct File {
+    pub checksums: bool,
+    pub root: Root,
+}
+
+struct Writer {
+    bytes: Vec<u8>,
+    lens: Vec<u64>,
+    checksums: bool,
+}
+
+impl Writer {
+    fn id(&mut self, id: impl Into<u8>) {
+        self.bytes.push(id.into());
+    }
+
+    fn varint(&mut self, value: u64) {
+        encode_prefix_varint(value, &mut self.bytes);
+    }
+
+    fn str(&mut self, value: &str) {
+        self.varint(value.len() as u64);
+        self.bytes.extend_from_slice(value.as_bytes());
+    }
+
+    /// Writes bytes whose length is stored at the end of the file, like EncoderStream::encode_with_len
+    fn with_len(&mut self, data: &[u8]) {
+        self.lens.push(data.len() as u64);
+        if self.checksums {
+            self.lens.push(crc32c::crc32c(data) as u64);
+        }
+        self.bytes.extend_from_slice(data);
+    }
+
+    fn root(&mut self, root: &Root) {
+        match root {
+            Root::Void => self.id(RootTypeId::Void),
+            Root::Bool(true) => self.id(RootTypeId::True),
+            Root::Bool(false) => self.id(RootTypeId::False),
+            Root::Zero => self.id(RootTypeId::Zero),
+            Root::One => self.id(RootTypeId::One),
+            Root::NegOne => self.id(RootTypeId::NegOne),
+            Root::Int { signed, width, value } => {
+                use RootTypeId::*;
+                let width = (*width % 8) as usize + 1;
+                let ids = if *signed {
+                    [IntS8, IntS16, IntS24, IntS32, IntS40, IntS48, IntS56, IntS64]
+                } else {
+                    [IntU8, IntU16, IntU24, IntU32, IntU40, IntU48, IntU56, IntU64]
+                };
+                self.id(ids[width - 1]);
+                self.bytes.extend_from_slice(&value.to_le_bytes()[..width]);
+            }
+            Root::F32(value) => {
+                self.id(RootTypeId::F32);
+                self.bytes.extend_from_slice(&value.to_le_bytes());
+            }
+            Root::F64(value) => {
+                self.id(RootTypeId::F64);
+                self.bytes.extend_from_slice(&value.to_le_bytes());
+            }
+            Root::NaN => self.id(RootTypeId::NaN),
+            Root::Str(value) => match value.len() {
+                0 => self.id(RootTypeId::Str0),
+                1 => self.id(RootTypeId::Str1),
+                2 => self.id(RootTypeId::Str2),
+                3 => self.id(RootTypeId::Str3),
+                _ => {
+                    self.id(RootTypeId::Str);
+                    self.varint(value.len() as u64);
+                }
+            },
+            Root::Blob(codec, data) => {
+                self.id(match codec {
+                    Codec::Raw => RootTypeId::Blob,
+                    Codec::Lz4 => RootTypeId::Lz4Blob,
+                    Codec::Brotli => RootTypeId::BrotliBlob,
+                });
+                self.varint(data.len() as u64);
+                self.bytes.extend_from_slice(data);
+            }
+            Root::Array0 => self.id(RootTypeId::Array0),
+            Root::Array1(item) => {
+                self.id(RootTypeId::Array1);
+                self.root(item);
+            }
+            Root::ArrayN(len, values) => {
+                self.id(RootTypeId::ArrayN);
+                self.varint(*len as u64);
+                self.array(values);
+            }
+            Root::Map0 => {
+                self.id(RootTypeId::Map);
+                self.varint(0);
+            }
+            Root::Map1(key, value) => {
+                self.id(RootTypeId::Map);
+                self.varint(1);
+                self.root(key);
+                self.root(value);
+            }
+            Root::MapN(len, keys, values) => {
+                self.id(RootTypeId::Map);
+                self.varint(*len as u64 + 2);
+                self.array(keys);
+                self.array(values);
+            }
+            Root::Tuple(first, second, rest) => {
+                use RootTypeId::*;
+                let ids = [Tuple2, Tuple3, Tuple4, Tuple5, Tuple6, Tuple7, Tuple8];
+                match ids.get(rest.len()) {
+                    Some(id) => self.id(*id),
+                    None => {
+                        self.id(TupleN);
+                        self.varint(rest.len() as u64 + 2 - 9);
+                    }
+                }
+                self.root(first);
+                self.root(second);
+                for field in rest {
+                    self.root(field);
+                }
+            }
+            Root::Obj(fields) => {
+                use RootTypeId::*;
+                let ids = [Obj0, Obj1, Obj2, Obj3, Obj4, Obj5, Obj6, Obj7, Obj8];
+                match ids.get(fields.len()) {
+                    Some(id) => self.id(*id),
+                    None => {
+                        self.id(ObjN);
+                        self.varint(fields.len() as u64 - 9);
+                    }
+                }
+                for (name, field) in fields {
+                    self.str(name);
+                    self.root(field);
+                }
+            }
+            Root::Enum(discriminant, value) => {
+                self.id(RootTypeId::Enum);
+                self.str(discriminant);
+                self.root(value);
+            }
+            Root::Tagged(kind, param, value) => {
+                self.id(RootTypeId::Tagged);
+                self.varint(*kind);
+                self.varint(*param);
+                self.root(value);
+            }
+        }
+    }
+
+    fn array(&mut self, array: &Array) {
+        match array {
+            Array::Void => self.id(ArrayTypeId::Void),
+            Array::Nullable(opt, values) => {
+                self.id(ArrayTypeId::Nullable);
+                self.array(opt);
+                self.array(values);
+            }
+            Array::ArrayVar(len, values) => {
+                self.id(ArrayTypeId::ArrayVar);
+                self.array(len);
+                // A Void len is an empty array, with no values
+                if !matches!(**len, Array::Void) {
+                    self.array(values);
+                }
+            }
+            Array::ArrayFixed(len, values) => {
+                self.id(ArrayTypeId::ArrayFixed);
+                self.varint(*len as u64);
+                self.array(values);
+            }
+            Array::Map(len, keys, values) => {
+                self.id(ArrayTypeId::Map);
+                self.array(len);
+                if !matches!(**len, Array::Void) {
+                    self.array(keys);
+                    self.array(values);
+                }
+            }
+            Array::Ints(ints) => {
+                let mut data = Vec::new();
+                let encoding = match ints {
+                    Ints::PrefixVarInt(values) => {
+                        for value in values {
+                            encode_prefix_varint(*value, &mut data);
+                        }
+                        IntEncoding::PrefixVarInt
+                    }
+                    Ints::DeltaZig(values) => {
+                        let mut prev = 0u32;
+                        for value in values {
+                            let delta = value.wrapping_sub(prev) as i32;
+                            encode_prefix_varint(((delta << 1) ^ (delta >> 31)) as u32 as u64, &mut data);
+                            prev = *value;
+                        }
+                        IntEncoding::DeltaZig
+                    }
+                    Ints::U8(values) => {
+                        data.extend_from_slice(values);
+                        IntEncoding::U8
+                    }
+                    Ints::Raw(encoding, bytes) => {
+                        data.extend_from_slice(bytes);
+                        *encoding
+                    }
+                };
+                self.id(match encoding {
+                    IntEncoding::PrefixVarInt => ArrayTypeId::IntPrefixVar,
+                    IntEncoding::Simple16 => ArrayTypeId::IntSimple16,
+                    IntEncoding::U8 => ArrayTypeId::U8,
+                    IntEncoding::DeltaZig => ArrayTypeId::DeltaZig,
+                });
+                self.with_len(&data);
+            }
+            Array::DeltaZig64(values) => {
+                let mut data = Vec::new();
+                let mut prev = 0u64;
+                for value in values {
+                    let delta = value.wrapping_sub(prev) as i64;
+                    encode_prefix_varint(((delta << 1) ^ (delta >> 63)) as u64, &mut data);
+                    prev = *value;
+                }
+                self.id(ArrayTypeId::DeltaZig64);
+                self.with_len(&data);
+            }
+            Array::PackedBool(data) => {
+                self.id(ArrayTypeId::PackedBool);
+                self.with_len(data);
+            }
+            Array::RleBool(first, runs) => {
+                self.id(if *first { ArrayTypeId::RLEBoolTrue } else { ArrayTypeId::RLEBoolFalse });
+                self.array(runs);
+            }
+            Array::F32(values) => {
+                self.id(ArrayTypeId::F32);
+                let data: Vec<u8> = values.iter().flat_map(|v| v.to_le_bytes().to_vec()).collect();
+                self.with_len(&data);
+            }
+            Array::F64(values) => {
+                self.id(ArrayTypeId::F64);
+                let data: Vec<u8> = values.iter().flat_map(|v| v.to_le_bytes().to_vec()).collect();
+                self.with_len(&data);
+            }
+            Array::Gorilla(data) => {
+                self.id(ArrayTypeId::DoubleGorilla);
+                self.with_len(data);
+            }
+            Array::Zfp32(data) => {
+                self.id(ArrayTypeId::Zfp32);
+                self.with_len(data);
+            }
+            Array::Zfp64(data) => {
+                self.id(ArrayTypeId::Zfp64);
+                self.with_len(data);
+            }
+            Array::Utf8(values) => {
+                self.id(ArrayTypeId::Utf8);
+                let mut data = Vec::new();
+                for value in values {
+                    encode_prefix_varint(value.len() as u64, &mut data);
+                    data.extend_from_slice(value.as_bytes());
+                }
+                self.with_len(&data);
+            }
+            Array::BrotliUtf8(data, lens) => {
+                self.id(ArrayTypeId::BrotliUtf8);
+                self.with_len(data);
+                self.array(lens);
+            }
+            Array::PrefixUtf8(prefixes, suffixes) => {
+                self.id(ArrayTypeId::PrefixUtf8);
+                self.array(prefixes);
+                self.array(suffixes);
+            }
+            Array::FsstUtf8(data, lens) => {
+                self.id(ArrayTypeId::FsstUtf8);
+                self.with_len(data);
+                self.array(lens);
+            }
+            Array::HexUtf8(data, lens) => {
+                self.id(ArrayTypeId::HexUtf8);
+                self.with_len(data);
+                self.array(lens);
+            }
+            Array::DecimalUtf8(mantissas, exponents) => {
+                self.id(ArrayTypeId::DecimalUtf8);
+                self.array(mantissas);
+                self.array(exponents);
+            }
+            Array::Blob(codec, data, lens) => {
+                self.id(match codec {
+                    Codec::Raw => ArrayTypeId::Blob,
+                    Codec::Lz4 => ArrayTypeId::Lz4Blob,
+                    Codec::Brotli => ArrayTypeId::BrotliBlob,
+                });
+                self.with_len(data);
+                self.array(lens);
+            }
+            Array::Enum(discriminants, variants) => {
+                self.id(ArrayTypeId::Enum);
+                self.varint(variants.len() as u64);
+                self.array(discriminants);
+                for (name, data) in variants {
+                    self.str(name);
+                    self.array(data);
+                }
+            }
+            Array::Rle(values, runs) => {
+                self.id(ArrayTypeId::RLE);
+                self.array(values);
+                self.array(runs);
+            }
+            Array::Dictionary(values, indices) => {
+                self.id(ArrayTypeId::Dictionary);
+                self.array(values);
+                self.array(indices);
+            }
+            Array::Tuple(first, second, rest) => {
+                use ArrayTypeId::*;
+                let ids = [Tuple2, Tuple3, Tuple4, Tuple5, Tuple6, Tuple7, Tuple8];
+                match ids.get(rest.len()) {
+                    Some(id) => self.id(*id),
+                    None => {
+                        self.id(TupleN);
+                        self.varint(rest.len() as u64 + 2 - 9);
+                    }
+                }
+                self.array(first);
+                self.array(second);
+                for field in rest {
+                    self.array(field);
+                }
+            }
+            Array::Obj(fields) => {
+                use ArrayTypeId::*;
+                let ids = [Obj0, Obj1, Obj2, Obj3, Obj4, Obj5, Obj6, Obj7, Obj8];
+                match ids.get(fields.len()) {
+                    Some(id) => self.id(*id),
+                    None => {
+                        self.id(ObjN);
+                        self.varint(fields.len() as u64 - 9);
+                    }
+                }
+                for (name, field) in fields {
+                    self.str(name);
+                    self.array(field);
+                }
+            }
+            Array::Extension(codec, data) => {
+                self.id(ArrayTypeId::Extension);
+                self.varint(*codec);
+                self.with_len(data);
+            }
+            Array::Tagged(kind, param, values) => {
+                self.id(ArrayTypeId::Tagged);
+                self.varint(*kind);
+                self.varint(*param);
+                self.array(values);
+            }
+        }
+    }
+}
+
+impl File {
+    /// Writes the bytes of the file, in the same layout as encode_with_options.
+    pub fn write(&self) -> Vec<u8> {
+        let mut writer = Writer {
+            bytes: Vec::new(),
+            lens: Vec::new(),
+            checksums: self.checksums,
+        };
+        if self.checksums {
+            writer.id(RootTypeId::Checksums);
+        }
+        writer.root(&self.root);
+
+        let Writer { mut bytes, lens, .. } = writer;
+        for len in lens.iter().rev() {
+            encode_suffix_varint(*len, &mut bytes);
+        }
+        if self.checksums {
+            let checksum = crc32c::crc32c(&bytes);
+            bytes.extend_from_slice(&checksum.to_le_bytes());
+        }
+        bytes
+    }
+}
+
+#[derive(Encode, Decode, Debug, PartialEq, Clone)]
+pub struct Struct {
+    pub id: u64,
+    pub name: String,
+    pub scores: Vec<f64>,
+    pub tags: Option<Vec<String>>,
+    pub nested: Option<Nested>,
+}
+
+#[derive(Encode, Decode, Debug, PartialEq, Clone)]
+pub struct Nested {
+    pub flag: bool,
+    pub values: Vec<u32>,
+}
+
+#[derive(Encode, Decode, Debug, PartialEq, Clone)]
+pub enum Enum {
+    Unit,
+    Int(u64),
+    Text(String),
+    Pair((u32, bool)),
+}
+
+/// Decodes with the limits that are recommended for untrusted input. Without them
+/// a few bytes can legitimately claim to hold billions of empty strings.
+pub fn decode_limited<T: Decodable>(bytes: &[u8]) -> Result<T, DecodeError> {
+    let options = decode_options! { options::MaxElements(1 << 20) };
+    options::decode_with_options(bytes, &options)
+}
+
+/// Decodes the bytes as each of the built-in types. The result is ignored,
+/// since the only requirement is that decoding fails with an error rather than a panic.
+pub fn decode_all_types(bytes: &[u8]) {
+    macro_rules! decode_all {
+        ($($T:ty),*) => {
+            $(
+                let _ = decode_limited::<$T>(bytes);
+                let _ = decode_limited::<Vec<$T>>(bytes);
+                let _ = decode_limited::<Option<$T>>(bytes);
+            )*
+        };
+    }
+
+    decode_all!(
+        u8,
+        u16,
+        u32,
+        u64,
+        f32,
+        f64,
+        bool,
+        String,
+        Blob,
+        Vec<u32>,
+        Vec<String>,
+        HashMap<String, u64>,
+        HashMap<u32, Vec<f64>>,
+        (u64, String),
+        (bool, f32, Vec<u8>),
+        Struct,
+        Enum
+    );
+}
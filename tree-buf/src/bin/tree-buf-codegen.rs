@@ -0,0 +1,33 @@
+//! Prints Rust types which can decode a Tree-Buf file.
+//!
+//! Usage: tree-buf-codegen <file> [root type name]
+
+use std::process::exit;
+use tree_buf::experimental::codegen::rust_source;
+
+fn main() {
+    let mut args = std::env::args().skip(1);
+    let path = match args.next() {
+        Some(path) => path,
+        None => {
+            eprintln!("Usage: tree-buf-codegen <file> [root type name]");
+            exit(2);
+        }
+    };
+    let root_name = args.next().unwrap_or_else(|| "Root".to_owned());
+
+    let bytes = match std::fs::read(&path) {
+        Ok(bytes) => bytes,
+        Err(err) => {
+            eprintln!("Failed to read {}: {}", path, err);
+            exit(1);
+        }
+    };
+    match rust_source(&bytes, &root_name) {
+        Ok(source) => print!("{}", source),
+        Err(err) => {
+            eprintln!("Failed to decode {}: {:?}", path, err);
+            exit(1);
+        }
+    }
+}
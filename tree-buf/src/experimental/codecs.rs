@@ -0,0 +1,11 @@
+//! Register codecs for integers, floats, strings, and bytes to be considered alongside the built in
+//! encodings. A codec is only used for an array when it is the smallest. Files which use a codec
+//! can only be decoded when the same codec is registered in the DecodeOptions.
+//!
+//! ```ignore
+//! let codecs = Codecs::new().with_integer(MyCodec);
+//! let bytes = encode_with_options(&data, &encode_options! { &codecs });
+//! let data: Data = decode_with_options(&bytes, &decode_options! { &codecs })?;
+//! ```
+
+pub use crate::internal::encodings::extension::{BytesCodec, Codecs, FloatCodec, IntegerCodec, StringCodec, Unsupported};
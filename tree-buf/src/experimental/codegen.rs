@@ -0,0 +1,292 @@
+//! Generates Rust types which match the schema of a Tree-Buf file, so that files from elsewhere
+//! can be decoded without writing the types by hand. Each object becomes a struct and each
+//! enum an enum, named after the field which holds it. Integers get the narrowest type which
+//! fits the values in the file, so a different file with larger values may need wider types.
+//! Negative integers, which are only written at the root, are generated as signed types. Those
+//! can't be written, so the types which contain them derive Decode but not Encode.
+//! Values written by an extension codec are generated as Ignore, since the codec isn't known.
+//!
+//! In an array, integers which are all 0 or 1 are written as bools, and the file doesn't say
+//! which they were. So, booleans in arrays are generated as u8, which can read either.
+//! Names which are keywords are generated as raw identifiers (eg: r#type), and those which
+//! can't be (eg: self) are renamed with #[tree_buf(rename = "...")].
+
+use crate::experimental::schema::*;
+use crate::prelude::*;
+use std::collections::HashSet;
+use std::fmt::Write;
+
+/// Generates the source of Rust types which can decode the file, with the root type named root_name.
+/// The file is decoded with no limits. This recurses for each level of nesting, so for files from
+/// untrusted sources use rust_source_with_options with UntrustedInput.
+#[cfg(feature = "decode")]
+pub fn rust_source(bytes: &[u8], root_name: &str) -> DecodeResult<String> {
+    rust_source_with_options(bytes, root_name, &DecodeOptionsDefault)
+}
+
+/// Like rust_source, within the limits of the options. Eg: MaxDepth also limits the recursion here.
+#[cfg(feature = "decode")]
+pub fn rust_source_with_options(bytes: &[u8], root_name: &str, options: &impl DecodeOptions) -> DecodeResult<String> {
+    let root = decode_root(bytes, options)?;
+    let mut ranges = IntegerRanges::default();
+    let schema = root_schema_with_ranges(&root, &mut ranges);
+
+    let mut generator = Generator {
+        ranges,
+        path: Vec::new(),
+        names: HashSet::new(),
+        items: Vec::new(),
+        uses_map: false,
+        signed: false,
+    };
+    match &schema {
+        Schema::Object(_) | Schema::Enum(_) => {
+            generator.named_type(&schema, root_name);
+        }
+        _ => {
+            let name = generator.unique_name(root_name);
+            let index = generator.reserve();
+            let ty = generator.type_of(&schema, &format!("{}Item", name));
+            generator.items[index] = format!("pub type {} = {};\n", name, ty);
+        }
+    }
+
+    let mut source = String::new();
+    if generator.uses_map {
+        source.push_str("use std::collections::HashMap;\n");
+    }
+    source.push_str("use tree_buf::prelude::*;\n");
+    for item in generator.items {
+        source.push('\n');
+        source.push_str(&item);
+    }
+    Ok(source)
+}
+
+struct Generator {
+    ranges: IntegerRanges,
+    /// The same segments as the paths of ranges
+    path: Vec<String>,
+    names: HashSet<String>,
+    /// The source of each struct, enum, or type alias, in the order they were first named.
+    items: Vec<String>,
+    uses_map: bool,
+    /// Whether a signed integer was generated within the current struct or enum
+    signed: bool,
+}
+
+impl Generator {
+    fn within(&mut self, segment: &str, f: impl FnOnce(&mut Self) -> String) -> String {
+        self.path.push(segment.to_owned());
+        let ty = f(self);
+        self.path.pop();
+        ty
+    }
+
+    /// Whether the value at path is one of many in an array, rather than at the root.
+    fn in_array(&self) -> bool {
+        self.path.iter().any(|segment| segment == "[]" || segment == "{key}" || segment == "{value}")
+    }
+
+    fn unique_name(&mut self, hint: &str) -> String {
+        let mut base = pascal_case(hint);
+        if base == "Self" {
+            base.push('_');
+        }
+        let mut name = base.clone();
+        let mut n = 2;
+        while !self.names.insert(name.clone()) {
+            name = format!("{}{}", base, n);
+            n += 1;
+        }
+        name
+    }
+
+    /// Keeps a place for an item, so that types are written before the types they contain.
+    fn reserve(&mut self) -> usize {
+        self.items.push(String::new());
+        self.items.len() - 1
+    }
+
+    /// The Rust type for the schema, where hint names any struct or enum that is needed.
+    fn type_of(&mut self, schema: &Schema, hint: &str) -> String {
+        match schema {
+            Schema::Void | Schema::Extension(_) => "tree_buf::Ignore".to_owned(),
+            Schema::Boolean if self.in_array() => "u8".to_owned(),
+            Schema::Boolean => "bool".to_owned(),
+            Schema::Integer(kind) => {
+                let ty = integer_type(self.ranges.get(&self.path).map(|range| (*range.start(), *range.end())), *kind);
+                self.signed |= ty.starts_with('i');
+                ty.to_owned()
+            }
+            Schema::Float(FloatKind::F32) => "f32".to_owned(),
+            Schema::Float(FloatKind::F64) | Schema::Float(FloatKind::Unknown) => "f64".to_owned(),
+            Schema::String => "String".to_owned(),
+            Schema::Blob => "tree_buf::Blob".to_owned(),
+            Schema::Array(item) => format!("Vec<{}>", self.within("[]", |generator| generator.type_of(item, hint))),
+            Schema::Nullable(value) => format!("Option<{}>", self.within("?", |generator| generator.type_of(value, hint))),
+            Schema::Map(key, value) => {
+                self.uses_map = true;
+                let key = self.within("{key}", |generator| generator.type_of(key, &format!("{}Key", hint)));
+                let value = self.within("{value}", |generator| generator.type_of(value, hint));
+                format!("HashMap<{}, {}>", key, value)
+            }
+            Schema::Tuple(items) => {
+                let items: Vec<_> = items
+                    .iter()
+                    .enumerate()
+                    .map(|(i, item)| self.within(&i.to_string(), |generator| generator.type_of(item, &format!("{}{}", hint, i))))
+                    .collect();
+                if items.len() == 1 {
+                    format!("({},)", items[0])
+                } else {
+                    format!("({})", items.join(", "))
+                }
+            }
+            Schema::Object(_) | Schema::Enum(_) => self.named_type(schema, hint),
+            Schema::Tagged(SemanticTag::IpAddr, _) => "std::net::IpAddr".to_owned(),
+            // Reading a tagged value as its untagged type skips the tag.
+            Schema::Tagged(_, value) => self.type_of(value, hint),
+        }
+    }
+
+    /// Writes the struct or enum for an object or enum schema, and returns its name.
+    fn named_type(&mut self, schema: &Schema, hint: &str) -> String {
+        let name = self.unique_name(hint);
+        let index = self.reserve();
+        let outer_signed = std::mem::replace(&mut self.signed, false);
+
+        let mut item = String::new();
+        match schema {
+            Schema::Object(fields) => {
+                writeln!(item, "pub struct {} {{", name).unwrap();
+                for field in fields {
+                    let ty = self.within(&field.name, |generator| generator.type_of(&field.schema, &field.name));
+                    let ident = escape(&mut item, snake_case(&field.name), &field.name);
+                    writeln!(item, "    pub {}: {},", ident, ty).unwrap();
+                }
+            }
+            Schema::Enum(variants) => {
+                writeln!(item, "pub enum {} {{", name).unwrap();
+                for variant in variants {
+                    let variant_name = escape(&mut item, pascal_case(&variant.name), &variant.name);
+                    if variant.schema == Schema::Void {
+                        writeln!(item, "    {},", variant_name).unwrap();
+                    } else {
+                        let ty = self.within(&variant.name, |generator| generator.type_of(&variant.schema, &variant.name));
+                        writeln!(item, "    {}({}),", variant_name, ty).unwrap();
+                    }
+                }
+            }
+            _ => unreachable!(),
+        }
+        item.push_str("}\n");
+
+        // The signed integer types are Decodable, but not Encodable
+        let derive = if self.signed {
+            "#[derive(Decode, Debug, Clone, PartialEq)]\n"
+        } else {
+            "#[derive(Encode, Decode, Debug, Clone, PartialEq)]\n"
+        };
+        self.signed |= outer_signed;
+        self.items[index] = format!("{}{}", derive, item);
+        name
+    }
+}
+
+/// The narrowest integer type which holds every value seen, or the widest if none were.
+fn integer_type(range: Option<(i128, i128)>, kind: IntegerKind) -> &'static str {
+    let (min, max) = match range {
+        Some(range) => range,
+        None => return if kind == IntegerKind::Signed { "i64" } else { "u64" },
+    };
+    if kind == IntegerKind::Signed || min < 0 {
+        if min >= i8::MIN as i128 && max <= i8::MAX as i128 {
+            "i8"
+        } else if min >= i16::MIN as i128 && max <= i16::MAX as i128 {
+            "i16"
+        } else if min >= i32::MIN as i128 && max <= i32::MAX as i128 {
+            "i32"
+        } else {
+            "i64"
+        }
+    } else if max <= u8::MAX as i128 {
+        "u8"
+    } else if max <= u16::MAX as i128 {
+        "u16"
+    } else if max <= u32::MAX as i128 {
+        "u32"
+    } else {
+        "u64"
+    }
+}
+
+/// Splits a name from the file into words. The derive macros write names in camelCase.
+fn words(name: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    for c in name.chars() {
+        if !c.is_alphanumeric() {
+            if !word.is_empty() {
+                words.push(std::mem::take(&mut word));
+            }
+            continue;
+        }
+        if c.is_uppercase() && !word.is_empty() {
+            words.push(std::mem::take(&mut word));
+        }
+        word.extend(c.to_lowercase());
+    }
+    if !word.is_empty() {
+        words.push(word);
+    }
+    words
+}
+
+fn snake_case(name: &str) -> String {
+    let name = words(name).join("_");
+    if name.is_empty() || name.starts_with(|c: char| c.is_numeric()) {
+        format!("_{}", name)
+    } else {
+        name
+    }
+}
+
+/// The keywords which may be used as raw identifiers, as of the 2018 edition.
+const KEYWORDS: &[&str] = &[
+    "abstract", "as", "async", "await", "become", "box", "break", "const", "continue", "do", "dyn", "else", "enum", "extern", "false", "final", "fn", "for", "if", "impl", "in",
+    "let", "loop", "macro", "match", "mod", "move", "mut", "override", "priv", "pub", "ref", "return", "static", "struct", "trait", "true", "try", "type", "typeof", "unsafe",
+    "unsized", "use", "virtual", "where", "while", "yield",
+];
+
+/// The keywords which can't be raw identifiers.
+const RESERVED: &[&str] = &["crate", "self", "Self", "super"];
+
+/// Makes a field or variant identifier valid. Keywords become raw identifiers, and those which
+/// can't be are renamed, writing the attribute which keeps the name in the file.
+fn escape(item: &mut String, ident: String, name: &str) -> String {
+    if RESERVED.contains(&ident.as_str()) {
+        writeln!(item, "    #[tree_buf(rename = {:?})]", name).unwrap();
+        format!("{}_", ident)
+    } else if KEYWORDS.contains(&ident.as_str()) {
+        format!("r#{}", ident)
+    } else {
+        ident
+    }
+}
+
+fn pascal_case(name: &str) -> String {
+    let mut pascal = String::new();
+    for word in words(name) {
+        let mut chars = word.chars();
+        if let Some(first) = chars.next() {
+            pascal.extend(first.to_uppercase());
+            pascal.extend(chars);
+        }
+    }
+    if pascal.is_empty() || pascal.starts_with(|c: char| c.is_numeric()) {
+        format!("T{}", pascal)
+    } else {
+        pascal
+    }
+}
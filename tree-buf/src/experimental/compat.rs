@@ -0,0 +1,255 @@
+//! Checks whether the values written by one type can be read by another, without writing a file.
+//! This is meant for tests which assert that a new version of a type can read the files
+//! written by the last release. See also check_compatible.
+
+use crate::prelude::*;
+use std::fmt;
+
+/// The shape of a Rust type as it is written or read by Tree-Buf. The derive macros
+/// produce this for structs and enums, and it is implemented for the built-in types.
+/// Types which don't describe themselves are Opaque.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TypeSchema {
+    /// Accepts any value when reading, and writes nothing.
+    Ignore,
+    Boolean,
+    /// An unsigned integer with the number of bits
+    Integer(u32),
+    /// A signed integer with the number of bits
+    SignedInteger(u32),
+    /// A float with the number of bits
+    Float(u32),
+    String,
+    Blob,
+    Array(Box<TypeSchema>),
+    FixedArray(usize, Box<TypeSchema>),
+    Map(Box<TypeSchema>, Box<TypeSchema>),
+    Option(Box<TypeSchema>),
+    Tuple(Vec<TypeSchema>),
+    /// The fields are named as they are in the file.
+    Struct {
+        name: &'static str,
+        fields: Vec<(&'static str, TypeSchema)>,
+    },
+    /// The variants are named as they are in the file. A unit variant has Ignore for its data.
+    /// other is the variant marked #[tree_buf(other)], if any.
+    Enum {
+        name: &'static str,
+        variants: Vec<(&'static str, TypeSchema)>,
+        other: Option<&'static str>,
+    },
+    /// A type which doesn't describe itself, by its Rust type name.
+    Opaque(&'static str),
+}
+
+/// Renders as a short Rust type name.
+impl fmt::Display for TypeSchema {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TypeSchema::Ignore => write!(f, "Ignore"),
+            TypeSchema::Boolean => write!(f, "bool"),
+            TypeSchema::Integer(bits) => write!(f, "u{}", bits),
+            TypeSchema::SignedInteger(bits) => write!(f, "i{}", bits),
+            TypeSchema::Float(bits) => write!(f, "f{}", bits),
+            TypeSchema::String => write!(f, "String"),
+            TypeSchema::Blob => write!(f, "Blob"),
+            TypeSchema::Array(item) => write!(f, "Vec<{}>", item),
+            TypeSchema::FixedArray(len, item) => write!(f, "[{}; {}]", item, len),
+            TypeSchema::Map(key, value) => write!(f, "HashMap<{}, {}>", key, value),
+            TypeSchema::Option(value) => write!(f, "Option<{}>", value),
+            TypeSchema::Tuple(items) => {
+                write!(f, "(")?;
+                for (i, item) in items.iter().enumerate() {
+                    if i != 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", item)?;
+                }
+                write!(f, ")")
+            }
+            TypeSchema::Struct { name, .. } | TypeSchema::Enum { name, .. } => write!(f, "{}", name),
+            TypeSchema::Opaque(name) => write!(f, "{}", name),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Change {
+    /// The reader requires a field which the writer doesn't write, or the writer
+    /// writes an enum variant which the reader doesn't have. Decoding fails.
+    Missing,
+    /// The reader has an Option field which the writer doesn't write. It decodes as None.
+    Defaulted,
+    /// The writer writes an enum variant which the reader doesn't have, and it decodes as
+    /// the reader's #[tree_buf(other)] variant.
+    Fallback { reader: String },
+    /// Every value of the writer's type fits the reader's type.
+    Widened { writer: String, reader: String },
+    /// Values which don't fit the reader's type fail to decode or lose precision.
+    Narrowed { writer: String, reader: String },
+    /// The reader can't decode what the writer writes.
+    Mismatched { writer: String, reader: String },
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Difference {
+    /// The fields, tuple items and enum variants which lead to the value, joined with '.'
+    /// The keys of a map are under "{key}".
+    pub path: String,
+    pub change: Change,
+}
+
+/// The differences between a writer and reader type. Those which are the same aren't listed.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Compatibility {
+    pub differences: Vec<Difference>,
+}
+
+impl Compatibility {
+    /// True if every value of the writer decodes as the reader without an error.
+    pub fn is_compatible(&self) -> bool {
+        self.differences.iter().all(|difference| match difference.change {
+            Change::Defaulted | Change::Fallback { .. } | Change::Widened { .. } => true,
+            Change::Missing | Change::Narrowed { .. } | Change::Mismatched { .. } => false,
+        })
+    }
+}
+
+impl fmt::Display for Compatibility {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for difference in self.differences.iter() {
+            let path = if difference.path.is_empty() { "<root>" } else { &difference.path };
+            match &difference.change {
+                Change::Missing => writeln!(f, "{}: missing", path)?,
+                Change::Defaulted => writeln!(f, "{}: defaulted to None", path)?,
+                Change::Fallback { reader } => writeln!(f, "{}: read as {}", path, reader)?,
+                Change::Widened { writer, reader } => writeln!(f, "{}: widened from {} to {}", path, writer, reader)?,
+                Change::Narrowed { writer, reader } => writeln!(f, "{}: narrowed from {} to {}", path, writer, reader)?,
+                Change::Mismatched { writer, reader } => writeln!(f, "{}: mismatched {} and {}", path, writer, reader)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Compares the schema of a type which writes files with one which reads them.
+/// The rules are those of decoding arrays, which are stricter than decoding a single value at the root.
+#[cfg(all(feature = "encode", feature = "decode"))]
+pub fn check_compatible<Writer: Encodable, Reader: Decodable>() -> Compatibility {
+    compare_schemas(&Writer::encode_schema(), &Reader::decode_schema())
+}
+
+/// Like check_compatible, for schemas which are already in hand.
+pub fn compare_schemas(writer: &TypeSchema, reader: &TypeSchema) -> Compatibility {
+    let mut compatibility = Compatibility::default();
+    compare(writer, reader, &mut String::new(), &mut compatibility.differences);
+    compatibility
+}
+
+fn within(path: &mut String, segment: &str, f: impl FnOnce(&mut String)) {
+    let len = path.len();
+    if !path.is_empty() {
+        path.push('.');
+    }
+    path.push_str(segment);
+    f(path);
+    path.truncate(len);
+}
+
+fn push(differences: &mut Vec<Difference>, path: &str, change: Change) {
+    differences.push(Difference { path: path.to_owned(), change });
+}
+
+fn compare(writer: &TypeSchema, reader: &TypeSchema, path: &mut String, differences: &mut Vec<Difference>) {
+    use TypeSchema::*;
+
+    match (writer, reader) {
+        (_, Ignore) => {}
+        (Ignore, Option(_)) => {}
+        (Boolean, Boolean) | (String, String) | (Blob, Blob) => {}
+        (Integer(w), Integer(r)) | (SignedInteger(w), SignedInteger(r)) | (Float(w), Float(r)) => {
+            let (writer, reader) = (writer.to_string(), reader.to_string());
+            if r > w {
+                push(differences, path, Change::Widened { writer, reader })
+            } else if r < w {
+                push(differences, path, Change::Narrowed { writer, reader })
+            }
+        }
+        // A signed integer needs a bit more than an unsigned one for the same values.
+        (Integer(w), SignedInteger(r)) => {
+            let (writer, reader) = (writer.to_string(), reader.to_string());
+            if r > w {
+                push(differences, path, Change::Widened { writer, reader })
+            } else {
+                push(differences, path, Change::Narrowed { writer, reader })
+            }
+        }
+        // Booleans decode as 0 or 1
+        (Boolean, Integer(_)) | (Boolean, SignedInteger(_)) => push(
+            differences,
+            path,
+            Change::Widened {
+                writer: writer.to_string(),
+                reader: reader.to_string(),
+            },
+        ),
+        // Every integer is exact in a float with enough mantissa bits. See also numeric.rs
+        (Integer(w), Float(r)) | (SignedInteger(w), Float(r)) => {
+            let (writer, reader) = (writer.to_string(), reader.to_string());
+            if (*r == 32 && *w <= 16) || (*r == 64 && *w <= 32) {
+                push(differences, path, Change::Widened { writer, reader })
+            } else {
+                push(differences, path, Change::Narrowed { writer, reader })
+            }
+        }
+        (Float(_), Integer(_)) | (Float(_), SignedInteger(_)) | (SignedInteger(_), Integer(_)) => push(
+            differences,
+            path,
+            Change::Narrowed {
+                writer: writer.to_string(),
+                reader: reader.to_string(),
+            },
+        ),
+        (Array(w), Array(r)) | (FixedArray(_, w), Array(r)) | (Option(w), Option(r)) => compare(w, r, path, differences),
+        (FixedArray(w_len, w), FixedArray(r_len, r)) if w_len == r_len => compare(w, r, path, differences),
+        (Map(w_key, w_value), Map(r_key, r_value)) => {
+            within(path, "{key}", |path| compare(w_key, r_key, path, differences));
+            compare(w_value, r_value, path, differences);
+        }
+        (Tuple(w), Tuple(r)) if w.len() == r.len() => {
+            for (i, (w, r)) in w.iter().zip(r.iter()).enumerate() {
+                within(path, &i.to_string(), |path| compare(w, r, path, differences));
+            }
+        }
+        (Struct { fields: w, .. }, Struct { fields: r, .. }) => {
+            // Fields which are written but not read are skipped when decoding.
+            for (name, r) in r.iter() {
+                within(path, name, |path| match (w.iter().find(|(w_name, _)| w_name == name), r) {
+                    (Some((_, w)), r) => compare(w, r, path, differences),
+                    (None, Ignore) => {}
+                    (None, Option(_)) => push(differences, path, Change::Defaulted),
+                    (None, _) => push(differences, path, Change::Missing),
+                });
+            }
+        }
+        (Enum { variants: w, .. }, Enum { variants: r, other, .. }) => {
+            // Variants which are read but never written don't matter.
+            for (name, w) in w.iter() {
+                within(path, name, |path| match (r.iter().find(|(r_name, _)| r_name == name), other) {
+                    (Some((_, r)), _) => compare(w, r, path, differences),
+                    (None, Some(other)) => push(differences, path, Change::Fallback { reader: (*other).to_owned() }),
+                    (None, None) => push(differences, path, Change::Missing),
+                });
+            }
+        }
+        (Opaque(w), Opaque(r)) if w == r => {}
+        _ => push(
+            differences,
+            path,
+            Change::Mismatched {
+                writer: writer.to_string(),
+                reader: reader.to_string(),
+            },
+        ),
+    }
+}
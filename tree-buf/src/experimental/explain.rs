@@ -0,0 +1,138 @@
+//! Explains why each array was encoded the way it was. When encoding with a report, every
+//! choice between compressors is recorded along with the estimate and actual size of each.
+//! This is useful for tuning the layout of data, and for finding estimates which are far off.
+
+use crate::prelude::*;
+use std::fmt;
+
+/// How one array of values was encoded.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CompressDecision {
+    /// The fields and tuple items which lead to the array, joined with '.'
+    /// Several arrays may have the same path, like the lens and values of a Vec.
+    pub path: String,
+    /// The Rust type of the values
+    pub value_type: &'static str,
+    /// The number of values in the array
+    pub len: usize,
+    /// The number of values that the estimates were made from, or None if there weren't any estimates.
+    pub sample_len: Option<usize>,
+    /// In the order of the compressor set, which is not the order they were tried in.
+    pub candidates: Vec<Candidate>,
+    /// The index of the candidate which was written.
+    pub chosen: usize,
+}
+
+/// One compressor which could have encoded an array.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Candidate {
+    pub compressor: String,
+    /// The size estimated by fast_size_for from the sample. None if it returned Err,
+    /// meaning that it couldn't compress the data or another compressor surely does better.
+    pub estimate: Option<usize>,
+    pub outcome: Outcome,
+    /// Arrays within this encoding which had their own decision. Eg: the indices of a Dictionary.
+    /// These are recorded even if this candidate wasn't written.
+    pub nested: Vec<CompressDecision>,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Outcome {
+    /// Another candidate was written before this one was tried.
+    NotTried,
+    /// The compressor returned Err when compressing the whole array.
+    Failed,
+    /// The compressor succeeded, but the result wasn't used. This happens when the encoding
+    /// is denied by EncodingRules, or with CompressionEffort::Exhaustive when another is smaller.
+    Rejected { encoding: ArrayTypeId, size: usize },
+    /// The compressor succeeded and was written. The size includes the lens of the encoding.
+    Written { encoding: ArrayTypeId, size: usize },
+}
+
+impl Outcome {
+    /// The actual size if the compressor succeeded.
+    pub fn size(&self) -> Option<usize> {
+        match self {
+            Outcome::Rejected { size, .. } | Outcome::Written { size, .. } => Some(*size),
+            Outcome::NotTried | Outcome::Failed => None,
+        }
+    }
+}
+
+/// Every decision made by compress during an encode, in the order written.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct EncodeReport {
+    pub decisions: Vec<CompressDecision>,
+}
+
+impl EncodeReport {
+    /// Every decision, including those nested within candidates, depth first.
+    pub fn all(&self) -> Vec<&CompressDecision> {
+        let mut all = Vec::new();
+        let mut stack: Vec<_> = self.decisions.iter().rev().collect();
+        while let Some(decision) = stack.pop() {
+            all.push(decision);
+            for candidate in decision.candidates.iter().rev() {
+                stack.extend(candidate.nested.iter().rev());
+            }
+        }
+        all
+    }
+}
+
+impl fmt::Display for EncodeReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for decision in self.decisions.iter() {
+            fmt_decision(decision, 0, f)?;
+        }
+        Ok(())
+    }
+}
+
+fn fmt_decision(decision: &CompressDecision, depth: usize, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let indent = "\t".repeat(depth);
+    let path = if decision.path.is_empty() { "<root>" } else { &decision.path };
+    write!(f, "{}{}: {}x {}", indent, path, decision.len, decision.value_type)?;
+    if let Some(sample_len) = decision.sample_len {
+        write!(f, ", sampled {}", sample_len)?;
+    }
+    writeln!(f)?;
+
+    for (i, candidate) in decision.candidates.iter().enumerate() {
+        let marker = if i == decision.chosen { '*' } else { ' ' };
+        write!(f, "{}\t{} {}: ", indent, marker, candidate.compressor)?;
+        match candidate.estimate {
+            Some(estimate) => write!(f, "estimate {}", estimate)?,
+            None => write!(f, "no estimate")?,
+        }
+        match candidate.outcome {
+            Outcome::NotTried => writeln!(f, ", not tried")?,
+            Outcome::Failed => writeln!(f, ", failed")?,
+            Outcome::Rejected { encoding, size } => writeln!(f, ", rejected {:?} of {}", encoding, size)?,
+            Outcome::Written { encoding, size } => writeln!(f, ", wrote {:?} of {}", encoding, size)?,
+        }
+        for nested in candidate.nested.iter() {
+            fmt_decision(nested, depth + 2, f)?;
+        }
+    }
+    Ok(())
+}
+
+struct RecordDecisions;
+impl EncodeOptionsOverride for RecordDecisions {
+    #[inline(always)]
+    fn record_decisions(&self) -> Option<bool> {
+        Some(true)
+    }
+}
+
+/// Encodes the same bytes as encode_with_options, along with the decisions made along the way.
+#[cfg(feature = "encode")]
+pub fn encode_with_report<T: Encodable>(value: &T, options: &impl EncodeOptions) -> (Vec<u8>, EncodeReport) {
+    profile_fn!(T, encode_with_report);
+    let options = override_encode_options(options, RecordDecisions);
+    let mut lens = Vec::new();
+    let mut bytes = Vec::new();
+    let decisions = crate::experimental::options::encode_into(value, &options, &mut lens, &mut bytes);
+    (bytes, EncodeReport { decisions })
+}
@@ -0,0 +1,419 @@
+//! Writes the values in a Tree-Buf file as JSON, for looking at files without the types that wrote them.
+//! Objects have their fields sorted by name, an enum value is {"variant": value} (or "variant"
+//! if it has no value) and a blob is a hex string. Values with a semantic tag are written as
+//! what they mean: timestamps as RFC 3339 strings, durations as ISO 8601 strings, UUIDs and IP
+//! addresses in their usual text form and decimals as numbers.
+
+use super::stats::push_json_str;
+use crate::prelude::*;
+use std::convert::TryFrom;
+use std::net::{Ipv4Addr, Ipv6Addr};
+use std::vec::IntoIter;
+
+/// Decodes a valid Tree-Buf file as JSON, with no limits. This recurses for each level of
+/// nesting, so for files from untrusted sources use to_json_with_options with UntrustedInput.
+pub fn to_json(bytes: &[u8]) -> DecodeResult<String> {
+    to_json_with_options(bytes, &DecodeOptionsDefault)
+}
+
+/// Like to_json, within the limits of the options. Eg: MaxDepth also limits the recursion here.
+pub fn to_json_with_options(bytes: &[u8], options: &impl DecodeOptions) -> DecodeResult<String> {
+    // See also decode_with_options
+    if options.max_elements() == usize::MAX {
+        return root_json(decode_root(bytes, options)?, options);
+    }
+    let budget = ElementBudget::new(options.max_elements());
+    let options = override_decode_options(options, &budget);
+    let root = decode_root(bytes, &options)?;
+    let result = root_json(root, &options);
+    if budget.exceeded() {
+        return Err(DecodeError::LimitExceeded);
+    }
+    result
+}
+
+/// The value of a tagged branch, before it is written as what the tag says.
+enum Scalar {
+    Integer(i128),
+    Float(f64),
+    Bytes(Vec<u8>),
+}
+
+/// The kinds of values in an array which are decoded by a type rather than by their shape.
+#[derive(Copy, Clone)]
+enum Leaf {
+    Integer,
+    Float,
+    Boolean,
+    String,
+    Blob,
+}
+
+fn root_json(branch: DynRootBranch<'_>, options: &impl DecodeOptions) -> DecodeResult<String> {
+    Ok(match branch {
+        DynRootBranch::Object { fields } => {
+            let mut fields: Vec<_> = fields.into_iter().collect();
+            fields.sort_by(|a, b| a.0.cmp(b.0));
+            let mut members = Vec::with_capacity(fields.len());
+            for (name, value) in fields {
+                members.push(member(&string(name), &root_json(value, options)?));
+            }
+            object(&members)
+        }
+        DynRootBranch::Tuple { fields } => array(&fields.into_iter().map(|field| root_json(field, options)).collect::<DecodeResult<Vec<_>>>()?),
+        DynRootBranch::Enum { discriminant, value } => match *value {
+            DynRootBranch::Void => string(discriminant),
+            value => object(&[member(&string(discriminant), &root_json(value, options)?)]),
+        },
+        DynRootBranch::Array0 => "[]".to_owned(),
+        DynRootBranch::Array1(item) => array(&[root_json(*item, options)?]),
+        DynRootBranch::Array { len, values } => array(&column(values, len, options)?),
+        DynRootBranch::Integer(RootInteger::U(value)) => value.to_string(),
+        DynRootBranch::Integer(RootInteger::S(value)) => value.to_string(),
+        DynRootBranch::Boolean(value) => value.to_string(),
+        DynRootBranch::Float(RootFloat::F64(value)) => float(value),
+        DynRootBranch::Float(RootFloat::F32(value)) => float(value as f64),
+        DynRootBranch::Float(RootFloat::NaN) => float(f64::NAN),
+        DynRootBranch::Void => "null".to_owned(),
+        DynRootBranch::String(value) => string(value),
+        DynRootBranch::Blob(bytes) => string(&hex(&bytes.decompress(options)?)),
+        DynRootBranch::Map0 => "{}".to_owned(),
+        DynRootBranch::Map1 { key, value } => object(&[member(&root_json(*key, options)?, &root_json(*value, options)?)]),
+        DynRootBranch::Map { len, keys, values } => {
+            let keys = column(keys, len, options)?;
+            let values = column(values, len, options)?;
+            object(&keys.iter().zip(values.iter()).map(|(key, value)| member(key, value)).collect::<Vec<_>>())
+        }
+        DynRootBranch::Tagged { tag, value } => {
+            let value = match *value {
+                DynRootBranch::Integer(RootInteger::U(value)) => Scalar::Integer(value as i128),
+                DynRootBranch::Integer(RootInteger::S(value)) => Scalar::Integer(value as i128),
+                DynRootBranch::Float(RootFloat::F64(value)) => Scalar::Float(value),
+                DynRootBranch::Float(RootFloat::F32(value)) => Scalar::Float(value as f64),
+                DynRootBranch::Float(RootFloat::NaN) => Scalar::Float(f64::NAN),
+                DynRootBranch::Blob(bytes) => Scalar::Bytes(bytes.decompress(options)?),
+                DynRootBranch::Tuple { fields } if fields.len() == 3 && time_unit(tag).is_some() => from_parts(tag, Decodable::decode(DynRootBranch::Tuple { fields }, options)?)?,
+                // A tag on anything else doesn't change how it is written.
+                value => return root_json(value, options),
+            };
+            tagged(tag, value)
+        }
+    })
+}
+
+/// The JSON of each of the first count values in an array branch.
+fn column(branch: DynArrayBranch<'_>, count: usize, options: &impl DecodeOptions) -> DecodeResult<Vec<String>> {
+    // Each value is written to its own string, even where the branch has nothing to decode.
+    take_elements(options, count)?;
+    Ok(match branch {
+        DynArrayBranch::Object { fields } => {
+            let mut fields: Vec<_> = fields.into_iter().collect();
+            fields.sort_by(|a, b| a.0.cmp(b.0));
+            let mut columns = Vec::with_capacity(fields.len());
+            for (name, field) in fields {
+                columns.push((string(name), column(field, count, options)?.into_iter()));
+            }
+            (0..count)
+                .map(|_| object(&columns.iter_mut().map(|(name, values)| member(name, &next(values))).collect::<Vec<_>>()))
+                .collect()
+        }
+        DynArrayBranch::Tuple { fields } => {
+            let mut columns = Vec::with_capacity(fields.len());
+            for field in fields {
+                columns.push(column(field, count, options)?.into_iter());
+            }
+            (0..count).map(|_| array(&columns.iter_mut().map(next).collect::<Vec<_>>())).collect()
+        }
+        DynArrayBranch::Array0 => vec!["[]".to_owned(); count],
+        DynArrayBranch::Array { len, values } => {
+            let lens = lens(*len, count, options)?;
+            let mut values = column(*values, total(&lens, options)?, options)?.into_iter();
+            lens.iter().map(|&len| array(&values.by_ref().take(len).collect::<Vec<_>>())).collect()
+        }
+        DynArrayBranch::ArrayFixed { len, values } => {
+            let lens = vec![len; count];
+            let mut values = column(*values, total(&lens, options)?, options)?.into_iter();
+            lens.iter().map(|&len| array(&values.by_ref().take(len).collect::<Vec<_>>())).collect()
+        }
+        DynArrayBranch::Map0 => vec!["{}".to_owned(); count],
+        DynArrayBranch::Map { len, keys, values } => {
+            let lens = lens(*len, count, options)?;
+            let total = total(&lens, options)?;
+            let mut keys = column(*keys, total, options)?.into_iter();
+            let mut values = column(*values, total, options)?.into_iter();
+            lens.iter()
+                .map(|&len| object(&keys.by_ref().zip(values.by_ref()).take(len).map(|(key, value)| member(&key, &value)).collect::<Vec<_>>()))
+                .collect()
+        }
+        DynArrayBranch::Nullable { opt, values } => {
+            let opt = decode_column::<bool>(*opt, count, options)?;
+            let mut values = column(*values, opt.iter().filter(|&&opt| opt).count(), options)?.into_iter();
+            opt.iter().map(|&opt| if opt { next(&mut values) } else { "null".to_owned() }).collect()
+        }
+        DynArrayBranch::Enum { discriminants, variants } => {
+            let discriminants = decode_column::<u64>(*discriminants, count, options)?;
+            let mut columns = Vec::with_capacity(variants.len());
+            for (i, variant) in variants.into_iter().enumerate() {
+                let variant_count = discriminants.iter().filter(|&&d| d == i as u64).count();
+                let unit = matches!(variant.data, DynArrayBranch::Void);
+                columns.push((string(variant.ident), unit, column(variant.data, variant_count, options)?.into_iter()));
+            }
+            let mut json = Vec::with_capacity(count);
+            for discriminant in discriminants {
+                let (name, unit, values) = columns.get_mut(discriminant as usize).ok_or(DecodeError::InvalidFormat)?;
+                json.push(if *unit { name.clone() } else { object(&[member(name, &next(values))]) });
+            }
+            json
+        }
+        DynArrayBranch::Tagged { tag, value } => {
+            let values: Vec<Scalar> = match *value {
+                DynArrayBranch::Tuple { fields } if fields.len() == 3 && time_unit(tag).is_some() => {
+                    let mut fields = fields.into_iter();
+                    let negatives = decode_column::<bool>(fields.next().unwrap(), count, options)?;
+                    let seconds = decode_column::<u64>(fields.next().unwrap(), count, options)?;
+                    let remainders = decode_column::<u64>(fields.next().unwrap(), count, options)?;
+                    negatives
+                        .into_iter()
+                        .zip(seconds)
+                        .zip(remainders)
+                        .map(|((negative, seconds), remainder)| from_parts(tag, (negative, seconds, remainder)))
+                        .collect::<DecodeResult<_>>()?
+                }
+                value => match leaf(&value) {
+                    Some(Leaf::Integer) => decode_column::<u64>(value, count, options)?.into_iter().map(|v| Scalar::Integer(v as i128)).collect(),
+                    Some(Leaf::Float) => decode_column::<f64>(value, count, options)?.into_iter().map(Scalar::Float).collect(),
+                    Some(Leaf::Blob) => decode_column::<Blob>(value, count, options)?.into_iter().map(|blob| Scalar::Bytes(blob.0)).collect(),
+                    // A tag on anything else doesn't change how it is written.
+                    _ => return column(value, count, options),
+                },
+            };
+            values.into_iter().map(|value| tagged(tag, value)).collect()
+        }
+        // Extensions can only be decoded by their codec, which this doesn't have.
+        DynArrayBranch::Void | DynArrayBranch::Extension { .. } => vec!["null".to_owned(); count],
+        branch => match leaf(&branch) {
+            Some(Leaf::Integer) => decode_column::<u64>(branch, count, options)?.iter().map(u64::to_string).collect(),
+            Some(Leaf::Float) => decode_column::<f64>(branch, count, options)?.into_iter().map(float).collect(),
+            Some(Leaf::Boolean) => decode_column::<bool>(branch, count, options)?.iter().map(bool::to_string).collect(),
+            Some(Leaf::String) => decode_column::<String>(branch, count, options)?.iter().map(|value| string(value)).collect(),
+            Some(Leaf::Blob) => decode_column::<Blob>(branch, count, options)?.iter().map(|blob| string(&hex(&blob.0))).collect(),
+            None => return Err(DecodeError::SchemaMismatch),
+        },
+    })
+}
+
+/// The kind of values in a branch, looking past the encodings which only change how they are stored.
+fn leaf(branch: &DynArrayBranch<'_>) -> Option<Leaf> {
+    match branch {
+        DynArrayBranch::RLE { runs: _, values } | DynArrayBranch::Dictionary { indices: _, values } => leaf(values),
+        DynArrayBranch::Integer(_) => Some(Leaf::Integer),
+        DynArrayBranch::Float(_) => Some(Leaf::Float),
+        DynArrayBranch::Boolean(_) => Some(Leaf::Boolean),
+        DynArrayBranch::String(_)
+        | DynArrayBranch::BrotliUtf8 { .. }
+        | DynArrayBranch::PrefixUtf8 { .. }
+        | DynArrayBranch::FsstUtf8 { .. }
+        | DynArrayBranch::HexUtf8 { .. }
+        | DynArrayBranch::DecimalUtf8 { .. } => Some(Leaf::String),
+        DynArrayBranch::Blob { .. } => Some(Leaf::Blob),
+        _ => None,
+    }
+}
+
+fn decode_column<T>(branch: DynArrayBranch<'_>, count: usize, options: &impl DecodeOptions) -> DecodeResult<Vec<T>>
+where
+    IntoIter<T>: InfallibleDecoderArray<Decode = T>,
+{
+    let mut values = IntoIter::<T>::new_infallible(branch, options)?;
+    Ok((0..count).map(|_| values.decode_next_infallible()).collect())
+}
+
+fn lens(branch: DynArrayBranch<'_>, count: usize, options: &impl DecodeOptions) -> DecodeResult<Vec<usize>> {
+    decode_column::<u64>(branch, count, options)?
+        .into_iter()
+        .map(|len| usize::try_from(len).map_err(|_| DecodeError::LimitExceeded))
+        .collect()
+}
+
+/// The number of values in all of the arrays, which a handful of bytes could otherwise make arbitrarily large.
+fn total(lens: &[usize], options: &impl DecodeOptions) -> DecodeResult<usize> {
+    let total = lens.iter().try_fold(0usize, |total, &len| total.checked_add(len)).ok_or(DecodeError::LimitExceeded)?;
+    if total > remaining_elements(options) {
+        return Err(DecodeError::LimitExceeded);
+    }
+    Ok(total)
+}
+
+/// Columns have exactly as many values as are taken from them.
+fn next(values: &mut IntoIter<String>) -> String {
+    values.next().unwrap_or_else(|| "null".to_owned())
+}
+
+fn tagged(tag: SemanticTag, value: Scalar) -> String {
+    match (tag, value) {
+        (SemanticTag::Timestamp(unit), value) => match nanos(&value, unit) {
+            Some(nanos) => string(&timestamp(nanos)),
+            None => untagged(value),
+        },
+        (SemanticTag::Duration(unit), value) => match nanos(&value, unit) {
+            Some(nanos) => string(&duration(nanos)),
+            None => untagged(value),
+        },
+        (SemanticTag::Uuid, Scalar::Bytes(bytes)) if bytes.len() == 16 => string(&uuid(&bytes)),
+        (SemanticTag::IpAddr, Scalar::Bytes(bytes)) if bytes.len() == 4 => string(&Ipv4Addr::new(bytes[0], bytes[1], bytes[2], bytes[3]).to_string()),
+        (SemanticTag::IpAddr, Scalar::Bytes(bytes)) if bytes.len() == 16 => {
+            let mut octets = [0u8; 16];
+            octets.copy_from_slice(&bytes);
+            string(&Ipv6Addr::from(octets).to_string())
+        }
+        (SemanticTag::Decimal { scale }, Scalar::Integer(mantissa)) => decimal(mantissa, scale),
+        (SemanticTag::Decimal { scale }, Scalar::Float(mantissa)) => float(mantissa / 10f64.powi(scale.min(i32::MAX as u32) as i32)),
+        (_, value) => untagged(value),
+    }
+}
+
+/// The unit of a tag whose values may be written as their sign and magnitude.
+fn time_unit(tag: SemanticTag) -> Option<TimeUnit> {
+    match tag {
+        SemanticTag::Timestamp(unit) | SemanticTag::Duration(unit) => Some(unit),
+        _ => None,
+    }
+}
+
+fn from_parts(tag: SemanticTag, (negative, seconds, remainder): (bool, u64, u64)) -> DecodeResult<Scalar> {
+    let unit = time_unit(tag).ok_or(DecodeError::SchemaMismatch)?;
+    let value = unit.from_parts(negative, seconds, remainder).ok_or(DecodeError::InvalidFormat)?;
+    Ok(Scalar::Integer(value))
+}
+
+fn untagged(value: Scalar) -> String {
+    match value {
+        Scalar::Integer(value) => value.to_string(),
+        Scalar::Float(value) => float(value),
+        Scalar::Bytes(bytes) => string(&hex(&bytes)),
+    }
+}
+
+const NANOS_PER_SECOND: i128 = 1_000_000_000;
+
+fn nanos(value: &Scalar, unit: TimeUnit) -> Option<i128> {
+    let per_unit = NANOS_PER_SECOND / unit.per_second() as i128;
+    match value {
+        Scalar::Integer(value) => value.checked_mul(per_unit),
+        Scalar::Float(value) => {
+            let nanos = (value * per_unit as f64).round();
+            // Beyond this, the date has far more digits than any tool reads.
+            if nanos.is_finite() && nanos.abs() < 1e30 {
+                Some(nanos as i128)
+            } else {
+                None
+            }
+        }
+        Scalar::Bytes(_) => None,
+    }
+}
+
+/// Eg: 2020-09-13T12:26:40.5Z
+fn timestamp(nanos: i128) -> String {
+    let seconds = nanos.div_euclid(NANOS_PER_SECOND);
+    let (days, seconds_of_day) = (seconds.div_euclid(86_400), seconds.rem_euclid(86_400));
+    let (year, month, day) = civil_from_days(days);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}{}Z",
+        year,
+        month,
+        day,
+        seconds_of_day / 3600,
+        seconds_of_day / 60 % 60,
+        seconds_of_day % 60,
+        fraction(nanos.rem_euclid(NANOS_PER_SECOND))
+    )
+}
+
+/// Eg: PT90.25S
+fn duration(nanos: i128) -> String {
+    let sign = if nanos < 0 { "-" } else { "" };
+    let nanos = nanos.abs();
+    format!("{}PT{}{}S", sign, nanos / NANOS_PER_SECOND, fraction(nanos % NANOS_PER_SECOND))
+}
+
+/// The fractional part of a second, without trailing zeros.
+fn fraction(nanos: i128) -> String {
+    if nanos == 0 {
+        String::new()
+    } else {
+        format!(".{:09}", nanos).trim_end_matches('0').to_owned()
+    }
+}
+
+/// The proleptic Gregorian date of a number of days since 1970-01-01.
+/// See also http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+fn civil_from_days(days: i128) -> (i128, i128, i128) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+fn decimal(mantissa: i128, scale: u32) -> String {
+    // An i128 has at most 39 digits, so a larger scale is only leading zeros.
+    if scale > 38 {
+        return format!("{}e-{}", mantissa, scale);
+    }
+    let scale = scale as usize;
+    let digits = format!("{:0width$}", mantissa.unsigned_abs(), width = scale + 1);
+    let (whole, fraction) = digits.split_at(digits.len() - scale);
+    let sign = if mantissa < 0 { "-" } else { "" };
+    if fraction.is_empty() {
+        format!("{}{}", sign, whole)
+    } else {
+        format!("{}{}.{}", sign, whole, fraction)
+    }
+}
+
+fn uuid(bytes: &[u8]) -> String {
+    let hex = hex(bytes);
+    format!("{}-{}-{}-{}-{}", &hex[0..8], &hex[8..12], &hex[12..16], &hex[16..20], &hex[20..32])
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// JSON has no NaN or infinity.
+fn float(value: f64) -> String {
+    if value.is_finite() {
+        value.to_string()
+    } else {
+        "null".to_owned()
+    }
+}
+
+fn string(value: &str) -> String {
+    let mut json = String::with_capacity(value.len() + 2);
+    push_json_str(&mut json, value);
+    json
+}
+
+/// JSON keys are strings, so keys of other types are written as the string of their JSON.
+fn member(key: &str, value: &str) -> String {
+    if key.starts_with('"') {
+        format!("{}:{}", key, value)
+    } else {
+        format!("{}:{}", string(key), value)
+    }
+}
+
+fn object(members: &[String]) -> String {
+    format!("{{{}}}", members.join(","))
+}
+
+fn array(items: &[String]) -> String {
+    format!("[{}]", items.join(","))
+}
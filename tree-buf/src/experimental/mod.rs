@@ -3,7 +3,14 @@
 //! or resulting file may change unexpectedly with any version. Even so, it is worth
 //! poking around.
 
-#[doc(hidden)]
+pub mod codecs;
+pub mod codegen;
+pub mod compat;
+pub mod explain;
+#[cfg(feature = "decode")]
+pub mod json;
+pub mod schema;
 pub mod scratch;
+pub mod semantic;
 pub mod stats;
 pub mod options;
\ No newline at end of file
@@ -3,39 +3,101 @@
 //! but the eventual goal is to be able to specify options hierarchically on fields, optionally
 //! recursively. This API is very likely to change.
 
+use crate::experimental::explain::CompressDecision;
 use crate::prelude::*;
 
 pub use crate::internal::options::{
+    Canonical,
+    Checksums,
+    CompressionEffort,
     DisableParallel,
     EnableParallel,
+    EncodingRules,
     LosslessFloat,
     LossyFloatTolerance,
+    LossyNumbers,
+    MaxCollectionLen,
+    MaxDepth,
+    MAX_DEPTH,
+    MaxElements,
+    MaxStringLen,
+    Sampling,
+    UntrustedInput,
     EncodeOptions, DecodeOptions
 };
 
+pub use crate::internal::ArrayTypeId;
 pub use crate::{encode_options, decode_options};
 
 
 #[cfg(feature = "encode")]
 pub fn encode_with_options<T: Encodable>(value: &T, options: &impl EncodeOptions) -> Vec<u8> {
     profile_fn!(T, encode_with_options);
-    use crate::internal::encodings::varint::encode_suffix_varint;
-
     let mut lens = Vec::new();
     let mut bytes = Vec::new();
-    let mut stream = EncoderStream::new(&mut bytes, &mut lens, options);
-    stream.encode_with_id(|stream| T::encode_root(value, stream));
+    encode_into(value, options, &mut lens, &mut bytes);
+    bytes
+}
+
+/// Appends the encoded value to bytes, returning any decisions recorded along the way.
+/// Lens must be empty, and is left holding garbage.
+#[cfg(feature = "encode")]
+pub(crate) fn encode_into<T: Encodable>(value: &T, options: &impl EncodeOptions, lens: &mut Vec<usize>, bytes: &mut Vec<u8>) -> Vec<CompressDecision> {
+    use crate::internal::encodings::varint::encode_suffix_varint;
+    debug_assert!(lens.is_empty());
+
+    let start = bytes.len();
+    // See also 5b8b0f0e-3c57-4f6a-9f3c-2f3e2e5e6f0b
+    if options.checksums() {
+        bytes.push(RootTypeId::Checksums.into());
+    }
+    let mut stream = EncoderStream::new(bytes, lens, options);
+    with_canonical_maps(options.canonical(), || stream.encode_with_id(|stream| T::encode_root(value, stream)));
+    let decisions = stream.decisions;
 
     for len in lens.iter().rev() {
-        encode_suffix_varint(*len as u64, &mut bytes);
+        encode_suffix_varint(*len as u64, bytes);
     }
 
-    bytes
+    if options.checksums() {
+        let checksum = crc32c::crc32c(&bytes[start..]);
+        bytes.extend_from_slice(&checksum.to_le_bytes());
+    }
+    decisions
 }
 
 #[cfg(feature = "decode")]
 pub fn decode_with_options<T: Decodable>(bytes: &[u8], options: &impl DecodeOptions) -> DecodeResult<T> {
     profile_fn!(T, decode_with_options);
-    let sticks = decode_root(bytes)?;
-    T::decode(sticks, options)
-}
\ No newline at end of file
+    // The budget is shared between threads, so it is only kept when there is something to count.
+    if options.max_elements() == usize::MAX {
+        let sticks = decode_root(bytes, options)?;
+        return T::decode(sticks, options);
+    }
+    let budget = ElementBudget::new(options.max_elements());
+    let options = crate::internal::options::override_decode_options(options, &budget);
+    let sticks = decode_root(bytes, &options)?;
+    let result = T::decode(sticks, &options);
+    if budget.exceeded() {
+        return Err(DecodeError::LimitExceeded);
+    }
+    result
+}
+
+/// See also decode_with_options and crate::decode_into
+#[cfg(feature = "decode")]
+pub fn decode_into_with_options<T: Decodable>(bytes: &[u8], options: &impl DecodeOptions, into: &mut T) -> DecodeResult<()> {
+    profile_fn!(T, decode_into_with_options);
+    if options.max_elements() == usize::MAX {
+        let sticks = decode_root(bytes, options)?;
+        return T::decode_into(sticks, options, into);
+    }
+    let budget = ElementBudget::new(options.max_elements());
+    let options = crate::internal::options::override_decode_options(options, &budget);
+    let sticks = decode_root(bytes, &options)?;
+    let result = T::decode_into(sticks, &options, into);
+    if budget.exceeded() {
+        return Err(DecodeError::LimitExceeded);
+    }
+    result
+}
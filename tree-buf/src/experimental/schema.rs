@@ -0,0 +1,290 @@
+//! Reads the logical schema of a Tree-Buf file, for files which come without the types that wrote them.
+//! The schema describes the shape of the data (fields, variants, nullability, and so on)
+//! rather than how it was encoded, so files of the same type have the same schema
+//! even when their data is compressed differently.
+//!
+//! Some types are lowered when written and can't be told apart in the file.
+//! Integer arrays with only 0 and 1 are written as booleans, and floats at the root
+//! which are whole numbers may be written as integers.
+
+use crate::prelude::*;
+use std::collections::HashMap;
+use std::fmt;
+use std::ops::RangeInclusive;
+use std::vec::IntoIter;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Schema {
+    /// Nothing was written. Eg: a unit enum variant, or the items of an empty array.
+    Void,
+    Boolean,
+    Integer(IntegerKind),
+    Float(FloatKind),
+    String,
+    Blob,
+    Array(Box<Schema>),
+    Map(Box<Schema>, Box<Schema>),
+    Nullable(Box<Schema>),
+    Tuple(Vec<Schema>),
+    /// Sorted by name
+    Object(Vec<Field>),
+    /// The variants which appear in the file, sorted by name. At the root, that's only the one written.
+    Enum(Vec<Field>),
+    /// Written by the codec with this id, which says what type the values are.
+    Extension(u64),
+    /// The value, with a tag which says what it means. Eg: a timestamp written as an integer.
+    Tagged(SemanticTag, Box<Schema>),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Field {
+    pub name: String,
+    pub schema: Schema,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum IntegerKind {
+    Unsigned,
+    /// Only a negative value at the root is known to be signed.
+    Signed,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum FloatKind {
+    F32,
+    F64,
+    /// The encoding doesn't say. Eg: Gorilla is used for both.
+    Unknown,
+}
+
+/// Reads the schema of a valid Tree-Buf file, with no limits. This recurses for each level of
+/// nesting, so for files from untrusted sources use schema_of_with_options with UntrustedInput.
+#[cfg(feature = "decode")]
+pub fn schema_of(bytes: &[u8]) -> DecodeResult<Schema> {
+    schema_of_with_options(bytes, &DecodeOptionsDefault)
+}
+
+/// Like schema_of, within the limits of the options. Eg: MaxDepth also limits the recursion here.
+#[cfg(feature = "decode")]
+pub fn schema_of_with_options(bytes: &[u8], options: &impl DecodeOptions) -> DecodeResult<Schema> {
+    let root = decode_root(bytes, options)?;
+    Ok(root_schema(&root))
+}
+
+/// The schema of a root branch and everything within it.
+pub fn root_schema(branch: &DynRootBranch<'_>) -> Schema {
+    Walk { path: Vec::new(), ranges: None }.root(branch)
+}
+
+/// The schema of each item in an array branch.
+pub fn array_schema(branch: &DynArrayBranch<'_>) -> Schema {
+    Walk { path: Vec::new(), ranges: None }.array(branch)
+}
+
+/// The smallest and largest integer seen at each path. The path has a segment for each
+/// field, tuple item ("0"), array item ("[]"), map key ("{key}") or value ("{value}"),
+/// nullable value ("?") and enum variant.
+pub(crate) type IntegerRanges = HashMap<Vec<String>, RangeInclusive<i128>>;
+
+/// Like root_schema, but also decodes each integer to find its range.
+#[cfg(feature = "decode")]
+pub(crate) fn root_schema_with_ranges(branch: &DynRootBranch<'_>, ranges: &mut IntegerRanges) -> Schema {
+    Walk {
+        path: Vec::new(),
+        ranges: Some(ranges),
+    }
+    .root(branch)
+}
+
+struct Walk<'r> {
+    path: Vec<String>,
+    ranges: Option<&'r mut IntegerRanges>,
+}
+
+impl Walk<'_> {
+    fn within(&mut self, segment: &str, f: impl FnOnce(&mut Self) -> Schema) -> Schema {
+        self.path.push(segment.to_owned());
+        let schema = f(self);
+        self.path.pop();
+        schema
+    }
+
+    fn fields<'a, T: 'a>(&mut self, fields: impl Iterator<Item = (&'a str, &'a T)>, mut schema: impl FnMut(&mut Self, &T) -> Schema) -> Vec<Field> {
+        let mut fields: Vec<_> = fields
+            .map(|(name, value)| Field {
+                name: name.to_owned(),
+                schema: self.within(name, |walk| schema(walk, value)),
+            })
+            .collect();
+        fields.sort_by(|a, b| a.name.cmp(&b.name));
+        fields
+    }
+
+    fn tuple<T>(&mut self, items: &[T], mut schema: impl FnMut(&mut Self, &T) -> Schema) -> Schema {
+        Schema::Tuple(items.iter().enumerate().map(|(i, item)| self.within(&i.to_string(), |walk| schema(walk, item))).collect())
+    }
+
+    fn observe(&mut self, values: impl Iterator<Item = i128>) {
+        if let Some(ranges) = self.ranges.as_mut() {
+            for value in values {
+                let range = ranges.entry(self.path.clone()).or_insert(value..=value);
+                *range = (*range.start()).min(value)..=(*range.end()).max(value);
+            }
+        }
+    }
+
+    fn root(&mut self, branch: &DynRootBranch<'_>) -> Schema {
+        match branch {
+            DynRootBranch::Object { fields } => Schema::Object(self.fields(fields.iter().map(|(name, value)| (*name, value)), Self::root)),
+            DynRootBranch::Tuple { fields } => self.tuple(fields, Self::root),
+            DynRootBranch::Enum { discriminant, value } => Schema::Enum(self.fields(std::iter::once((*discriminant, &**value)), Self::root)),
+            DynRootBranch::Array0 => Schema::Array(Box::new(Schema::Void)),
+            DynRootBranch::Array1(item) => Schema::Array(Box::new(self.within("[]", |walk| walk.root(item)))),
+            DynRootBranch::Array { len: _, values } => Schema::Array(Box::new(self.within("[]", |walk| walk.array(values)))),
+            DynRootBranch::Integer(RootInteger::U(value)) => {
+                self.observe(std::iter::once(*value as i128));
+                Schema::Integer(IntegerKind::Unsigned)
+            }
+            DynRootBranch::Integer(RootInteger::S(value)) => {
+                self.observe(std::iter::once(*value as i128));
+                Schema::Integer(IntegerKind::Signed)
+            }
+            DynRootBranch::Boolean(_) => Schema::Boolean,
+            DynRootBranch::Float(RootFloat::F32(_)) => Schema::Float(FloatKind::F32),
+            DynRootBranch::Float(RootFloat::F64(_)) => Schema::Float(FloatKind::F64),
+            DynRootBranch::Float(RootFloat::NaN) => Schema::Float(FloatKind::Unknown),
+            DynRootBranch::Void => Schema::Void,
+            DynRootBranch::String(_) => Schema::String,
+            DynRootBranch::Blob(_) => Schema::Blob,
+            DynRootBranch::Map0 => Schema::Map(Box::new(Schema::Void), Box::new(Schema::Void)),
+            DynRootBranch::Map1 { key, value } => Schema::Map(
+                Box::new(self.within("{key}", |walk| walk.root(key))),
+                Box::new(self.within("{value}", |walk| walk.root(value))),
+            ),
+            DynRootBranch::Map { len: _, keys, values } => Schema::Map(
+                Box::new(self.within("{key}", |walk| walk.array(keys))),
+                Box::new(self.within("{value}", |walk| walk.array(values))),
+            ),
+            DynRootBranch::Tagged { tag, value } => Schema::Tagged(*tag, Box::new(self.root(value))),
+        }
+    }
+
+    fn array(&mut self, branch: &DynArrayBranch<'_>) -> Schema {
+        match branch {
+            DynArrayBranch::Object { fields } => Schema::Object(self.fields(fields.iter().map(|(name, value)| (*name, value)), Self::array)),
+            DynArrayBranch::Tuple { fields } => self.tuple(fields, Self::array),
+            DynArrayBranch::Array0 => Schema::Array(Box::new(Schema::Void)),
+            DynArrayBranch::Array { len: _, values } | DynArrayBranch::ArrayFixed { len: _, values } => Schema::Array(Box::new(self.within("[]", |walk| walk.array(values)))),
+            DynArrayBranch::Map0 => Schema::Map(Box::new(Schema::Void), Box::new(Schema::Void)),
+            DynArrayBranch::Map { len: _, keys, values } => Schema::Map(
+                Box::new(self.within("{key}", |walk| walk.array(keys))),
+                Box::new(self.within("{value}", |walk| walk.array(values))),
+            ),
+            DynArrayBranch::Integer(_) => {
+                if self.ranges.is_some() {
+                    // A file which doesn't decode has no range, which is the same as no values.
+                    if let Ok(values) = IntoIter::<u64>::new_infallible(branch.clone(), &DecodeOptionsDefault) {
+                        self.observe(values.map(|value| value as i128));
+                    }
+                }
+                Schema::Integer(IntegerKind::Unsigned)
+            }
+            DynArrayBranch::Nullable { opt: _, values } => Schema::Nullable(Box::new(self.within("?", |walk| walk.array(values)))),
+            DynArrayBranch::Boolean(_) => Schema::Boolean,
+            DynArrayBranch::Float(float) => Schema::Float(match float {
+                ArrayFloat::F32(_) | ArrayFloat::Zfp32(_) => FloatKind::F32,
+                ArrayFloat::F64(_) | ArrayFloat::Zfp64(_) => FloatKind::F64,
+                ArrayFloat::DoubleGorilla(_) => FloatKind::Unknown,
+            }),
+            DynArrayBranch::Void => Schema::Void,
+            DynArrayBranch::String(_)
+            | DynArrayBranch::BrotliUtf8 { .. }
+            | DynArrayBranch::PrefixUtf8 { .. }
+            | DynArrayBranch::FsstUtf8 { .. }
+            | DynArrayBranch::HexUtf8 { .. }
+            | DynArrayBranch::DecimalUtf8 { .. } => Schema::String,
+            DynArrayBranch::Blob { .. } => Schema::Blob,
+            DynArrayBranch::Enum { discriminants: _, variants } => Schema::Enum(self.fields(variants.iter().map(|variant| (variant.ident, &variant.data)), Self::array)),
+            // These only change how the values are stored
+            DynArrayBranch::RLE { runs: _, values } | DynArrayBranch::Dictionary { indices: _, values } => self.array(values),
+            DynArrayBranch::Extension { id, bytes: _ } => Schema::Extension(*id),
+            DynArrayBranch::Tagged { tag, value } => Schema::Tagged(*tag, Box::new(self.array(value))),
+        }
+    }
+}
+
+/// Renders like a Rust type, with objects and enums written out in place.
+impl fmt::Display for Schema {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt_schema(self, 0, f)
+    }
+}
+
+fn fmt_schema(schema: &Schema, depth: usize, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let indent = "    ";
+    match schema {
+        Schema::Void => write!(f, "()"),
+        Schema::Boolean => write!(f, "bool"),
+        Schema::Integer(IntegerKind::Unsigned) => write!(f, "uint"),
+        Schema::Integer(IntegerKind::Signed) => write!(f, "int"),
+        Schema::Float(FloatKind::F32) => write!(f, "f32"),
+        Schema::Float(FloatKind::F64) => write!(f, "f64"),
+        Schema::Float(FloatKind::Unknown) => write!(f, "float"),
+        Schema::String => write!(f, "String"),
+        Schema::Blob => write!(f, "Blob"),
+        Schema::Extension(id) => write!(f, "Extension<{}>", id),
+        Schema::Tagged(tag, value) => {
+            fmt_schema(value, depth, f)?;
+            write!(f, " /* {} */", tag)
+        }
+        Schema::Array(item) => {
+            write!(f, "Vec<")?;
+            fmt_schema(item, depth, f)?;
+            write!(f, ">")
+        }
+        Schema::Nullable(value) => {
+            write!(f, "Option<")?;
+            fmt_schema(value, depth, f)?;
+            write!(f, ">")
+        }
+        Schema::Map(key, value) => {
+            write!(f, "HashMap<")?;
+            fmt_schema(key, depth, f)?;
+            write!(f, ", ")?;
+            fmt_schema(value, depth, f)?;
+            write!(f, ">")
+        }
+        Schema::Tuple(items) => {
+            write!(f, "(")?;
+            for (i, item) in items.iter().enumerate() {
+                if i != 0 {
+                    write!(f, ", ")?;
+                }
+                fmt_schema(item, depth, f)?;
+            }
+            write!(f, ")")
+        }
+        Schema::Object(fields) => {
+            writeln!(f, "{{")?;
+            for field in fields {
+                write!(f, "{}{}: ", indent.repeat(depth + 1), field.name)?;
+                fmt_schema(&field.schema, depth + 1, f)?;
+                writeln!(f, ",")?;
+            }
+            write!(f, "{}}}", indent.repeat(depth))
+        }
+        Schema::Enum(variants) => {
+            writeln!(f, "enum {{")?;
+            for variant in variants {
+                write!(f, "{}{}", indent.repeat(depth + 1), variant.name)?;
+                if variant.schema != Schema::Void {
+                    write!(f, "(")?;
+                    fmt_schema(&variant.schema, depth + 1, f)?;
+                    write!(f, ")")?;
+                }
+                writeln!(f, ",")?;
+            }
+            write!(f, "{}}}", indent.repeat(depth))
+        }
+    }
+}
@@ -1,29 +1,28 @@
-// TODO: Remove this allow when scratch is used
-#![allow(dead_code)]
 use crate::prelude::*;
 use std::cell::RefCell;
 use std::rc::Rc;
 
-#[derive(Clone)]
+/// Holds on to the allocations made during an encode so that the next encode can re-use them.
+#[derive(Clone, Default)]
 pub struct Scratch {
     buffers: Rc<RefCell<BufferPool>>,
-}
-
-impl Scratch {
-    pub(crate) fn take_buffer<T: Copy>(&self) -> Buffer<T> {
-        self.buffers.borrow_mut().take()
-    }
-    pub(crate) fn put_buffer<T>(&self, buffer: Buffer<T>) {
-        self.buffers.borrow_mut().put(buffer)
-    }
+    lens: Vec<usize>,
 }
 
 /// A re-usable object which may increase performance when encoding over and over again
 /// in a loop. Avoids allocations
 pub fn scratch<T: Encodable>() -> Scratch {
-    Scratch { buffers: Default::default() }
+    Scratch::default()
 }
 
-pub fn encode_into_with_scratch<T: Encodable>(_value: &T, _scratch: &mut Scratch, _into: &mut Vec<u8>) {
-    todo!()
+/// Appends the encoded value to `into`, using buffers left over from previous
+/// encodes with the same scratch rather than allocating new ones.
+#[cfg(feature = "encode")]
+pub fn encode_into_with_scratch<T: Encodable>(value: &T, scratch: &mut Scratch, into: &mut Vec<u8>) {
+    profile_fn!(T, encode_into_with_scratch);
+    let options = EncodeOptionsDefault;
+    let mut lens = std::mem::take(&mut scratch.lens);
+    with_pool(&scratch.buffers, || crate::experimental::options::encode_into(value, &options, &mut lens, into));
+    lens.clear();
+    scratch.lens = lens;
 }
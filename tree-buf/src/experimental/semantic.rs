@@ -0,0 +1,10 @@
+//! Semantic tags say what an integer, float or blob in a file means. Eg: that an integer is
+//! a timestamp in milliseconds. Tools like schema and json show the values as what they mean,
+//! and types which know what they are written as check the tag when they are decoded.
+//! Types which read the value as it is stored, like u64 or Blob, skip over the tag.
+//!
+//! To write a tag, a type wraps its encode_root in encode_tagged_root and its flush in flush_tagged.
+
+#[cfg(feature = "encode")]
+pub use crate::internal::{encode_tagged_root, flush_tagged};
+pub use crate::internal::{SemanticTag, TimeUnit};
@@ -1,14 +1,17 @@
 //! Introspect the contents of a Tree-Buf file
 
+use crate::internal::encodings::varint::size_for_varint;
 use crate::prelude::*;
 use std::collections::HashMap;
 use std::default::Default;
 use std::fmt;
+use std::vec::IntoIter;
 
 #[derive(Default)]
 struct Path {
     names: String,
     types: String,
+    parent: String,
 }
 
 impl Path {
@@ -29,165 +32,431 @@ impl Path {
     pub fn a(&self, name: &impl fmt::Display, type_id: &impl fmt::Display) -> Self {
         let names = Self::c(&self.names, name);
         let types = Self::c(&self.types, type_id);
-        Self { names, types }
+        Self {
+            names,
+            types,
+            parent: self.names.clone(),
+        }
     }
 }
 
-struct PathAggregation {
-    types: String,
-    size: usize,
+/// The bytes of the values at one path in the file.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PathSize {
+    /// Eg: `data.orders.[1000].price`
+    pub path: String,
+    /// The path this is nested within, which is empty at the root.
+    pub parent: String,
+    /// The kind of each branch along the path. Eg: `Object.Object.Array.Object.Prefix Varint`
+    pub types: String,
+    /// The encoding of the bytes. Eg: `Prefix Varint`
+    pub encoding: String,
+    pub size: usize,
+    /// The number of values at this path, or None if that can't be known without
+    /// decoding the values themselves.
+    pub count: Option<usize>,
 }
 
-#[derive(Default, Clone)]
-struct TypeAggregation {
-    size: usize,
-    count: usize,
+/// The bytes of all paths which were written with an encoding.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TypeSize {
+    pub encoding: String,
+    pub size: usize,
+    /// The number of paths written with this encoding.
+    pub paths: usize,
 }
 
-struct SizeBreakdown {
-    by_path: HashMap<String, PathAggregation>,
-    by_type: HashMap<String, TypeAggregation>,
-    total: usize,
+/// The bytes which aren't the values at any path.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Overhead {
+    /// One byte for each branch, saying how it was encoded.
+    pub type_ids: usize,
+    /// The names of fields and enum variants.
+    pub idents: usize,
+    /// The lengths of the values at each path, and checksums if the file has them.
+    pub lens: usize,
+    /// Everything else. Eg: values at the root, which are written inline, and lengths of collections.
+    pub other: usize,
 }
 
-impl fmt::Display for SizeBreakdown {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let mut by_path: Vec<_> = self.by_path.iter().collect();
-        let mut by_type: Vec<_> = self.by_type.iter().collect();
+impl Overhead {
+    pub fn total(&self) -> usize {
+        self.type_ids + self.idents + self.lens + self.other
+    }
+}
 
-        by_path.sort_by_key(|i| usize::MAX - i.1.size);
-        by_type.sort_by_key(|i| usize::MAX - i.1.size);
+/// Details how each byte of a Tree-Buf file is allocated. Display gives the format
+/// of size_breakdown, and to_json or to_csv give a format suitable for other tools.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SizeReport {
+    /// Largest first
+    pub paths: Vec<PathSize>,
+    /// Largest first
+    pub by_type: Vec<TypeSize>,
+    pub overhead: Overhead,
+    pub total: usize,
+}
 
+impl fmt::Display for SizeReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         writeln!(f, "Largest by path:")?;
-        for (path, agg) in by_path.iter() {
-            writeln!(f, "\t{}\n\t   {}\n\t   {}", agg.size, path, agg.types)?;
+        for path in self.paths.iter() {
+            writeln!(f, "\t{}\n\t   {}\n\t   {}", path.size, path.path, path.types)?;
         }
 
         writeln!(f)?;
         writeln!(f, "Largest by type:")?;
-        for (t, agg) in by_type.iter() {
-            writeln!(f, "\t {}x {} @ {}", agg.count, agg.size, t)?;
+        for t in self.by_type.iter() {
+            writeln!(f, "\t {}x {} @ {}", t.paths, t.size, t.encoding)?;
         }
 
-        let accounted: usize = by_type.iter().map(|i| (i.1).size).sum();
-
         writeln!(f)?;
-        writeln!(f, "Other: {}", self.total - accounted)?;
+        writeln!(f, "Other: {}", self.overhead.total())?;
         writeln!(f, "Total: {}", self.total)?;
 
         Ok(())
     }
 }
 
+impl SizeReport {
+    /// All of the report as a JSON object, with the fields named as they are here.
+    pub fn to_json(&self) -> String {
+        let mut json = String::new();
+        json.push_str(&format!("{{\"total\":{},\"overhead\":{{", self.total));
+        json.push_str(&format!(
+            "\"type_ids\":{},\"idents\":{},\"lens\":{},\"other\":{}",
+            self.overhead.type_ids, self.overhead.idents, self.overhead.lens, self.overhead.other
+        ));
+        json.push_str("},\"paths\":[");
+        for (i, path) in self.paths.iter().enumerate() {
+            if i != 0 {
+                json.push(',');
+            }
+            json.push_str("{\"path\":");
+            push_json_str(&mut json, &path.path);
+            json.push_str(",\"parent\":");
+            push_json_str(&mut json, &path.parent);
+            json.push_str(",\"types\":");
+            push_json_str(&mut json, &path.types);
+            json.push_str(",\"encoding\":");
+            push_json_str(&mut json, &path.encoding);
+            json.push_str(&format!(",\"size\":{},\"count\":", path.size));
+            match path.count {
+                Some(count) => json.push_str(&count.to_string()),
+                None => json.push_str("null"),
+            }
+            json.push('}');
+        }
+        json.push_str("],\"by_type\":[");
+        for (i, t) in self.by_type.iter().enumerate() {
+            if i != 0 {
+                json.push(',');
+            }
+            json.push_str("{\"encoding\":");
+            push_json_str(&mut json, &t.encoding);
+            json.push_str(&format!(",\"size\":{},\"paths\":{}}}", t.size, t.paths));
+        }
+        json.push_str("]}");
+        json
+    }
+
+    /// One row per path, with a header. The count is empty where it isn't known.
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("path,parent,types,encoding,size,count\n");
+        for path in self.paths.iter() {
+            for field in [&path.path, &path.parent, &path.types, &path.encoding].iter() {
+                push_csv_field(&mut csv, field);
+                csv.push(',');
+            }
+            csv.push_str(&path.size.to_string());
+            csv.push(',');
+            if let Some(count) = path.count {
+                csv.push_str(&count.to_string());
+            }
+            csv.push('\n');
+        }
+        csv
+    }
+}
+
+pub(crate) fn push_json_str(json: &mut String, value: &str) {
+    json.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            '\t' => json.push_str("\\t"),
+            c if (c as u32) < 0x20 => json.push_str(&format!("\\u{:04x}", c as u32)),
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+}
+
+fn push_csv_field(csv: &mut String, value: &str) {
+    if value.contains([',', '"', '\n', '\r']) {
+        csv.push('"');
+        csv.push_str(&value.replace('"', "\"\""));
+        csv.push('"');
+    } else {
+        csv.push_str(value);
+    }
+}
+
+#[derive(Default)]
+struct SizeBreakdown {
+    paths: Vec<PathSize>,
+    by_path: HashMap<String, usize>,
+    overhead: Overhead,
+    checksums: bool,
+    total: usize,
+}
+
 impl SizeBreakdown {
-    fn add(&mut self, path: &Path, type_id: &'static str, bytes: &Bytes<'_>) {
+    fn add(&mut self, path: &Path, count: Option<usize>, type_id: &'static str, bytes: &Bytes<'_>) {
+        // Bytes in an array are preceded by their len at the end of the file
+        self.overhead.lens += size_for_varint(bytes.len() as u64);
+        if self.checksums {
+            self.overhead.lens += size_for_varint(crc32c::crc32c(bytes) as u64);
+        }
+        self.add_inline(path, count, type_id, bytes);
+    }
+
+    fn add_inline(&mut self, path: &Path, count: Option<usize>, type_id: &'static str, bytes: &Bytes<'_>) {
         let len = bytes.len();
-        let before = self.by_type.get(type_id).cloned().unwrap_or_default();
-        self.by_type.insert(
-            type_id.to_owned(),
-            TypeAggregation {
-                count: before.count + 1,
-                size: before.size + len,
-            },
-        );
-
-        let types = Path::c(&path.types, &type_id);
-
-        let prev = self.by_path.insert(path.names.clone(), PathAggregation { types, size: len });
-        assert!(prev.is_none());
+        // The encoder never writes the same path twice, but a corrupt file might
+        // (Eg: an object with duplicate field names). Combine them rather than panic.
+        if let Some(&i) = self.by_path.get(&path.names) {
+            let existing = &mut self.paths[i];
+            existing.size += len;
+            existing.count = existing.count.and_then(|a| count.map(|b| a.saturating_add(b)));
+            return;
+        }
+        self.by_path.insert(path.names.clone(), self.paths.len());
+        self.paths.push(PathSize {
+            path: path.names.clone(),
+            parent: path.parent.clone(),
+            types: Path::c(&path.types, &type_id),
+            encoding: type_id.to_owned(),
+            size: len,
+            count,
+        });
+    }
+
+    fn add_ident(&mut self, ident: &str) {
+        self.overhead.idents += size_for_varint(ident.len() as u64) + ident.len();
+    }
+
+    fn finish(self) -> SizeReport {
+        let Self {
+            mut paths, mut overhead, total, ..
+        } = self;
+
+        let mut by_type: Vec<TypeSize> = Vec::new();
+        for path in paths.iter() {
+            match by_type.iter_mut().find(|t| t.encoding == path.encoding) {
+                Some(t) => {
+                    t.size += path.size;
+                    t.paths += 1;
+                }
+                None => by_type.push(TypeSize {
+                    encoding: path.encoding.clone(),
+                    size: path.size,
+                    paths: 1,
+                }),
+            }
+        }
+
+        // Ties are broken by name so that the report is the same each time.
+        paths.sort_by(|a, b| b.size.cmp(&a.size).then_with(|| a.path.cmp(&b.path)));
+        by_type.sort_by(|a, b| b.size.cmp(&a.size).then_with(|| a.encoding.cmp(&b.encoding)));
+
+        let accounted = by_type.iter().map(|t| t.size).sum::<usize>() + overhead.type_ids + overhead.idents + overhead.lens;
+        overhead.other = total.saturating_sub(accounted);
+
+        SizeReport { paths, by_type, overhead, total }
     }
 }
 
-// TODO: (Security) Re-write without recursion
-fn visit_array(path: Path, branch: &DynArrayBranch, breakdown: &mut SizeBreakdown) {
+/// How many elements size_report may decode for each byte of the file, to count the values.
+const COUNT_ELEMENTS_PER_BYTE: usize = 64;
+
+/// Decodes the integers in a branch, for counting the values in the branches nested within it.
+/// None if that would take more elements than the options allow.
+fn decode_u64s(branch: &DynArrayBranch<'_>, options: &impl DecodeOptions) -> Option<Vec<u64>> {
+    IntoIter::<u64>::new_infallible(branch.clone(), options).ok().map(|values| values.collect())
+}
+
+fn count_true(branch: &DynArrayBranch<'_>, count: Option<usize>, options: &impl DecodeOptions) -> Option<usize> {
+    let values = IntoIter::<bool>::new_infallible(branch.clone(), options).ok()?;
+    Some(values.take(count?).filter(|&value| value).count())
+}
+
+fn sum_lens(branch: &DynArrayBranch<'_>, count: Option<usize>, options: &impl DecodeOptions) -> Option<usize> {
+    let lens = decode_u64s(branch, options)?;
+    Some(lens.iter().take(count?).fold(0usize, |sum, &len| sum.saturating_add(len as usize)))
+}
+
+enum Branch<'a, 'b> {
+    Root(&'b DynRootBranch<'a>),
+    Array(&'b DynArrayBranch<'a>),
+}
+
+type Stack<'a, 'b> = Vec<(Path, Option<usize>, Branch<'a, 'b>)>;
+
+fn visit_array<'a, 'b>(path: Path, count: Option<usize>, branch: &'b DynArrayBranch<'a>, options: &impl DecodeOptions, breakdown: &mut SizeBreakdown, stack: &mut Stack<'a, 'b>) {
+    breakdown.overhead.type_ids += 1;
     match branch {
-        DynArrayBranch::ArrayFixed { values, len } => visit_array(path.a(&format!("[{}]", len), &"Array Fixed"), values, breakdown),
+        DynArrayBranch::ArrayFixed { values, len } => {
+            let values_count = count.map(|count| count.saturating_mul(*len));
+            stack.push((path.a(&format!("[{}]", len), &"Array Fixed"), values_count, Branch::Array(values)))
+        }
         DynArrayBranch::Array { len, values } => {
-            visit_array(path.a(&"len", &"Array"), len, breakdown);
-            visit_array(path.a(&"values", &"Array"), values, breakdown);
+            stack.push((path.a(&"len", &"Array"), count, Branch::Array(len)));
+            stack.push((path.a(&"values", &"Array"), sum_lens(len, count, options), Branch::Array(values)));
         }
         DynArrayBranch::Enum { discriminants, variants } => {
-            visit_array(path.a(&"discriminants", &"Enum"), discriminants, breakdown);
-            for variant in variants.iter() {
-                visit_array(path.a(&variant.ident, &"Enum"), &variant.data, breakdown);
+            stack.push((path.a(&"discriminants", &"Enum"), count, Branch::Array(discriminants)));
+            let discriminants = count.and_then(|count| decode_u64s(discriminants, options).map(|d| d.into_iter().take(count).collect::<Vec<_>>()));
+            for (i, variant) in variants.iter().enumerate() {
+                breakdown.add_ident(variant.ident);
+                let variant_count = discriminants.as_ref().map(|d| d.iter().filter(|&&d| d == i as u64).count());
+                stack.push((path.a(&variant.ident, &"Enum"), variant_count, Branch::Array(&variant.data)));
             }
         }
         DynArrayBranch::Boolean(enc) => match enc {
-            ArrayBool::Packed(b) => breakdown.add(&path, "Packed Boolean", b),
-            ArrayBool::RLE(_first, runs) => visit_array(path.a(&"runs", &"Bool RLE"), runs, breakdown),
+            ArrayBool::Packed(b) => breakdown.add(&path, count, "Packed Boolean", b),
+            ArrayBool::RLE(_first, runs) => {
+                let runs_count = decode_u64s(runs, options).map(|runs| runs.len());
+                stack.push((path.a(&"runs", &"Bool RLE"), runs_count, Branch::Array(runs)))
+            }
         },
         DynArrayBranch::Float(f) => match f {
-            ArrayFloat::DoubleGorilla(b) => breakdown.add(&path, "Gorilla", b),
-            ArrayFloat::F32(b) => breakdown.add(&path, "Fixed F32", b),
-            ArrayFloat::F64(b) => breakdown.add(&path, "Fixed F64", b),
-            ArrayFloat::Zfp32(b) => breakdown.add(&path, "Zfp 64", b),
-            ArrayFloat::Zfp64(b) => breakdown.add(&path, "Zfp 32", b),
+            ArrayFloat::DoubleGorilla(b) => breakdown.add(&path, count, "Gorilla", b),
+            ArrayFloat::F32(b) => breakdown.add(&path, count, "Fixed F32", b),
+            ArrayFloat::F64(b) => breakdown.add(&path, count, "Fixed F64", b),
+            ArrayFloat::Zfp32(b) => breakdown.add(&path, count, "Zfp 64", b),
+            ArrayFloat::Zfp64(b) => breakdown.add(&path, count, "Zfp 32", b),
         },
         DynArrayBranch::Integer(ArrayInteger { bytes, encoding }) => match encoding {
-            ArrayIntegerEncoding::PrefixVarInt => breakdown.add(&path, "Prefix Varint", bytes),
-            ArrayIntegerEncoding::Simple16 => breakdown.add(&path, "Simple16", bytes),
-            ArrayIntegerEncoding::U8 => breakdown.add(&path, "U8 Fixed", bytes),
-            ArrayIntegerEncoding::DeltaZig => breakdown.add(&path, "DeltaZig", bytes),
+            ArrayIntegerEncoding::PrefixVarInt => breakdown.add(&path, count, "Prefix Varint", bytes),
+            ArrayIntegerEncoding::Simple16 => breakdown.add(&path, count, "Simple16", bytes),
+            ArrayIntegerEncoding::U8 => breakdown.add(&path, count, "U8 Fixed", bytes),
+            ArrayIntegerEncoding::DeltaZig => breakdown.add(&path, count, "DeltaZig", bytes),
+            ArrayIntegerEncoding::DeltaZig64 => breakdown.add(&path, count, "DeltaZig64", bytes),
         },
         DynArrayBranch::Map { len, keys, values } => {
-            visit_array(path.a(&"len", &"Map"), len, breakdown);
-            visit_array(path.a(&"keys", &"Map"), keys, breakdown);
-            visit_array(path.a(&"values", &"Map"), values, breakdown);
+            let entries = sum_lens(len, count, options);
+            stack.push((path.a(&"len", &"Map"), count, Branch::Array(len)));
+            stack.push((path.a(&"keys", &"Map"), entries, Branch::Array(keys)));
+            stack.push((path.a(&"values", &"Map"), entries, Branch::Array(values)));
         }
         DynArrayBranch::Object { fields } => {
             for (name, field) in fields {
-                visit_array(path.a(name, &"Object"), field, breakdown);
+                breakdown.add_ident(name);
+                stack.push((path.a(name, &"Object"), count, Branch::Array(field)));
             }
         }
         DynArrayBranch::RLE { runs, values } => {
-            visit_array(path.a(&"runs", &"RLE"), runs, breakdown);
-            visit_array(path.a(&"values", &"RLE"), values, breakdown);
+            let runs_count = decode_u64s(runs, options).map(|runs| runs.len());
+            stack.push((path.a(&"runs", &"RLE"), runs_count, Branch::Array(runs)));
+            stack.push((path.a(&"values", &"RLE"), runs_count, Branch::Array(values)));
         }
         DynArrayBranch::Dictionary { indices, values } => {
-            visit_array(path.a(&"indices", &"Dictionary"), indices, breakdown);
-            visit_array(path.a(&"values", &"Dictionary"), values, breakdown);
+            // Each value in the dictionary is used, so there are as many as the largest index says.
+            let values_count = count.and_then(|count| decode_u64s(indices, options).map(|i| i.into_iter().take(count).max().map_or(0, |max| max as usize + 1)));
+            stack.push((path.a(&"indices", &"Dictionary"), count, Branch::Array(indices)));
+            stack.push((path.a(&"values", &"Dictionary"), values_count, Branch::Array(values)));
         }
-        DynArrayBranch::String(b) => breakdown.add(&path, "UTF-8", b),
+        DynArrayBranch::String(b) => breakdown.add(&path, count, "UTF-8", b),
         DynArrayBranch::BrotliUtf8 { utf8, lens } => {
-            breakdown.add(&path, "BrotliUtf8", utf8);
-            visit_array(path.a(&"lens", &"Dictionary"), lens, breakdown);
+            breakdown.add(&path, count, "BrotliUtf8", utf8);
+            stack.push((path.a(&"lens", &"BrotliUtf8"), count, Branch::Array(lens)));
+        }
+        DynArrayBranch::PrefixUtf8 { prefixes, suffixes } => {
+            stack.push((path.a(&"prefixes", &"Prefix"), count, Branch::Array(prefixes)));
+            // Empty suffixes at the end may be left out
+            stack.push((path.a(&"suffixes", &"Prefix"), None, Branch::Array(suffixes)));
+        }
+        DynArrayBranch::FsstUtf8 { fsst, lens } => {
+            breakdown.add(&path, count, "FsstUtf8", fsst);
+            stack.push((path.a(&"lens", &"FsstUtf8"), count, Branch::Array(lens)));
+        }
+        DynArrayBranch::HexUtf8 { hex, lens } => {
+            breakdown.add(&path, count, "HexUtf8", hex);
+            stack.push((path.a(&"lens", &"HexUtf8"), count, Branch::Array(lens)));
+        }
+        DynArrayBranch::DecimalUtf8 { mantissas, exponents } => {
+            stack.push((path.a(&"mantissas", &"DecimalUtf8"), count, Branch::Array(mantissas)));
+            stack.push((path.a(&"exponents", &"DecimalUtf8"), count, Branch::Array(exponents)));
+        }
+        DynArrayBranch::Blob { bytes, lens } => {
+            visit_blob(&path, count, bytes, true, breakdown);
+            stack.push((path.a(&"lens", &"Blob"), count, Branch::Array(lens)));
         }
         DynArrayBranch::Tuple { fields } => {
             for (i, field) in fields.iter().enumerate() {
-                visit_array(path.a(&i, &"Tuple"), field, breakdown);
+                stack.push((path.a(&i, &"Tuple"), count, Branch::Array(field)));
             }
         }
         DynArrayBranch::Nullable { opt, values } => {
-            visit_array(path.a(&"opt", &"Nullable"), opt, breakdown);
-            visit_array(path.a(&"values", &"Nullable"), values, breakdown);
+            stack.push((path.a(&"opt", &"Nullable"), count, Branch::Array(opt)));
+            stack.push((path.a(&"values", &"Nullable"), count_true(opt, count, options), Branch::Array(values)));
         }
-        DynArrayBranch::Void | DynArrayBranch::Map0 | DynArrayBranch::Array0 => {}
+        DynArrayBranch::Extension { id: _, bytes } => breakdown.add(&path, count, "Extension", bytes),
+        // The tag only says what the values mean
+        DynArrayBranch::Tagged { tag: _, value } => stack.push((path, count, Branch::Array(value))),
+        // An empty array or map is followed by a Void len
+        DynArrayBranch::Array0 | DynArrayBranch::Map0 => breakdown.overhead.type_ids += 1,
+        DynArrayBranch::Void => {}
     }
 }
 
-fn visit(path: Path, branch: &DynRootBranch<'_>, breakdown: &mut SizeBreakdown) {
+fn visit_blob(path: &Path, count: Option<usize>, bytes: &BlobBytes, in_array: bool, breakdown: &mut SizeBreakdown) {
+    let (type_id, b) = match bytes {
+        BlobBytes::Raw(b) => ("Blob", b),
+        BlobBytes::Lz4(b) => ("Lz4 Blob", b),
+        BlobBytes::Brotli(b) => ("Brotli Blob", b),
+    };
+    if in_array {
+        breakdown.add(path, count, type_id, b)
+    } else {
+        breakdown.add_inline(path, count, type_id, b)
+    }
+}
+
+fn visit_root<'a, 'b>(path: Path, branch: &'b DynRootBranch<'a>, breakdown: &mut SizeBreakdown, stack: &mut Stack<'a, 'b>) {
+    breakdown.overhead.type_ids += 1;
+    let one = Some(1);
     match branch {
         DynRootBranch::Object { fields } => {
             for (name, value) in fields.iter() {
-                visit(path.a(name, &"Object"), value, breakdown);
+                breakdown.add_ident(name);
+                stack.push((path.a(name, &"Object"), one, Branch::Root(value)));
             }
         }
-        DynRootBranch::Enum { discriminant, value } => visit(path.a(discriminant, &"Enum"), value, breakdown),
-        DynRootBranch::Map { len: _, keys, values } => {
-            visit_array(path.a(&"keys", &"Map"), keys, breakdown);
-            visit_array(path.a(&"values", &"Values"), values, breakdown);
+        DynRootBranch::Enum { discriminant, value } => {
+            breakdown.add_ident(discriminant);
+            stack.push((path.a(discriminant, &"Enum"), one, Branch::Root(value)))
+        }
+        DynRootBranch::Map { len, keys, values } => {
+            stack.push((path.a(&"keys", &"Map"), Some(*len), Branch::Array(keys)));
+            stack.push((path.a(&"values", &"Values"), Some(*len), Branch::Array(values)));
         }
         DynRootBranch::Tuple { fields } => {
             for (i, field) in fields.iter().enumerate() {
-                visit(path.a(&i, &"Tuple"), field, breakdown);
+                stack.push((path.a(&i, &"Tuple"), one, Branch::Root(field)));
             }
         }
         DynRootBranch::Map1 { key, value } => {
-            visit(path.a(&"key", &"Map1"), key, breakdown);
-            visit(path.a(&"value", &"Map1"), value, breakdown);
+            stack.push((path.a(&"key", &"Map1"), one, Branch::Root(key)));
+            stack.push((path.a(&"value", &"Map1"), one, Branch::Root(value)));
         }
-        DynRootBranch::Array { len, values } => visit_array(path.a(&format!("[{}]", len), &"Array"), values, breakdown),
-        DynRootBranch::Array1(item) => visit(path.a(&"1", &"Array1"), item, breakdown),
+        DynRootBranch::Array { len, values } => stack.push((path.a(&format!("[{}]", len), &"Array"), Some(*len), Branch::Array(values))),
+        DynRootBranch::Array1(item) => stack.push((path.a(&"1", &"Array1"), one, Branch::Root(item))),
+        DynRootBranch::Blob(bytes) => visit_blob(&path, one, bytes, false, breakdown),
+        DynRootBranch::Tagged { tag: _, value } => stack.push((path, one, Branch::Root(value))),
         DynRootBranch::Boolean(_)
         | DynRootBranch::Array0
         | DynRootBranch::Map0
@@ -198,8 +467,46 @@ fn visit(path: Path, branch: &DynRootBranch<'_>, breakdown: &mut SizeBreakdown)
     }
 }
 
+fn visit(root: &DynRootBranch<'_>, options: &impl DecodeOptions, breakdown: &mut SizeBreakdown) {
+    // Uses a stack rather than recursion so that deeply nested files can't overflow the call stack.
+    let mut stack = vec![(Path::default(), Some(1), Branch::Root(root))];
+    while let Some((path, count, branch)) = stack.pop() {
+        match branch {
+            Branch::Root(root) => visit_root(path, root, breakdown, &mut stack),
+            Branch::Array(array) => visit_array(path, count, array, options, breakdown, &mut stack),
+        }
+    }
+}
+
+/// Details how each byte of a valid Tree-Buf file is allocated, in a form that can be compared
+/// between files. See also size_breakdown.
+pub fn size_report(data: &[u8]) -> DecodeResult<SizeReport> {
+    size_report_with_options(data, &DecodeOptionsDefault)
+}
+
+/// Like size_report, within the limits of the options. The branches are visited from a stack
+/// rather than by recursion.
+pub fn size_report_with_options(data: &[u8], options: &impl DecodeOptions) -> DecodeResult<SizeReport> {
+    let root = decode_root(data, options)?;
+
+    // Counting the values decodes columns like the lens of arrays and the runs of RLE, which a
+    // handful of bytes can make arbitrarily large. Counts past this budget are left unknown.
+    let budget = ElementBudget::new(options.max_elements().min(data.len().saturating_mul(COUNT_ELEMENTS_PER_BYTE)));
+    let options = override_decode_options(options, &budget);
+
+    let mut breakdown = SizeBreakdown {
+        checksums: data.first() == Some(&RootTypeId::Checksums.into()),
+        total: data.len(),
+        ..Default::default()
+    };
+    visit(&root, &options, &mut breakdown);
+
+    Ok(breakdown.finish())
+}
+
 /// When used on a valid Tree-Buf file, details how each byte is allocated. The output is not meant to be parseable.
-/// Instead, this should only be used for information and debugging.
+/// Instead, this should only be used for information and debugging. Use size_report for the same information
+/// in a form that can be parsed.
 /// Example from the GraphQL benchmark:
 /// ```ignore
 /// let sizes = tree_buf::experimental::stats::size_breakdown(&bytes);
@@ -267,14 +574,19 @@ fn visit(path: Path, branch: &DynRootBranch<'_>, breakdown: &mut SizeBreakdown)
 /// Other: 400
 /// Total: 51423
 pub fn size_breakdown(data: &[u8]) -> DecodeResult<String> {
-    let root = decode_root(data)?;
+    Ok(format!("{}", size_report(data)?))
+}
 
-    let mut breakdown = SizeBreakdown {
-        by_path: HashMap::new(),
-        by_type: HashMap::new(),
-        total: data.len(),
-    };
-    visit(Path::default(), &root, &mut breakdown);
+#[cfg(all(test, feature = "encode"))]
+mod tests {
+    use super::*;
 
-    Ok(format!("{}", breakdown))
+    #[test]
+    fn breakdown_visits_nested_branches() {
+        let data: Vec<(Vec<u64>, String)> = (0..10).map(|i| ((0..i).collect(), format!("{}", i))).collect();
+        let bytes = crate::encode(&data);
+        let breakdown = size_breakdown(&bytes).unwrap();
+        assert!(breakdown.contains("[10].0.values"), "{}", breakdown);
+        assert!(breakdown.contains("[10].1"), "{}", breakdown);
+    }
 }
@@ -6,6 +6,7 @@ use std::fmt;
 use std::ops::Deref;
 
 /// This wrapper is just to make the Debug impl not display every byte
+#[derive(Clone, Copy)]
 pub struct Bytes<'a>(&'a [u8]);
 
 impl fmt::Debug for Bytes<'_> {
@@ -29,7 +30,7 @@ impl Deref for Bytes<'_> {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum ArrayFloat<'a> {
     F64(Bytes<'a>),
     F32(Bytes<'a>),
@@ -38,19 +39,27 @@ pub enum ArrayFloat<'a> {
     Zfp64(Bytes<'a>),
 }
 
-#[derive(Debug)]
+/// The bytes of one or more blobs, along with how they were compressed.
+#[derive(Debug, Clone)]
+pub enum BlobBytes<'a> {
+    Raw(Bytes<'a>),
+    Lz4(Bytes<'a>),
+    Brotli(Bytes<'a>),
+}
+
+#[derive(Debug, Clone)]
 pub enum ArrayBool<'a> {
     Packed(Bytes<'a>),
     RLE(bool, Box<DynArrayBranch<'a>>),
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct ArrayEnumVariant<'a> {
     pub ident: Ident<'a>,
     pub data: DynArrayBranch<'a>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum DynArrayBranch<'a> {
     Object {
         fields: HashMap<Ident<'a>, DynArrayBranch<'a>>,
@@ -86,6 +95,26 @@ pub enum DynArrayBranch<'a> {
         utf8: Bytes<'a>,
         lens: Box<DynArrayBranch<'a>>,
     },
+    PrefixUtf8 {
+        prefixes: Box<DynArrayBranch<'a>>,
+        suffixes: Box<DynArrayBranch<'a>>,
+    },
+    FsstUtf8 {
+        fsst: Bytes<'a>,
+        lens: Box<DynArrayBranch<'a>>,
+    },
+    HexUtf8 {
+        hex: Bytes<'a>,
+        lens: Box<DynArrayBranch<'a>>,
+    },
+    DecimalUtf8 {
+        mantissas: Box<DynArrayBranch<'a>>,
+        exponents: Box<DynArrayBranch<'a>>,
+    },
+    Blob {
+        bytes: BlobBytes<'a>,
+        lens: Box<DynArrayBranch<'a>>,
+    },
     Enum {
         discriminants: Box<DynArrayBranch<'a>>,
         variants: Vec<ArrayEnumVariant<'a>>,
@@ -98,6 +127,16 @@ pub enum DynArrayBranch<'a> {
         indices: Box<DynArrayBranch<'a>>,
         values: Box<DynArrayBranch<'a>>,
     },
+    /// Encoded by a codec registered with Codecs, which is needed to decode it.
+    Extension {
+        id: u64,
+        bytes: Bytes<'a>,
+    },
+    /// See also SemanticTag
+    Tagged {
+        tag: SemanticTag,
+        value: Box<DynArrayBranch<'a>>,
+    },
     // TODO:
     // In any array context, we can have a 'dynamic' value, which resolves to an array of DynRootBranch (like a nested file)
     // This generally should not be used, but the existance of it is an escape hatch bringing the capability to use truly unstructured
@@ -105,175 +144,316 @@ pub enum DynArrayBranch<'a> {
     // Dynamic(Bytes<'a>)
 }
 
-// TODO: Make this stack based instead of recursion based to not crash on deep inputs
-pub fn decode_next_array<'a>(bytes: &'a [u8], offset: &'_ mut usize, lens: &'_ mut usize) -> DecodeResult<DynArrayBranch<'a>> {
-    let id = ArrayTypeId::decode_next(bytes, offset)?;
+/// A branch which has been started, and is waiting for its children to be decoded.
+/// Anything which comes before the children in the file is read when the branch is started.
+enum PendingArray<'a> {
+    Nullable,
+    Tuple,
+    Object(Vec<Ident<'a>>),
+    ArrayVar,
+    ArrayFixed(usize),
+    Map,
+    RleBool(bool),
+    BrotliUtf8(Bytes<'a>),
+    PrefixUtf8,
+    FsstUtf8(Bytes<'a>),
+    HexUtf8(Bytes<'a>),
+    DecimalUtf8,
+    Blob(BlobBytes<'a>),
+    Enum(Vec<Ident<'a>>),
+    Rle,
+    Dictionary,
+    Tagged(SemanticTag),
+}
 
-    use ArrayTypeId::*;
+struct ArrayFrame<'a> {
+    pending: PendingArray<'a>,
+    children: Vec<DynArrayBranch<'a>>,
+    count: usize,
+}
 
-    fn decode_ints<'a>(bytes: &'a [u8], offset: &'_ mut usize, lens: &'_ mut usize, encoding: ArrayIntegerEncoding) -> DecodeResult<DynArrayBranch<'a>> {
-        let bytes = decode_bytes_from_len(bytes, offset, lens)?.into();
-        Ok(DynArrayBranch::Integer(ArrayInteger { bytes, encoding }))
-    }
+enum NextArray<'a> {
+    Done(DynArrayBranch<'a>),
+    Pending(ArrayFrame<'a>),
+}
 
-    fn decode_bytes_from_len<'a>(bytes: &'a [u8], offset: &'_ mut usize, lens: &'_ mut usize) -> DecodeResult<Bytes<'a>> {
-        let len = decode_suffix_varint(bytes, lens)?;
-        Ok(decode_bytes(len as usize, bytes, offset)?.into())
+impl<'a> ArrayFrame<'a> {
+    fn start(pending: PendingArray<'a>, count: usize) -> NextArray<'a> {
+        NextArray::Pending(Self {
+            pending,
+            children: Vec::new(),
+            count,
+        })
     }
 
-    // See also e25db64d-8424-46b9-bdc1-cdb618807513
-    fn decode_tuple<'a>(num_fields: usize, bytes: &'a [u8], offset: &'_ mut usize, lens: &'_ mut usize) -> DecodeResult<DynArrayBranch<'a>> {
-        let mut fields = Vec::with_capacity(num_fields);
-        for _ in 0..num_fields {
-            let child = decode_next_array(bytes, offset, lens)?;
-            fields.push(child);
+    /// Reads anything that is interleaved with the children, like the names of fields.
+    fn before_child(&mut self, bytes: &'a [u8], offset: &'_ mut usize) -> DecodeResult<()> {
+        match &mut self.pending {
+            // See also 47a1482f-5ce3-4b78-b356-30c66dc60cda
+            PendingArray::Object(names) => names.push(decode_ident(bytes, offset)?),
+            // The discriminants come before the first variant
+            PendingArray::Enum(idents) if !self.children.is_empty() => idents.push(decode_ident(bytes, offset)?),
+            _ => {}
         }
-        Ok(DynArrayBranch::Tuple { fields })
+        Ok(())
     }
 
-    // See also 47a1482f-5ce3-4b78-b356-30c66dc60cda
-    fn decode_obj<'a>(num_fields: usize, bytes: &'a [u8], offset: &'_ mut usize, lens: &'_ mut usize) -> DecodeResult<DynArrayBranch<'a>> {
-        let mut fields = HashMap::with_capacity(num_fields);
-        for _ in 0..num_fields {
-            let name = crate::internal::decode_ident(bytes, offset)?;
-            let child = decode_next_array(bytes, offset, lens)?;
-            fields.insert(name, child);
+    fn push(&mut self, child: DynArrayBranch<'a>) {
+        // An empty array or map has no values, so the len is the only child.
+        if self.children.is_empty() && matches!(child, DynArrayBranch::Void) && matches!(self.pending, PendingArray::ArrayVar | PendingArray::Map) {
+            self.count = 1;
         }
-        Ok(DynArrayBranch::Object { fields })
+        self.children.push(child);
     }
 
-    let branch = match id {
-        Nullable => {
-            let opt = decode_next_array(bytes, offset, lens)?.into();
-            let values = decode_next_array(bytes, offset, lens)?.into();
-            DynArrayBranch::Nullable { opt, values }
-        }
-        Void => DynArrayBranch::Void,
-        Tuple2 => decode_tuple(2, bytes, offset, lens)?,
-        Tuple3 => decode_tuple(3, bytes, offset, lens)?,
-        Tuple4 => decode_tuple(4, bytes, offset, lens)?,
-        Tuple5 => decode_tuple(5, bytes, offset, lens)?,
-        Tuple6 => decode_tuple(6, bytes, offset, lens)?,
-        Tuple7 => decode_tuple(7, bytes, offset, lens)?,
-        Tuple8 => decode_tuple(8, bytes, offset, lens)?,
-        TupleN => decode_tuple(decode_prefix_varint(bytes, offset)? as usize + 9, bytes, offset, lens)?,
-        ArrayVar => {
-            let len = decode_next_array(bytes, offset, lens)?;
-            match len {
-                DynArrayBranch::Void => DynArrayBranch::Array0,
-                _ => {
-                    let len = Box::new(len);
-                    let values = decode_next_array(bytes, offset, lens)?;
-                    let values = Box::new(values);
-                    DynArrayBranch::Array { len, values }
+    fn finish(self) -> DynArrayBranch<'a> {
+        let Self { pending, children, .. } = self;
+        let mut children = children.into_iter();
+        let mut next = || Box::new(children.next().unwrap_or_default());
+
+        match pending {
+            PendingArray::Nullable => {
+                let opt = next();
+                let values = next();
+                DynArrayBranch::Nullable { opt, values }
+            }
+            PendingArray::Tuple => DynArrayBranch::Tuple { fields: children.collect() },
+            PendingArray::Object(names) => DynArrayBranch::Object {
+                fields: names.into_iter().zip(children).collect(),
+            },
+            PendingArray::ArrayVar => {
+                let len = next();
+                match *len {
+                    DynArrayBranch::Void => DynArrayBranch::Array0,
+                    _ => {
+                        let values = next();
+                        DynArrayBranch::Array { len, values }
+                    }
                 }
             }
+            PendingArray::ArrayFixed(len) => DynArrayBranch::ArrayFixed { len, values: next() },
+            PendingArray::Map => {
+                let len = next();
+                match *len {
+                    DynArrayBranch::Void => DynArrayBranch::Map0,
+                    _ => {
+                        let keys = next();
+                        let values = next();
+                        DynArrayBranch::Map { len, keys, values }
+                    }
+                }
+            }
+            PendingArray::RleBool(first) => DynArrayBranch::Boolean(ArrayBool::RLE(first, next())),
+            PendingArray::BrotliUtf8(utf8) => DynArrayBranch::BrotliUtf8 { utf8, lens: next() },
+            PendingArray::PrefixUtf8 => {
+                let prefixes = next();
+                let suffixes = next();
+                DynArrayBranch::PrefixUtf8 { prefixes, suffixes }
+            }
+            PendingArray::FsstUtf8(fsst) => DynArrayBranch::FsstUtf8 { fsst, lens: next() },
+            PendingArray::HexUtf8(hex) => DynArrayBranch::HexUtf8 { hex, lens: next() },
+            PendingArray::DecimalUtf8 => {
+                let mantissas = next();
+                let exponents = next();
+                DynArrayBranch::DecimalUtf8 { mantissas, exponents }
+            }
+            PendingArray::Blob(bytes) => DynArrayBranch::Blob { bytes, lens: next() },
+            PendingArray::Enum(idents) => {
+                let discriminants = next();
+                let variants = idents.into_iter().zip(children).map(|(ident, data)| ArrayEnumVariant { ident, data }).collect();
+                DynArrayBranch::Enum { discriminants, variants }
+            }
+            PendingArray::Rle => {
+                let values = next();
+                let runs = next();
+                DynArrayBranch::RLE { runs, values }
+            }
+            PendingArray::Dictionary => {
+                let values = next();
+                let indices = next();
+                DynArrayBranch::Dictionary { values, indices }
+            }
+            PendingArray::Tagged(tag) => DynArrayBranch::Tagged { tag, value: next() },
         }
+    }
+}
+
+fn decode_bytes_from_len<'a>(bytes: &'a [u8], offset: &'_ mut usize, lens: &'_ mut Lens) -> DecodeResult<Bytes<'a>> {
+    let len = decode_suffix_varint(bytes, &mut lens.offset)?;
+    let data = decode_bytes(len as usize, bytes, offset)?;
+    if lens.checksums {
+        let checksum = decode_suffix_varint(bytes, &mut lens.offset)?;
+        if checksum != crc32c::crc32c(data) as u64 {
+            return Err(DecodeError::ChecksumMismatch);
+        }
+    }
+    Ok(data.into())
+}
+
+/// Reads the type id of the next branch, and either the whole branch if it has
+/// no children or what comes before its children.
+fn start_array<'a>(bytes: &'a [u8], offset: &'_ mut usize, lens: &'_ mut Lens, limits: &'_ mut Limits) -> DecodeResult<NextArray<'a>> {
+    limits.enter()?;
+    let id = ArrayTypeId::decode_next(bytes, offset)?;
+
+    use ArrayTypeId::*;
+
+    fn decode_ints<'a>(bytes: &'a [u8], offset: &'_ mut usize, lens: &'_ mut Lens, encoding: ArrayIntegerEncoding) -> DecodeResult<NextArray<'a>> {
+        let bytes = decode_bytes_from_len(bytes, offset, lens)?;
+        Ok(NextArray::Done(DynArrayBranch::Integer(ArrayInteger { bytes, encoding })))
+    }
+
+    // See also e25db64d-8424-46b9-bdc1-cdb618807513
+    fn decode_tuple<'a>(num_fields: usize, limits: &'_ mut Limits) -> DecodeResult<NextArray<'a>> {
+        limits.collection(num_fields)?;
+        Ok(ArrayFrame::start(PendingArray::Tuple, num_fields))
+    }
+
+    // See also 47a1482f-5ce3-4b78-b356-30c66dc60cda
+    fn decode_obj<'a>(num_fields: usize, limits: &'_ mut Limits) -> DecodeResult<NextArray<'a>> {
+        limits.collection(num_fields)?;
+        Ok(ArrayFrame::start(PendingArray::Object(Vec::new()), num_fields))
+    }
+
+    let next = match id {
+        Nullable => ArrayFrame::start(PendingArray::Nullable, 2),
+        Void => NextArray::Done(DynArrayBranch::Void),
+        Tuple2 => decode_tuple(2, limits)?,
+        Tuple3 => decode_tuple(3, limits)?,
+        Tuple4 => decode_tuple(4, limits)?,
+        Tuple5 => decode_tuple(5, limits)?,
+        Tuple6 => decode_tuple(6, limits)?,
+        Tuple7 => decode_tuple(7, limits)?,
+        Tuple8 => decode_tuple(8, limits)?,
+        TupleN => decode_tuple(decode_usize(bytes, offset)?.checked_add(9).ok_or(DecodeError::InvalidFormat)?, limits)?,
+        ArrayVar => ArrayFrame::start(PendingArray::ArrayVar, 2),
         ArrayFixed => {
             let len = decode_usize(bytes, offset)?;
-            let values = decode_next_array(bytes, offset, lens)?;
-            let values = Box::new(values);
-            DynArrayBranch::ArrayFixed { len, values }
-        }
-        Map => {
-            let len = decode_next_array(bytes, offset, lens)?;
-            match len {
-                DynArrayBranch::Void => DynArrayBranch::Map0,
-                _ => {
-                    let len = Box::new(len);
-                    let keys = decode_next_array(bytes, offset, lens)?;
-                    let keys = Box::new(keys);
-                    let values = decode_next_array(bytes, offset, lens)?;
-                    let values = Box::new(values);
-                    DynArrayBranch::Map { len, keys, values }
-                }
-            }
+            limits.collection(len)?;
+            ArrayFrame::start(PendingArray::ArrayFixed(len), 1)
         }
+        Map => ArrayFrame::start(PendingArray::Map, 3),
 
         // See also: fadaec14-35ad-4dc1-b6dc-6106ab811669
-        Obj0 => decode_obj(0, bytes, offset, lens)?,
-        Obj1 => decode_obj(1, bytes, offset, lens)?,
-        Obj2 => decode_obj(2, bytes, offset, lens)?,
-        Obj3 => decode_obj(3, bytes, offset, lens)?,
-        Obj4 => decode_obj(4, bytes, offset, lens)?,
-        Obj5 => decode_obj(5, bytes, offset, lens)?,
-        Obj6 => decode_obj(6, bytes, offset, lens)?,
-        Obj7 => decode_obj(7, bytes, offset, lens)?,
-        Obj8 => decode_obj(8, bytes, offset, lens)?,
-        ObjN => decode_obj(decode_prefix_varint(bytes, offset)? as usize + 9, bytes, offset, lens)?,
+        Obj0 => decode_obj(0, limits)?,
+        Obj1 => decode_obj(1, limits)?,
+        Obj2 => decode_obj(2, limits)?,
+        Obj3 => decode_obj(3, limits)?,
+        Obj4 => decode_obj(4, limits)?,
+        Obj5 => decode_obj(5, limits)?,
+        Obj6 => decode_obj(6, limits)?,
+        Obj7 => decode_obj(7, limits)?,
+        Obj8 => decode_obj(8, limits)?,
+        ObjN => decode_obj(decode_usize(bytes, offset)?.checked_add(9).ok_or(DecodeError::InvalidFormat)?, limits)?,
         PackedBool => {
             let bytes = decode_bytes_from_len(bytes, offset, lens)?;
-            DynArrayBranch::Boolean(ArrayBool::Packed(bytes))
+            NextArray::Done(DynArrayBranch::Boolean(ArrayBool::Packed(bytes)))
         }
         RLEBoolTrue | RLEBoolFalse => {
             let first = matches!(id, ArrayTypeId::RLEBoolTrue);
-            let runs = decode_next_array(bytes, offset, lens)?;
-            DynArrayBranch::Boolean(ArrayBool::RLE(first, runs.into()))
+            ArrayFrame::start(PendingArray::RleBool(first), 1)
         }
         IntSimple16 => decode_ints(bytes, offset, lens, ArrayIntegerEncoding::Simple16)?,
         IntPrefixVar => decode_ints(bytes, offset, lens, ArrayIntegerEncoding::PrefixVarInt)?,
         U8 => decode_ints(bytes, offset, lens, ArrayIntegerEncoding::U8)?,
         DeltaZig => decode_ints(bytes, offset, lens, ArrayIntegerEncoding::DeltaZig)?,
+        DeltaZig64 => decode_ints(bytes, offset, lens, ArrayIntegerEncoding::DeltaZig64)?,
         F32 => {
             let bytes = decode_bytes_from_len(bytes, offset, lens)?;
-            DynArrayBranch::Float(ArrayFloat::F32(bytes))
+            NextArray::Done(DynArrayBranch::Float(ArrayFloat::F32(bytes)))
         }
         F64 => {
             let bytes = decode_bytes_from_len(bytes, offset, lens)?;
-            DynArrayBranch::Float(ArrayFloat::F64(bytes))
+            NextArray::Done(DynArrayBranch::Float(ArrayFloat::F64(bytes)))
         }
         Zfp32 => {
             let bytes = decode_bytes_from_len(bytes, offset, lens)?;
-            DynArrayBranch::Float(ArrayFloat::Zfp32(bytes))
+            NextArray::Done(DynArrayBranch::Float(ArrayFloat::Zfp32(bytes)))
         }
         Zfp64 => {
             let bytes = decode_bytes_from_len(bytes, offset, lens)?;
-            DynArrayBranch::Float(ArrayFloat::Zfp64(bytes))
+            NextArray::Done(DynArrayBranch::Float(ArrayFloat::Zfp64(bytes)))
         }
         Utf8 => {
             let bytes = decode_bytes_from_len(bytes, offset, lens)?;
-            DynArrayBranch::String(bytes)
+            NextArray::Done(DynArrayBranch::String(bytes))
         }
         BrotliUtf8 => {
             let utf8 = decode_bytes_from_len(bytes, offset, lens)?;
-            let lens = decode_next_array(bytes, offset, lens)?.into();
-            DynArrayBranch::BrotliUtf8 { utf8, lens }
+            ArrayFrame::start(PendingArray::BrotliUtf8(utf8), 1)
+        }
+        PrefixUtf8 => ArrayFrame::start(PendingArray::PrefixUtf8, 2),
+        FsstUtf8 => {
+            let fsst = decode_bytes_from_len(bytes, offset, lens)?;
+            ArrayFrame::start(PendingArray::FsstUtf8(fsst), 1)
+        }
+        HexUtf8 => {
+            let hex = decode_bytes_from_len(bytes, offset, lens)?;
+            ArrayFrame::start(PendingArray::HexUtf8(hex), 1)
+        }
+        DecimalUtf8 => ArrayFrame::start(PendingArray::DecimalUtf8, 2),
+        Blob => {
+            let blob = BlobBytes::Raw(decode_bytes_from_len(bytes, offset, lens)?);
+            ArrayFrame::start(PendingArray::Blob(blob), 1)
+        }
+        Lz4Blob => {
+            let blob = BlobBytes::Lz4(decode_bytes_from_len(bytes, offset, lens)?);
+            ArrayFrame::start(PendingArray::Blob(blob), 1)
+        }
+        BrotliBlob => {
+            let blob = BlobBytes::Brotli(decode_bytes_from_len(bytes, offset, lens)?);
+            ArrayFrame::start(PendingArray::Blob(blob), 1)
         }
         DoubleGorilla => {
             let bytes = decode_bytes_from_len(bytes, offset, lens)?;
-            DynArrayBranch::Float(ArrayFloat::DoubleGorilla(bytes))
+            NextArray::Done(DynArrayBranch::Float(ArrayFloat::DoubleGorilla(bytes)))
+        }
+        Extension => {
+            let id = decode_prefix_varint(bytes, offset)?;
+            let bytes = decode_bytes_from_len(bytes, offset, lens)?;
+            NextArray::Done(DynArrayBranch::Extension { id, bytes })
         }
         Enum => {
             let count = decode_prefix_varint(bytes, offset)? as usize;
-            let mut variants = Vec::with_capacity(count);
-
+            limits.collection(count)?;
             // TODO: Elide discriminants when there are 0 or 1 variants
-            let discriminants = decode_next_array(bytes, offset, lens)?.into();
-
-            if count != 0 {
-                for _ in 0..count {
-                    variants.push(ArrayEnumVariant {
-                        ident: decode_ident(bytes, offset)?,
-                        data: decode_next_array(bytes, offset, lens)?,
-                    });
-                }
-            }
-
-            DynArrayBranch::Enum { discriminants, variants }
-        }
-        RLE => {
-            let values = decode_next_array(bytes, offset, lens)?.into();
-            let runs = decode_next_array(bytes, offset, lens)?.into();
-            DynArrayBranch::RLE { runs, values }
-        }
-        Dictionary => {
-            let values = decode_next_array(bytes, offset, lens)?.into();
-            let indices = decode_next_array(bytes, offset, lens)?.into();
-            DynArrayBranch::Dictionary { values, indices }
+            let count = count.checked_add(1).ok_or(DecodeError::InvalidFormat)?;
+            ArrayFrame::start(PendingArray::Enum(Vec::new()), count)
         }
+        RLE => ArrayFrame::start(PendingArray::Rle, 2),
+        Dictionary => ArrayFrame::start(PendingArray::Dictionary, 2),
+        Tagged => ArrayFrame::start(PendingArray::Tagged(SemanticTag::decode(bytes, offset)?), 1),
     };
 
-    Ok(branch)
+    Ok(next)
+}
+
+/// Decodes the branches with a stack on the heap rather than with recursion,
+/// so that deeply nested input can't overflow the stack.
+pub fn decode_next_array<'a>(bytes: &'a [u8], offset: &'_ mut usize, lens: &'_ mut Lens, limits: &'_ mut Limits) -> DecodeResult<DynArrayBranch<'a>> {
+    let mut stack = Vec::new();
+    let mut next = start_array(bytes, offset, lens, limits)?;
+
+    loop {
+        next = match next {
+            NextArray::Pending(frame) if frame.children.len() == frame.count => NextArray::Done(frame.finish()),
+            NextArray::Pending(mut frame) => {
+                frame.before_child(bytes, offset)?;
+                stack.push(frame);
+                start_array(bytes, offset, lens, limits)?
+            }
+            NextArray::Done(branch) => {
+                limits.exit();
+                match stack.pop() {
+                    None => return Ok(branch),
+                    Some(mut frame) => {
+                        frame.push(branch);
+                        NextArray::Pending(frame)
+                    }
+                }
+            }
+        };
+    }
 }
 
 impl<'a> Default for DynArrayBranch<'a> {
@@ -304,9 +484,19 @@ impl_type_id!(ArrayTypeId, [
     RLEBoolFalse: 19,
     DeltaZig: 20,
     BrotliUtf8: 21,
+    PrefixUtf8: 22,
+    FsstUtf8: 23,
+    HexUtf8: 24,
+    DecimalUtf8: 25,
+    Blob: 26,
+    Lz4Blob: 27,
+    BrotliBlob: 28,
+    Extension: 29,
+    Tagged: 30,
+    DeltaZig64: 31,
 ]);
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct ArrayInteger<'a> {
     pub bytes: Bytes<'a>,
     //delta: bool,
@@ -314,10 +504,12 @@ pub struct ArrayInteger<'a> {
     pub encoding: ArrayIntegerEncoding,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum ArrayIntegerEncoding {
     PrefixVarInt,
     Simple16,
     U8,
     DeltaZig,
+    /// Like DeltaZig, with deltas which wrap at 64 bits rather than 32.
+    DeltaZig64,
 }
@@ -115,14 +115,101 @@ pub trait TypeId: Copy + Into<u8> + PartialEq + std::fmt::Debug {
 // Note that the object model may be just defined in terms of eg: Number, where Number is the sum type of F64, u64, and i64 with downcasts.
 
 #[cfg(feature = "decode")]
-pub fn decode_root(bytes: &[u8]) -> DecodeResult<DynRootBranch<'_>> {
+pub fn decode_root<'a>(bytes: &'a [u8], options: &impl DecodeOptions) -> DecodeResult<DynRootBranch<'a>> {
     profile_fn!(decode_root);
     if bytes.len() == 0 {
         return Ok(DynRootBranch::Void);
     }
-    let mut lens = bytes.len() - 1;
+
+    // See also 5b8b0f0e-3c57-4f6a-9f3c-2f3e2e5e6f0b
+    if bytes[0] == RootTypeId::Checksums.into() {
+        if bytes.len() < 5 {
+            return Err(DecodeError::InvalidFormat);
+        }
+        let (bytes, checksum) = bytes.split_at(bytes.len() - 4);
+        if crc32c::crc32c(bytes).to_le_bytes() != checksum {
+            return Err(DecodeError::ChecksumMismatch);
+        }
+        let mut lens = Lens::new(bytes, true);
+        let mut limits = Limits::new(options);
+        let mut offset = 1;
+        return decode_next_root(bytes, &mut offset, &mut lens, &mut limits);
+    }
+
+    let mut lens = Lens::new(bytes, false);
+    let mut limits = Limits::new(options);
     let mut offset = 0;
-    decode_next_root(bytes, &mut offset, &mut lens)
+    decode_next_root(bytes, &mut offset, &mut lens, &mut limits)
+}
+
+/// Position in the lens at the end of the file, which are read from back to front.
+#[cfg(feature = "decode")]
+pub struct Lens {
+    pub offset: usize,
+    /// Whether each len is followed by a checksum of the bytes it refers to.
+    pub checksums: bool,
+}
+
+#[cfg(feature = "decode")]
+impl Lens {
+    fn new(bytes: &[u8], checksums: bool) -> Self {
+        Self {
+            offset: bytes.len() - 1,
+            checksums,
+        }
+    }
+}
+
+/// The limits from DecodeOptions which can be checked while reading the branches,
+/// before anything is allocated for the values.
+#[cfg(feature = "decode")]
+pub struct Limits {
+    depth: usize,
+    max_depth: usize,
+    max_string_len: usize,
+    max_collection_len: usize,
+}
+
+#[cfg(feature = "decode")]
+impl Limits {
+    fn new(options: &impl DecodeOptions) -> Self {
+        Self {
+            depth: 0,
+            max_depth: options.max_depth().min(MAX_DEPTH),
+            max_string_len: options.max_string_len(),
+            max_collection_len: options.max_collection_len(),
+        }
+    }
+
+    #[inline]
+    fn enter(&mut self) -> DecodeResult<()> {
+        if self.depth == self.max_depth {
+            return Err(DecodeError::LimitExceeded);
+        }
+        self.depth += 1;
+        Ok(())
+    }
+
+    #[inline]
+    fn exit(&mut self) {
+        self.depth -= 1;
+    }
+
+    #[inline]
+    fn string(&self, len: usize) -> DecodeResult<()> {
+        if len > self.max_string_len {
+            return Err(DecodeError::LimitExceeded);
+        }
+        Ok(())
+    }
+
+    #[inline]
+    fn collection(&self, len: usize) -> DecodeResult<()> {
+        if len > self.max_collection_len {
+            return Err(DecodeError::LimitExceeded);
+        }
+        Ok(())
+    }
 }
 
 #[cfg(test)]
@@ -12,9 +12,7 @@ use std::convert::{TryFrom, TryInto};
 // offset can be used to find if the data type required by the schema
 // matches.
 
-// TODO: GUID. Consider having some sort of "semantic" flag to denote other kinds of values (like f64/u64 -> timestamp/date, 128 bit [u8] -> GUID)
 // TODO: Other kinds of self-description may also be interesting, since this is for data self-description is higher value
-// TODO: Bytes/Blog = [u8] compressed (eg: gzip), uncompressed
 
 #[derive(Debug)]
 pub enum DynRootBranch<'a> {
@@ -39,6 +37,7 @@ pub enum DynRootBranch<'a> {
     Float(RootFloat),
     Void,
     String(&'a str),
+    Blob(BlobBytes<'a>),
     Map0,
     Map1 {
         key: Box<DynRootBranch<'a>>,
@@ -49,35 +48,97 @@ pub enum DynRootBranch<'a> {
         keys: DynArrayBranch<'a>,
         values: DynArrayBranch<'a>,
     },
+    /// See also SemanticTag
+    Tagged {
+        tag: SemanticTag,
+        value: Box<DynRootBranch<'a>>,
+    },
+}
+
+/// A branch which has been started, and is waiting for its children to be decoded.
+/// Only children which are themselves root branches are waited on here. Array
+/// branches are decoded when the root branch is started.
+enum PendingRoot<'a> {
+    Tuple,
+    Object(Vec<Ident<'a>>),
+    Array1,
+    Map1,
+    Enum(Ident<'a>),
+    Tagged(SemanticTag),
+}
+
+struct RootFrame<'a> {
+    pending: PendingRoot<'a>,
+    children: Vec<DynRootBranch<'a>>,
+    count: usize,
+}
+
+enum NextRoot<'a> {
+    Done(DynRootBranch<'a>),
+    Pending(RootFrame<'a>),
+}
+
+impl<'a> RootFrame<'a> {
+    fn start(pending: PendingRoot<'a>, count: usize) -> NextRoot<'a> {
+        NextRoot::Pending(Self {
+            pending,
+            children: Vec::new(),
+            count,
+        })
+    }
+
+    fn before_child(&mut self, bytes: &'a [u8], offset: &'_ mut usize) -> DecodeResult<()> {
+        // See also 47a1482f-5ce3-4b78-b356-30c66dc60cda
+        if let PendingRoot::Object(names) = &mut self.pending {
+            names.push(decode_ident(bytes, offset)?);
+        }
+        Ok(())
+    }
+
+    fn finish(self) -> DynRootBranch<'a> {
+        let Self { pending, children, .. } = self;
+        let mut children = children.into_iter();
+        let mut next = || Box::new(children.next().unwrap_or_default());
+
+        match pending {
+            PendingRoot::Tuple => DynRootBranch::Tuple { fields: children.collect() },
+            PendingRoot::Object(names) => DynRootBranch::Object {
+                fields: names.into_iter().zip(children).collect(),
+            },
+            PendingRoot::Array1 => DynRootBranch::Array1(next()),
+            PendingRoot::Map1 => {
+                let key = next();
+                let value = next();
+                DynRootBranch::Map1 { key, value }
+            }
+            PendingRoot::Enum(discriminant) => DynRootBranch::Enum { discriminant, value: next() },
+            PendingRoot::Tagged(tag) => DynRootBranch::Tagged { tag, value: next() },
+        }
+    }
 }
 
-pub fn decode_next_root<'a>(bytes: &'a [u8], offset: &'_ mut usize, lens: &'_ mut usize) -> DecodeResult<DynRootBranch<'a>> {
+/// Reads the type id of the next branch, and either the whole branch if it has
+/// no root branches as children or what comes before its children.
+fn start_root<'a>(bytes: &'a [u8], offset: &'_ mut usize, lens: &'_ mut Lens, limits: &'_ mut Limits) -> DecodeResult<NextRoot<'a>> {
+    limits.enter()?;
     let id = RootTypeId::decode_next(bytes, offset)?;
 
     // See also e25db64d-8424-46b9-bdc1-cdb618807513
-    fn decode_tuple<'a>(num_fields: usize, bytes: &'a [u8], offset: &'_ mut usize, lens: &'_ mut usize) -> DecodeResult<DynRootBranch<'a>> {
-        let mut fields = Vec::with_capacity(num_fields);
-        for _ in 0..num_fields {
-            let child = decode_next_root(bytes, offset, lens)?;
-            fields.push(child);
-        }
-        Ok(DynRootBranch::Tuple { fields })
+    fn decode_tuple<'a>(num_fields: usize, limits: &'_ mut Limits) -> DecodeResult<NextRoot<'a>> {
+        limits.collection(num_fields)?;
+        Ok(RootFrame::start(PendingRoot::Tuple, num_fields))
     }
 
-    fn decode_array<'a>(len: usize, bytes: &'a [u8], offset: &'_ mut usize, lens: &'_ mut usize) -> DecodeResult<DynRootBranch<'a>> {
-        let values = decode_next_array(bytes, offset, lens)?;
-        Ok(DynRootBranch::Array { len, values })
+    fn decode_array<'a>(len: usize, bytes: &'a [u8], offset: &'_ mut usize, lens: &'_ mut Lens, limits: &'_ mut Limits) -> DecodeResult<NextRoot<'a>> {
+        limits.collection(len)?;
+        let values = decode_next_array(bytes, offset, lens, limits)?;
+        Ok(NextRoot::Done(DynRootBranch::Array { len, values }))
     }
 
     // See also 47a1482f-5ce3-4b78-b356-30c66dc60cda
-    fn decode_obj<'a>(num_fields: usize, bytes: &'a [u8], offset: &'_ mut usize, lens: &'_ mut usize) -> DecodeResult<DynRootBranch<'a>> {
-        let mut fields = HashMap::with_capacity(num_fields);
-        for _ in 0..num_fields {
-            let name = crate::internal::decode_ident(bytes, offset)?;
-            let child = decode_next_root(bytes, offset, lens)?;
-            fields.insert(name, child);
-        }
-        Ok(DynRootBranch::Object { fields })
+    fn decode_obj<'a>(num_fields: usize, limits: &'_ mut Limits) -> DecodeResult<NextRoot<'a>> {
+        limits.collection(num_fields)?;
+        Ok(RootFrame::start(PendingRoot::Object(Vec::new()), num_fields))
     }
 
     fn decode_str<'a>(len: usize, bytes: &'a [u8], offset: &'_ mut usize) -> DecodeResult<DynRootBranch<'a>> {
@@ -86,54 +147,58 @@ pub fn decode_next_root<'a>(bytes: &'a [u8], offset: &'_ mut usize, lens: &'_ mu
         Ok(DynRootBranch::String(s))
     }
 
+    fn decode_blob<'a>(bytes: &'a [u8], offset: &'_ mut usize, limits: &'_ mut Limits, f: impl FnOnce(Bytes<'a>) -> BlobBytes<'a>) -> DecodeResult<DynRootBranch<'a>> {
+        let len = decode_prefix_varint(bytes, offset)? as usize;
+        limits.string(len)?;
+        let bytes = decode_bytes(len, bytes, offset)?;
+        Ok(DynRootBranch::Blob(f(bytes.into())))
+    }
+
     use RootTypeId::*;
     let branch = match id {
         Void => DynRootBranch::Void,
 
-        Tuple2 => decode_tuple(2, bytes, offset, lens)?,
-        Tuple3 => decode_tuple(3, bytes, offset, lens)?,
-        Tuple4 => decode_tuple(4, bytes, offset, lens)?,
-        Tuple5 => decode_tuple(5, bytes, offset, lens)?,
-        Tuple6 => decode_tuple(6, bytes, offset, lens)?,
-        Tuple7 => decode_tuple(7, bytes, offset, lens)?,
-        Tuple8 => decode_tuple(8, bytes, offset, lens)?,
-        TupleN => decode_tuple(decode_prefix_varint(bytes, offset)? as usize + 9, bytes, offset, lens)?,
+        Tuple2 => return decode_tuple(2, limits),
+        Tuple3 => return decode_tuple(3, limits),
+        Tuple4 => return decode_tuple(4, limits),
+        Tuple5 => return decode_tuple(5, limits),
+        Tuple6 => return decode_tuple(6, limits),
+        Tuple7 => return decode_tuple(7, limits),
+        Tuple8 => return decode_tuple(8, limits),
+        TupleN => return decode_tuple(decode_usize(bytes, offset)?.checked_add(9).ok_or(DecodeError::InvalidFormat)?, limits),
 
         Array0 => DynRootBranch::Array0,
-        Array1 => DynRootBranch::Array1(Box::new(decode_next_root(bytes, offset, lens)?)),
-        //Array2 => decode_array(2, bytes, offset, lens)?,
-        //Array3 => decode_array(3, bytes, offset, lens)?,
-        //Array4 => decode_array(4, bytes, offset, lens)?,
+        Array1 => return Ok(RootFrame::start(PendingRoot::Array1, 1)),
+        //Array2 => decode_array(2, bytes, offset, lens, limits)?,
+        //Array3 => decode_array(3, bytes, offset, lens, limits)?,
+        //Array4 => decode_array(4, bytes, offset, lens, limits)?,
         // TODO: usize - 2
-        ArrayN => decode_array(decode_usize(bytes, offset)?, bytes, offset, lens)?,
+        ArrayN => return decode_array(decode_usize(bytes, offset)?, bytes, offset, lens, limits),
         Map => {
             let len = decode_usize(bytes, offset)?;
+            limits.collection(len)?;
             match len {
                 0 => DynRootBranch::Map0,
-                1 => {
-                    let key = decode_next_root(bytes, offset, lens)?.into();
-                    let value = decode_next_root(bytes, offset, lens)?.into();
-                    DynRootBranch::Map1 { key, value }
-                }
+                1 => return Ok(RootFrame::start(PendingRoot::Map1, 2)),
                 _ => {
-                    let keys = decode_next_array(bytes, offset, lens)?;
-                    let values = decode_next_array(bytes, offset, lens)?;
+                    let keys = decode_next_array(bytes, offset, lens, limits)?;
+                    let values = decode_next_array(bytes, offset, lens, limits)?;
                     DynRootBranch::Map { len, keys, values }
                 }
             }
         }
 
         // See also: fadaec14-35ad-4dc1-b6dc-6106ab811669
-        Obj0 => decode_obj(0, bytes, offset, lens)?,
-        Obj1 => decode_obj(1, bytes, offset, lens)?,
-        Obj2 => decode_obj(2, bytes, offset, lens)?,
-        Obj3 => decode_obj(3, bytes, offset, lens)?,
-        Obj4 => decode_obj(4, bytes, offset, lens)?,
-        Obj5 => decode_obj(5, bytes, offset, lens)?,
-        Obj6 => decode_obj(6, bytes, offset, lens)?,
-        Obj7 => decode_obj(7, bytes, offset, lens)?,
-        Obj8 => decode_obj(8, bytes, offset, lens)?,
-        ObjN => decode_obj(decode_prefix_varint(bytes, offset)? as usize + 9, bytes, offset, lens)?,
+        Obj0 => return decode_obj(0, limits),
+        Obj1 => return decode_obj(1, limits),
+        Obj2 => return decode_obj(2, limits),
+        Obj3 => return decode_obj(3, limits),
+        Obj4 => return decode_obj(4, limits),
+        Obj5 => return decode_obj(5, limits),
+        Obj6 => return decode_obj(6, limits),
+        Obj7 => return decode_obj(7, limits),
+        Obj8 => return decode_obj(8, limits),
+        ObjN => return decode_obj(decode_usize(bytes, offset)?.checked_add(9).ok_or(DecodeError::InvalidFormat)?, limits),
 
         Enum => {
             // TODO: Consider having the enum be:
@@ -149,8 +214,7 @@ pub fn decode_next_root<'a>(bytes: &'a [u8], offset: &'_ mut usize, lens: &'_ mu
             // object in instance_data. So many questions though, like does static_data need to be the same type for each discriminant?
             // If so, it would be an ArrayBranch with a fixed size.
             let discriminant = decode_ident(bytes, offset)?;
-            let value = decode_next_root(bytes, offset, lens)?.into();
-            DynRootBranch::Enum { discriminant, value }
+            return Ok(RootFrame::start(PendingRoot::Enum(discriminant), 1));
         }
 
         True => DynRootBranch::Boolean(true),
@@ -188,9 +252,49 @@ pub fn decode_next_root<'a>(bytes: &'a [u8], offset: &'_ mut usize, lens: &'_ mu
         Str1 => decode_str(1, bytes, offset)?,
         Str2 => decode_str(2, bytes, offset)?,
         Str3 => decode_str(3, bytes, offset)?,
-        Str => decode_str(decode_prefix_varint(bytes, offset)? as usize, bytes, offset)?,
+        Str => {
+            let len = decode_prefix_varint(bytes, offset)? as usize;
+            limits.string(len)?;
+            decode_str(len, bytes, offset)?
+        }
+
+        Blob => decode_blob(bytes, offset, limits, BlobBytes::Raw)?,
+        Lz4Blob => decode_blob(bytes, offset, limits, BlobBytes::Lz4)?,
+        BrotliBlob => decode_blob(bytes, offset, limits, BlobBytes::Brotli)?,
+
+        Checksums => return Err(DecodeError::InvalidFormat),
+
+        Tagged => return Ok(RootFrame::start(PendingRoot::Tagged(SemanticTag::decode(bytes, offset)?), 1)),
     };
-    Ok(branch)
+    Ok(NextRoot::Done(branch))
+}
+
+/// Decodes the branches with a stack on the heap rather than with recursion,
+/// so that deeply nested input can't overflow the stack.
+pub fn decode_next_root<'a>(bytes: &'a [u8], offset: &'_ mut usize, lens: &'_ mut Lens, limits: &'_ mut Limits) -> DecodeResult<DynRootBranch<'a>> {
+    let mut stack = Vec::new();
+    let mut next = start_root(bytes, offset, lens, limits)?;
+
+    loop {
+        next = match next {
+            NextRoot::Pending(frame) if frame.children.len() == frame.count => NextRoot::Done(frame.finish()),
+            NextRoot::Pending(mut frame) => {
+                frame.before_child(bytes, offset)?;
+                stack.push(frame);
+                start_root(bytes, offset, lens, limits)?
+            }
+            NextRoot::Done(branch) => {
+                limits.exit();
+                match stack.pop() {
+                    None => return Ok(branch),
+                    Some(mut frame) => {
+                        frame.children.push(branch);
+                        NextRoot::Pending(frame)
+                    }
+                }
+            }
+        };
+    }
 }
 
 impl<'a> Default for DynRootBranch<'a> {
@@ -242,6 +346,13 @@ impl_type_id!(RootTypeId, [
     // TODO: BrotliUtf8?
     Enum: 33,
     Map: 34,
+    Blob: 35,
+    Lz4Blob: 36,
+    BrotliBlob: 37,
+    // Not a value. Only valid as the first byte of a file.
+    // See also 5b8b0f0e-3c57-4f6a-9f3c-2f3e2e5e6f0b
+    Checksums: 38,
+    Tagged: 39,
 ]);
 
 impl RootInteger {
@@ -287,7 +398,8 @@ impl RootInteger {
                 let b = [bytes[0], bytes[1], bytes[2], bytes[3], bytes[4], bytes[5], bytes[6], 0];
                 u64::from_le_bytes(b) as i64 * -1
             }),
-            (8, true) => Self::S(u64::from_le_bytes(bytes.try_into().unwrap()) as i64 * -1),
+            // Wrapping, because i64::MIN is written as its magnitude 2^63, which is out of range for i64 before negating.
+            (8, true) => Self::S((u64::from_le_bytes(bytes.try_into().unwrap()) as i64).wrapping_neg()),
             (8, false) => Self::U(u64::from_le_bytes(bytes.try_into().unwrap()).into()),
             _ => unreachable!(),
         };
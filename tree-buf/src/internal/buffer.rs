@@ -1,10 +1,13 @@
 // TODO: Remove this allow
 #![allow(dead_code)]
 use std::alloc::{alloc, dealloc, Layout};
+use std::cell::RefCell;
 use std::mem::{align_of, size_of, transmute};
 use std::ops::Deref;
 use std::ptr::{self, NonNull};
+use std::rc::Rc;
 use std::slice;
+use std::thread_local;
 
 const SIZE: usize = 2 * 64 * 1024;
 const ALIGN: usize = 64;
@@ -125,9 +128,20 @@ impl<T> Buffer<T> {
     }
 }
 
+/// Allocations which have been given back by a Vec to be re-used by another.
+/// An allocation may come back as a Vec of a different type, so long as the alignment
+/// is the same and the size is a multiple of the new type's size.
 #[derive(Default)]
 pub(crate) struct BufferPool {
-    pool: Vec<Buffer<u8>>,
+    pool: Vec<(NonNull<u8>, Layout)>,
+}
+
+impl Drop for BufferPool {
+    fn drop(&mut self) {
+        for (ptr, layout) in self.pool.drain(..) {
+            unsafe { dealloc(ptr.as_ptr(), layout) }
+        }
+    }
 }
 
 impl BufferPool {
@@ -135,16 +149,109 @@ impl BufferPool {
         Default::default()
     }
 
-    /// Returns an empty Buffer<T>.
-    pub fn take<T: Copy>(&mut self) -> Buffer<T> {
-        self.pool.pop().unwrap_or_default().transmute_empty()
+    /// Returns an empty Vec<T>, re-using the largest compatible allocation if there is one.
+    pub fn take<T>(&mut self) -> Vec<T> {
+        if size_of::<T>() == 0 {
+            return Vec::new();
+        }
+        let best = self
+            .pool
+            .iter()
+            .enumerate()
+            .filter(|(_, (_, layout))| layout.align() == align_of::<T>() && layout.size() % size_of::<T>() == 0)
+            .max_by_key(|(_, (_, layout))| layout.size())
+            .map(|(i, _)| i);
+
+        match best {
+            // Safety: The allocation was made by a Vec with the same alignment, and its size is
+            // exactly capacity * size_of::<T>(), which is what Vec requires to later free it.
+            Some(i) => {
+                let (ptr, layout) = self.pool.swap_remove(i);
+                unsafe { Vec::from_raw_parts(ptr.as_ptr() as *mut T, 0, layout.size() / size_of::<T>()) }
+            }
+            None => Vec::new(),
+        }
     }
-    /// Puts a Buffer<T> into the pool for later.
-    pub fn put<T>(&mut self, buffer: Buffer<T>) {
-        self.pool.push(buffer.transmute_empty());
+
+    /// Puts the allocation of a Vec<T> into the pool for later, dropping any items.
+    pub fn put<T>(&mut self, mut buffer: Vec<T>) {
+        buffer.clear();
+        if size_of::<T>() == 0 || buffer.capacity() == 0 {
+            return;
+        }
+        let mut buffer = std::mem::ManuallyDrop::new(buffer);
+        // Unwrap is ok, because the Vec already holds an allocation of this size.
+        let layout = Layout::array::<T>(buffer.capacity()).unwrap();
+        // Unwrap is ok, because a Vec with capacity has a non-null pointer.
+        let ptr = NonNull::new(buffer.as_mut_ptr() as *mut u8).unwrap();
+        self.pool.push((ptr, layout));
     }
 }
 
+// The pool is made available to the whole encode this way rather than being passed
+// around because EncoderArray is constructed with Default in many places, and
+// the compressors only receive options when estimating sizes.
+// See also within_rle, which has the same problem.
+thread_local! {
+    static CURRENT: RefCell<Option<Rc<RefCell<BufferPool>>>> = const { RefCell::new(None) };
+}
+
+/// Restores the previous pool even if the encode panics.
+struct Restore(Option<Rc<RefCell<BufferPool>>>);
+
+impl Drop for Restore {
+    fn drop(&mut self) {
+        let previous = self.0.take();
+        CURRENT.with(|current| *current.borrow_mut() = previous);
+    }
+}
+
+/// Makes the pool available to take_vec and put_vec for the duration of f.
+pub(crate) fn with_pool<R>(pool: &Rc<RefCell<BufferPool>>, f: impl FnOnce() -> R) -> R {
+    let previous = CURRENT.with(|current| current.borrow_mut().replace(pool.clone()));
+    let _restore = Restore(previous);
+    f()
+}
+
+/// Returns an empty Vec, re-using an allocation from the current pool if there is one.
+pub(crate) fn take_vec<T>() -> Vec<T> {
+    CURRENT.with(|current| match &*current.borrow() {
+        Some(pool) => pool.borrow_mut().take(),
+        None => Vec::new(),
+    })
+}
+
+/// See also take_vec
+pub(crate) fn take_vec_with_capacity<T>(capacity: usize) -> Vec<T> {
+    let mut vec = take_vec();
+    vec.reserve(capacity);
+    vec
+}
+
+/// Gives a Vec an allocation from the current pool before it is first pushed to.
+#[inline(always)]
+pub(crate) fn take_vec_if_unallocated<T>(vec: &mut Vec<T>) {
+    if vec.capacity() == 0 {
+        *vec = take_vec();
+    }
+}
+
+/// Gives the allocation of a Vec back to the current pool, if there is one.
+pub(crate) fn put_vec<T>(vec: Vec<T>) {
+    CURRENT.with(|current| {
+        if let Some(pool) = &*current.borrow() {
+            pool.borrow_mut().put(vec);
+        }
+    })
+}
+
+/// Collects into a Vec from the current pool. See also take_vec
+pub(crate) fn collect_vec<T>(iter: impl IntoIterator<Item = T>) -> Vec<T> {
+    let mut vec = take_vec();
+    vec.extend(iter);
+    vec
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -194,4 +301,25 @@ mod tests {
 
         assert_eq!(&data[..], &buffer[..]);
     }
+
+    #[test]
+    pub fn pool_reuses_allocations() {
+        let mut pool = BufferPool::new();
+        let mut a: Vec<u64> = pool.take();
+        a.extend_from_slice(&[1, 2, 3, 4]);
+        let capacity = a.capacity();
+        let ptr = a.as_ptr() as usize;
+        pool.put(a);
+
+        // Same alignment, and a size which divides evenly
+        let b: Vec<f64> = pool.take();
+        assert_eq!(b.len(), 0);
+        assert_eq!(b.capacity(), capacity);
+        assert_eq!(b.as_ptr() as usize, ptr);
+        pool.put(b);
+
+        // Different alignment
+        let c: Vec<u8> = pool.take();
+        assert_eq!(c.capacity(), 0);
+    }
 }
@@ -1,3 +1,4 @@
+use crate::experimental::explain::CompressDecision;
 use crate::prelude::*;
 
 // REMEMBER: The reason this is not a trait is because of partial borrows.
@@ -7,12 +8,30 @@ pub struct EncoderStream<'a, O> {
     pub bytes: &'a mut Vec<u8>,
     pub lens: &'a mut Vec<usize>,
     pub options: &'a O,
+    /// The names of the fields and tuple items which lead to the data being encoded.
+    pub path: Vec<&'static str>,
+    /// Filled by compress if options.record_decisions()
+    pub decisions: Vec<CompressDecision>,
 }
 
 #[cfg(feature = "encode")]
 impl<'a, O: EncodeOptions> EncoderStream<'a, O> {
     pub fn new(bytes: &'a mut Vec<u8>, lens: &'a mut Vec<usize>, options: &'a O) -> Self {
-        Self { bytes, lens, options }
+        Self {
+            bytes,
+            lens,
+            options,
+            path: Vec::new(),
+            decisions: Vec::new(),
+        }
+    }
+
+    /// Encodes the data of a field or tuple item, which options may refer to by path.
+    pub fn encode_field<T>(&mut self, name: &'static str, f: impl FnOnce(&mut Self) -> T) -> T {
+        self.path.push(name);
+        let result = f(self);
+        self.path.pop();
+        result
     }
 
     pub fn encode_with_id<T: TypeId>(&mut self, f: impl FnOnce(&mut Self) -> T) -> T {
@@ -27,6 +46,10 @@ impl<'a, O: EncodeOptions> EncoderStream<'a, O> {
         let start = self.bytes.len();
         let result = f(self);
         self.lens.push(self.bytes.len() - start);
+        if self.options.checksums() {
+            // Read back after the len. See also 5b8b0f0e-3c57-4f6a-9f3c-2f3e2e5e6f0b
+            self.lens.push(crc32c::crc32c(&self.bytes[start..]) as usize);
+        }
         result
     }
 }
@@ -39,12 +62,26 @@ pub trait Encodable: Sized {
     // by not requiring that both be fully buffered simultaneously.
     #[must_use]
     fn encode_root<O: EncodeOptions>(&self, stream: &mut EncoderStream<'_, O>) -> RootTypeId;
+    /// Describes what is written, for check_compatible.
+    fn encode_schema() -> TypeSchema {
+        TypeSchema::Opaque(std::any::type_name::<Self>())
+    }
 }
 
 #[cfg(feature = "decode")]
 pub trait Decodable: Sized {
     type DecoderArray: DecoderArray<Decode = Self>;
     fn decode(sticks: DynRootBranch<'_>, options: &impl DecodeOptions) -> DecodeResult<Self>;
+    /// Like decode, but re-uses allocations already held by the value.
+    /// If an error is returned, the value may be partially overwritten.
+    fn decode_into(sticks: DynRootBranch<'_>, options: &impl DecodeOptions, into: &mut Self) -> DecodeResult<()> {
+        *into = Self::decode(sticks, options)?;
+        Ok(())
+    }
+    /// Describes what can be read, for check_compatible.
+    fn decode_schema() -> TypeSchema {
+        TypeSchema::Opaque(std::any::type_name::<Self>())
+    }
 }
 
 // TODO: Introduce a separate "Scratch" type to make eg: EncoderArray re-usable.
@@ -83,6 +120,11 @@ pub trait DecoderArray: Sized + Send {
     // I think that may require GAT though the way things are setup so come back to this later.
     fn new(sticks: DynArrayBranch<'_>, options: &impl DecodeOptions) -> DecodeResult<Self>;
     fn decode_next(&mut self) -> Result<Self::Decode, Self::Error>;
+    /// See also Decodable::decode_into
+    fn decode_next_into(&mut self, into: &mut Self::Decode) -> Result<(), Self::Error> {
+        *into = self.decode_next()?;
+        Ok(())
+    }
 }
 
 pub trait InfallibleDecoderArray: Sized {
@@ -1,49 +1,313 @@
+#[cfg(feature = "encode")]
+use crate::experimental::explain::{Candidate, CompressDecision, Outcome};
+#[cfg(feature = "encode")]
+use crate::internal::encodings::varint::size_for_varint;
 use crate::prelude::*;
+#[cfg(feature = "encode")]
+use std::{borrow::Cow, cmp::Reverse};
 
 #[cfg(feature = "encode")]
-pub(crate) fn compress<T: PartialEq, O: EncodeOptions>(data: &[T], stream: &mut EncoderStream<'_, O>, compressors: &impl CompressorSet<T>) -> ArrayTypeId {
+pub(crate) fn compress<T: PartialEq + Clone + Sync, O: EncodeOptions>(data: &[T], stream: &mut EncoderStream<'_, O>, compressors: &impl CompressorSet<T>) -> ArrayTypeId {
     profile_fn!(master_compress);
 
     // If there aren't multiple compressors, no need to be dynamic
     if compressors.len() == 1 {
-        return compressors.compress(0, data, stream).unwrap();
+        let mark = Mark::new(stream);
+        let id = compressors.compress(0, data, stream).unwrap();
+        if let Some(mut recording) = Recording::new(data, stream, compressors) {
+            recording.tried(0, Ok(id), mark, stream);
+            recording.finish(0, stream);
+        }
+        return id;
     }
 
+    let (sample_size, sampling) = match stream.options.compression_effort() {
+        CompressionEffort::Sample(sample_size, sampling) => (sample_size, sampling),
+        CompressionEffort::Exhaustive => return compress_exhaustive(data, stream, compressors),
+    };
+
     profile_section!(samples);
+    let mut recording = Recording::new(data, stream, compressors);
     let restore_bytes = stream.bytes.len();
     let restore_lens = stream.lens.len();
-    let sample_size = data.len().min(256);
-    let sample = &data[..sample_size];
+    let sample = take_sample(data, sample_size, sampling);
 
     // Rank compressors by how well they do on a sample of the data
     // TODO: Use second-stack, or considering how few items there are fixed tuples with sort or iter.
-    let mut by_size = Vec::new();
-    for i in 0..compressors.len() {
+    let mut by_size = take_vec();
+    let options = stream.options;
+    parallel_filter_map_into(
+        &mut by_size,
+        compressors.len(),
         // FIXME: A lot of these implementations are wrong, because they do not account for the lens or type id
         // If a compressor returns Err, that's because it determines as an early out that another compressor is always going to be better.
-        if let Ok(size) = compressors.fast_size_for(i, sample, stream.options) {
-            by_size.push((i, size));
-        }
+        |i| compressors.fast_size_for(i, &sample, options).ok().map(|size| (i, size)),
+        options,
+    );
+    if let Some(recording) = &mut recording {
+        recording.estimates(sample.len(), &by_size);
+    }
+    if let Cow::Owned(sample) = sample {
+        put_vec(sample);
     }
 
     drop(samples);
 
     profile_section!(actual_compress);
 
-    by_size.sort_unstable_by_key(|&(_, size)| size);
+    // Ties go to the earliest compressor, so that the choice doesn't depend on the sort algorithm.
+    by_size.sort_unstable_by_key(|&(i, size)| (size, i));
+
+    // Return the first compressor that succeeds with an encoding the rules want. Without
+    // forced encodings, the first pass will succeed unless all the encodings able to represent
+    // the data are denied, in which case the last pass takes whatever works.
+    let mut wanted = match stream.options.encoding_rules() {
+        Some(rules) if rules.forces(&stream.path) => EncodingPreference::Forced,
+        _ => EncodingPreference::Allowed,
+    };
+    loop {
+        for ranked in by_size.iter() {
+            // Whatever the other compressors write is thrown away while an encoding is forced.
+            if wanted == EncodingPreference::Forced && !may_write_forced(stream, compressors.encodings(ranked.0)) {
+                continue;
+            }
+            let mark = Mark::new(stream);
+            let result = compressors.compress(ranked.0, data, stream);
+            if let Some(recording) = &mut recording {
+                recording.tried(ranked.0, result, mark, stream);
+            }
+            if let Ok(ok) = result {
+                if encoding_preference(stream, ok) >= wanted {
+                    if let Some(recording) = recording {
+                        recording.finish(ranked.0, stream);
+                    }
+                    put_vec(by_size);
+                    return ok;
+                }
+            }
+            // If the compressor failed or isn't wanted, clear out whatever it wrote to try again.
+            stream.bytes.truncate(restore_bytes);
+            stream.lens.truncate(restore_lens);
+        }
+        wanted = match wanted {
+            EncodingPreference::Forced => EncodingPreference::Allowed,
+            EncodingPreference::Allowed => EncodingPreference::Denied,
+            // This must be called with at least one infallable compressor.
+            EncodingPreference::Denied => panic!("Missing infallable compressor for type"),
+        };
+    }
+}
+
+#[cfg(feature = "encode")]
+fn encoding_preference<O: EncodeOptions>(stream: &EncoderStream<'_, O>, encoding: ArrayTypeId) -> EncodingPreference {
+    match stream.options.encoding_rules() {
+        Some(rules) => rules.preference(&stream.path, encoding),
+        None => EncodingPreference::Allowed,
+    }
+}
+
+/// Whether a compressor which writes these encodings may write one which the rules force.
+#[cfg(feature = "encode")]
+fn may_write_forced<O: EncodeOptions>(stream: &EncoderStream<'_, O>, encodings: &[ArrayTypeId]) -> bool {
+    encodings.iter().any(|&encoding| encoding_preference(stream, encoding) == EncodingPreference::Forced)
+}
+
+/// Compresses the data with each compressor into separate buffers,
+/// then writes out whichever the rules most want, and of those the smallest including the lens.
+#[cfg(feature = "encode")]
+fn compress_exhaustive<T: PartialEq + Sync, O: EncodeOptions>(data: &[T], stream: &mut EncoderStream<'_, O>, compressors: &impl CompressorSet<T>) -> ArrayTypeId {
+    profile_fn!(compress_exhaustive);
+
+    let options = stream.options;
+    let path = &stream.path;
+    let mut candidates = take_vec();
+    parallel_filter_map_into(
+        &mut candidates,
+        compressors.len(),
+        |i| {
+            let mut bytes = take_vec();
+            let mut lens = take_vec();
+            let mut stream = EncoderStream::new(&mut bytes, &mut lens, options);
+            stream.path.clone_from(path);
+            let result = compressors.compress(i, data, &mut stream);
+            let decisions = stream.decisions;
+            let result = match result {
+                Ok(id) => Ok((bytes, lens, id)),
+                Err(()) => {
+                    put_vec(bytes);
+                    put_vec(lens);
+                    Err(())
+                }
+            };
+            Some((result, decisions))
+        },
+        options,
+    );
+
+    // Ties go to the earliest compressor, so the output is the same whether or not this ran in parallel.
+    let best = candidates
+        .iter()
+        .enumerate()
+        .filter_map(|(i, (result, _))| result.as_ref().ok().map(|ok| (i, ok)))
+        .min_by_key(|(_, (bytes, lens, id))| (Reverse(encoding_preference(stream, *id)), size_with_lens(bytes, lens)))
+        .map(|(i, _)| i)
+        // This must be called with at least one infallable compressor.
+        .expect("Missing infallable compressor for type");
+
+    let mut recording = Recording::new(data, stream, compressors);
+    let mut written = ArrayTypeId::Void;
+    for (i, (result, decisions)) in candidates.drain(..).enumerate() {
+        if let Some(recording) = &mut recording {
+            recording.record(i, result.as_ref().map(|(bytes, lens, id)| (*id, size_with_lens(bytes, lens))).map_err(|&()| ()), decisions);
+        }
+        if let Ok((bytes, lens, id)) = result {
+            if i == best {
+                stream.bytes.extend_from_slice(&bytes);
+                stream.lens.extend_from_slice(&lens);
+                written = id;
+            }
+            put_vec(bytes);
+            put_vec(lens);
+        }
+    }
+    put_vec(candidates);
+    if let Some(recording) = recording {
+        recording.finish(best, stream);
+    }
+    written
+}
+
+#[cfg(feature = "encode")]
+fn size_with_lens(bytes: &[u8], lens: &[usize]) -> usize {
+    bytes.len() + lens.iter().map(|&len| size_for_varint(len as u64)).sum::<usize>()
+}
+
+/// Where the stream was before a compressor was tried, to measure what it wrote.
+#[cfg(feature = "encode")]
+#[derive(Copy, Clone)]
+struct Mark {
+    bytes: usize,
+    lens: usize,
+    decisions: usize,
+}
 
-    // Return the first compressor that succeeds
-    for ranked in by_size.iter() {
-        if let Ok(ok) = compressors.compress(ranked.0, data, stream) {
-            return ok;
+#[cfg(feature = "encode")]
+impl Mark {
+    fn new<O>(stream: &EncoderStream<'_, O>) -> Self {
+        Self {
+            bytes: stream.bytes.len(),
+            lens: stream.lens.len(),
+            decisions: stream.decisions.len(),
         }
-        // If the compressor failed, clear out whatever it wrote to try again.
-        stream.bytes.truncate(restore_bytes);
-        stream.lens.truncate(restore_lens);
     }
+}
 
-    // This must be called with at least one infallable compressor.
-    panic!("Missing infallable compressor for type");
+/// The decision being made by compress, if the options ask for decisions to be recorded.
+#[cfg(feature = "encode")]
+struct Recording {
+    decision: CompressDecision,
+}
+
+#[cfg(feature = "encode")]
+impl Recording {
+    fn new<T, O: EncodeOptions>(data: &[T], stream: &EncoderStream<'_, O>, compressors: &impl CompressorSet<T>) -> Option<Self> {
+        if !stream.options.record_decisions() {
+            return None;
+        }
+        let candidates = (0..compressors.len())
+            .map(|i| Candidate {
+                compressor: compressors.name(i),
+                estimate: None,
+                outcome: Outcome::NotTried,
+                nested: Vec::new(),
+            })
+            .collect();
+        Some(Self {
+            decision: CompressDecision {
+                path: stream.path.join("."),
+                value_type: std::any::type_name::<T>(),
+                len: data.len(),
+                sample_len: None,
+                candidates,
+                chosen: 0,
+            },
+        })
+    }
+
+    fn estimates(&mut self, sample_len: usize, by_size: &[(usize, usize)]) {
+        self.decision.sample_len = Some(sample_len);
+        for &(i, size) in by_size {
+            self.decision.candidates[i].estimate = Some(size);
+        }
+    }
+
+    /// Records what a compressor wrote since the mark, including the decisions made within it.
+    fn tried<O>(&mut self, compressor: usize, result: Result<ArrayTypeId, ()>, mark: Mark, stream: &mut EncoderStream<'_, O>) {
+        let decisions = stream.decisions.drain(mark.decisions..).collect();
+        let result = result.map(|id| (id, size_with_lens(&stream.bytes[mark.bytes..], &stream.lens[mark.lens..])));
+        self.record(compressor, result, decisions);
+    }
+
+    fn record(&mut self, compressor: usize, result: Result<(ArrayTypeId, usize), ()>, decisions: Vec<CompressDecision>) {
+        let candidate = &mut self.decision.candidates[compressor];
+        candidate.outcome = match result {
+            Ok((encoding, size)) => Outcome::Rejected { encoding, size },
+            Err(()) => Outcome::Failed,
+        };
+        candidate.nested = decisions;
+    }
+
+    fn finish<O>(mut self, chosen: usize, stream: &mut EncoderStream<'_, O>) {
+        let candidate = &mut self.decision.candidates[chosen];
+        if let Outcome::Rejected { encoding, size } = candidate.outcome {
+            candidate.outcome = Outcome::Written { encoding, size };
+        }
+        self.decision.chosen = chosen;
+        stream.decisions.push(self.decision);
+    }
+}
+
+/// The number of runs a strided or random sample is split into.
+#[cfg(feature = "encode")]
+const SAMPLE_RUNS: usize = 8;
+
+/// Returns up to sample_size items, only copying if the items aren't the head of the data.
+#[cfg(feature = "encode")]
+fn take_sample<T: Clone>(data: &[T], sample_size: usize, sampling: Sampling) -> Cow<'_, [T]> {
+    if data.len() <= sample_size {
+        return Cow::Borrowed(data);
+    }
+    let runs = SAMPLE_RUNS.min(sample_size);
+    if sampling == Sampling::Head || runs == 0 {
+        return Cow::Borrowed(&data[..sample_size]);
+    }
+
+    // Each run comes from its own region, so the runs are in order and don't overlap.
+    let run_len = sample_size / runs;
+    let region_len = data.len() / runs;
+    let mut rng = match sampling {
+        Sampling::Random(seed) => seed,
+        _ => 0,
+    };
+    let mut sample = take_vec_with_capacity(run_len * runs);
+    for run in 0..runs {
+        let offset = match sampling {
+            Sampling::Random(_) => (split_mix(&mut rng) % (region_len - run_len + 1) as u64) as usize,
+            _ => 0,
+        };
+        let start = run * region_len + offset;
+        sample.extend_from_slice(&data[start..start + run_len]);
+    }
+    Cow::Owned(sample)
+}
+
+#[cfg(feature = "encode")]
+fn split_mix(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
 }
 
 #[cfg(feature = "encode")]
@@ -63,19 +327,28 @@ pub(crate) fn fast_size_for<T: PartialEq, O: EncodeOptions>(data: &[T], compress
 }
 
 #[cfg(feature = "encode")]
-pub(crate) trait Compressor<T> {
+pub(crate) trait Compressor<T>: Sync {
     /// Report how big the data will be without actually doing the work of compressing.
     /// Only return Err in 2 cases:
     ///   * If the compressor would fail to compress the data
     ///   * If it is known that another compressor surely can compress better
     fn fast_size_for<O: EncodeOptions>(&self, data: &[T], options: &O) -> Result<usize, ()>;
     fn compress<O: EncodeOptions>(&self, data: &[T], stream: &mut EncoderStream<'_, O>) -> Result<ArrayTypeId, ()>;
+    /// Every encoding that compress may return.
+    fn encodings(&self) -> &'static [ArrayTypeId];
+    /// Identifies the compressor when decisions are recorded. Defaults to the name of the type.
+    fn name(&self) -> &'static str {
+        let name = std::any::type_name::<Self>();
+        let name = &name[..name.find('<').unwrap_or(name.len())];
+        &name[name.rfind("::").map_or(0, |i| i + 2)..]
+    }
 }
 
-pub(crate) trait CompressorSet<T> {
+pub(crate) trait CompressorSet<T>: Sync {
     fn len(&self) -> usize;
+    fn name(&self, compressor: usize) -> String;
+    fn encodings(&self, compressor: usize) -> &'static [ArrayTypeId];
     // TODO: Replace with fast_smallest_size(&self, data: &[T], options: &O) -> usize;
-    // Then make parallel
     fn fast_size_for<O: EncodeOptions>(&self, compressor: usize, data: &[T], options: &O) -> Result<usize, ()>;
     fn compress<O: EncodeOptions>(&self, compressor: usize, data: &[T], stream: &mut EncoderStream<'_, O>) -> Result<ArrayTypeId, ()>;
 }
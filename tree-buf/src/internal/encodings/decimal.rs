@@ -0,0 +1,147 @@
+use crate::prelude::*;
+use std::borrow::Borrow;
+use std::convert::TryFrom;
+use std::vec::IntoIter;
+
+/// Stores columns of decimal digit strings like `"200000000000000000000"` as a pair of integer
+/// columns, a mantissa and a count of trailing zeros. Amounts with many trailing zeros are common
+/// in financial and blockchain data, and splitting them this way lets both columns use the integer
+/// compressors. So that the exact strings are reconstructed on decode, values with leading zeros
+/// are not eligible.
+pub(crate) struct DecimalCompressor;
+
+/// The mantissa and count of trailing zeros of a value, if it is eligible.
+fn get_part(value: &str) -> Option<(u64, u64)> {
+    let bytes = value.as_bytes();
+    if bytes.is_empty() || !bytes.iter().all(u8::is_ascii_digit) {
+        return None;
+    }
+    if value == "0" {
+        return Some((0, 0));
+    }
+    if bytes[0] == b'0' {
+        return None;
+    }
+    let trimmed = value.trim_end_matches('0');
+    // Fails if the mantissa does not fit in 64 bits.
+    let mantissa = trimmed.parse().ok()?;
+    Some((mantissa, (value.len() - trimmed.len()) as u64))
+}
+
+fn get_parts<T: Borrow<String>>(data: &[T]) -> Result<(Vec<u64>, Vec<u64>), ()> {
+    if data.is_empty() {
+        return Err(());
+    }
+
+    profile_fn!(decimal_get_parts);
+
+    let mut mantissas = take_vec_with_capacity(data.len());
+    let mut exponents = take_vec_with_capacity(data.len());
+
+    for value in data.iter() {
+        if let Some((mantissa, exponent)) = get_part(value.borrow()) {
+            mantissas.push(mantissa);
+            exponents.push(exponent);
+        } else {
+            put_vec(mantissas);
+            put_vec(exponents);
+            return Err(());
+        }
+    }
+
+    Ok((mantissas, exponents))
+}
+
+#[cfg(feature = "encode")]
+impl<T: Borrow<String>> Compressor<T> for DecimalCompressor {
+    fn fast_size_for<O: EncodeOptions>(&self, data: &[T], options: &O) -> Result<usize, ()> {
+        profile_method!(fast_size_for);
+
+        let (mantissas, exponents) = get_parts(data)?;
+
+        let from_mantissas = Vec::<u64>::fast_size_for_all(&mantissas[..], options);
+        let from_exponents = Vec::<u64>::fast_size_for_all(&exponents[..], options);
+        put_vec(mantissas);
+        put_vec(exponents);
+        let from_ids = 2;
+
+        Ok(from_ids + from_mantissas + from_exponents)
+    }
+
+    fn compress<O: EncodeOptions>(&self, data: &[T], stream: &mut EncoderStream<'_, O>) -> Result<ArrayTypeId, ()> {
+        profile_method!(compress);
+
+        let (mantissas, exponents) = get_parts(data)?;
+
+        stream.encode_with_id(|stream| mantissas.flush(stream));
+        stream.encode_with_id(|stream| exponents.flush(stream));
+
+        Ok(ArrayTypeId::DecimalUtf8)
+    }
+
+    fn encodings(&self) -> &'static [ArrayTypeId] {
+        &[ArrayTypeId::DecimalUtf8]
+    }
+}
+
+#[cfg(feature = "decode")]
+pub fn decode_decimal(mantissas: IntoIter<u64>, mut exponents: IntoIter<u64>, options: &impl DecodeOptions) -> DecodeResult<Vec<String>> {
+    profile_fn!(decode_decimal);
+
+    let mut result = Vec::with_capacity(mantissas.len());
+    let mut total = 0;
+    for mantissa in mantissas {
+        let exponent = exponents.decode_next_infallible();
+        let mut value = mantissa.to_string();
+        if exponent != 0 {
+            let exponent = usize::try_from(exponent).map_err(|_| DecodeError::LimitExceeded)?;
+            check_string_total(options, &mut total, value.len().saturating_add(exponent))?;
+            // Not something the encoder would write
+            if mantissa == 0 {
+                return Err(DecodeError::InvalidFormat);
+            }
+            for _ in 0..exponent {
+                value.push('0');
+            }
+        }
+        result.push(value);
+    }
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn owned(data: &[&str]) -> Vec<String> {
+        data.iter().map(|s| String::from(*s)).collect()
+    }
+
+    #[test]
+    fn splits_trailing_zeros() {
+        let data = owned(&["200000000000000000000", "0", "1050", "18446744073709551615"]);
+        let (mantissas, exponents) = get_parts(&data).unwrap();
+        assert_eq!(mantissas, vec![2, 0, 105, u64::MAX]);
+        assert_eq!(exponents, vec![20, 0, 1, 0]);
+
+        #[cfg(feature = "decode")]
+        assert_eq!(decode_decimal(mantissas.into_iter(), exponents.into_iter(), &DecodeOptionsDefault), Ok(data));
+    }
+
+    #[test]
+    fn rejects_non_canonical() {
+        assert!(get_parts(&owned(&["01"])).is_err());
+        assert!(get_parts(&owned(&[""])).is_err());
+        assert!(get_parts(&owned(&["-1"])).is_err());
+        assert!(get_parts(&owned(&["1.5"])).is_err());
+        // Mantissa too large for u64
+        assert!(get_parts(&owned(&["18446744073709551616"])).is_err());
+    }
+
+    #[cfg(feature = "decode")]
+    #[test]
+    fn zero_mantissa_with_exponent_is_error() {
+        let decoded = decode_decimal(vec![1, 0].into_iter(), vec![0, 3].into_iter(), &DecodeOptionsDefault);
+        assert_eq!(decoded, Err(DecodeError::InvalidFormat));
+    }
+}
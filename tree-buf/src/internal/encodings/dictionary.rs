@@ -1,5 +1,6 @@
 use crate::prelude::*;
 use std::collections::HashMap;
+use std::convert::TryFrom;
 use std::hash::Hash;
 use std::vec::IntoIter;
 
@@ -11,24 +12,15 @@ pub struct DictionaryIterator<T> {
     // to remove a 0 from the end of indexes. If both happen, this could be a problem.
     // See also 522d2f4f-c5f7-478c-8d94-e7457ae45b29
     indexes: IntoIter<u64>,
-    values: IntoIter<T>,
-    cache: HashMap<u64, T>,
+    values: Vec<T>,
 }
 
 impl<T: Clone + Default> Iterator for DictionaryIterator<T> {
     type Item = T;
     fn next(&mut self) -> Option<T> {
-        match self.indexes.next() {
-            None => None,
-            Some(index) => {
-                while self.cache.len() <= index as usize {
-                    // Have to use unwrap_or_default() here, because we used the compress fn
-                    let next = self.values.next().unwrap_or_default();
-                    self.cache.insert(self.cache.len() as u64, next);
-                }
-                Some(self.cache.get(&index).unwrap().clone())
-            }
-        }
+        let index = self.indexes.next()?;
+        // Have to use unwrap_or_default() here, because we used the compress fn
+        Some(usize::try_from(index).ok().and_then(|i| self.values.get(i)).cloned().unwrap_or_default())
     }
 }
 
@@ -47,8 +39,7 @@ impl<T: Send + Clone> DictionaryIterator<T> {
 
         Ok(Self {
             indexes,
-            values,
-            cache: HashMap::new(),
+            values: values.collect(),
         })
     }
 }
@@ -75,8 +66,8 @@ fn get_lookup_table<T: PartialEq + Copy + std::fmt::Debug + Hash + Eq>(data: &[T
     profile_fn!(get_lookup_table);
 
     // TODO: This calls for a specialized data structure
-    let mut indices = Vec::<u64>::new();
-    let mut values = Vec::new();
+    let mut indices = take_vec::<u64>();
+    let mut values = take_vec();
     let mut lookup = HashMap::new();
 
     for value in data.iter() {
@@ -100,13 +91,15 @@ fn get_lookup_table<T: PartialEq + Copy + std::fmt::Debug + Hash + Eq>(data: &[T
     // so don't go through the compression step
     // for nothing.
     if indices.len() == values.len() {
+        put_vec(indices);
+        put_vec(values);
         return Err(());
     }
 
     Ok((indices, values))
 }
 
-impl<T: PartialEq + Copy + std::fmt::Debug + Hash + Eq, S: CompressorSet<T>> Compressor<T> for Dictionary<S> {
+impl<T: PartialEq + Copy + std::fmt::Debug + Hash + Eq + Sync, S: CompressorSet<T>> Compressor<T> for Dictionary<S> {
     // TODO: fast_size_for
     fn compress<O: EncodeOptions>(&self, data: &[T], stream: &mut EncoderStream<'_, O>) -> Result<ArrayTypeId, ()> {
         // Prevent panic on indexing first item.
@@ -116,6 +109,7 @@ impl<T: PartialEq + Copy + std::fmt::Debug + Hash + Eq, S: CompressorSet<T>> Com
 
         stream.encode_with_id(|stream| compress(&values[..], stream, &self.sub_compressors));
         stream.encode_with_id(|stream| indices.flush(stream));
+        put_vec(values);
 
         Ok(ArrayTypeId::Dictionary)
     }
@@ -127,9 +121,30 @@ impl<T: PartialEq + Copy + std::fmt::Debug + Hash + Eq, S: CompressorSet<T>> Com
 
         let from_values = fast_size_for(&values[..], &self.sub_compressors, options);
         let from_indices = Vec::<u64>::fast_size_for_all(&indices[..], options);
+        put_vec(indices);
+        put_vec(values);
 
         let from_ids = 2;
 
         Ok(from_ids + from_indices + from_values)
     }
+
+    fn encodings(&self) -> &'static [ArrayTypeId] {
+        &[ArrayTypeId::Dictionary]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Previously, each index past the end of values filled a cache with defaults up to that index.
+    #[test]
+    fn index_out_of_range_is_default() {
+        let dictionary = DictionaryIterator {
+            indexes: vec![1, u64::MAX, 0, 2].into_iter(),
+            values: vec![String::from("a"), String::from("b")],
+        };
+        assert_eq!(dictionary.collect::<Vec<_>>(), vec!["b", "", "a", ""]);
+    }
 }
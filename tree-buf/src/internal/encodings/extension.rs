@@ -0,0 +1,259 @@
+//! Codecs supplied by users of the crate, for when they know something about their data that the
+//! built in encodings don't. Arrays encoded by a codec have the type id Extension, followed by the
+//! id of the codec and then the bytes it wrote. Since the built in encodings have their own type ids,
+//! every codec id is available for extensions.
+
+#[cfg(feature = "encode")]
+use crate::internal::encodings::varint::*;
+use crate::prelude::*;
+use std::sync::Arc;
+
+/// Returned by a codec which can't represent the values it was given.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Unsupported;
+
+/// Encodes arrays of unsigned integers. Integers of every size are given to the codec as u64.
+pub trait IntegerCodec: Send + Sync {
+    /// Identifies the codec in files. This must not change once files have been written with it.
+    fn id(&self) -> u64;
+    /// Appends the encoded values to bytes, or returns Err if this codec can't represent them
+    /// so that another encoding will be used instead.
+    #[cfg(feature = "encode")]
+    fn encode(&self, values: &[u64], bytes: &mut Vec<u8>) -> Result<(), Unsupported>;
+    /// The bytes may come from an untrusted file, so this should fail rather than
+    /// allocate far more than the bytes could reasonably hold.
+    #[cfg(feature = "decode")]
+    fn decode(&self, bytes: &[u8]) -> DecodeResult<Vec<u64>>;
+}
+
+/// Encodes arrays of floats. f32 values are given to the codec as f64.
+pub trait FloatCodec: Send + Sync {
+    /// See also IntegerCodec::id
+    fn id(&self) -> u64;
+    /// See also IntegerCodec::encode
+    #[cfg(feature = "encode")]
+    fn encode(&self, values: &[f64], bytes: &mut Vec<u8>) -> Result<(), Unsupported>;
+    /// See also IntegerCodec::decode
+    #[cfg(feature = "decode")]
+    fn decode(&self, bytes: &[u8]) -> DecodeResult<Vec<f64>>;
+}
+
+/// Encodes arrays of Strings.
+pub trait StringCodec: Send + Sync {
+    /// See also IntegerCodec::id
+    fn id(&self) -> u64;
+    /// See also IntegerCodec::encode
+    #[cfg(feature = "encode")]
+    fn encode(&self, values: &[&str], bytes: &mut Vec<u8>) -> Result<(), Unsupported>;
+    /// See also IntegerCodec::decode
+    #[cfg(feature = "decode")]
+    fn decode(&self, bytes: &[u8]) -> DecodeResult<Vec<String>>;
+}
+
+/// Encodes arrays of Blob, or other byte buffers which are encoded as blobs.
+pub trait BytesCodec: Send + Sync {
+    /// See also IntegerCodec::id
+    fn id(&self) -> u64;
+    /// See also IntegerCodec::encode
+    #[cfg(feature = "encode")]
+    fn encode(&self, values: &[&[u8]], bytes: &mut Vec<u8>) -> Result<(), Unsupported>;
+    /// See also IntegerCodec::decode
+    #[cfg(feature = "decode")]
+    fn decode(&self, bytes: &[u8]) -> DecodeResult<Vec<Vec<u8>>>;
+}
+
+/// The codecs which may be used when encoding, and which are consulted when decoding
+/// an array that was encoded with one. The same codecs must be given to both.
+#[derive(Clone, Default)]
+pub struct Codecs {
+    integers: Vec<Arc<dyn IntegerCodec>>,
+    floats: Vec<Arc<dyn FloatCodec>>,
+    strings: Vec<Arc<dyn StringCodec>>,
+    bytes: Vec<Arc<dyn BytesCodec>>,
+}
+
+impl Codecs {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_integer(mut self, codec: impl IntegerCodec + 'static) -> Self {
+        self.integers.push(Arc::new(codec));
+        self
+    }
+
+    pub fn with_float(mut self, codec: impl FloatCodec + 'static) -> Self {
+        self.floats.push(Arc::new(codec));
+        self
+    }
+
+    pub fn with_string(mut self, codec: impl StringCodec + 'static) -> Self {
+        self.strings.push(Arc::new(codec));
+        self
+    }
+
+    pub fn with_bytes(mut self, codec: impl BytesCodec + 'static) -> Self {
+        self.bytes.push(Arc::new(codec));
+        self
+    }
+
+    pub(crate) fn integers(&self) -> &[Arc<dyn IntegerCodec>] {
+        &self.integers
+    }
+
+    pub(crate) fn floats(&self) -> &[Arc<dyn FloatCodec>] {
+        &self.floats
+    }
+
+    pub(crate) fn strings(&self) -> &[Arc<dyn StringCodec>] {
+        &self.strings
+    }
+
+    pub(crate) fn bytes(&self) -> &[Arc<dyn BytesCodec>] {
+        &self.bytes
+    }
+}
+
+impl EncodeOptionsOverride for &'_ Codecs {
+    #[inline(always)]
+    fn codecs(&self) -> Option<Option<&Codecs>> {
+        Some(Some(*self))
+    }
+}
+
+impl DecodeOptionsOverride for &'_ Codecs {
+    #[inline(always)]
+    fn codecs(&self) -> Option<Option<&Codecs>> {
+        Some(Some(*self))
+    }
+}
+
+/// Finds the codec which encoded an array, failing if it wasn't registered.
+#[cfg(feature = "decode")]
+pub(crate) fn find_codec<'a, C: ?Sized + ExtensionCodec>(codecs: Option<&'a Codecs>, list: impl FnOnce(&'a Codecs) -> &'a [Arc<C>], id: u64) -> DecodeResult<&'a C> {
+    codecs
+        .and_then(|codecs| list(codecs).iter().find(|codec| codec.codec_id() == id))
+        .map(|codec| &**codec)
+        .ok_or(DecodeError::UnknownCodec)
+}
+
+/// Lets the codecs of each kind be treated alike.
+pub(crate) trait ExtensionCodec: Send + Sync {
+    fn codec_id(&self) -> u64;
+}
+
+/// Converts the data to what a codec of some kind accepts.
+#[cfg(feature = "encode")]
+pub(crate) trait EncodeWithCodec<T>: ExtensionCodec {
+    fn encode_values(&self, data: &[T], bytes: &mut Vec<u8>) -> Result<(), ()>;
+}
+
+macro_rules! impl_extension_codec {
+    ($($Codec:ident),*) => {
+        $(
+            impl ExtensionCodec for dyn $Codec {
+                fn codec_id(&self) -> u64 {
+                    self.id()
+                }
+            }
+        )*
+    };
+}
+
+impl_extension_codec!(IntegerCodec, FloatCodec, StringCodec, BytesCodec);
+
+#[cfg(feature = "encode")]
+impl<T: Copy + Into<u64>> EncodeWithCodec<T> for dyn IntegerCodec {
+    fn encode_values(&self, data: &[T], bytes: &mut Vec<u8>) -> Result<(), ()> {
+        let values = collect_vec(data.iter().map(|&value| value.into()));
+        let result = self.encode(&values, bytes);
+        put_vec(values);
+        result.map_err(|Unsupported| ())
+    }
+}
+
+#[cfg(feature = "encode")]
+impl<T: Copy + Into<f64>> EncodeWithCodec<T> for dyn FloatCodec {
+    fn encode_values(&self, data: &[T], bytes: &mut Vec<u8>) -> Result<(), ()> {
+        let values = collect_vec(data.iter().map(|&value| value.into()));
+        let result = self.encode(&values, bytes);
+        put_vec(values);
+        result.map_err(|Unsupported| ())
+    }
+}
+
+#[cfg(feature = "encode")]
+impl<T: AsRef<str>> EncodeWithCodec<T> for dyn StringCodec {
+    fn encode_values(&self, data: &[T], bytes: &mut Vec<u8>) -> Result<(), ()> {
+        let values = collect_vec(data.iter().map(|value| value.as_ref()));
+        let result = self.encode(&values, bytes);
+        put_vec(values);
+        result.map_err(|Unsupported| ())
+    }
+}
+
+#[cfg(feature = "encode")]
+impl<T: AsRef<[u8]>> EncodeWithCodec<T> for dyn BytesCodec {
+    fn encode_values(&self, data: &[T], bytes: &mut Vec<u8>) -> Result<(), ()> {
+        let values = collect_vec(data.iter().map(|value| value.as_ref()));
+        let result = self.encode(&values, bytes);
+        put_vec(values);
+        result.map_err(|Unsupported| ())
+    }
+}
+
+/// The built in compressors for a type, followed by the registered codecs for it.
+#[cfg(feature = "encode")]
+pub(crate) struct WithCodecs<'a, S, C: ?Sized> {
+    builtin: S,
+    codecs: &'a [Arc<C>],
+}
+
+#[cfg(feature = "encode")]
+pub(crate) fn with_codecs<S, C: ?Sized>(builtin: S, codecs: &[Arc<C>]) -> WithCodecs<'_, S, C> {
+    WithCodecs { builtin, codecs }
+}
+
+#[cfg(feature = "encode")]
+impl<T, S: CompressorSet<T>, C: ?Sized + EncodeWithCodec<T>> CompressorSet<T> for WithCodecs<'_, S, C> {
+    fn len(&self) -> usize {
+        self.builtin.len() + self.codecs.len()
+    }
+
+    fn name(&self, compressor: usize) -> String {
+        if compressor < self.builtin.len() {
+            return self.builtin.name(compressor);
+        }
+        format!("Codec {}", self.codecs[compressor - self.builtin.len()].codec_id())
+    }
+
+    fn encodings(&self, compressor: usize) -> &'static [ArrayTypeId] {
+        if compressor < self.builtin.len() {
+            return self.builtin.encodings(compressor);
+        }
+        &[ArrayTypeId::Extension]
+    }
+
+    fn fast_size_for<O: EncodeOptions>(&self, compressor: usize, data: &[T], options: &O) -> Result<usize, ()> {
+        if compressor < self.builtin.len() {
+            return self.builtin.fast_size_for(compressor, data, options);
+        }
+        let codec = &self.codecs[compressor - self.builtin.len()];
+        let mut bytes = take_vec();
+        let result = codec.encode_values(data, &mut bytes);
+        let size = size_for_varint(codec.codec_id()) + size_for_varint(bytes.len() as u64) + bytes.len();
+        put_vec(bytes);
+        result.map(|()| size)
+    }
+
+    fn compress<O: EncodeOptions>(&self, compressor: usize, data: &[T], stream: &mut EncoderStream<'_, O>) -> Result<ArrayTypeId, ()> {
+        if compressor < self.builtin.len() {
+            return self.builtin.compress(compressor, data, stream);
+        }
+        profile_method!(compress_extension);
+        let codec = &self.codecs[compressor - self.builtin.len()];
+        encode_prefix_varint(codec.codec_id(), stream.bytes);
+        stream.encode_with_len(|stream| codec.encode_values(data, stream.bytes))?;
+        Ok(ArrayTypeId::Extension)
+    }
+}
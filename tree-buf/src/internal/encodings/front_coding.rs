@@ -0,0 +1,156 @@
+use crate::prelude::*;
+use std::borrow::Borrow;
+use std::convert::TryInto;
+use std::vec::IntoIter;
+
+/// Front coding stores each string as the length of the prefix it shares with the previous
+/// string, followed by the remaining suffix. This does well on sorted or clustered columns
+/// like URLs, paths, and addresses, and unlike Brotli is cheap to decode.
+pub(crate) struct FrontCoding<S> {
+    sub_compressors: S,
+}
+
+impl<S> FrontCoding<S> {
+    pub fn new(sub_compressors: S) -> Self {
+        Self { sub_compressors }
+    }
+}
+
+fn shared_prefix_len(a: &str, b: &str) -> usize {
+    let mut len = a.bytes().zip(b.bytes()).take_while(|(a, b)| a == b).count();
+    // Only split on a char boundary, so that every suffix is valid UTF-8 on its own.
+    while !b.is_char_boundary(len) {
+        len -= 1;
+    }
+    len
+}
+
+fn get_prefixes<T: Borrow<String>>(data: &[T]) -> Result<(Vec<u64>, Vec<String>), ()> {
+    // It will always be more efficient to just defer to another encoding.
+    if data.len() < 2 {
+        return Err(());
+    }
+
+    profile_fn!(get_prefixes);
+
+    let mut prefixes = take_vec_with_capacity(data.len());
+    let mut suffixes = Vec::with_capacity(data.len());
+    let mut shared_total = 0;
+    let mut prev = "";
+    for value in data.iter() {
+        let value = value.borrow().as_str();
+        let shared = shared_prefix_len(prev, value);
+        shared_total += shared;
+        prefixes.push(shared as u64);
+        suffixes.push(value[shared..].to_owned());
+        prev = value;
+    }
+
+    // If no bytes are shared, it is determined
+    // that this cannot possibly be better,
+    // so don't go through the compression step
+    // for nothing.
+    if shared_total == 0 {
+        return Err(());
+    }
+
+    Ok((prefixes, suffixes))
+}
+
+#[cfg(feature = "encode")]
+impl<T: Borrow<String>, S: CompressorSet<String>> Compressor<T> for FrontCoding<S> {
+    fn compress<O: EncodeOptions>(&self, data: &[T], stream: &mut EncoderStream<'_, O>) -> Result<ArrayTypeId, ()> {
+        profile_method!(compress);
+
+        let (prefixes, suffixes) = get_prefixes(data)?;
+
+        stream.encode_with_id(|stream| prefixes.flush(stream));
+        stream.encode_with_id(|stream| compress(&suffixes[..], stream, &self.sub_compressors));
+
+        Ok(ArrayTypeId::PrefixUtf8)
+    }
+
+    fn fast_size_for<O: EncodeOptions>(&self, data: &[T], options: &O) -> Result<usize, ()> {
+        profile_method!(fast_size_for);
+
+        let (prefixes, suffixes) = get_prefixes(data)?;
+
+        let from_prefixes = Vec::<u64>::fast_size_for_all(&prefixes[..], options);
+        put_vec(prefixes);
+        let from_suffixes = fast_size_for(&suffixes[..], &self.sub_compressors, options);
+
+        let from_ids = 2;
+
+        Ok(from_ids + from_prefixes + from_suffixes)
+    }
+
+    fn encodings(&self) -> &'static [ArrayTypeId] {
+        &[ArrayTypeId::PrefixUtf8]
+    }
+}
+
+#[cfg(feature = "decode")]
+pub fn decode_front_coding(prefixes: IntoIter<u64>, mut suffixes: IntoIter<String>, options: &impl DecodeOptions) -> DecodeResult<Vec<String>> {
+    profile_fn!(decode_front_coding);
+
+    let mut result: Vec<String> = Vec::with_capacity(prefixes.len());
+    let mut total = 0;
+    for prefix in prefixes {
+        let prefix = prefix.try_into().map_err(|_| DecodeError::InvalidFormat)?;
+        let shared = match result.last() {
+            // Using get here both checks the length and that the prefix ends on a char boundary.
+            Some(prev) => match prev.get(..prefix) {
+                Some(shared) => shared,
+                None => return Err(DecodeError::InvalidFormat),
+            },
+            None if prefix == 0 => "",
+            None => return Err(DecodeError::InvalidFormat),
+        };
+        let suffix = suffixes.decode_next_infallible();
+        check_string_total(options, &mut total, shared.len() + suffix.len())?;
+        let mut value = String::with_capacity(shared.len() + suffix.len());
+        value.push_str(shared);
+        value.push_str(&suffix);
+        result.push(value);
+    }
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prefixes_respect_char_boundaries() {
+        assert_eq!(shared_prefix_len("abc", "abd"), 2);
+        assert_eq!(shared_prefix_len("", "abd"), 0);
+        // 'ä' and 'å' share their first UTF-8 byte
+        assert_eq!(shared_prefix_len("xä", "xå"), 1);
+    }
+
+    #[cfg(feature = "decode")]
+    #[test]
+    fn decodes_from_parts() {
+        let data = vec!["0x8a9d", "0x8a9e", "0x8b", "1"].into_iter().map(String::from).collect::<Vec<_>>();
+        let (prefixes, suffixes) = get_prefixes(&data[..]).unwrap();
+        assert_eq!(prefixes, vec![0, 5, 3, 0]);
+        let decoded = decode_front_coding(prefixes.into_iter(), suffixes.into_iter(), &DecodeOptionsDefault).unwrap();
+        assert_eq!(decoded, data);
+    }
+
+    #[cfg(feature = "decode")]
+    #[test]
+    fn prefix_beyond_previous_is_error() {
+        let prefixes = vec![0u64, 9].into_iter();
+        let suffixes = vec![String::from("ab"), String::from("c")].into_iter();
+        assert_eq!(decode_front_coding(prefixes, suffixes, &DecodeOptionsDefault), Err(DecodeError::InvalidFormat));
+    }
+
+    #[cfg(feature = "decode")]
+    #[test]
+    fn rejects_prefixes_past_the_suffixes() {
+        let prefixes = vec![0u64, 2, 9].into_iter();
+        let suffixes = vec![String::from("ab")].into_iter();
+        assert_eq!(decode_front_coding(prefixes, suffixes, &DecodeOptionsDefault), Err(DecodeError::InvalidFormat));
+    }
+}
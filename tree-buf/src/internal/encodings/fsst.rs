@@ -0,0 +1,223 @@
+//! FSST (Fast Static Symbol Table) string compression.
+//! See also https://www.vldb.org/pvldb/vol13/p2649-boncz.pdf
+//!
+//! A table of up to 255 symbols, each 1-8 bytes long, is learned from a sample
+//! of the data. Each occurrence of a symbol is replaced with a 1 byte code. Bytes
+//! not covered by the table are written as an escape code followed by the literal.
+//! Unlike Brotli, decoding is a single table lookup per code.
+use crate::prelude::*;
+use std::collections::HashMap;
+
+const ESCAPE: u8 = 255;
+const MAX_SYMBOLS: usize = 255;
+const MAX_SYMBOL_LEN: usize = 8;
+// The paper finds that training on 16KB is enough to get most of the benefit.
+const SAMPLE_SIZE: usize = 16 * 1024;
+const GENERATIONS: usize = 5;
+
+#[cfg(feature = "encode")]
+#[derive(Default)]
+pub struct SymbolTable {
+    symbols: Vec<Vec<u8>>,
+    lookup: HashMap<Vec<u8>, u8>,
+}
+
+#[cfg(feature = "encode")]
+impl SymbolTable {
+    fn from_symbols(symbols: impl Iterator<Item = Vec<u8>>) -> Self {
+        let symbols: Vec<_> = symbols.take(MAX_SYMBOLS).collect();
+        let lookup = symbols.iter().enumerate().map(|(code, symbol)| (symbol.clone(), code as u8)).collect();
+        Self { symbols, lookup }
+    }
+
+    /// Learns a table from a prefix of the data.
+    pub fn build<'a>(data: impl Iterator<Item = &'a [u8]>) -> Self {
+        profile_fn!(fsst_build);
+
+        let mut sample = Vec::new();
+        let mut sample_len = 0;
+        for value in data {
+            if sample_len >= SAMPLE_SIZE {
+                break;
+            }
+            sample_len += value.len();
+            sample.push(value);
+        }
+
+        // Each generation compresses the sample using the previous table, counting
+        // the symbols used and the concatenations of adjacent symbols. The
+        // candidates which would have saved the most bytes make the next table.
+        let mut table = Self::default();
+        for _ in 0..GENERATIONS {
+            let mut counts = HashMap::<&[u8], usize>::new();
+            for value in sample.iter() {
+                let mut offset = 0;
+                let mut prev = None;
+                while offset < value.len() {
+                    let len = table.find_longest(&value[offset..]).map(|(_, len)| len).unwrap_or(1);
+                    *counts.entry(&value[offset..offset + len]).or_default() += 1;
+                    if len != 1 {
+                        *counts.entry(&value[offset..offset + 1]).or_default() += 1;
+                    }
+                    if let Some(prev) = prev {
+                        let end = (offset + len).min(prev + MAX_SYMBOL_LEN);
+                        *counts.entry(&value[prev..end]).or_default() += 1;
+                    }
+                    prev = Some(offset);
+                    offset += len;
+                }
+            }
+
+            // Every symbol costs len + 1 bytes to store in the table, so there is no point
+            // keeping a symbol that does not cover at least that many bytes.
+            let mut candidates: Vec<_> = counts
+                .into_iter()
+                .map(|(symbol, count)| (symbol, count * symbol.len()))
+                .filter(|(symbol, gain)| *gain > symbol.len() + 1)
+                .collect();
+            // Break ties by the symbol itself so that the output does not depend on HashMap order.
+            candidates.sort_unstable_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(b.0)));
+            table = Self::from_symbols(candidates.into_iter().map(|(symbol, _)| symbol.to_vec()));
+        }
+
+        table
+    }
+
+    #[inline]
+    fn find_longest(&self, value: &[u8]) -> Option<(u8, usize)> {
+        for len in (1..=value.len().min(MAX_SYMBOL_LEN)).rev() {
+            if let Some(code) = self.lookup.get(&value[..len]) {
+                return Some((*code, len));
+            }
+        }
+        None
+    }
+
+    pub fn size_for_table(&self) -> usize {
+        1 + self.symbols.len() + self.symbols.iter().map(|s| s.len()).sum::<usize>()
+    }
+
+    /// Writes the count, then the length of each symbol, then the concatenated symbols.
+    pub fn encode_table(&self, bytes: &mut Vec<u8>) {
+        bytes.push(self.symbols.len() as u8);
+        for symbol in self.symbols.iter() {
+            bytes.push(symbol.len() as u8);
+        }
+        for symbol in self.symbols.iter() {
+            bytes.extend_from_slice(symbol);
+        }
+    }
+
+    pub fn size_for(&self, mut value: &[u8]) -> usize {
+        let mut size = 0;
+        while !value.is_empty() {
+            match self.find_longest(value) {
+                Some((_, len)) => {
+                    size += 1;
+                    value = &value[len..];
+                }
+                None => {
+                    size += 2;
+                    value = &value[1..];
+                }
+            }
+        }
+        size
+    }
+
+    pub fn encode(&self, mut value: &[u8], bytes: &mut Vec<u8>) {
+        while !value.is_empty() {
+            match self.find_longest(value) {
+                Some((code, len)) => {
+                    bytes.push(code);
+                    value = &value[len..];
+                }
+                None => {
+                    bytes.push(ESCAPE);
+                    bytes.push(value[0]);
+                    value = &value[1..];
+                }
+            }
+        }
+    }
+}
+
+/// Decodes a symbol table followed by codes, as written by `SymbolTable::encode_table` and `SymbolTable::encode`
+#[cfg(feature = "decode")]
+pub fn decompress(bytes: &[u8]) -> DecodeResult<Vec<u8>> {
+    profile_fn!(fsst_decompress);
+
+    let mut offset = 0;
+    let count = decode_bytes(1, bytes, &mut offset)?[0] as usize;
+    let lens = decode_bytes(count, bytes, &mut offset)?;
+    let mut symbols = Vec::with_capacity(count);
+    for len in lens.iter() {
+        let len = *len as usize;
+        if len == 0 || len > MAX_SYMBOL_LEN {
+            return Err(DecodeError::InvalidFormat);
+        }
+        symbols.push(decode_bytes(len, bytes, &mut offset)?);
+    }
+
+    let mut result = Vec::new();
+    while offset < bytes.len() {
+        let code = bytes[offset];
+        offset += 1;
+        if code == ESCAPE {
+            result.push(*bytes.get(offset).ok_or(DecodeError::InvalidFormat)?);
+            offset += 1;
+        } else {
+            let symbol = symbols.get(code as usize).ok_or(DecodeError::InvalidFormat)?;
+            result.extend_from_slice(symbol);
+        }
+    }
+
+    Ok(result)
+}
+
+#[cfg(all(test, feature = "encode", feature = "decode"))]
+mod tests {
+    use super::*;
+
+    fn round_trip(data: &[&str]) -> usize {
+        let table = SymbolTable::build(data.iter().map(|s| s.as_bytes()));
+        let mut bytes = Vec::new();
+        table.encode_table(&mut bytes);
+        assert_eq!(bytes.len(), table.size_for_table());
+        let mut expect = Vec::new();
+        for value in data {
+            let before = bytes.len();
+            table.encode(value.as_bytes(), &mut bytes);
+            assert_eq!(bytes.len() - before, table.size_for(value.as_bytes()));
+            expect.extend_from_slice(value.as_bytes());
+        }
+        assert_eq!(decompress(&bytes).unwrap(), expect);
+        bytes.len()
+    }
+
+    #[test]
+    fn empty() {
+        assert_eq!(round_trip(&[]), 1);
+        assert_eq!(round_trip(&["", ""]), 1);
+    }
+
+    #[test]
+    fn repetitive_urls() {
+        let data: Vec<_> = (0..100).map(|i| format!("https://example.com/api/v1/items/{}", i)).collect();
+        let data: Vec<_> = data.iter().map(|s| s.as_str()).collect();
+        let raw: usize = data.iter().map(|s| s.len()).sum();
+        assert!(round_trip(&data) < raw / 2);
+    }
+
+    #[test]
+    fn unicode_and_escapes() {
+        round_trip(&["ℝ∀∃", "🎉 party 🎉", "\u{ff}\u{fe}", "plain"]);
+    }
+
+    #[test]
+    fn bad_code_is_error() {
+        // Table with 1 symbol, followed by an out of range code
+        let bytes = [1, 1, b'a', 0, 7];
+        assert_eq!(decompress(&bytes), Err(DecodeError::InvalidFormat));
+    }
+}
@@ -1,13 +1,84 @@
 use crate::prelude::*;
 use num_traits::AsPrimitive;
+use std::convert::TryInto as _;
 
-// TODO: Remove warning
-#[allow(dead_code)]
-pub fn decompress<T: 'static + Copy>(_bytes: &[u8]) -> DecodeResult<Vec<T>>
+/// Reads bits most significant first from a sequence of words, as written by `compress`.
+struct BitReader {
+    words: Vec<u64>,
+    position: usize,
+    len: usize,
+}
+
+impl BitReader {
+    fn read(&mut self, count: u32) -> Option<u64> {
+        if self.len - self.position < count as usize {
+            return None;
+        }
+        let mut result = 0u64;
+        let mut remaining = count;
+        while remaining > 0 {
+            let used = (self.position % 64) as u32;
+            let take = (64 - used).min(remaining);
+            let bits = (self.words[self.position / 64] << used) >> (64 - take);
+            result = result.checked_shl(take).unwrap_or(0) | bits;
+            remaining -= take;
+            self.position += take as usize;
+        }
+        Some(result)
+    }
+}
+
+pub fn decompress<T: 'static + Copy>(bytes: &[u8]) -> DecodeResult<Vec<T>>
 where
     f64: AsPrimitive<T>,
 {
-    todo!()
+    // FIXME: Should do schema mismatch for f32 -> f64
+
+    // The last byte is the number of bits used in the last word,
+    // preceded by only as many bytes of that word as are necessary.
+    let (&last_bits, bytes) = bytes.split_last().ok_or(DecodeError::InvalidFormat)?;
+    if last_bits == 0 || last_bits > 64 {
+        return Err(DecodeError::InvalidFormat);
+    }
+    let last_byte_count = (last_bits as usize).div_ceil(8);
+    if bytes.len() < last_byte_count || !(bytes.len() - last_byte_count).is_multiple_of(8) {
+        return Err(DecodeError::InvalidFormat);
+    }
+    let (bytes, last) = bytes.split_at(bytes.len() - last_byte_count);
+
+    let mut words: Vec<u64> = bytes.chunks_exact(8).map(|w| u64::from_le_bytes(w.try_into().unwrap())).collect();
+    let mut last_word = [0u8; 8];
+    last_word[8 - last.len()..].copy_from_slice(last);
+    words.push(u64::from_le_bytes(last_word));
+
+    let len = (words.len() - 1) * 64 + last_bits as usize;
+    let mut reader = BitReader { words, position: 0, len };
+
+    let mut value = reader.read(64).ok_or(DecodeError::InvalidFormat)?;
+    let mut xor = value;
+    let mut result = vec![f64::from_bits(value).as_()];
+
+    // See also the comments in compress, which this mirrors.
+    while let Some(control) = reader.read(1) {
+        if control == 1 {
+            let new_window = reader.read(1).ok_or(DecodeError::InvalidFormat)?;
+            let (meaningful_bit_count, tz) = if new_window == 1 {
+                let lz = reader.read(5).ok_or(DecodeError::InvalidFormat)? as u32;
+                let meaningful_bit_count = reader.read(6).ok_or(DecodeError::InvalidFormat)? as u32 + 1;
+                let tz = 64u32.checked_sub(meaningful_bit_count + lz).ok_or(DecodeError::InvalidFormat)?;
+                (meaningful_bit_count, tz)
+            } else {
+                let prev_lz = xor.leading_zeros();
+                let prev_tz = if prev_lz == 64 { 0 } else { xor.trailing_zeros() };
+                (64 - prev_tz - prev_lz, prev_tz)
+            };
+            xor = reader.read(meaningful_bit_count).ok_or(DecodeError::InvalidFormat)? << tz;
+            value ^= xor;
+        }
+        result.push(f64::from_bits(value).as_());
+    }
+
+    Ok(result)
 }
 
 pub fn size_for(data: impl Iterator<Item = f64>) -> Result<usize, ()> {
@@ -135,3 +206,33 @@ pub fn compress(data: impl Iterator<Item = f64>, bytes: &mut Vec<u8>) -> Result<
 
     Ok(ArrayTypeId::DoubleGorilla)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(all(feature = "decode", feature = "encode"))]
+    #[test]
+    fn round_trips() {
+        let data = [1.0, 1.0, 1.5, -2.25, 1e300, f64::NAN.copysign(-1.0), 0.0, 3.0];
+        let mut bytes = Vec::new();
+        compress(data.iter().copied(), &mut bytes).unwrap();
+        let decoded = decompress::<f64>(&bytes).unwrap();
+        assert_eq!(
+            decoded.iter().map(|f| f.to_bits()).collect::<Vec<_>>(),
+            data.iter().map(|f| f.to_bits()).collect::<Vec<_>>()
+        );
+    }
+
+    // The decoder this replaced panicked on each of these.
+    #[cfg(feature = "decode")]
+    #[test]
+    fn truncated() {
+        assert_eq!(decompress::<f64>(&[]), Err(DecodeError::InvalidFormat));
+        assert_eq!(decompress::<f64>(&[16]), Err(DecodeError::InvalidFormat));
+        assert_eq!(decompress::<f64>(&[1, 2, 9]), Err(DecodeError::InvalidFormat));
+        assert_eq!(decompress::<f64>(&[0; 9]), Err(DecodeError::InvalidFormat));
+        // A window which is wider than 64 bits
+        assert_eq!(decompress::<f64>(&[0, 0, 0, 0, 0, 0, 0, 0, 0xff, 0xff, 0xff, 21]), Err(DecodeError::InvalidFormat));
+    }
+}
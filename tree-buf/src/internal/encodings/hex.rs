@@ -0,0 +1,199 @@
+use crate::prelude::*;
+use std::borrow::Borrow;
+use std::convert::TryInto;
+use std::vec::IntoIter;
+
+// Flags stored in the first byte, shared by every value in the column.
+const PREFIX_0X: u8 = 1 << 0;
+const UPPERCASE: u8 = 1 << 1;
+
+/// Stores columns of hex strings like hashes and addresses (`0x8a9d...`) as packed nibbles,
+/// halving their size. To reconstruct the exact original strings on decode, this only applies
+/// when every value either has or lacks the `0x` prefix, and the letters are all of one case.
+pub(crate) struct HexCompressor;
+
+fn get_flags<T: Borrow<String>>(data: &[T]) -> Result<u8, ()> {
+    let first = data.first().ok_or(())?;
+    let prefix = first.borrow().starts_with("0x");
+    let mut upper = None;
+
+    for value in data.iter() {
+        let mut digits = value.borrow().as_bytes();
+        if prefix {
+            digits = digits.strip_prefix(b"0x").ok_or(())?;
+        }
+        // An empty value isn't hex.
+        if digits.is_empty() {
+            return Err(());
+        }
+        for digit in digits {
+            let is_upper = match digit {
+                b'0'..=b'9' => continue,
+                b'a'..=b'f' => false,
+                b'A'..=b'F' => true,
+                _ => return Err(()),
+            };
+            if *upper.get_or_insert(is_upper) != is_upper {
+                return Err(());
+            }
+        }
+    }
+
+    let mut flags = 0;
+    if prefix {
+        flags |= PREFIX_0X;
+    }
+    if upper == Some(true) {
+        flags |= UPPERCASE;
+    }
+    Ok(flags)
+}
+
+#[inline]
+fn nibble(digit: u8) -> u8 {
+    match digit {
+        b'0'..=b'9' => digit - b'0',
+        b'a'..=b'f' => digit - b'a' + 10,
+        b'A'..=b'F' => digit - b'A' + 10,
+        _ => unreachable!("Digits were checked in get_flags"),
+    }
+}
+
+fn digits<T: Borrow<String>>(value: &T, flags: u8) -> &[u8] {
+    let value = value.borrow().as_bytes();
+    if flags & PREFIX_0X != 0 {
+        &value[2..]
+    } else {
+        value
+    }
+}
+
+#[cfg(feature = "encode")]
+impl<T: Borrow<String>> Compressor<T> for HexCompressor {
+    fn fast_size_for<O: EncodeOptions>(&self, data: &[T], options: &O) -> Result<usize, ()> {
+        profile_method!(fast_size_for);
+
+        let flags = get_flags(data)?;
+        let lens: Vec<u64> = data.iter().map(|value| digits(value, flags).len() as u64).collect();
+        let packed = 1 + lens.iter().map(|len| (len / 2 + len % 2) as usize).sum::<usize>();
+        let from_lens = Vec::<u64>::fast_size_for_all(&lens[..], options);
+        let from_ids = 1;
+
+        Ok(packed + size_for_varint(packed as u64) + from_lens + from_ids)
+    }
+
+    fn compress<O: EncodeOptions>(&self, data: &[T], stream: &mut EncoderStream<'_, O>) -> Result<ArrayTypeId, ()> {
+        profile_method!(compress);
+
+        let flags = get_flags(data)?;
+        let mut lens = take_vec_with_capacity(data.len());
+
+        stream.encode_with_len(|stream| {
+            stream.bytes.push(flags);
+            for value in data.iter() {
+                let digits = digits(value, flags);
+                lens.push(digits.len() as u64);
+                for pair in digits.chunks(2) {
+                    let high = nibble(pair[0]) << 4;
+                    let low = pair.get(1).map(|d| nibble(*d)).unwrap_or(0);
+                    stream.bytes.push(high | low);
+                }
+            }
+        });
+
+        stream.encode_with_id(|stream| lens.flush(stream));
+
+        Ok(ArrayTypeId::HexUtf8)
+    }
+
+    fn encodings(&self) -> &'static [ArrayTypeId] {
+        &[ArrayTypeId::HexUtf8]
+    }
+}
+
+#[cfg(feature = "decode")]
+pub fn decode_hex(bytes: &[u8], lens: IntoIter<u64>) -> DecodeResult<Vec<String>> {
+    profile_fn!(decode_hex);
+
+    let mut offset = 0;
+    let flags = decode_bytes(1, bytes, &mut offset)?[0];
+    if flags & !(PREFIX_0X | UPPERCASE) != 0 {
+        return Err(DecodeError::InvalidFormat);
+    }
+    let alphabet = if flags & UPPERCASE != 0 { b"0123456789ABCDEF" } else { b"0123456789abcdef" };
+
+    let mut result = Vec::with_capacity(lens.len());
+    for len in lens {
+        let len: usize = len.try_into().map_err(|_| DecodeError::InvalidFormat)?;
+        let packed = decode_bytes((len / 2) + (len % 2), bytes, &mut offset)?;
+        let mut value = String::with_capacity(len + 2);
+        if flags & PREFIX_0X != 0 {
+            value.push_str("0x");
+        }
+        for (i, byte) in packed.iter().enumerate() {
+            value.push(alphabet[(byte >> 4) as usize] as char);
+            if i * 2 + 1 < len {
+                value.push(alphabet[(byte & 0xF) as usize] as char);
+            } else if byte & 0xF != 0 {
+                // Padding for an odd number of digits must be 0
+                return Err(DecodeError::InvalidFormat);
+            }
+        }
+        result.push(value);
+    }
+
+    if offset != bytes.len() {
+        return Err(DecodeError::InvalidFormat);
+    }
+
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn owned(data: &[&str]) -> Vec<String> {
+        data.iter().map(|s| String::from(*s)).collect()
+    }
+
+    #[test]
+    fn detects_format() {
+        assert_eq!(get_flags(&owned(&["0x8a9d", "0x01"])), Ok(PREFIX_0X));
+        assert_eq!(get_flags(&owned(&["8A9D", "FF", "01"])), Ok(UPPERCASE));
+        assert_eq!(get_flags(&owned(&["0123", "99"])), Ok(0));
+        // Mixed prefix
+        assert_eq!(get_flags(&owned(&["0x8a9d", "8a9d"])), Err(()));
+        // Mixed case would not round trip
+        assert_eq!(get_flags(&owned(&["0x8a9d", "0x8A9D"])), Err(()));
+        assert_eq!(get_flags(&owned(&["0xg"])), Err(()));
+        // No digits
+        assert_eq!(get_flags(&owned(&["0x8a9d", "0x"])), Err(()));
+        assert_eq!(get_flags(&owned(&["8a9d", ""])), Err(()));
+        assert_eq!(get_flags::<String>(&[]), Err(()));
+    }
+
+    #[cfg(feature = "decode")]
+    #[test]
+    fn odd_padding_must_be_zero() {
+        let bytes = [0, 0x12];
+        assert_eq!(decode_hex(&bytes, vec![1].into_iter()), Err(DecodeError::InvalidFormat));
+        assert_eq!(decode_hex(&bytes, vec![2].into_iter()), Ok(owned(&["12"])));
+    }
+
+    #[cfg(feature = "decode")]
+    #[test]
+    fn rejects_lens_past_the_end() {
+        let bytes = [0, 0x12];
+        assert_eq!(decode_hex(&bytes, vec![2, 0, 1].into_iter()), Err(DecodeError::InvalidFormat));
+        // Zero lens take no bytes
+        assert_eq!(decode_hex(&bytes, vec![2, 0].into_iter()), Ok(owned(&["12", ""])));
+    }
+
+    #[cfg(feature = "decode")]
+    #[test]
+    fn rejects_leftover_bytes() {
+        let bytes = [0, 0x12, 0x34];
+        assert_eq!(decode_hex(&bytes, vec![2].into_iter()), Err(DecodeError::InvalidFormat));
+    }
+}
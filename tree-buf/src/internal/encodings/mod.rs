@@ -1,6 +1,11 @@
 mod compress;
+mod decimal;
 pub mod delta;
 mod dictionary;
+pub mod extension;
+mod front_coding;
+pub mod fsst;
+mod hex;
 pub mod packed_bool;
 pub mod rle;
 pub mod rle_bool;
@@ -8,14 +13,16 @@ pub mod varint;
 
 use crate::prelude::*;
 pub(crate) use compress::*;
+pub(crate) use decimal::*;
 pub(crate) use dictionary::*;
+pub(crate) use extension::*;
+pub(crate) use front_coding::*;
+pub(crate) use hex::*;
 pub(crate) use rle::*;
 
 mod gorilla_new;
-mod gorilla_old;
 pub mod gorilla {
-    pub use super::gorilla_new::{compress, size_for};
-    pub use super::gorilla_old::decompress;
+    pub use super::gorilla_new::{compress, decompress, size_for};
 }
 
 //pub mod zfp;
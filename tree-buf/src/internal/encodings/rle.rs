@@ -1,5 +1,6 @@
 use crate::prelude::*;
 use std::cell::RefCell;
+use std::convert::TryFrom;
 use std::thread_local;
 use std::vec::IntoIter;
 
@@ -11,6 +12,19 @@ thread_local! {
     static IN_RLE_ENCODE: RefCell<bool> = RefCell::new(false);
 }
 
+/// Runs f as though within_rle had or had not been called. This is for carrying the
+/// state over to another thread when encoding in parallel.
+pub(crate) fn with_rle_state<T>(in_rle: bool, f: impl FnOnce() -> T) -> T {
+    let previous = IN_RLE_ENCODE.with(|v| v.replace(in_rle));
+    let result = f();
+    IN_RLE_ENCODE.with(|v| *v.borrow_mut() = previous);
+    result
+}
+
+pub(crate) fn rle_state() -> bool {
+    IN_RLE_ENCODE.with(|v| *v.borrow())
+}
+
 pub(crate) fn within_rle<T>(f: impl FnOnce() -> Result<T, ()>) -> Result<T, ()> {
     if IN_RLE_ENCODE.with(|v| *v.borrow()) {
         Err(())
@@ -64,6 +78,7 @@ impl<T: Send + Clone> RleIterator<T> {
         let (runs, values) = parallel(|| <u64 as Decodable>::DecoderArray::new(*runs, options), || f(*values), options);
         let runs = runs?;
         let values = values?;
+        check_runs(&runs, options)?;
 
         Ok(Self {
             current_run: None,
@@ -74,6 +89,24 @@ impl<T: Send + Clone> RleIterator<T> {
     }
 }
 
+/// Fails if expanding the runs would exceed max_elements, so that a handful of bytes
+/// can't be made into an arbitrarily large allocation.
+#[cfg(feature = "decode")]
+pub(crate) fn check_runs(runs: &IntoIter<u64>, options: &impl DecodeOptions) -> DecodeResult<()> {
+    let mut total: usize = 0;
+    for &run in runs.as_slice() {
+        total = usize::try_from(run)
+            .ok()
+            .and_then(|run| run.checked_add(1))
+            .and_then(|run| total.checked_add(run))
+            .ok_or(DecodeError::LimitExceeded)?;
+    }
+    if total > remaining_elements(options) {
+        return Err(DecodeError::LimitExceeded);
+    }
+    Ok(())
+}
+
 pub(crate) struct RLE<S> {
     // TODO: (Performance) Do not require the allocation of this Vec
     sub_compressors: S,
@@ -95,10 +128,10 @@ fn get_runs<T: PartialEq + Copy>(data: &[T]) -> Result<(Vec<u64>, Vec<T>), ()> {
     // Prevent panic on indexing first item.
     profile_fn!(rle_get_runs);
 
-    let mut runs = Vec::new();
+    let mut runs = take_vec();
     let mut current_run = 0u64;
     let mut current_value = data[0];
-    let mut values = vec![];
+    let mut values = take_vec();
     for item in data[1..].iter() {
         if current_value == *item {
             current_run += 1;
@@ -118,13 +151,15 @@ fn get_runs<T: PartialEq + Copy>(data: &[T]) -> Result<(Vec<u64>, Vec<T>), ()> {
     // so don't go through the compression step
     // for nothing.
     if values.len() == data.len() {
+        put_vec(runs);
+        put_vec(values);
         Err(())
     } else {
         Ok((runs, values))
     }
 }
 
-impl<T: PartialEq + Copy + std::fmt::Debug, S: CompressorSet<T>> Compressor<T> for RLE<S> {
+impl<T: PartialEq + Copy + std::fmt::Debug + Sync, S: CompressorSet<T>> Compressor<T> for RLE<S> {
     fn compress<O: EncodeOptions>(&self, data: &[T], stream: &mut EncoderStream<'_, O>) -> Result<ArrayTypeId, ()> {
         profile_method!(compress);
 
@@ -133,6 +168,7 @@ impl<T: PartialEq + Copy + std::fmt::Debug, S: CompressorSet<T>> Compressor<T> f
 
             stream.encode_with_id(|stream| compress(&values[..], stream, &self.sub_compressors));
             stream.encode_with_id(|stream| runs.flush(stream));
+            put_vec(values);
 
             Ok(ArrayTypeId::RLE)
         })
@@ -146,10 +182,16 @@ impl<T: PartialEq + Copy + std::fmt::Debug, S: CompressorSet<T>> Compressor<T> f
 
             let from_values = fast_size_for(&values[..], &self.sub_compressors, options);
             let from_runs = Vec::<u64>::fast_size_for_all(&runs[..], options);
+            put_vec(runs);
+            put_vec(values);
 
             let from_ids = 2;
 
             Ok(from_ids + from_runs + from_values)
         })
     }
+
+    fn encodings(&self) -> &'static [ArrayTypeId] {
+        &[ArrayTypeId::RLE]
+    }
 }
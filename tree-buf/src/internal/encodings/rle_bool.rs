@@ -34,7 +34,7 @@ fn bool_runs_and_id(items: &[bool]) -> Result<(Vec<u64>, ArrayTypeId), ()> {
     let type_id = if current_value { ArrayTypeId::RLEBoolTrue } else { ArrayTypeId::RLEBoolFalse };
     let mut current_run: u64 = 0;
     // TODO: (Performance) use second-stack
-    let mut runs = Vec::new();
+    let mut runs = take_vec();
     let items = &items[1..];
     for item in items {
         if *item == current_value {
@@ -55,7 +55,11 @@ pub fn encode_rle_bool<O: EncodeOptions>(items: &[bool], stream: &mut EncoderStr
     profile_fn!(encode_rle_bool);
 
     let (runs, type_id) = bool_runs_and_id(items)?;
-    stream.encode_with_id(|stream| runs.flush(stream));
+    // Each run decodes to at least one bool, so the runs must not be padded
+    // the way Simple16 pads its last word. Otherwise, the decoded array would
+    // be longer than the one encoded.
+    stream.encode_with_id(|stream| compress(&runs, stream, &(PrefixVarIntCompressor,)));
+    put_vec(runs);
 
     Ok(type_id)
 }
@@ -63,7 +67,8 @@ pub fn encode_rle_bool<O: EncodeOptions>(items: &[bool], stream: &mut EncoderStr
 #[cfg(feature = "encode")]
 pub fn size_of_rle_bool<O: EncodeOptions>(items: &[bool], options: &O) -> Result<usize, ()> {
     let (runs, _) = bool_runs_and_id(items)?;
-    let runs_size = Vec::<u64>::fast_size_for_all(&runs[..], options);
+    let runs_size = PrefixVarIntCompressor.fast_size_for(&runs[..], options)?;
+    put_vec(runs);
     // + the type id for runs
     Ok(runs_size + 1)
 }
@@ -10,6 +10,12 @@ pub enum DecodeError {
     // so it's not useful information. Removing the variants makes it so that at
     // least for now we can avoid boxing.
     InvalidFormat,
+    /// The file was written with checksums, and the data did not match them.
+    ChecksumMismatch,
+    /// Decoding would exceed one of the limits in DecodeOptions, like max_depth.
+    LimitExceeded,
+    /// The file was written with a codec which was not given to DecodeOptions.
+    UnknownCodec,
 }
 
 use coercible_errors::coercible_errors;
@@ -21,6 +27,9 @@ impl Display for DecodeError {
         match self {
             DecodeError::SchemaMismatch => f.write_str("The expected schema did not match that in the document."),
             DecodeError::InvalidFormat => f.write_str("The format was not a valid Tree-Buf"),
+            DecodeError::ChecksumMismatch => f.write_str("The data did not match the checksum, and may be corrupt"),
+            DecodeError::LimitExceeded => f.write_str("Decoding would exceed a limit set in the options"),
+            DecodeError::UnknownCodec => f.write_str("The document uses a codec which was not registered in the options"),
         }
     }
 }
@@ -0,0 +1,169 @@
+//! Supports #[tree_buf(flatten)], which writes the fields of a struct as though they were
+//! fields of the struct containing it. The derive macros implement these traits for every
+//! struct, so that any struct may be flattened into another.
+
+#[cfg(feature = "encode")]
+use crate::internal::encodings::varint::encode_prefix_varint;
+use crate::prelude::*;
+#[cfg(feature = "decode")]
+use std::collections::HashMap;
+
+#[cfg(feature = "encode")]
+pub trait EncodableFields: Encodable {
+    /// The number of fields written, counting each field of flattened structs.
+    const FIELD_COUNT: usize;
+    /// The name of each field written, including the fields of flattened structs.
+    const FIELD_NAMES: FieldNames;
+    /// Writes the name and value of each field, as encode_root does after the number of fields.
+    fn encode_root_fields<O: EncodeOptions>(&self, stream: &mut EncoderStream<'_, O>);
+    /// Flushes each field in order, as flush does when not encoding in parallel.
+    fn flush_fields_in_order<O: EncodeOptions>(array: Self::EncoderArray, stream: &mut EncoderStream<'_, O>);
+    /// The encoder for each field, as flush passes to encode_fields when encoding in parallel.
+    fn flush_fields<'a, O: EncodeOptions>(array: Self::EncoderArray) -> Vec<FieldEncoder<'a, O>>;
+}
+
+#[cfg(feature = "decode")]
+pub trait DecodableFields: Decodable {
+    /// Decodes from the fields of an object, removing the fields that are used.
+    fn decode_root_fields<'a>(fields: &mut HashMap<Ident<'a>, DynRootBranch<'a>>, options: &impl DecodeOptions) -> DecodeResult<Self>;
+    /// Like decode_root_fields, for the fields of an array of objects.
+    fn new_array_fields<'a>(fields: &mut HashMap<Ident<'a>, DynArrayBranch<'a>>, options: &impl DecodeOptions) -> DecodeResult<Self::DecoderArray>;
+}
+
+/// The names of the fields of a struct, with the names of the fields of each flattened struct
+/// kept in their own FieldNames. A struct can only refer to the constants of the others,
+/// so they are not gathered into one slice.
+#[cfg(feature = "encode")]
+#[derive(Debug)]
+pub struct FieldNames {
+    pub names: &'static [&'static str],
+    pub flattened: &'static [&'static FieldNames],
+}
+
+#[cfg(feature = "encode")]
+impl FieldNames {
+    const fn len(&self) -> usize {
+        let mut len = self.names.len();
+        let mut i = 0;
+        while i < self.flattened.len() {
+            len += self.flattened[i].len();
+            i += 1;
+        }
+        len
+    }
+
+    const fn get(&self, mut index: usize) -> &'static str {
+        if index < self.names.len() {
+            return self.names[index];
+        }
+        index -= self.names.len();
+        let mut i = 0;
+        loop {
+            let len = self.flattened[i].len();
+            if index < len {
+                return self.flattened[i].get(index);
+            }
+            index -= len;
+            i += 1;
+        }
+    }
+}
+
+#[cfg(feature = "encode")]
+const fn str_eq(a: &str, b: &str) -> bool {
+    let (a, b) = (a.as_bytes(), b.as_bytes());
+    if a.len() != b.len() {
+        return false;
+    }
+    let mut i = 0;
+    while i < a.len() {
+        if a[i] != b[i] {
+            return false;
+        }
+        i += 1;
+    }
+    true
+}
+
+/// Panics if a struct would write two fields with the same name, which flattening makes possible.
+/// A file with both could not be read, since only one of the values can be found by name.
+/// The derive evaluates this in a constant, so a struct like this does not compile:
+///
+/// ```compile_fail
+/// use tree_buf::prelude::*;
+///
+/// #[derive(Encode)]
+/// struct Edit {
+///     id: u64,
+/// }
+///
+/// #[derive(Encode)]
+/// struct Clashing {
+///     id: u64,
+///     #[tree_buf(flatten)]
+///     edit: Edit,
+/// }
+/// ```
+#[cfg(feature = "encode")]
+pub const fn check_field_names(fields: &FieldNames) {
+    let len = fields.len();
+    let mut i = 0;
+    while i < len {
+        let mut j = i + 1;
+        while j < len {
+            if str_eq(fields.get(i), fields.get(j)) {
+                panic!("The struct has more than one field with the same name, counting the fields of flattened structs");
+            }
+            j += 1;
+        }
+        i += 1;
+    }
+}
+
+/// Writes the number of fields of a struct, if it doesn't fit in the type id.
+// See also: fadaec14-35ad-4dc1-b6dc-6106ab811669
+#[cfg(feature = "encode")]
+pub fn encode_root_object<O: EncodeOptions>(num_fields: usize, stream: &mut EncoderStream<'_, O>) -> RootTypeId {
+    match num_fields {
+        0 => RootTypeId::Obj0,
+        1 => RootTypeId::Obj1,
+        2 => RootTypeId::Obj2,
+        3 => RootTypeId::Obj3,
+        4 => RootTypeId::Obj4,
+        5 => RootTypeId::Obj5,
+        6 => RootTypeId::Obj6,
+        7 => RootTypeId::Obj7,
+        8 => RootTypeId::Obj8,
+        _ => {
+            encode_prefix_varint(num_fields as u64 - 9, stream.bytes);
+            RootTypeId::ObjN
+        }
+    }
+}
+
+/// Like encode_root_object, for an array of structs.
+#[cfg(feature = "encode")]
+pub fn encode_array_object<O: EncodeOptions>(num_fields: usize, stream: &mut EncoderStream<'_, O>) -> ArrayTypeId {
+    match num_fields {
+        0 => ArrayTypeId::Obj0,
+        1 => ArrayTypeId::Obj1,
+        2 => ArrayTypeId::Obj2,
+        3 => ArrayTypeId::Obj3,
+        4 => ArrayTypeId::Obj4,
+        5 => ArrayTypeId::Obj5,
+        6 => ArrayTypeId::Obj6,
+        7 => ArrayTypeId::Obj7,
+        8 => ArrayTypeId::Obj8,
+        _ => {
+            encode_prefix_varint(num_fields as u64 - 9, stream.bytes);
+            ArrayTypeId::ObjN
+        }
+    }
+}
+
+/// Appends the fields of a struct's schema to those of the struct it is flattened into.
+pub fn flatten_schema(fields: &mut Vec<(&'static str, TypeSchema)>, schema: TypeSchema) {
+    if let TypeSchema::Struct { fields: flattened, .. } = schema {
+        fields.extend(flattened);
+    }
+}
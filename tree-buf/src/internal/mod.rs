@@ -10,14 +10,16 @@ pub mod buffer;
 pub mod chunk;
 pub mod encoder_decoder;
 pub mod error;
+pub mod flatten;
 pub mod markers;
 pub mod options;
 pub mod parallel;
 pub mod rust_std;
+pub mod semantic;
 pub mod types;
 
-pub use {branch::*, encoder_decoder::*, encodings::*, options::*, parallel::*, rust_std::*, types::*};
 pub(crate) use buffer::*;
+pub use {branch::*, encoder_decoder::*, encodings::*, flatten::*, options::*, parallel::*, rust_std::*, semantic::*, types::*};
 
 pub(crate) use markers::*;
 
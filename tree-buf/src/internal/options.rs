@@ -5,7 +5,10 @@
 // territory (eg: FlatBuffers). Though Flatbuffers by way of example doesn't allow for in-place encoding,
 //
 
-// TODO: Option for encode sample size. This could be a method which actually returns the sample array
+use crate::prelude::*;
+use std::convert::TryFrom;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
 
 macro_rules! options {
     ($Options:ident, $Default:ident, $Override:ident, $Hierarchy:ident, {$($name:ident: $T:ty = $fallback:expr),*}) => {
@@ -39,19 +42,46 @@ macro_rules! options {
             fallback: T0,
             overrides: T1,
         }
+
+        impl<T: $Options + ?Sized> $Options for &'_ T {
+            $(
+                #[inline(always)]
+                fn $name(&self) -> $T {
+                    (**self).$name()
+                }
+            )*
+        }
     };
 }
 
-// TODO: Option for parallel
 // TODO: Put scratch in options?
 options!(EncodeOptions, EncodeOptionsDefault, EncodeOptionsOverride, EncodeOptionsHierarchy, {
-    lossy_float_tolerance: Option<i32> = None
+    lossy_float_tolerance: Option<i32> = None,
+    checksums: bool = false,
+    parallel: bool = false,
+    compression_effort: CompressionEffort = CompressionEffort::DEFAULT,
+    encoding_rules: Option<&EncodingRules> = None,
+    codecs: Option<&Codecs> = None,
+    canonical: bool = false,
+    record_decisions: bool = false
 });
 
+// The limits exist to decode untrusted data without running out of memory or stack.
+// The defaults limit nothing beyond MAX_DEPTH. See also UntrustedInput
 options!(DecodeOptions, DecodeOptionsDefault, DecodeOptionsOverride, DecodeOptionsHierarchy, {
-    parallel: bool = true
+    parallel: bool = true,
+    max_depth: usize = usize::MAX,
+    max_elements: usize = usize::MAX,
+    max_string_len: usize = usize::MAX,
+    max_collection_len: usize = usize::MAX,
+    element_budget: Option<&ElementBudget> = None,
+    codecs: Option<&Codecs> = None,
+    lossy_numbers: bool = false
 });
 
+/// When decoding, decodes independent branches at the same time. This is the default.
+/// When encoding, encodes the fields of structs and tries compressors at the same time.
+/// The result is the same either way.
 pub struct EnableParallel;
 impl DecodeOptionsOverride for EnableParallel {
     #[inline(always)]
@@ -59,6 +89,12 @@ impl DecodeOptionsOverride for EnableParallel {
         Some(true)
     }
 }
+impl EncodeOptionsOverride for EnableParallel {
+    #[inline(always)]
+    fn parallel(&self) -> Option<bool> {
+        Some(true)
+    }
+}
 
 pub struct DisableParallel;
 impl DecodeOptionsOverride for DisableParallel {
@@ -67,6 +103,194 @@ impl DecodeOptionsOverride for DisableParallel {
         Some(false)
     }
 }
+impl EncodeOptionsOverride for DisableParallel {
+    #[inline(always)]
+    fn parallel(&self) -> Option<bool> {
+        Some(false)
+    }
+}
+
+/// The maximum nesting depth of the schema. Eg: `Vec<Vec<u8>>` has a depth of 3.
+/// A larger limit than MAX_DEPTH has no effect.
+pub struct MaxDepth(pub usize);
+
+impl DecodeOptionsOverride for MaxDepth {
+    #[inline(always)]
+    fn max_depth(&self) -> Option<usize> {
+        Some(self.0)
+    }
+}
+
+/// The nesting depth beyond which no file is decoded, whatever the options.
+/// The branches are decoded without recursion, but dropping them and walking
+/// them (eg: to_json) is recursive. This keeps that well within the stack.
+pub const MAX_DEPTH: usize = 1024;
+
+/// Limits for files from untrusted sources, generous enough for most schemas and data: a depth of 64,
+/// 2^26 elements in all, and 2^24 bytes in any string or items in any collection. Options after it
+/// take precedence, so an application with more data can raise a limit. Eg:
+/// `decode_options! { UntrustedInput, MaxElements(1 << 30) }`
+pub struct UntrustedInput;
+impl DecodeOptionsOverride for UntrustedInput {
+    #[inline(always)]
+    fn max_depth(&self) -> Option<usize> {
+        Some(64)
+    }
+    #[inline(always)]
+    fn max_elements(&self) -> Option<usize> {
+        Some(1 << 26)
+    }
+    #[inline(always)]
+    fn max_string_len(&self) -> Option<usize> {
+        Some(1 << 24)
+    }
+    #[inline(always)]
+    fn max_collection_len(&self) -> Option<usize> {
+        Some(1 << 24)
+    }
+}
+
+/// The maximum number of elements allocated over the course of the decode.
+/// This includes items in all arrays and bytes in all strings, including intermediate
+/// values like the lengths of nested arrays.
+pub struct MaxElements(pub usize);
+impl DecodeOptionsOverride for MaxElements {
+    #[inline(always)]
+    fn max_elements(&self) -> Option<usize> {
+        Some(self.0)
+    }
+}
+
+/// The maximum length in bytes of any single String or Blob.
+pub struct MaxStringLen(pub usize);
+impl DecodeOptionsOverride for MaxStringLen {
+    #[inline(always)]
+    fn max_string_len(&self) -> Option<usize> {
+        Some(self.0)
+    }
+}
+
+/// The maximum length of any single array or map, and the maximum number of fields in an object or tuple.
+pub struct MaxCollectionLen(pub usize);
+impl DecodeOptionsOverride for MaxCollectionLen {
+    #[inline(always)]
+    fn max_collection_len(&self) -> Option<usize> {
+        Some(self.0)
+    }
+}
+
+/// Allows decoding numbers into a type which changes their value. Eg: 300 into a u8
+/// is clamped to 255, and an f64 into an f32 is rounded. Without this, those are a SchemaMismatch.
+/// Conversions which keep the value exactly, like 3.0 into a u8, are always allowed.
+pub struct LossyNumbers;
+impl DecodeOptionsOverride for LossyNumbers {
+    #[inline(always)]
+    fn lossy_numbers(&self) -> Option<bool> {
+        Some(true)
+    }
+}
+
+/// Shared between threads to count elements allocated during a decode, so that max_elements
+/// applies to the whole decode rather than to each array.
+#[derive(Clone)]
+pub struct ElementBudget {
+    remaining: Arc<AtomicUsize>,
+    exceeded: Arc<AtomicBool>,
+}
+
+impl ElementBudget {
+    pub fn new(max_elements: usize) -> Self {
+        Self {
+            remaining: Arc::new(AtomicUsize::new(max_elements)),
+            exceeded: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    pub fn take(&self, count: usize) -> DecodeResult<()> {
+        self.remaining
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |remaining| remaining.checked_sub(count))
+            .map(|_| ())
+            .map_err(|_| {
+                self.exceeded.store(true, Ordering::Relaxed);
+                DecodeError::LimitExceeded
+            })
+    }
+
+    /// Whether any take failed. Some decoders can't return an error from decode_next,
+    /// so they stop allocating and it is up to the caller to check this afterward.
+    pub fn exceeded(&self) -> bool {
+        self.exceeded.load(Ordering::Relaxed)
+    }
+}
+
+impl DecodeOptionsOverride for &'_ ElementBudget {
+    #[inline(always)]
+    fn element_budget(&self) -> Option<Option<&ElementBudget>> {
+        Some(Some(*self))
+    }
+}
+
+/// Counts elements against max_elements. Should be called before allocating when the count
+/// comes from the file, or after allocating when the count is limited by the size of the file.
+#[cfg(feature = "decode")]
+pub fn take_elements(options: &impl DecodeOptions, count: usize) -> DecodeResult<()> {
+    match options.element_budget() {
+        Some(budget) => budget.take(count),
+        None if count > options.max_elements() => Err(DecodeError::LimitExceeded),
+        None => Ok(()),
+    }
+}
+
+/// The number of elements which may still be allocated.
+#[cfg(feature = "decode")]
+pub fn remaining_elements(options: &impl DecodeOptions) -> usize {
+    match options.element_budget() {
+        Some(budget) => budget.remaining.load(Ordering::Relaxed),
+        None => options.max_elements(),
+    }
+}
+
+#[cfg(feature = "decode")]
+pub fn check_string_len(options: &impl DecodeOptions, len: usize) -> DecodeResult<()> {
+    if len > options.max_string_len() {
+        return Err(DecodeError::LimitExceeded);
+    }
+    Ok(())
+}
+
+#[cfg(feature = "decode")]
+pub fn check_collection_len(options: &impl DecodeOptions, len: usize) -> DecodeResult<()> {
+    if len > options.max_collection_len() {
+        return Err(DecodeError::LimitExceeded);
+    }
+    Ok(())
+}
+
+/// Adds the length of a string which is about to be built to a running total, failing if either
+/// is too long. This is for expanding encodings, which can produce far more bytes than they read.
+/// The total should still be counted against max_elements once the strings are built.
+#[cfg(feature = "decode")]
+pub fn check_string_total(options: &impl DecodeOptions, total: &mut usize, len: usize) -> DecodeResult<()> {
+    check_string_len(options, len)?;
+    *total = total
+        .checked_add(len)
+        .filter(|&total| total <= remaining_elements(options))
+        .ok_or(DecodeError::LimitExceeded)?;
+    Ok(())
+}
+
+/// Checks the lengths of many arrays or maps against max_collection_len,
+/// then counts the total against max_elements.
+#[cfg(feature = "decode")]
+pub fn take_lens(options: &impl DecodeOptions, lens: &[u64]) -> DecodeResult<()> {
+    let mut total: usize = 0;
+    for &len in lens {
+        let len = usize::try_from(len).map_err(|_| DecodeError::LimitExceeded)?;
+        check_collection_len(options, len)?;
+        total = total.checked_add(len).ok_or(DecodeError::LimitExceeded)?;
+    }
+    take_elements(options, total)
+}
 
 pub struct LosslessFloat;
 impl EncodeOptionsOverride for LosslessFloat {
@@ -84,6 +308,163 @@ impl EncodeOptionsOverride for LossyFloatTolerance {
     }
 }
 
+/// How much work to put into choosing the compression for each array.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum CompressionEffort {
+    /// Estimates the size of each candidate compressor on a sample of this many items,
+    /// then uses the smallest which succeeds on the whole array.
+    Sample(usize, Sampling),
+    /// Compresses the whole array with every candidate and keeps the smallest output.
+    /// This is much slower, but accounts for everything which the estimates miss.
+    Exhaustive,
+}
+
+impl CompressionEffort {
+    pub const DEFAULT: Self = CompressionEffort::Sample(256, Sampling::Head);
+}
+
+impl EncodeOptionsOverride for CompressionEffort {
+    #[inline(always)]
+    fn compression_effort(&self) -> Option<CompressionEffort> {
+        Some(*self)
+    }
+}
+
+/// Which items of an array make up the sample used to estimate compressed sizes.
+/// Other than Head, samples are taken in several runs across the array
+/// so that encodings which depend on neighboring items are estimated fairly.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Sampling {
+    /// The first items. This is the fastest, but misses changes later in the array.
+    Head,
+    /// Runs spaced evenly throughout the array.
+    Strided,
+    /// Runs at random offsets throughout the array, chosen deterministically from the seed.
+    Random(u64),
+}
+
+/// Restricts which encodings may be chosen for arrays, either everywhere or beneath a path.
+/// Paths are the names of fields and enum variants as they appear in the file (camelCase),
+/// and tuple items by index, separated by '.'. Eg: "readings.0.values". Arrays, Options and maps
+/// do not add to the path, and the empty path matches everything.
+///
+/// The rules only affect the choice between encodings. When every encoding which is able to
+/// represent some data is denied, one of them is used anyway so that the encode can succeed.
+#[derive(Clone, Debug, Default)]
+pub struct EncodingRules {
+    rules: Vec<EncodingRule>,
+}
+
+#[derive(Clone, Debug)]
+struct EncodingRule {
+    path: Vec<String>,
+    kind: EncodingRuleKind,
+    encodings: Vec<ArrayTypeId>,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum EncodingRuleKind {
+    Deny,
+    AllowOnly,
+    Force,
+}
+
+/// How much the rules want an encoding, from least to most.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) enum EncodingPreference {
+    Denied,
+    Allowed,
+    Forced,
+}
+
+impl EncodingRules {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Never use these encodings beneath the path.
+    pub fn deny(self, path: &str, encodings: &[ArrayTypeId]) -> Self {
+        self.with_rule(path, EncodingRuleKind::Deny, encodings)
+    }
+
+    /// Use only these encodings beneath the path. Eg: To debug with plain encodings
+    /// allow only IntPrefixVar, F64, Utf8, PackedBool, and so on.
+    pub fn allow_only(self, path: &str, encodings: &[ArrayTypeId]) -> Self {
+        self.with_rule(path, EncodingRuleKind::AllowOnly, encodings)
+    }
+
+    /// Use these encodings beneath the path wherever they are able to represent the data,
+    /// even when another encoding would be smaller.
+    pub fn force(self, path: &str, encodings: &[ArrayTypeId]) -> Self {
+        self.with_rule(path, EncodingRuleKind::Force, encodings)
+    }
+
+    fn with_rule(mut self, path: &str, kind: EncodingRuleKind, encodings: &[ArrayTypeId]) -> Self {
+        let path = path.split('.').filter(|name| !name.is_empty()).map(|name| name.to_owned()).collect();
+        self.rules.push(EncodingRule {
+            path,
+            kind,
+            encodings: encodings.to_vec(),
+        });
+        self
+    }
+
+    fn matching<'a>(&'a self, path: &'a [&str]) -> impl Iterator<Item = &'a EncodingRule> {
+        self.rules
+            .iter()
+            .filter(move |rule| rule.path.len() <= path.len() && rule.path.iter().zip(path.iter()).all(|(a, b)| a == b))
+    }
+
+    /// Whether any encodings are forced beneath the path.
+    pub(crate) fn forces(&self, path: &[&str]) -> bool {
+        self.matching(path).any(|rule| rule.kind == EncodingRuleKind::Force)
+    }
+
+    /// A denial by any matching rule wins over everything else.
+    pub(crate) fn preference(&self, path: &[&str], encoding: ArrayTypeId) -> EncodingPreference {
+        let mut preference = EncodingPreference::Allowed;
+        for rule in self.matching(path) {
+            let listed = rule.encodings.contains(&encoding);
+            match rule.kind {
+                EncodingRuleKind::Deny if listed => return EncodingPreference::Denied,
+                EncodingRuleKind::AllowOnly if !listed => return EncodingPreference::Denied,
+                EncodingRuleKind::Force if listed => preference = EncodingPreference::Forced,
+                _ => {}
+            }
+        }
+        preference
+    }
+}
+
+impl EncodeOptionsOverride for &'_ EncodingRules {
+    #[inline(always)]
+    fn encoding_rules(&self) -> Option<Option<&EncodingRules>> {
+        Some(Some(*self))
+    }
+}
+
+/// Writes a checksum for the whole file, and for each branch of data within it.
+/// These are verified when decoding, returning DecodeError::ChecksumMismatch on failure.
+pub struct Checksums;
+impl EncodeOptionsOverride for Checksums {
+    #[inline(always)]
+    fn checksums(&self) -> Option<bool> {
+        Some(true)
+    }
+}
+
+/// Encodes equal values to the same bytes, regardless of the order a HashMap iterates in
+/// or which NaN or zero a float holds. Map entries are ordered by the encoding of their keys,
+/// every NaN is written as the same NaN, and -0.0 is written as 0.0, so floats no longer round-trip
+/// bit-for-bit.
+pub struct Canonical;
+impl EncodeOptionsOverride for Canonical {
+    #[inline(always)]
+    fn canonical(&self) -> Option<bool> {
+        Some(true)
+    }
+}
+
 // TODO: Move the remainder here into the macro
 pub fn override_encode_options(options: impl EncodeOptions, overrides: impl EncodeOptionsOverride) -> impl EncodeOptions {
     EncodeOptionsHierarchy { fallback: options, overrides }
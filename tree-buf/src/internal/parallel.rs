@@ -1,5 +1,7 @@
 use crate::prelude::*;
 use rayon;
+#[cfg(feature = "encode")]
+use rayon::prelude::*;
 
 // TODO: Have a way to not use rayon when the operation is considered 'trivial'
 #[inline(always)]
@@ -10,3 +12,60 @@ pub fn parallel<A: Send, B: Send>(a: impl FnOnce() -> A + Send, b: impl FnOnce()
         (a(), b())
     }
 }
+
+/// Calls f for each index, in parallel if the options allow, appending the results which are Some in order.
+#[cfg(feature = "encode")]
+pub(crate) fn parallel_filter_map_into<T: Send>(into: &mut Vec<T>, count: usize, f: impl Fn(usize) -> Option<T> + Sync, options: &impl EncodeOptions) {
+    if options.parallel() {
+        let in_rle = rle_state();
+        let canonical = options.canonical();
+        into.par_extend(
+            (0..count)
+                .into_par_iter()
+                .filter_map(|i| with_canonical_maps(canonical, || with_rle_state(in_rle, || f(i)))),
+        );
+    } else {
+        into.extend((0..count).filter_map(f));
+    }
+}
+
+#[cfg(feature = "encode")]
+pub type FieldEncoder<'a, O> = Box<dyn FnOnce(&mut EncoderStream<'_, O>) + Send + 'a>;
+
+/// Runs each encoder in order. When encoding in parallel, each writes to its own
+/// buffers which are then appended to the stream, giving the same result.
+#[cfg(feature = "encode")]
+pub fn encode_fields<O: EncodeOptions>(stream: &mut EncoderStream<'_, O>, encoders: Vec<FieldEncoder<'_, O>>) {
+    if !stream.options.parallel() || encoders.len() < 2 {
+        for encoder in encoders {
+            encoder(stream);
+        }
+        return;
+    }
+
+    let options = stream.options;
+    let path = &stream.path;
+    let in_rle = rle_state();
+    let encoded: Vec<_> = encoders
+        .into_par_iter()
+        .map(|encoder| {
+            with_canonical_maps(options.canonical(), || {
+                with_rle_state(in_rle, || {
+                    let mut bytes = Vec::new();
+                    let mut lens = Vec::new();
+                    let mut stream = EncoderStream::new(&mut bytes, &mut lens, options);
+                    stream.path.clone_from(path);
+                    encoder(&mut stream);
+                    let decisions = stream.decisions;
+                    (bytes, lens, decisions)
+                })
+            })
+        })
+        .collect();
+
+    for (bytes, lens, mut decisions) in encoded {
+        stream.bytes.extend_from_slice(&bytes);
+        stream.lens.extend_from_slice(&lens);
+        stream.decisions.append(&mut decisions);
+    }
+}
@@ -1,4 +1,6 @@
+mod net;
 mod smart_pointers;
+mod time_;
 mod usize_;
 
 pub use self::usize_::*;
@@ -0,0 +1,101 @@
+use crate::prelude::*;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::vec::IntoIter;
+
+// IP addresses are written as blobs of their octets, tagged with SemanticTag::IpAddr.
+// An IpAddr is 4 or 16 bytes depending on the version, so the two can be read as each other.
+
+#[cfg(feature = "encode")]
+fn octets(ip: IpAddr) -> Vec<u8> {
+    match ip {
+        IpAddr::V4(ip) => ip.octets().to_vec(),
+        IpAddr::V6(ip) => ip.octets().to_vec(),
+    }
+}
+
+#[cfg(feature = "decode")]
+fn from_octets(bytes: &[u8]) -> DecodeResult<IpAddr> {
+    match bytes.len() {
+        4 => Ok(IpAddr::V4(Ipv4Addr::new(bytes[0], bytes[1], bytes[2], bytes[3]))),
+        16 => {
+            let mut octets = [0u8; 16];
+            octets.copy_from_slice(bytes);
+            Ok(IpAddr::V6(Ipv6Addr::from(octets)))
+        }
+        _ => Err(DecodeError::SchemaMismatch),
+    }
+}
+
+#[cfg(feature = "decode")]
+fn is_ip_addr(tag: SemanticTag) -> bool {
+    tag == SemanticTag::IpAddr
+}
+
+macro_rules! impl_ip {
+    ($T:ty, $unspecified:expr, $from_ip:expr) => {
+        #[cfg(feature = "encode")]
+        impl Encodable for $T {
+            type EncoderArray = Vec<$T>;
+            fn encode_schema() -> TypeSchema {
+                TypeSchema::Blob
+            }
+            fn encode_root<O: EncodeOptions>(&self, stream: &mut EncoderStream<'_, O>) -> RootTypeId {
+                let octets = octets((*self).into());
+                encode_tagged_root(SemanticTag::IpAddr, stream, |stream| encode_blob_root(&octets, stream))
+            }
+        }
+
+        #[cfg(feature = "encode")]
+        impl EncoderArray<$T> for Vec<$T> {
+            fn buffer_one<'a, 'b: 'a>(&'a mut self, value: &'b $T) {
+                self.push(*value);
+            }
+            fn flush<O: EncodeOptions>(self, stream: &mut EncoderStream<'_, O>) -> ArrayTypeId {
+                profile_method!(flush);
+                let octets: Vec<_> = self.into_iter().map(|ip| octets(ip.into())).collect();
+                flush_tagged(SemanticTag::IpAddr, stream, |stream| encode_blob_array(&octets[..], stream))
+            }
+        }
+
+        #[cfg(feature = "decode")]
+        impl Decodable for $T {
+            type DecoderArray = IntoIter<$T>;
+            fn decode_schema() -> TypeSchema {
+                TypeSchema::Blob
+            }
+            fn decode(sticks: DynRootBranch<'_>, options: &impl DecodeOptions) -> DecodeResult<Self> {
+                profile_method!(decode);
+                let blob = Blob::decode(sticks.expect_tag(is_ip_addr)?, options)?;
+                from_octets(&blob).and_then($from_ip)
+            }
+        }
+
+        #[cfg(feature = "decode")]
+        impl InfallibleDecoderArray for IntoIter<$T> {
+            type Decode = $T;
+            fn new_infallible(sticks: DynArrayBranch<'_>, options: &impl DecodeOptions) -> DecodeResult<Self> {
+                profile_method!(new_infallible);
+                let mut all = decode_blob_array(sticks.expect_tag(is_ip_addr)?, options)?;
+                // Zeros padding the lens decode as empty blobs after the last address
+                while all.last().map_or(false, |bytes| bytes.is_empty()) {
+                    all.pop();
+                }
+                let values = all.iter().map(|bytes| from_octets(bytes).and_then($from_ip)).collect::<DecodeResult<Vec<$T>>>()?;
+                Ok(values.into_iter())
+            }
+            fn decode_next_infallible(&mut self) -> Self::Decode {
+                self.next().unwrap_or($unspecified)
+            }
+        }
+    };
+}
+
+impl_ip!(IpAddr, IpAddr::V4(Ipv4Addr::UNSPECIFIED), Ok);
+impl_ip!(Ipv4Addr, Ipv4Addr::UNSPECIFIED, |ip| match ip {
+    IpAddr::V4(ip) => Ok(ip),
+    IpAddr::V6(_) => Err(DecodeError::SchemaMismatch),
+});
+impl_ip!(Ipv6Addr, Ipv6Addr::UNSPECIFIED, |ip| match ip {
+    IpAddr::V6(ip) => Ok(ip),
+    IpAddr::V4(_) => Err(DecodeError::SchemaMismatch),
+});
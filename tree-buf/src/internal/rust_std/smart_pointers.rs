@@ -11,6 +11,9 @@ pub struct BoxEncoderArray<T> {
 #[cfg(feature = "encode")]
 impl<T: Encodable> Encodable for Box<T> {
     type EncoderArray = BoxEncoderArray<T::EncoderArray>;
+    fn encode_schema() -> TypeSchema {
+        T::encode_schema()
+    }
     fn encode_root<O: EncodeOptions>(&self, stream: &mut EncoderStream<'_, O>) -> RootTypeId {
         self.deref().encode_root(stream)
     }
@@ -24,9 +27,15 @@ pub struct BoxDecoderArray<T> {
 #[cfg(feature = "decode")]
 impl<T: Decodable> Decodable for Box<T> {
     type DecoderArray = BoxDecoderArray<T::DecoderArray>;
+    fn decode_schema() -> TypeSchema {
+        T::decode_schema()
+    }
     fn decode(sticks: DynRootBranch<'_>, options: &impl DecodeOptions) -> DecodeResult<Self> {
         Ok(Box::new(T::decode(sticks, options)?))
     }
+    fn decode_into(sticks: DynRootBranch<'_>, options: &impl DecodeOptions, into: &mut Self) -> DecodeResult<()> {
+        T::decode_into(sticks, options, into)
+    }
 }
 
 #[cfg(feature = "encode")]
@@ -49,4 +58,7 @@ impl<T: DecoderArray> DecoderArray for BoxDecoderArray<T> {
     fn decode_next(&mut self) -> Result<Self::Decode, Self::Error> {
         Ok(Box::new(self.inner.decode_next()?))
     }
+    fn decode_next_into(&mut self, into: &mut Self::Decode) -> Result<(), Self::Error> {
+        self.inner.decode_next_into(into)
+    }
 }
@@ -0,0 +1,361 @@
+use crate::prelude::*;
+use std::convert::TryFrom;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use std::vec::IntoIter;
+
+// Times and durations are written as whole numbers of the largest unit which holds each
+// value exactly, tagged with SemanticTag::Timestamp or SemanticTag::Duration and the unit.
+// Being integers, arrays of them get delta encodings like any other integers. Arrays don't
+// have signed integers, so if any value is before 1970, negative, or beyond a u64 in the unit,
+// each value in the array is written as a tuple of its sign and magnitude instead. See
+// TimeUnit::to_parts. At the root, a value which isn't a u64 is an i64 where it fits.
+//
+// The unit is only known from the tag, so untagged numbers are not read as times.
+
+const NANOS_PER_SECOND: i128 = 1_000_000_000;
+const UNITS: [TimeUnit; 4] = [TimeUnit::Seconds, TimeUnit::Millis, TimeUnit::Micros, TimeUnit::Nanos];
+
+/// A point or span of time as a number of nanoseconds, which covers each of the types here.
+pub(crate) trait TimeValue: Copy {
+    /// The tag for a value written in the unit.
+    fn tag(unit: TimeUnit) -> SemanticTag;
+    /// For decode_next_infallible
+    fn zero() -> Self;
+    fn to_nanos(self) -> i128;
+    /// None if the type doesn't hold the value.
+    fn from_nanos(nanos: i128) -> Option<Self>;
+}
+
+fn nanos_per(unit: TimeUnit) -> i128 {
+    NANOS_PER_SECOND / unit.per_second() as i128
+}
+
+/// The largest unit in which each value is whole, so that the numbers written are small.
+#[cfg(feature = "encode")]
+fn unit_for(nanos: &[i128]) -> TimeUnit {
+    UNITS
+        .iter()
+        .copied()
+        .find(|&unit| nanos.iter().all(|nanos| nanos % nanos_per(unit) == 0))
+        .unwrap_or(TimeUnit::Nanos)
+}
+
+#[cfg(feature = "encode")]
+pub(crate) fn encode_time_root<T: TimeValue, O: EncodeOptions>(value: T, stream: &mut EncoderStream<'_, O>) -> RootTypeId {
+    let nanos = value.to_nanos();
+    let unit = unit_for(&[nanos]);
+    let value = nanos / nanos_per(unit);
+    encode_tagged_root(T::tag(unit), stream, |stream| {
+        if let Ok(value) = u64::try_from(value) {
+            value.encode_root(stream)
+        } else if let Ok(value) = i64::try_from(value) {
+            encode_root_sint(value, stream.bytes)
+        } else {
+            unit.to_parts(value).encode_root(stream)
+        }
+    })
+}
+
+#[cfg(feature = "encode")]
+pub(crate) fn flush_times<T: TimeValue, O: EncodeOptions>(values: &[T], stream: &mut EncoderStream<'_, O>) -> ArrayTypeId {
+    let nanos: Vec<i128> = values.iter().map(|value| value.to_nanos()).collect();
+    let unit = unit_for(&nanos);
+    let values: Vec<i128> = nanos.iter().map(|nanos| nanos / nanos_per(unit)).collect();
+    let integers: Option<Vec<u64>> = values.iter().map(|&value| u64::try_from(value).ok()).collect();
+    flush_tagged(T::tag(unit), stream, |stream| match integers {
+        Some(integers) => <u64 as Encodable>::EncoderArray::encode_all(&integers, stream),
+        None => {
+            let parts: Vec<_> = values.iter().map(|&value| unit.to_parts(value)).collect();
+            <(bool, u64, u64) as Encodable>::EncoderArray::encode_all(&parts, stream)
+        }
+    })
+}
+
+#[cfg(feature = "decode")]
+fn unit_of<T: TimeValue>(tag: SemanticTag) -> DecodeResult<TimeUnit> {
+    UNITS.iter().copied().find(|&unit| T::tag(unit) == tag).ok_or(DecodeError::SchemaMismatch)
+}
+
+#[cfg(feature = "decode")]
+fn from_parts(unit: TimeUnit, (negative, seconds, remainder): (bool, u64, u64)) -> DecodeResult<i128> {
+    let value = unit.from_parts(negative, seconds, remainder).ok_or(DecodeError::InvalidFormat)?;
+    Ok(value * nanos_per(unit))
+}
+
+// Floats are never written here, but are read from other writers.
+#[cfg(feature = "decode")]
+fn from_float(value: f64, unit: TimeUnit) -> DecodeResult<i128> {
+    let nanos = (value * nanos_per(unit) as f64).round();
+    // Far beyond the range of any of the types, which fail below.
+    if nanos.is_finite() && nanos.abs() < 1e30 {
+        Ok(nanos as i128)
+    } else {
+        Err(DecodeError::SchemaMismatch)
+    }
+}
+
+#[cfg(feature = "decode")]
+fn from_nanos<T: TimeValue>(nanos: i128) -> DecodeResult<T> {
+    T::from_nanos(nanos).ok_or(DecodeError::SchemaMismatch)
+}
+
+#[cfg(feature = "decode")]
+pub(crate) fn decode_time_root<T: TimeValue>(sticks: DynRootBranch<'_>, options: &impl DecodeOptions) -> DecodeResult<T> {
+    let (unit, value) = match sticks {
+        DynRootBranch::Tagged { tag, value } => (unit_of::<T>(tag)?, value.untagged()),
+        _ => return Err(DecodeError::SchemaMismatch),
+    };
+    let nanos = match value {
+        DynRootBranch::Integer(RootInteger::U(value)) => value as i128 * nanos_per(unit),
+        DynRootBranch::Integer(RootInteger::S(value)) => value as i128 * nanos_per(unit),
+        value @ DynRootBranch::Tuple { .. } => from_parts(unit, Decodable::decode(value, options)?)?,
+        value => from_float(f64::decode(value, options)?, unit)?,
+    };
+    from_nanos(nanos)
+}
+
+#[cfg(feature = "decode")]
+pub(crate) fn decode_times<T: TimeValue>(sticks: DynArrayBranch<'_>, options: &impl DecodeOptions) -> DecodeResult<Vec<T>> {
+    let (unit, value) = match sticks {
+        DynArrayBranch::Tagged { tag, value } => (unit_of::<T>(tag)?, value.untagged()),
+        DynArrayBranch::Void => return Ok(Vec::new()),
+        _ => return Err(DecodeError::SchemaMismatch),
+    };
+    match value {
+        DynArrayBranch::Float(_) => {
+            let floats = <IntoIter<f64> as InfallibleDecoderArray>::new_infallible(value, options)?;
+            floats.map(|value| from_nanos(from_float(value, unit)?)).collect()
+        }
+        DynArrayBranch::Tuple { fields } if fields.len() == 3 => {
+            let mut fields = fields.into_iter();
+            let mut negatives = <IntoIter<bool> as InfallibleDecoderArray>::new_infallible(fields.next().unwrap(), options)?;
+            let seconds = <IntoIter<u64> as InfallibleDecoderArray>::new_infallible(fields.next().unwrap(), options)?;
+            let mut remainders = <IntoIter<u64> as InfallibleDecoderArray>::new_infallible(fields.next().unwrap(), options)?;
+            seconds
+                .map(|seconds| {
+                    let parts = (negatives.decode_next_infallible(), seconds, remainders.decode_next_infallible());
+                    from_nanos(from_parts(unit, parts)?)
+                })
+                .collect()
+        }
+        value => {
+            let integers = <IntoIter<u64> as InfallibleDecoderArray>::new_infallible(value, options)?;
+            integers.map(|value| from_nanos(value as i128 * nanos_per(unit))).collect()
+        }
+    }
+}
+
+fn duration_from_nanos(nanos: i128) -> Option<Duration> {
+    let seconds = u64::try_from(nanos.div_euclid(NANOS_PER_SECOND)).ok()?;
+    Some(Duration::new(seconds, nanos.rem_euclid(NANOS_PER_SECOND) as u32))
+}
+
+impl TimeValue for Duration {
+    fn tag(unit: TimeUnit) -> SemanticTag {
+        SemanticTag::Duration(unit)
+    }
+    fn zero() -> Self {
+        Duration::from_secs(0)
+    }
+    fn to_nanos(self) -> i128 {
+        self.as_nanos() as i128
+    }
+    fn from_nanos(nanos: i128) -> Option<Self> {
+        duration_from_nanos(nanos)
+    }
+}
+
+impl TimeValue for SystemTime {
+    fn tag(unit: TimeUnit) -> SemanticTag {
+        SemanticTag::Timestamp(unit)
+    }
+    fn zero() -> Self {
+        UNIX_EPOCH
+    }
+    fn to_nanos(self) -> i128 {
+        match self.duration_since(UNIX_EPOCH) {
+            Ok(since) => since.as_nanos() as i128,
+            Err(before) => -(before.duration().as_nanos() as i128),
+        }
+    }
+    fn from_nanos(nanos: i128) -> Option<Self> {
+        if nanos < 0 {
+            UNIX_EPOCH.checked_sub(duration_from_nanos(-nanos)?)
+        } else {
+            UNIX_EPOCH.checked_add(duration_from_nanos(nanos)?)
+        }
+    }
+}
+
+macro_rules! impl_time {
+    ($T:ty) => {
+        #[cfg(feature = "encode")]
+        impl Encodable for $T {
+            type EncoderArray = Vec<$T>;
+            fn encode_root<O: EncodeOptions>(&self, stream: &mut EncoderStream<'_, O>) -> RootTypeId {
+                encode_time_root(*self, stream)
+            }
+        }
+
+        #[cfg(feature = "encode")]
+        impl EncoderArray<$T> for Vec<$T> {
+            fn buffer_one<'a, 'b: 'a>(&'a mut self, value: &'b $T) {
+                self.push(*value);
+            }
+            fn flush<O: EncodeOptions>(self, stream: &mut EncoderStream<'_, O>) -> ArrayTypeId {
+                profile_method!(flush);
+                flush_times(&self[..], stream)
+            }
+        }
+
+        #[cfg(feature = "decode")]
+        impl Decodable for $T {
+            type DecoderArray = IntoIter<$T>;
+            fn decode(sticks: DynRootBranch<'_>, options: &impl DecodeOptions) -> DecodeResult<Self> {
+                profile_method!(decode);
+                decode_time_root(sticks, options)
+            }
+        }
+
+        #[cfg(feature = "decode")]
+        impl InfallibleDecoderArray for IntoIter<$T> {
+            type Decode = $T;
+            fn new_infallible(sticks: DynArrayBranch<'_>, options: &impl DecodeOptions) -> DecodeResult<Self> {
+                profile_method!(new_infallible);
+                let values = decode_times(sticks, options)?;
+                take_elements(options, values.len())?;
+                Ok(values.into_iter())
+            }
+            fn decode_next_infallible(&mut self) -> Self::Decode {
+                self.next().unwrap_or_else(<$T as TimeValue>::zero)
+            }
+        }
+    };
+}
+
+impl_time!(Duration);
+impl_time!(SystemTime);
+
+/// Dates and times without a time zone are written as though they were in UTC.
+#[cfg(feature = "chrono")]
+mod chrono_impl {
+    use super::*;
+    use chrono::{DateTime, NaiveDateTime, TimeDelta, Utc};
+
+    fn split(nanos: i128) -> Option<(i64, u32)> {
+        let seconds = i64::try_from(nanos.div_euclid(NANOS_PER_SECOND)).ok()?;
+        Some((seconds, nanos.rem_euclid(NANOS_PER_SECOND) as u32))
+    }
+
+    impl TimeValue for DateTime<Utc> {
+        fn tag(unit: TimeUnit) -> SemanticTag {
+            SemanticTag::Timestamp(unit)
+        }
+        fn zero() -> Self {
+            DateTime::UNIX_EPOCH
+        }
+        fn to_nanos(self) -> i128 {
+            self.timestamp() as i128 * NANOS_PER_SECOND + self.timestamp_subsec_nanos() as i128
+        }
+        fn from_nanos(nanos: i128) -> Option<Self> {
+            let (seconds, nanos) = split(nanos)?;
+            DateTime::from_timestamp(seconds, nanos)
+        }
+    }
+
+    impl TimeValue for NaiveDateTime {
+        fn tag(unit: TimeUnit) -> SemanticTag {
+            SemanticTag::Timestamp(unit)
+        }
+        fn zero() -> Self {
+            DateTime::UNIX_EPOCH.naive_utc()
+        }
+        fn to_nanos(self) -> i128 {
+            self.and_utc().to_nanos()
+        }
+        fn from_nanos(nanos: i128) -> Option<Self> {
+            DateTime::<Utc>::from_nanos(nanos).map(|time| time.naive_utc())
+        }
+    }
+
+    impl TimeValue for TimeDelta {
+        fn tag(unit: TimeUnit) -> SemanticTag {
+            SemanticTag::Duration(unit)
+        }
+        fn zero() -> Self {
+            TimeDelta::zero()
+        }
+        fn to_nanos(self) -> i128 {
+            // The nanoseconds have the same sign as the seconds
+            self.num_seconds() as i128 * NANOS_PER_SECOND + self.subsec_nanos() as i128
+        }
+        fn from_nanos(nanos: i128) -> Option<Self> {
+            let (seconds, nanos) = split(nanos)?;
+            TimeDelta::new(seconds, nanos)
+        }
+    }
+
+    impl_time!(DateTime<Utc>);
+    impl_time!(NaiveDateTime);
+    impl_time!(TimeDelta);
+}
+
+/// An OffsetDateTime is written as the instant, and read in UTC. Dates and times without
+/// an offset are written as though they were in UTC.
+#[cfg(feature = "time")]
+mod time_impl {
+    use super::*;
+    use time::{OffsetDateTime, PrimitiveDateTime};
+
+    impl TimeValue for OffsetDateTime {
+        fn tag(unit: TimeUnit) -> SemanticTag {
+            SemanticTag::Timestamp(unit)
+        }
+        fn zero() -> Self {
+            OffsetDateTime::UNIX_EPOCH
+        }
+        fn to_nanos(self) -> i128 {
+            self.unix_timestamp_nanos()
+        }
+        fn from_nanos(nanos: i128) -> Option<Self> {
+            OffsetDateTime::from_unix_timestamp_nanos(nanos).ok()
+        }
+    }
+
+    impl TimeValue for PrimitiveDateTime {
+        fn tag(unit: TimeUnit) -> SemanticTag {
+            SemanticTag::Timestamp(unit)
+        }
+        fn zero() -> Self {
+            PrimitiveDateTime::new(OffsetDateTime::UNIX_EPOCH.date(), OffsetDateTime::UNIX_EPOCH.time())
+        }
+        fn to_nanos(self) -> i128 {
+            self.assume_utc().unix_timestamp_nanos()
+        }
+        fn from_nanos(nanos: i128) -> Option<Self> {
+            let time = OffsetDateTime::from_unix_timestamp_nanos(nanos).ok()?;
+            Some(PrimitiveDateTime::new(time.date(), time.time()))
+        }
+    }
+
+    impl TimeValue for time::Duration {
+        fn tag(unit: TimeUnit) -> SemanticTag {
+            SemanticTag::Duration(unit)
+        }
+        fn zero() -> Self {
+            time::Duration::ZERO
+        }
+        fn to_nanos(self) -> i128 {
+            self.whole_nanoseconds()
+        }
+        fn from_nanos(nanos: i128) -> Option<Self> {
+            // Both parts have the sign of the whole
+            let seconds = i64::try_from(nanos / NANOS_PER_SECOND).ok()?;
+            Some(time::Duration::new(seconds, (nanos % NANOS_PER_SECOND) as i32))
+        }
+    }
+
+    impl_time!(OffsetDateTime);
+    impl_time!(PrimitiveDateTime);
+    impl_time!(time::Duration);
+}
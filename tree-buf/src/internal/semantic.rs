@@ -0,0 +1,213 @@
+//! A semantic tag says what an integer, float or blob means, so that tools can show it
+//! as more than a number. The tag is written before the branch it applies to, and the
+//! types which read the value as a number, float or blob skip over it.
+
+#[cfg(feature = "decode")]
+use crate::internal::encodings::varint::decode_prefix_varint;
+#[cfg(feature = "encode")]
+use crate::internal::encodings::varint::encode_prefix_varint;
+use crate::prelude::*;
+use std::convert::TryFrom;
+use std::fmt;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum SemanticTag {
+    /// An integer or float of time since the Unix epoch, in UTC. A value which isn't
+    /// a u64 may instead be a tuple of its sign and magnitude. See TimeUnit::to_parts
+    Timestamp(TimeUnit),
+    /// An integer or float of elapsed time, or a tuple like that of Timestamp.
+    Duration(TimeUnit),
+    /// A blob of 16 bytes.
+    Uuid,
+    /// A blob of 4 bytes for IPv4, or 16 bytes for IPv6.
+    IpAddr,
+    /// An integer which is the value times 10^scale. Eg: 12345 with a scale of 2 is 123.45
+    Decimal { scale: u32 },
+    /// A tag which this version doesn't know, from a later one. The value is read as it is.
+    Unknown { kind: u64, param: u64 },
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum TimeUnit {
+    Seconds,
+    Millis,
+    Micros,
+    Nanos,
+}
+
+impl TimeUnit {
+    pub fn per_second(self) -> u64 {
+        match self {
+            TimeUnit::Seconds => 1,
+            TimeUnit::Millis => 1_000,
+            TimeUnit::Micros => 1_000_000,
+            TimeUnit::Nanos => 1_000_000_000,
+        }
+    }
+
+    /// A value in this unit as (negative, whole seconds, remainder in this unit). Arrays don't
+    /// have signed integers, and the magnitude in nanoseconds may be beyond a u64, but the
+    /// seconds of any time or duration in the standard library, chrono or time are not.
+    pub fn to_parts(self, value: i128) -> (bool, u64, u64) {
+        let per_second = self.per_second() as u128;
+        let magnitude = value.unsigned_abs();
+        (value < 0, (magnitude / per_second) as u64, (magnitude % per_second) as u64)
+    }
+
+    /// The inverse of to_parts. None if the remainder is not less than a second.
+    pub fn from_parts(self, negative: bool, seconds: u64, remainder: u64) -> Option<i128> {
+        if remainder >= self.per_second() {
+            return None;
+        }
+        let magnitude = seconds as i128 * self.per_second() as i128 + remainder as i128;
+        Some(if negative { -magnitude } else { magnitude })
+    }
+
+    fn from_param(param: u64) -> Option<Self> {
+        Some(match param {
+            0 => TimeUnit::Seconds,
+            1 => TimeUnit::Millis,
+            2 => TimeUnit::Micros,
+            3 => TimeUnit::Nanos,
+            _ => return None,
+        })
+    }
+
+    fn param(self) -> u64 {
+        match self {
+            TimeUnit::Seconds => 0,
+            TimeUnit::Millis => 1,
+            TimeUnit::Micros => 2,
+            TimeUnit::Nanos => 3,
+        }
+    }
+}
+
+impl fmt::Display for TimeUnit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TimeUnit::Seconds => write!(f, "s"),
+            TimeUnit::Millis => write!(f, "ms"),
+            TimeUnit::Micros => write!(f, "us"),
+            TimeUnit::Nanos => write!(f, "ns"),
+        }
+    }
+}
+
+// The tag is written as a kind and a parameter, so that a tag which
+// isn't known can still be skipped.
+const TIMESTAMP: u64 = 0;
+const DURATION: u64 = 1;
+const UUID: u64 = 2;
+const IP_ADDR: u64 = 3;
+const DECIMAL: u64 = 4;
+
+impl SemanticTag {
+    fn kind_and_param(self) -> (u64, u64) {
+        match self {
+            SemanticTag::Timestamp(unit) => (TIMESTAMP, unit.param()),
+            SemanticTag::Duration(unit) => (DURATION, unit.param()),
+            SemanticTag::Uuid => (UUID, 0),
+            SemanticTag::IpAddr => (IP_ADDR, 0),
+            SemanticTag::Decimal { scale } => (DECIMAL, scale as u64),
+            SemanticTag::Unknown { kind, param } => (kind, param),
+        }
+    }
+
+    fn from_kind_and_param(kind: u64, param: u64) -> Self {
+        let tag = match kind {
+            TIMESTAMP => TimeUnit::from_param(param).map(SemanticTag::Timestamp),
+            DURATION => TimeUnit::from_param(param).map(SemanticTag::Duration),
+            UUID if param == 0 => Some(SemanticTag::Uuid),
+            IP_ADDR if param == 0 => Some(SemanticTag::IpAddr),
+            DECIMAL => u32::try_from(param).ok().map(|scale| SemanticTag::Decimal { scale }),
+            _ => None,
+        };
+        tag.unwrap_or(SemanticTag::Unknown { kind, param })
+    }
+
+    #[cfg(feature = "encode")]
+    pub(crate) fn encode(self, bytes: &mut Vec<u8>) {
+        let (kind, param) = self.kind_and_param();
+        encode_prefix_varint(kind, bytes);
+        encode_prefix_varint(param, bytes);
+    }
+
+    #[cfg(feature = "decode")]
+    pub(crate) fn decode(bytes: &[u8], offset: &mut usize) -> DecodeResult<Self> {
+        let kind = decode_prefix_varint(bytes, offset)?;
+        let param = decode_prefix_varint(bytes, offset)?;
+        Ok(Self::from_kind_and_param(kind, param))
+    }
+}
+
+impl fmt::Display for SemanticTag {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SemanticTag::Timestamp(unit) => write!(f, "timestamp({})", unit),
+            SemanticTag::Duration(unit) => write!(f, "duration({})", unit),
+            SemanticTag::Uuid => write!(f, "uuid"),
+            SemanticTag::IpAddr => write!(f, "ip"),
+            SemanticTag::Decimal { scale } => write!(f, "decimal({})", scale),
+            SemanticTag::Unknown { kind, param } => write!(f, "tag({}, {})", kind, param),
+        }
+    }
+}
+
+/// Writes the value at the root with a tag. f is what encode_root would otherwise be.
+#[cfg(feature = "encode")]
+pub fn encode_tagged_root<O: EncodeOptions>(tag: SemanticTag, stream: &mut EncoderStream<'_, O>, f: impl FnOnce(&mut EncoderStream<'_, O>) -> RootTypeId) -> RootTypeId {
+    tag.encode(stream.bytes);
+    stream.encode_with_id(f);
+    RootTypeId::Tagged
+}
+
+/// Writes the values of an array with a tag. f is what flush would otherwise be.
+#[cfg(feature = "encode")]
+pub fn flush_tagged<O: EncodeOptions>(tag: SemanticTag, stream: &mut EncoderStream<'_, O>, f: impl FnOnce(&mut EncoderStream<'_, O>) -> ArrayTypeId) -> ArrayTypeId {
+    tag.encode(stream.bytes);
+    stream.encode_with_id(f);
+    ArrayTypeId::Tagged
+}
+
+#[cfg(feature = "decode")]
+impl<'a> DynRootBranch<'a> {
+    /// The branch without its tag, for types which read the value the same either way.
+    pub fn untagged(self) -> Self {
+        let mut branch = self;
+        while let DynRootBranch::Tagged { value, .. } = branch {
+            branch = *value;
+        }
+        branch
+    }
+
+    /// The branch without its tag, which must be the expected one if there is a tag.
+    pub fn expect_tag(self, expected: impl Fn(SemanticTag) -> bool) -> DecodeResult<Self> {
+        match self {
+            DynRootBranch::Tagged { tag, value } if expected(tag) => Ok(value.untagged()),
+            DynRootBranch::Tagged { .. } => Err(DecodeError::SchemaMismatch),
+            branch => Ok(branch),
+        }
+    }
+}
+
+#[cfg(feature = "decode")]
+impl<'a> DynArrayBranch<'a> {
+    /// See also DynRootBranch::untagged
+    pub fn untagged(self) -> Self {
+        let mut branch = self;
+        while let DynArrayBranch::Tagged { value, .. } = branch {
+            branch = *value;
+        }
+        branch
+    }
+
+    /// See also DynRootBranch::expect_tag
+    pub fn expect_tag(self, expected: impl Fn(SemanticTag) -> bool) -> DecodeResult<Self> {
+        match self {
+            DynArrayBranch::Tagged { tag, value } if expected(tag) => Ok(value.untagged()),
+            DynArrayBranch::Tagged { .. } => Err(DecodeError::SchemaMismatch),
+            branch => Ok(branch),
+        }
+    }
+}
@@ -4,6 +4,9 @@ use std::vec::IntoIter;
 #[cfg(feature = "encode")]
 impl<T: Encodable> Encodable for Vec<T> {
     type EncoderArray = VecArrayEncoder<T::EncoderArray>;
+    fn encode_schema() -> TypeSchema {
+        TypeSchema::Array(Box::new(T::encode_schema()))
+    }
     fn encode_root<O: EncodeOptions>(&self, stream: &mut EncoderStream<'_, O>) -> RootTypeId {
         profile_method!(encode_root);
         match self.len() {
@@ -34,6 +37,9 @@ where
     DecodeError: From<<<T as Decodable>::DecoderArray as DecoderArray>::Error>,
 {
     type DecoderArray = Option<VecArrayDecoder<T::DecoderArray>>;
+    fn decode_schema() -> TypeSchema {
+        TypeSchema::Array(Box::new(T::decode_schema()))
+    }
     fn decode(sticks: DynRootBranch<'_>, options: &impl DecodeOptions) -> DecodeResult<Self> {
         profile_method!(decode);
         match sticks {
@@ -43,6 +49,7 @@ where
                 Ok(vec![inner])
             }
             DynRootBranch::Array { len, values } => {
+                take_elements(options, len)?;
                 let mut v = Vec::with_capacity(len);
                 // TODO: Some of what the code is actually doing here is silly.
                 // Actual DecoderArray's may be IntoIter, which moved out of a Vec
@@ -56,6 +63,40 @@ where
             _ => Err(DecodeError::SchemaMismatch),
         }
     }
+    fn decode_into(sticks: DynRootBranch<'_>, options: &impl DecodeOptions, into: &mut Self) -> DecodeResult<()> {
+        profile_method!(decode_into);
+        match sticks {
+            DynRootBranch::Array0 => into.clear(),
+            DynRootBranch::Array1(inner) => {
+                into.truncate(1);
+                match into.first_mut() {
+                    Some(first) => T::decode_into(*inner, options, first)?,
+                    None => into.push(T::decode(*inner, options)?),
+                }
+            }
+            DynRootBranch::Array { len, values } => {
+                take_elements(options, len)?;
+                let mut decoder = T::DecoderArray::new(values, options)?;
+                decode_all_into(&mut decoder, len, into)?;
+            }
+            _ => return Err(DecodeError::SchemaMismatch),
+        }
+        Ok(())
+    }
+}
+
+/// Decodes len items into the Vec, re-using the items which are already there.
+#[cfg(feature = "decode")]
+fn decode_all_into<T: DecoderArray>(decoder: &mut T, len: usize, into: &mut Vec<T::Decode>) -> Result<(), T::Error> {
+    into.truncate(len);
+    for item in into.iter_mut() {
+        decoder.decode_next_into(item)?;
+    }
+    into.reserve(len - into.len());
+    for _ in into.len()..len {
+        into.push(decoder.decode_next()?);
+    }
+    Ok(())
 }
 
 #[cfg(feature = "encode")]
@@ -69,14 +110,19 @@ pub struct VecArrayEncoder<T> {
 
 // TODO: usize
 enum FixedOrVariableLength {
-    Fixed(usize),
+    Fixed(usize, ElementBudget),
     Variable(IntoIter<u64>),
 }
 
 impl FixedOrVariableLength {
     fn next(&mut self) -> usize {
         match self {
-            Self::Fixed(v) => *v,
+            // How many arrays there are isn't known up front, so each is counted as it is decoded.
+            // This can't return an error, so decode_with_options checks whether the budget was exceeded.
+            Self::Fixed(v, budget) => match budget.take(*v) {
+                Ok(()) => *v,
+                Err(_) => 0,
+            },
             Self::Variable(i) => i.decode_next_infallible() as usize,
         }
     }
@@ -101,6 +147,7 @@ impl<T: Encodable> EncoderArray<Vec<T>> for VecArrayEncoder<T::EncoderArray> {
         if let Some(values) = values {
             if len.iter().all(|l| *l == len[0]) {
                 encode_usize(len[0] as usize, stream);
+                put_vec(len);
                 stream.encode_with_id(|stream| values.flush(stream));
                 return ArrayTypeId::ArrayFixed;
             }
@@ -132,13 +179,16 @@ impl<T: DecoderArray> DecoderArray for Option<VecArrayDecoder<T>> {
                     options,
                 );
                 let values = values?;
-                let len = FixedOrVariableLength::Variable(len?);
+                let len = len?;
+                take_lens(options, len.as_slice())?;
+                let len = FixedOrVariableLength::Variable(len);
                 Ok(Some(VecArrayDecoder { len, values }))
             }
             DynArrayBranch::ArrayFixed { len, values } => Ok(if len == 0 {
                 None
             } else {
-                let len = FixedOrVariableLength::Fixed(len);
+                let budget = options.element_budget().cloned().unwrap_or_else(|| ElementBudget::new(options.max_elements()));
+                let len = FixedOrVariableLength::Fixed(len, budget);
                 let values = T::new(*values, options)?;
                 Some(VecArrayDecoder { len, values })
             }),
@@ -157,4 +207,13 @@ impl<T: DecoderArray> DecoderArray for Option<VecArrayDecoder<T>> {
             Ok(Vec::new())
         }
     }
+    fn decode_next_into(&mut self, into: &mut Self::Decode) -> Result<(), Self::Error> {
+        if let Some(inner) = self {
+            let len = inner.len.next();
+            decode_all_into(&mut inner.values, len, into)
+        } else {
+            into.clear();
+            Ok(())
+        }
+    }
 }
@@ -12,6 +12,9 @@ macro_rules! impl_fixed {
             #[cfg(feature = "encode")]
             impl<T: Encodable> Encodable for [T; $size] {
                 type EncoderArray = ArrayEncoder<T::EncoderArray>;
+                fn encode_schema() -> TypeSchema {
+                    TypeSchema::FixedArray($size, Box::new(T::encode_schema()))
+                }
                 fn encode_root<O: EncodeOptions>(&self, stream: &mut EncoderStream<'_, O>) -> RootTypeId {
                     profile_method!(encode_root);
                     match self.len() {
@@ -41,6 +44,9 @@ macro_rules! impl_fixed {
             #[cfg(feature = "decode")]
             impl<T: Decodable + Sized> Decodable for [T; $size] where DecodeError : From<<T::DecoderArray as DecoderArray>::Error> {
                 type DecoderArray = ArrayDecoder<T::DecoderArray>;
+                fn decode_schema() -> TypeSchema {
+                    TypeSchema::FixedArray($size, Box::new(T::decode_schema()))
+                }
                 fn decode(sticks: DynRootBranch<'_>, options: &impl DecodeOptions) -> DecodeResult<Self> {
                     profile_method!(decode);
                     match sticks {
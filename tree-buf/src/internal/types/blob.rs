@@ -0,0 +1,419 @@
+use crate::internal::encodings::varint::*;
+use crate::prelude::*;
+use std::convert::TryInto;
+use std::io::Cursor;
+use std::ops::{Deref, DerefMut};
+use std::vec::IntoIter;
+
+#[cfg(feature = "encode")]
+use brotli::{enc::BrotliEncoderParams, BrotliCompress};
+
+/// An opaque buffer of bytes, like an embedded image or a serialized message.
+///
+/// A `Vec<u8>` is treated as an array of integers, which is the right choice for
+/// small arrays of numbers. A `Blob` instead stores the bytes as-is, or compresses
+/// them with either LZ4 or Brotli depending on what works best on a sample.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Blob(pub Vec<u8>);
+
+impl From<Vec<u8>> for Blob {
+    #[inline]
+    fn from(value: Vec<u8>) -> Self {
+        Blob(value)
+    }
+}
+
+impl From<Blob> for Vec<u8> {
+    #[inline]
+    fn from(value: Blob) -> Self {
+        value.0
+    }
+}
+
+impl AsRef<[u8]> for Blob {
+    #[inline]
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl Deref for Blob {
+    type Target = Vec<u8>;
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl DerefMut for Blob {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+// Compressing is expensive, so the codec is chosen based on how well it does on a prefix of the data.
+#[cfg(feature = "encode")]
+const SAMPLE_SIZE: usize = 16 * 1024;
+
+// LZ4 cannot expand data by more than this, so larger sizes are not something the encoder would write.
+#[cfg(feature = "decode")]
+const LZ4_MAX_RATIO: usize = 255;
+
+#[cfg(feature = "encode")]
+#[derive(Copy, Clone, Debug)]
+pub(crate) enum BlobCodec {
+    Raw,
+    Lz4,
+    Brotli,
+}
+
+#[cfg(feature = "encode")]
+impl BlobCodec {
+    fn compress_into(self, data: &[u8], bytes: &mut Vec<u8>) {
+        profile_method!(compress_into);
+
+        match self {
+            BlobCodec::Raw => bytes.extend_from_slice(data),
+            BlobCodec::Lz4 => {
+                encode_prefix_varint(data.len() as u64, bytes);
+                bytes.extend_from_slice(&lz4_flex::block::compress(data));
+            }
+            BlobCodec::Brotli => {
+                let params = BrotliEncoderParams::default();
+                let mut r = Cursor::new(data);
+                let mut w = Cursor::new(Vec::new());
+                BrotliCompress(&mut r, &mut w, &params).expect("Failed to brotli");
+                bytes.extend_from_slice(w.get_ref());
+            }
+        }
+    }
+
+    /// Estimates the compressed size of all the data by compressing a sample from the start.
+    fn size_for<T: AsRef<[u8]>>(self, data: &[T]) -> usize {
+        profile_method!(size_for);
+
+        let total = data.iter().map(|d| d.as_ref().len()).sum::<usize>();
+        if let BlobCodec::Raw = self {
+            return total;
+        }
+
+        let mut sample = Vec::new();
+        for value in data.iter() {
+            let value = value.as_ref();
+            let take = value.len().min(SAMPLE_SIZE - sample.len());
+            sample.extend_from_slice(&value[..take]);
+            if sample.len() == SAMPLE_SIZE {
+                break;
+            }
+        }
+        if sample.is_empty() {
+            return 1;
+        }
+
+        let mut compressed = Vec::new();
+        self.compress_into(&sample, &mut compressed);
+        compressed.len() * total / sample.len()
+    }
+
+    fn root_type_id(self) -> RootTypeId {
+        match self {
+            BlobCodec::Raw => RootTypeId::Blob,
+            BlobCodec::Lz4 => RootTypeId::Lz4Blob,
+            BlobCodec::Brotli => RootTypeId::BrotliBlob,
+        }
+    }
+
+    fn array_type_id(self) -> ArrayTypeId {
+        match self {
+            BlobCodec::Raw => ArrayTypeId::Blob,
+            BlobCodec::Lz4 => ArrayTypeId::Lz4Blob,
+            BlobCodec::Brotli => ArrayTypeId::BrotliBlob,
+        }
+    }
+}
+
+#[cfg(feature = "encode")]
+pub(crate) fn encode_blob_root<O: EncodeOptions>(value: &[u8], stream: &mut EncoderStream<'_, O>) -> RootTypeId {
+    profile_fn!(encode_blob_root);
+
+    let codecs = [BlobCodec::Raw, BlobCodec::Lz4, BlobCodec::Brotli];
+    // min_by_key returns the first of equal elements, which prefers the codecs that are faster to decode.
+    let codec = *codecs.iter().min_by_key(|codec| codec.size_for(&[value])).unwrap();
+
+    let mut payload = Vec::new();
+    codec.compress_into(value, &mut payload);
+    encode_prefix_varint(payload.len() as u64, stream.bytes);
+    stream.bytes.extend_from_slice(&payload);
+
+    codec.root_type_id()
+}
+
+#[cfg(feature = "encode")]
+pub(crate) struct BlobCompressor(pub BlobCodec);
+
+#[cfg(feature = "encode")]
+impl<T: AsRef<[u8]>> Compressor<T> for BlobCompressor {
+    fn fast_size_for<O: EncodeOptions>(&self, data: &[T], options: &O) -> Result<usize, ()> {
+        profile_method!(fast_size_for);
+
+        let payload = self.0.size_for(data);
+        let lens: Vec<u64> = data.iter().map(|d| d.as_ref().len() as u64).collect();
+        let from_lens = Vec::<u64>::fast_size_for_all(&lens[..], options);
+        let from_ids = 1;
+
+        Ok(payload + size_for_varint(payload as u64) + from_lens + from_ids)
+    }
+
+    fn compress<O: EncodeOptions>(&self, data: &[T], stream: &mut EncoderStream<'_, O>) -> Result<ArrayTypeId, ()> {
+        profile_method!(compress);
+
+        let (buffer, lens) = {
+            profile_section!(buffer);
+            let mut buffer = take_vec();
+            let mut lens = take_vec_with_capacity(data.len());
+            for value in data.iter() {
+                let value = value.as_ref();
+                buffer.extend_from_slice(value);
+                lens.push(value.len() as u64);
+            }
+            (buffer, lens)
+        };
+
+        stream.encode_with_len(|stream| self.0.compress_into(&buffer, stream.bytes));
+        put_vec(buffer);
+        stream.encode_with_id(|stream| lens.flush(stream));
+
+        Ok(self.0.array_type_id())
+    }
+
+    fn encodings(&self) -> &'static [ArrayTypeId] {
+        &[ArrayTypeId::Blob, ArrayTypeId::Lz4Blob, ArrayTypeId::BrotliBlob]
+    }
+}
+
+#[cfg(feature = "encode")]
+pub(crate) fn encode_blob_array<T: AsRef<[u8]> + PartialEq + Clone + Sync, O: EncodeOptions>(data: &[T], stream: &mut EncoderStream<'_, O>) -> ArrayTypeId {
+    let compressors = (BlobCompressor(BlobCodec::Raw), BlobCompressor(BlobCodec::Lz4), BlobCompressor(BlobCodec::Brotli));
+    let options = stream.options;
+    let codecs = options.codecs().map(Codecs::bytes).unwrap_or_default();
+    compress(data, stream, &with_codecs(compressors, codecs))
+}
+
+#[cfg(feature = "decode")]
+impl BlobBytes<'_> {
+    pub fn decompress(&self, options: &impl DecodeOptions) -> DecodeResult<Vec<u8>> {
+        profile_method!(decompress);
+
+        let out = match self {
+            BlobBytes::Raw(bytes) => bytes.to_vec(),
+            BlobBytes::Lz4(bytes) => {
+                let mut offset = 0;
+                let len: usize = decode_prefix_varint(bytes, &mut offset)?.try_into().map_err(|_| DecodeError::InvalidFormat)?;
+                let compressed = &bytes[offset..];
+                if len > compressed.len().saturating_mul(LZ4_MAX_RATIO) {
+                    return Err(DecodeError::InvalidFormat);
+                }
+                if len > remaining_elements(options) {
+                    return Err(DecodeError::LimitExceeded);
+                }
+                let out = lz4_flex::block::decompress(compressed, len).map_err(|_| DecodeError::InvalidFormat)?;
+                if out.len() != len {
+                    return Err(DecodeError::InvalidFormat);
+                }
+                out
+            }
+            BlobBytes::Brotli(bytes) => decompress_brotli(bytes, options)?,
+        };
+        take_elements(options, out.len())?;
+        Ok(out)
+    }
+}
+
+#[cfg(feature = "decode")]
+pub(crate) fn decode_blob_array(sticks: DynArrayBranch<'_>, options: &impl DecodeOptions) -> DecodeResult<Vec<Vec<u8>>> {
+    profile_fn!(decode_blob_array);
+
+    match sticks.untagged() {
+        DynArrayBranch::Blob { bytes, lens } => {
+            let (all, lens) = parallel(|| bytes.decompress(options), || <u64 as Decodable>::DecoderArray::new(*lens, options), options);
+            let (all, lens) = (all?, lens?);
+
+            let mut result = Vec::with_capacity(lens.len());
+            let mut start: usize = 0;
+            for len in lens {
+                let len: usize = len.try_into().map_err(|_| DecodeError::InvalidFormat)?;
+                check_string_len(options, len)?;
+                let end = start.checked_add(len).ok_or(DecodeError::InvalidFormat)?;
+                result.push(all.get(start..end).ok_or(DecodeError::InvalidFormat)?.to_vec());
+                start = end;
+            }
+            if start != all.len() {
+                return Err(DecodeError::InvalidFormat);
+            }
+            Ok(result)
+        }
+        DynArrayBranch::Extension { id, bytes } => {
+            let result = find_codec(options.codecs(), Codecs::bytes, id)?.decode(&bytes)?;
+            let mut total: usize = 0;
+            for value in result.iter() {
+                check_string_len(options, value.len())?;
+                total = total.checked_add(value.len()).ok_or(DecodeError::LimitExceeded)?;
+            }
+            take_elements(options, total)?;
+            Ok(result)
+        }
+        _ => Err(DecodeError::SchemaMismatch),
+    }
+}
+
+#[cfg(feature = "encode")]
+impl Encodable for Blob {
+    type EncoderArray = Vec<&'static Blob>;
+    fn encode_schema() -> TypeSchema {
+        TypeSchema::Blob
+    }
+    fn encode_root<O: EncodeOptions>(&self, stream: &mut EncoderStream<'_, O>) -> RootTypeId {
+        encode_blob_root(&self.0, stream)
+    }
+}
+
+#[cfg(feature = "encode")]
+impl EncoderArray<Blob> for Vec<&'static Blob> {
+    fn buffer_one<'a, 'b: 'a>(&'a mut self, value: &'b Blob) {
+        // See also the comment for Vec<&'static String>, which has the same lifetime issue.
+        take_vec_if_unallocated(self);
+        self.push(unsafe { std::mem::transmute::<&Blob, &'static Blob>(value) });
+    }
+
+    fn flush<O: EncodeOptions>(self, stream: &mut EncoderStream<'_, O>) -> ArrayTypeId {
+        profile_method!(flush);
+        let id = encode_blob_array(&self[..], stream);
+        put_vec(self);
+        id
+    }
+}
+
+#[cfg(feature = "decode")]
+impl Decodable for Blob {
+    type DecoderArray = IntoIter<Blob>;
+    fn decode_schema() -> TypeSchema {
+        TypeSchema::Blob
+    }
+    fn decode(sticks: DynRootBranch<'_>, options: &impl DecodeOptions) -> DecodeResult<Self> {
+        profile_method!(decode);
+        match sticks.untagged() {
+            DynRootBranch::Blob(bytes) => {
+                let bytes = bytes.decompress(options)?;
+                check_string_len(options, bytes.len())?;
+                Ok(Blob(bytes))
+            }
+            _ => Err(DecodeError::SchemaMismatch),
+        }
+    }
+}
+
+#[cfg(feature = "decode")]
+impl InfallibleDecoderArray for IntoIter<Blob> {
+    type Decode = Blob;
+
+    fn new_infallible(sticks: DynArrayBranch<'_>, options: &impl DecodeOptions) -> DecodeResult<Self> {
+        profile_method!(new_infallible);
+        let all = decode_blob_array(sticks, options)?;
+        Ok(all.into_iter().map(Blob).collect::<Vec<_>>().into_iter())
+    }
+    fn decode_next_infallible(&mut self) -> Self::Decode {
+        self.next().unwrap_or_default()
+    }
+}
+
+#[cfg(feature = "bytes")]
+mod bytes_impl {
+    use super::*;
+
+    #[cfg(feature = "encode")]
+    impl Encodable for ::bytes::Bytes {
+        type EncoderArray = Vec<&'static ::bytes::Bytes>;
+        fn encode_schema() -> TypeSchema {
+            TypeSchema::Blob
+        }
+        fn encode_root<O: EncodeOptions>(&self, stream: &mut EncoderStream<'_, O>) -> RootTypeId {
+            encode_blob_root(self, stream)
+        }
+    }
+
+    #[cfg(feature = "encode")]
+    impl EncoderArray<::bytes::Bytes> for Vec<&'static ::bytes::Bytes> {
+        fn buffer_one<'a, 'b: 'a>(&'a mut self, value: &'b ::bytes::Bytes) {
+            // See also the comment for Vec<&'static String>, which has the same lifetime issue.
+            take_vec_if_unallocated(self);
+            self.push(unsafe { std::mem::transmute::<&::bytes::Bytes, &'static ::bytes::Bytes>(value) });
+        }
+
+        fn flush<O: EncodeOptions>(self, stream: &mut EncoderStream<'_, O>) -> ArrayTypeId {
+            profile_method!(flush);
+            let id = encode_blob_array(&self[..], stream);
+            put_vec(self);
+            id
+        }
+    }
+
+    #[cfg(feature = "decode")]
+    impl Decodable for ::bytes::Bytes {
+        type DecoderArray = IntoIter<::bytes::Bytes>;
+        fn decode_schema() -> TypeSchema {
+            TypeSchema::Blob
+        }
+        fn decode(sticks: DynRootBranch<'_>, options: &impl DecodeOptions) -> DecodeResult<Self> {
+            Ok(Blob::decode(sticks, options)?.0.into())
+        }
+    }
+
+    #[cfg(feature = "decode")]
+    impl InfallibleDecoderArray for IntoIter<::bytes::Bytes> {
+        type Decode = ::bytes::Bytes;
+
+        fn new_infallible(sticks: DynArrayBranch<'_>, options: &impl DecodeOptions) -> DecodeResult<Self> {
+            profile_method!(new_infallible);
+            let all = decode_blob_array(sticks, options)?;
+            Ok(all.into_iter().map(::bytes::Bytes::from).collect::<Vec<_>>().into_iter())
+        }
+        fn decode_next_infallible(&mut self) -> Self::Decode {
+            self.next().unwrap_or_default()
+        }
+    }
+}
+
+#[cfg(all(test, feature = "encode", feature = "decode"))]
+mod tests {
+    use super::*;
+
+    fn round_trip(codec: BlobCodec, data: &[u8]) {
+        let mut bytes = Vec::new();
+        codec.compress_into(data, &mut bytes);
+        let blob = match codec {
+            BlobCodec::Raw => BlobBytes::Raw(bytes[..].into()),
+            BlobCodec::Lz4 => BlobBytes::Lz4(bytes[..].into()),
+            BlobCodec::Brotli => BlobBytes::Brotli(bytes[..].into()),
+        };
+        assert_eq!(blob.decompress(&DecodeOptionsDefault).unwrap(), data);
+    }
+
+    #[test]
+    fn codecs_round_trip() {
+        let data: Vec<u8> = (0..10_000u32).map(|i| (i % 251) as u8).collect();
+        for codec in [BlobCodec::Raw, BlobCodec::Lz4, BlobCodec::Brotli].iter() {
+            round_trip(*codec, &[]);
+            round_trip(*codec, &data);
+        }
+    }
+
+    #[test]
+    fn lz4_size_is_bounded() {
+        // Claims to decompress 1GB from 2 bytes
+        let mut bytes = Vec::new();
+        encode_prefix_varint(1 << 30, &mut bytes);
+        bytes.extend_from_slice(&[0, 0]);
+        assert_eq!(BlobBytes::Lz4(bytes[..].into()).decompress(&DecodeOptionsDefault), Err(DecodeError::InvalidFormat));
+    }
+}
@@ -6,6 +6,9 @@ use std::vec::IntoIter;
 #[cfg(feature = "encode")]
 impl Encodable for bool {
     type EncoderArray = Vec<bool>;
+    fn encode_schema() -> TypeSchema {
+        TypeSchema::Boolean
+    }
     #[inline]
     fn encode_root<O: EncodeOptions>(&self, _stream: &mut EncoderStream<'_, O>) -> RootTypeId {
         if *self {
@@ -19,6 +22,9 @@ impl Encodable for bool {
 #[cfg(feature = "decode")]
 impl Decodable for bool {
     type DecoderArray = IntoIter<bool>;
+    fn decode_schema() -> TypeSchema {
+        TypeSchema::Boolean
+    }
     fn decode(sticks: DynRootBranch<'_>, _options: &impl DecodeOptions) -> DecodeResult<Self> {
         profile_method!(decode);
         match sticks {
@@ -31,10 +37,12 @@ impl Decodable for bool {
 #[cfg(feature = "encode")]
 impl EncoderArray<bool> for Vec<bool> {
     fn buffer_one<'a, 'b: 'a>(&'a mut self, value: &'b bool) {
+        take_vec_if_unallocated(self);
         self.push(*value);
     }
     fn buffer_many<'a, 'b: 'a>(&'a mut self, values: &'b [bool]) {
         profile_method!(buffer_many);
+        take_vec_if_unallocated(self);
         self.extend_from_slice(values);
     }
     fn encode_all<O: EncodeOptions>(values: &[bool], stream: &mut EncoderStream<'_, O>) -> ArrayTypeId {
@@ -45,7 +53,9 @@ impl EncoderArray<bool> for Vec<bool> {
         compress(values, stream, &compressors)
     }
     fn flush<O: EncodeOptions>(self, stream: &mut EncoderStream<'_, O>) -> ArrayTypeId {
-        Self::encode_all(&self[..], stream)
+        let id = Self::encode_all(&self[..], stream);
+        put_vec(self);
+        id
     }
 }
 
@@ -69,6 +79,9 @@ impl Compressor<bool> for PackedBoolCompressor {
         stream.encode_with_len(|stream| encode_packed_bool(data, stream.bytes));
         Ok(ArrayTypeId::PackedBool)
     }
+    fn encodings(&self) -> &'static [ArrayTypeId] {
+        &[ArrayTypeId::PackedBool]
+    }
 }
 
 struct RLEBoolCompressor;
@@ -81,6 +94,9 @@ impl Compressor<bool> for RLEBoolCompressor {
     fn fast_size_for<O: EncodeOptions>(&self, data: &[bool], options: &O) -> Result<usize, ()> {
         within_rle(|| size_of_rle_bool(data, options))
     }
+    fn encodings(&self) -> &'static [ArrayTypeId] {
+        &[ArrayTypeId::RLEBoolTrue, ArrayTypeId::RLEBoolFalse]
+    }
 }
 
 #[cfg(feature = "decode")]
@@ -96,9 +112,11 @@ impl InfallibleDecoderArray for IntoIter<bool> {
                     ArrayBool::Packed(bytes) => decode_packed_bool(&bytes).into_iter(),
                     ArrayBool::RLE(first, runs) => {
                         let runs = <u64 as Decodable>::DecoderArray::new(*runs, options)?;
+                        check_runs(&runs, options)?;
                         decode_rle_bool(runs, first)
                     }
                 };
+                take_elements(options, v.len())?;
                 Ok(v)
             }
             _ => Err(DecodeError::SchemaMismatch),
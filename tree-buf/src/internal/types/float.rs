@@ -22,7 +22,6 @@ macro_rules! impl_float {
     ($T:ident, $id:ident) => {
         //use crate::encodings::zfp;
         use crate::prelude::*;
-        use num_traits::AsPrimitive as _;
         use std::convert::TryInto;
         use std::mem::size_of;
         use std::vec::IntoIter;
@@ -34,6 +33,18 @@ macro_rules! impl_float {
             bytes.extend_from_slice(&b);
         }
 
+        /// Replaces values which are equal to another value but have different bits.
+        #[cfg(feature = "encode")]
+        fn canonicalize(item: $T) -> $T {
+            if item.is_nan() {
+                <$T>::NAN
+            } else if item == 0.0 {
+                0.0
+            } else {
+                item
+            }
+        }
+
         #[cfg(feature = "decode")]
         pub(super) fn decode_item(bytes: &[u8], offset: &mut usize) -> DecodeResult<$T> {
             let bytes = decode_bytes(size_of::<$T>(), bytes, offset)?;
@@ -44,12 +55,15 @@ macro_rules! impl_float {
         #[cfg(feature = "encode")]
         impl Encodable for $T {
             type EncoderArray = Vec<$T>;
+            fn encode_schema() -> TypeSchema {
+                TypeSchema::Float(std::mem::size_of::<$T>() as u32 * 8)
+            }
             fn encode_root<O: EncodeOptions>(&self, stream: &mut EncoderStream<'_, O>) -> RootTypeId {
                 let value = *self;
 
                 // Check for positive sign so that -0.0 goes through
                 // the unhappy path but round-trips bit-for-bit
-                if value == 0.0 && value.is_sign_positive() {
+                if value == 0.0 && (value.is_sign_positive() || stream.options.canonical()) {
                     RootTypeId::Zero
                 } else if value == 1.0 {
                     RootTypeId::One
@@ -69,42 +83,22 @@ macro_rules! impl_float {
         #[cfg(feature = "decode")]
         impl Decodable for $T {
             type DecoderArray = IntoIter<$T>;
-            fn decode(sticks: DynRootBranch<'_>, _options: &impl DecodeOptions) -> DecodeResult<Self> {
+            fn decode_schema() -> TypeSchema {
+                TypeSchema::Float(std::mem::size_of::<$T>() as u32 * 8)
+            }
+            fn decode(sticks: DynRootBranch<'_>, options: &impl DecodeOptions) -> DecodeResult<Self> {
                 profile_method!(decode);
-                match sticks {
-                    DynRootBranch::Integer(root_integer) => {
-                        // FIXME: Fast and lose to get refactoring done. Double check here.
-                        // Also, float can express some (but not all) integers larger than MAX_SAFE_INT
-                        match root_integer {
-                            RootInteger::U(u) => {
-                                if u < (2 << std::$T::MANTISSA_DIGITS) {
-                                    Ok(u as $T)
-                                } else {
-                                    Err(DecodeError::SchemaMismatch)
-                                }
-                            }
-                            RootInteger::S(s) => {
-                                if s < (2 << std::$T::MANTISSA_DIGITS) && s > (-2 << (std::$T::MANTISSA_DIGITS - 1)) {
-                                    // FIXME: Made up number
-                                    Ok(s as $T)
-                                } else {
-                                    Err(DecodeError::SchemaMismatch)
-                                }
-                            }
-                        }
-                    }
-                    DynRootBranch::Float(root_float) => {
-                        match root_float {
-                            // FIXME: Macro here - should be schema mismatch for f64 -> f32
-                            RootFloat::F64(v) => Ok(v as $T),
-                            RootFloat::NaN => Ok(std::$T::NAN),
-                            // This should be safe to cast without loss of information.
-                            // Double-check that the meaning of various NaN values
-                            // is preserved though (signaling, non-signaling, etc)
-                            // https://stackoverflow.com/a/59795029/11837266
-                            RootFloat::F32(v) => Ok(v as $T),
-                        }
-                    }
+                let lossy = options.lossy_numbers();
+                match sticks.untagged() {
+                    DynRootBranch::Integer(root_integer) => match root_integer {
+                        RootInteger::U(u) => <$T>::from_u64(u, lossy),
+                        RootInteger::S(s) => <$T>::from_i64(s, lossy),
+                    },
+                    DynRootBranch::Float(root_float) => match root_float {
+                        RootFloat::F64(v) => <$T>::from_f64(v, lossy),
+                        RootFloat::NaN => Ok(<$T>::NAN),
+                        RootFloat::F32(v) => <$T>::from_f32(v, lossy),
+                    },
                     _ => Err(DecodeError::SchemaMismatch),
                 }
             }
@@ -113,26 +107,24 @@ macro_rules! impl_float {
         #[cfg(feature = "decode")]
         impl InfallibleDecoderArray for IntoIter<$T> {
             type Decode = $T;
-            fn new_infallible(sticks: DynArrayBranch<'_>, _options: &impl DecodeOptions) -> DecodeResult<Self> {
+            fn new_infallible(sticks: DynArrayBranch<'_>, options: &impl DecodeOptions) -> DecodeResult<Self> {
                 profile_method!(new_infallibe);
 
-                match sticks {
+                let lossy = options.lossy_numbers();
+                let sticks = sticks.untagged();
+                let values: DecodeResult<Vec<$T>> = match sticks {
                     DynArrayBranch::Float(float) => {
                         match float {
                             ArrayFloat::F64(bytes) => {
                                 profile_section!(array_f64);
-
-                                // FIXME: Should do schema mismatch for f32 -> f64
-                                let values = decode_all(&bytes, |bytes, offset| Ok(super::_f64::decode_item(bytes, offset)?.as_()))?;
-                                Ok(values.into_iter())
+                                decode_all(&bytes, |bytes, offset| <$T>::from_f64(super::_f64::decode_item(bytes, offset)?, lossy))
                             }
                             ArrayFloat::F32(bytes) => {
                                 profile_section!(array_f32);
-
-                                let values = decode_all(&bytes, |bytes, offset| Ok(super::_f32::decode_item(bytes, offset)?.as_()))?;
-                                Ok(values.into_iter())
+                                decode_all(&bytes, |bytes, offset| <$T>::from_f32(super::_f32::decode_item(bytes, offset)?, lossy))
                             }
-                            ArrayFloat::DoubleGorilla(bytes) => gorilla::decompress::<$T>(&bytes).map(|f| f.into_iter()),
+                            // Gorilla always stores an f64, even for f32 values
+                            ArrayFloat::DoubleGorilla(bytes) => gorilla::decompress::<f64>(&bytes)?.into_iter().map(|v| <$T>::from_f64(v, lossy)).collect(),
                             /*
                             ArrayFloat::Zfp32(bytes) => {
                                 // FIXME: This is likely a bug switching between 32 and 64 might just get garbage data out
@@ -148,14 +140,25 @@ macro_rules! impl_float {
                                 Ok(values.into_iter())
                             }
                             */
-                            ArrayFloat::Zfp32(_bytes) => unimplemented!("zfp32"),
-                            ArrayFloat::Zfp64(_bytes) => unimplemented!("zfp64"),
+                            // Zfp is never written by the encoder, so a file containing it is corrupt.
+                            ArrayFloat::Zfp32(_bytes) | ArrayFloat::Zfp64(_bytes) => Err(DecodeError::InvalidFormat),
                         }
                     }
-                    // TODO: There are some conversions that are infallable.
-                    // Eg: Simple16.
+                    DynArrayBranch::Extension { id, bytes } => {
+                        let codec = find_codec(options.codecs(), Codecs::floats, id)?;
+                        codec.decode(&bytes)?.into_iter().map(|v| <$T>::from_f64(v, lossy)).collect()
+                    }
+                    // Integers, including those which were lowered to bool.
+                    // Floats are never written with RLE, so that is an integer too.
+                    DynArrayBranch::Integer(_) | DynArrayBranch::Boolean(_) | DynArrayBranch::RLE { .. } => {
+                        let integers = <IntoIter<u64> as InfallibleDecoderArray>::new_infallible(sticks, options)?;
+                        integers.map(|v| <$T>::from_u64(v, lossy)).collect()
+                    }
                     _ => Err(DecodeError::SchemaMismatch),
-                }
+                };
+                let values = values?.into_iter();
+                take_elements(options, values.len())?;
+                Ok(values)
             }
             fn decode_next_infallible(&mut self) -> Self::Decode {
                 self.next().unwrap_or_default()
@@ -165,10 +168,12 @@ macro_rules! impl_float {
         #[cfg(feature = "encode")]
         impl EncoderArray<$T> for Vec<$T> {
             fn buffer_one<'a, 'b: 'a>(&'a mut self, value: &'b $T) {
+                take_vec_if_unallocated(self);
                 self.push(*value);
             }
             fn buffer_many<'a, 'b: 'a>(&'a mut self, values: &'b [$T]) {
                 profile_method!(buffer_many);
+                take_vec_if_unallocated(self);
                 self.extend_from_slice(values);
             }
             fn encode_all<O: EncodeOptions>(values: &[$T], stream: &mut EncoderStream<'_, O>) -> ArrayTypeId {
@@ -180,10 +185,20 @@ macro_rules! impl_float {
                     Gorilla,
                 );
 
-                compress(values, stream, &compressors)
+                let options = stream.options;
+                let codecs = options.codecs().map(Codecs::floats).unwrap_or_default();
+                if options.canonical() && values.iter().any(|&value| canonicalize(value).to_bits() != value.to_bits()) {
+                    let values = collect_vec(values.iter().map(|&value| canonicalize(value)));
+                    let id = compress(&values, stream, &with_codecs(compressors, codecs));
+                    put_vec(values);
+                    return id;
+                }
+                compress(values, stream, &with_codecs(compressors, codecs))
             }
             fn flush<O: EncodeOptions>(self, stream: &mut EncoderStream<'_, O>) -> ArrayTypeId {
-                Self::encode_all(&self[..], stream)
+                let id = Self::encode_all(&self[..], stream);
+                put_vec(self);
+                id
             }
         }
 
@@ -194,7 +209,8 @@ macro_rules! impl_float {
                     Fixed, //Zfp,
                     Gorilla,
                 );
-                fast_size_for(values, &compressors, options)
+                let codecs = options.codecs().map(Codecs::floats).unwrap_or_default();
+                fast_size_for(values, &with_codecs(compressors, codecs), options)
             }
         }
 
@@ -213,6 +229,9 @@ macro_rules! impl_float {
                 });
                 Ok(ArrayTypeId::$id)
             }
+            fn encodings(&self) -> &'static [ArrayTypeId] {
+                &[ArrayTypeId::$id]
+            }
         }
 
         struct Gorilla;
@@ -248,6 +267,10 @@ macro_rules! impl_float {
                     }
                 })
             }
+
+            fn encodings(&self) -> &'static [ArrayTypeId] {
+                &[ArrayTypeId::DoubleGorilla]
+            }
         }
     };
 }
@@ -1,11 +1,59 @@
 use crate::prelude::*;
+#[cfg(feature = "encode")]
+use std::cell::Cell;
 use std::collections::HashMap;
 use std::hash::{BuildHasher, Hash};
+#[cfg(feature = "encode")]
+use std::thread_local;
 use std::vec::IntoIter;
 
+// EncoderArray::buffer_one doesn't have the options, so whether maps
+// are being encoded canonically is carried alongside the encode instead.
+#[cfg(feature = "encode")]
+thread_local! {
+    static CANONICAL_MAPS: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Runs f with maps buffered canonically or not. This is for carrying the option over
+/// to buffer_one, including on other threads when encoding in parallel.
+#[cfg(feature = "encode")]
+pub(crate) fn with_canonical_maps<T>(canonical: bool, f: impl FnOnce() -> T) -> T {
+    let previous = CANONICAL_MAPS.with(|v| v.replace(canonical));
+    let result = f();
+    CANONICAL_MAPS.with(|v| v.set(previous));
+    result
+}
+
+#[cfg(feature = "encode")]
+fn canonical_maps() -> bool {
+    CANONICAL_MAPS.with(|v| v.get())
+}
+
+/// Calls f with each entry of the map. When canonical, the entries are ordered by the encoding
+/// of their keys rather than by hash, so that equal maps are always encoded the same.
+#[cfg(feature = "encode")]
+fn for_each_entry<'a, K: Encodable, V, S>(map: &'a HashMap<K, V, S>, canonical: bool, mut f: impl FnMut(&'a K, &'a V)) {
+    if !canonical || map.len() < 2 {
+        for (key, value) in map.iter() {
+            f(key, value);
+        }
+        return;
+    }
+    profile_fn!(sort_canonical_entries);
+    let options = override_encode_options(EncodeOptionsDefault, Canonical);
+    let mut entries: Vec<_> = map.iter().collect();
+    entries.sort_by_cached_key(|(key, _)| crate::experimental::options::encode_with_options(*key, &options));
+    for (key, value) in entries {
+        f(key, value);
+    }
+}
+
 #[cfg(feature = "encode")]
 impl<K: Encodable, V: Encodable, S: Default + BuildHasher> Encodable for HashMap<K, V, S> {
     type EncoderArray = HashMapArrayEncoder<K::EncoderArray, V::EncoderArray, S>;
+    fn encode_schema() -> TypeSchema {
+        TypeSchema::Map(Box::new(K::encode_schema()), Box::new(V::encode_schema()))
+    }
     fn encode_root<O: EncodeOptions>(&self, stream: &mut EncoderStream<'_, O>) -> RootTypeId {
         profile_method!(encode_root);
 
@@ -22,15 +70,12 @@ impl<K: Encodable, V: Encodable, S: Default + BuildHasher> Encodable for HashMap
             }
             _ => {
                 let mut keys_encoder = K::EncoderArray::default();
-                for key in self.keys() {
-                    keys_encoder.buffer_one(key);
-                }
-                stream.encode_with_id(|stream| keys_encoder.flush(stream));
-                // TODO: Verify that iterating keys and values is always the same order!
                 let mut values_encoder = V::EncoderArray::default();
-                for value in self.values() {
+                for_each_entry(self, stream.options.canonical(), |key, value| {
+                    keys_encoder.buffer_one(key);
                     values_encoder.buffer_one(value);
-                }
+                });
+                stream.encode_with_id(|stream| keys_encoder.flush(stream));
                 stream.encode_with_id(|stream| values_encoder.flush(stream));
             }
         }
@@ -48,6 +93,9 @@ where
     DecodeError: From<<<V as Decodable>::DecoderArray as DecoderArray>::Error>,
 {
     type DecoderArray = Option<HashMapArrayDecoder<K::DecoderArray, V::DecoderArray, S>>;
+    fn decode_schema() -> TypeSchema {
+        TypeSchema::Map(Box::new(K::decode_schema()), Box::new(V::decode_schema()))
+    }
     fn decode(sticks: DynRootBranch<'_>, options: &impl DecodeOptions) -> DecodeResult<Self> {
         profile_method!(decode);
 
@@ -60,6 +108,7 @@ where
                 Ok(v)
             }
             DynRootBranch::Map { len, keys, values } => {
+                take_elements(options, len)?;
                 let (keys, values) = parallel(|| K::DecoderArray::new(keys, options), || V::DecoderArray::new(values, options), options);
                 let mut keys = keys?;
                 let mut values = values?;
@@ -73,6 +122,33 @@ where
             _ => Err(DecodeError::SchemaMismatch),
         }
     }
+    fn decode_into(sticks: DynRootBranch<'_>, options: &impl DecodeOptions, into: &mut Self) -> DecodeResult<()> {
+        profile_method!(decode_into);
+
+        // The keys and values are decoded fresh, but the capacity of the map is kept.
+        match sticks {
+            DynRootBranch::Map0 => into.clear(),
+            DynRootBranch::Map1 { key, value } => {
+                let (key, value) = parallel(move || K::decode(*key, options), move || V::decode(*value, options), options);
+                into.clear();
+                into.insert(key?, value?);
+            }
+            DynRootBranch::Map { len, keys, values } => {
+                take_elements(options, len)?;
+                let (keys, values) = parallel(|| K::DecoderArray::new(keys, options), || V::DecoderArray::new(values, options), options);
+                let mut keys = keys?;
+                let mut values = values?;
+                into.clear();
+                for _ in 0..len {
+                    if into.insert(keys.decode_next()?, values.decode_next()?).is_some() {
+                        return Err(DecodeError::InvalidFormat);
+                    }
+                }
+            }
+            _ => return Err(DecodeError::SchemaMismatch),
+        }
+        Ok(())
+    }
 }
 
 #[cfg(feature = "encode")]
@@ -97,10 +173,10 @@ impl<K: Encodable, V: Encodable, S: Default + BuildHasher> EncoderArray<HashMap<
         profile_method!(EncoderArray::buffer);
         self.len.buffer_one(&(value.len() as u64));
         let (keys, values) = self.items.get_or_insert_with(Default::default);
-        for (key, value) in value.iter() {
+        for_each_entry(value, canonical_maps(), |key, value| {
             keys.buffer_one(key);
             values.buffer_one(value);
-        }
+        });
     }
     fn flush<O: EncodeOptions>(self, stream: &mut EncoderStream<'_, O>) -> ArrayTypeId {
         profile_method!(flush);
@@ -148,6 +224,7 @@ where
                 let keys = keys?;
                 let values = values?;
                 let len = len?;
+                take_lens(options, len.as_slice())?;
                 Ok(Some(HashMapArrayDecoder {
                     len,
                     keys,
@@ -177,4 +254,18 @@ where
             Ok(Default::default())
         }
     }
+    fn decode_next_into(&mut self, into: &mut Self::Decode) -> Result<(), Self::Error> {
+        into.clear();
+        if let Some(inner) = self {
+            let len = inner.len.decode_next_infallible();
+            for _ in 0..len {
+                let key = inner.keys.decode_next()?;
+                let value = inner.values.decode_next()?;
+                if into.insert(key, value).is_some() {
+                    return Err(DecodeError::InvalidFormat);
+                };
+            }
+        }
+        Ok(())
+    }
 }
@@ -1,11 +1,14 @@
 use crate::prelude::*;
 
-#[derive(Default, Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd)]
+#[derive(Default, Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
 pub struct Ignore;
 
 #[cfg(feature = "encode")]
 impl Encodable for Ignore {
     type EncoderArray = Ignore;
+    fn encode_schema() -> TypeSchema {
+        TypeSchema::Ignore
+    }
     fn encode_root<O: EncodeOptions>(&self, _stream: &mut EncoderStream<'_, O>) -> RootTypeId {
         RootTypeId::Void
     }
@@ -14,6 +17,9 @@ impl Encodable for Ignore {
 #[cfg(feature = "decode")]
 impl Decodable for Ignore {
     type DecoderArray = Ignore;
+    fn decode_schema() -> TypeSchema {
+        TypeSchema::Ignore
+    }
     fn decode(_sticks: DynRootBranch<'_>, _options: &impl DecodeOptions) -> DecodeResult<Self> {
         Ok(Self)
     }
@@ -68,6 +68,9 @@ macro_rules! impl_lowerable {
             #[cfg(feature = "encode")]
             impl Encodable for UType {
                 type EncoderArray = Vec<UType>;
+                fn encode_schema() -> TypeSchema {
+                    TypeSchema::Integer(std::mem::size_of::<UType>() as u32 * 8)
+                }
                 fn encode_root<O: EncodeOptions>(&self, stream: &mut EncoderStream<'_, O>) -> RootTypeId {
                     encode_root_uint(*self as u64, stream.bytes)
                 }
@@ -78,10 +81,12 @@ macro_rules! impl_lowerable {
             #[cfg(feature = "encode")]
             impl EncoderArray<UType> for Vec<UType> {
                 fn buffer_one<'a, 'b: 'a>(&'a mut self, value: &'b UType) {
+                    take_vec_if_unallocated(self);
                     self.push(*value);
                 }
                 fn buffer_many<'a, 'b: 'a>(&'a mut self, values: &'b [UType]) {
                     profile_method!(buffer_many);
+                    take_vec_if_unallocated(self);
                     self.extend_from_slice(values);
                 }
                 fn encode_all<O: EncodeOptions>(values: &[UType], stream: &mut EncoderStream<'_, O>) -> ArrayTypeId {
@@ -96,7 +101,7 @@ macro_rules! impl_lowerable {
                         // Lower to bool if possible. This is especially nice for enums
                         // with 2 variants.
                         if *max < 2 {
-                            let bools = values.iter().map(|i| *i == 1).collect::<Vec<_>>();
+                            let bools = collect_vec(values.iter().map(|i| *i == 1));
                             bools.flush(stream)
                         } else {
                             encode_array(values, *max, stream)
@@ -106,7 +111,9 @@ macro_rules! impl_lowerable {
                     }
                 }
                 fn flush<O: EncodeOptions>(self, stream: &mut EncoderStream<'_, O>) -> ArrayTypeId {
-                    Self::encode_all(&self[..], stream)
+                    let id = Self::encode_all(&self[..], stream);
+                    put_vec(self);
+                    id
                 }
             }
 
@@ -119,8 +126,10 @@ macro_rules! impl_lowerable {
                         // Lower to bool if possible. This is especially nice for enums
                         // with 2 variants.
                         if *max < 2 {
-                            let bools = values.iter().map(|i| *i == 1).collect::<Vec<_>>();
-                            Vec::<bool>::fast_size_for_all(&bools[..], options)
+                            let bools = collect_vec(values.iter().map(|i| *i == 1));
+                            let size = Vec::<bool>::fast_size_for_all(&bools[..], options);
+                            put_vec(bools);
+                            size
                         } else {
                             fast_size_for_array(values, *max, options)
                         }
@@ -133,15 +142,29 @@ macro_rules! impl_lowerable {
             #[cfg(feature = "decode")]
             impl Decodable for UType {
                 type DecoderArray = IntoIter<UType>;
-                fn decode(sticks: DynRootBranch<'_>, _options: &impl DecodeOptions) -> DecodeResult<Self> {
+                fn decode_schema() -> TypeSchema {
+                    TypeSchema::Integer(std::mem::size_of::<UType>() as u32 * 8)
+                }
+                fn decode(sticks: DynRootBranch<'_>, options: &impl DecodeOptions) -> DecodeResult<Self> {
                     profile_method!(decode);
-                    match sticks {
+                    let lossy = options.lossy_numbers();
+                    match sticks.untagged() {
                         DynRootBranch::Integer(root_int) => {
                             match root_int {
-                                RootInteger::U(v) => v.try_into().map_err(|_| DecodeError::SchemaMismatch),
-                                _ => Err(DecodeError::SchemaMismatch),
+                                RootInteger::U(v) => UType::from_u64(v, lossy),
+                                RootInteger::S(v) => UType::from_i64(v, lossy),
+                            }
+                        }
+                        DynRootBranch::Float(root_float) => {
+                            match root_float {
+                                RootFloat::F64(v) => UType::from_f64(v, lossy),
+                                RootFloat::F32(v) => UType::from_f32(v, lossy),
+                                RootFloat::NaN => UType::from_f64(f64::NAN, lossy),
                             }
                         }
+                        // Arrays of integers which are all 0 or 1 are written as bools, and read back
+                        // as integers. So that a type can read either, the same goes for the root.
+                        DynRootBranch::Boolean(v) => UType::from_u64(v as u64, lossy),
                         _ => Err(DecodeError::SchemaMismatch),
                     }
                 }
@@ -153,7 +176,9 @@ macro_rules! impl_lowerable {
                 fn new_infallible(sticks: DynArrayBranch<'_>, options: &impl DecodeOptions) -> DecodeResult<Self> {
                     profile_method!(new_infallible);
 
-                    match sticks {
+                    let lossy = options.lossy_numbers();
+                    let sticks = sticks.untagged();
+                    let values: DecodeResult<Self> = match sticks {
                         DynArrayBranch::Integer(array_int) => {
                             let ArrayInteger { bytes, encoding } = array_int;
                             match encoding {
@@ -163,8 +188,7 @@ macro_rules! impl_lowerable {
                                     let v: Vec<UType> = decode_all(
                                             &bytes,
                                             |bytes, offset| {
-                                                let r: UType = decode_prefix_varint(bytes, offset)?.try_into().map_err(|_| DecodeError::SchemaMismatch)?;
-                                                Ok(r)
+                                                UType::from_u64(decode_prefix_varint(bytes, offset)?, lossy)
                                             }
                                     )?;
                                     Ok(v.into_iter())
@@ -172,10 +196,9 @@ macro_rules! impl_lowerable {
                                 ArrayIntegerEncoding::Simple16 => {
                                     profile_section!(simple_16);
 
-                                    let mut v = Vec::new();
+                                    let mut v: Vec<u32> = Vec::new();
                                     simple_16::decompress(&bytes, &mut v).map_err(|_| DecodeError::InvalidFormat)?;
-                                    let result: Result<Vec<_>, _> = v.into_iter().map(TryInto::<UType>::try_into).collect();
-                                    let v = result.map_err(|_| DecodeError::SchemaMismatch)?;
+                                    let v = v.into_iter().map(|v| UType::from_u64(v as u64, lossy)).collect::<DecodeResult<Vec<_>>>()?;
                                     Ok(v.into_iter())
                                 },
                                 ArrayIntegerEncoding::U8 => {
@@ -196,7 +219,20 @@ macro_rules! impl_lowerable {
                                         let next: i32 = ZigZag::decode(next);
                                         let next = prev.wrapping_add(next as u32);
                                         prev = next;
-                                        v.push(next.try_into().map_err(|_| DecodeError::InvalidFormat)?);
+                                        v.push(UType::from_u64(next as u64, lossy)?);
+                                    }
+                                    Ok(v.into_iter())
+                                }
+                                ArrayIntegerEncoding::DeltaZig64 => {
+                                    profile_section!(delta_zig_64);
+                                    let mut v = Vec::new();
+                                    let mut prev: u64 = 0;
+                                    let mut offset = 0;
+                                    while offset < bytes.len() {
+                                        let next: i64 = ZigZag::decode(decode_prefix_varint(&bytes, &mut offset)?);
+                                        let next = prev.wrapping_add(next as u64);
+                                        prev = next;
+                                        v.push(UType::from_u64(next, lossy)?);
                                     }
                                     Ok(v.into_iter())
                                 }
@@ -207,6 +243,16 @@ macro_rules! impl_lowerable {
                             let all = rle.collect::<Vec<_>>();
                             Ok(all.into_iter())
                         },
+                        DynArrayBranch::Extension { id, bytes } => {
+                            let codec = find_codec(options.codecs(), Codecs::integers, id)?;
+                            let v = codec.decode(&bytes)?.into_iter().map(|v| UType::from_u64(v, lossy)).collect::<DecodeResult<Vec<_>>>()?;
+                            Ok(v.into_iter())
+                        },
+                        DynArrayBranch::Float(_) => {
+                            let floats = <IntoIter<f64> as InfallibleDecoderArray>::new_infallible(sticks, options)?;
+                            let v = floats.map(|v| UType::from_f64(v, lossy)).collect::<DecodeResult<Vec<_>>>()?;
+                            Ok(v.into_iter())
+                        },
                         // FIXME: This fixes a particular test.
                         // It is unclear if this is canon.
                         // See also: 84d15459-35e4-4f04-896f-0f4ea9ce52a9
@@ -219,7 +265,10 @@ macro_rules! impl_lowerable {
                             let mapped = bools.map(|i| if i {1} else {0}).collect::<Vec<_>>();
                             Ok(mapped.into_iter())
                         },
-                    }
+                    };
+                    let values = values?;
+                    take_elements(options, values.len())?;
+                    Ok(values)
                 }
                 fn decode_next_infallible(&mut self) -> Self::Decode {
                     self.next().unwrap_or_default()
@@ -243,7 +292,8 @@ macro_rules! impl_lowerable {
                         $(<$compressions>::new(max),)+
                         RLE::new(($(<$compressions>::new(max),)+))
                     );
-                    fast_size_for(data, &compressors, options)
+                    let codecs = options.codecs().map(Codecs::integers).unwrap_or_default();
+                    fast_size_for(data, &with_codecs(compressors, codecs), options)
                 }
 
                 // Convert data to as<T>, using a transmute if that's already correct
@@ -255,9 +305,11 @@ macro_rules! impl_lowerable {
                     // TODO: (Performance) Use second-stack
                     let v = {
                         profile_section!(copy_to_lowered);
-                        data.iter().map(|i| i.as_()).collect::<Vec<_>>()
+                        collect_vec(data.iter().map(|i| i.as_()))
                     };
-                    fast_inner(&v, options, max.as_())
+                    let size = fast_inner(&v, options, max.as_());
+                    put_vec(v);
+                    size
                 }
             }
 
@@ -278,7 +330,9 @@ macro_rules! impl_lowerable {
                         $(<$compressions>::new(max),)+
                         RLE::new(($(<$compressions>::new(max),)+))
                     );
-                    compress(data, stream, &compressors)
+                    let options = stream.options;
+                    let codecs = options.codecs().map(Codecs::integers).unwrap_or_default();
+                    compress(data, stream, &with_codecs(compressors, codecs))
                 }
 
                 // Convert data to as<T>, using a transmute if that's already correct
@@ -290,9 +344,11 @@ macro_rules! impl_lowerable {
                     // TODO: (Performance) Use second-stack
                     let v = {
                         profile_section!(needless_lowered_copy);
-                        data.iter().map(|i| i.as_()).collect::<Vec<_>>()
+                        collect_vec(data.iter().map(|i| i.as_()))
                     };
-                    encode_inner(&v, stream, max.as_())
+                    let id = encode_inner(&v, stream, max.as_());
+                    put_vec(v);
+                    id
                 }
             }
         }
@@ -306,11 +362,73 @@ macro_rules! impl_lowerable {
 // Broadly we only want to downcast if it allows for some other kind of compressor to be used.
 
 // Type, array encoder, next lower, next lower encoder, non-inferred lowers
-impl_lowerable!(u64, i64, _64, _32, (u16), (PrefixVarIntCompressor));
+impl_lowerable!(u64, i64, _64, _32, (u16), (DeltaZigZagCompressor, PrefixVarIntCompressor));
 impl_lowerable!(u32, i32, _32, _16, (), (Simple16Compressor<u32>, DeltaZigZagCompressor, PrefixVarIntCompressor)); // TODO: Consider adding Fixed.
 impl_lowerable!(u16, i16, _16, _8, (), (Simple16Compressor<u16>, PrefixVarIntCompressor));
 impl_lowerable!(u8, i8, _8, _0, (), (Simple16Compressor<u8>, BytesCompressor));
 
+// Arrays have no encoding for negative integers, so the signed types can't be written.
+// They read the unsigned integers and floats in a file, converting as in numeric.rs
+macro_rules! impl_signed_decodable {
+    ($($T:ty),*) => {
+        $(
+            #[cfg(feature = "decode")]
+            impl Decodable for $T {
+                type DecoderArray = IntoIter<$T>;
+                fn decode_schema() -> TypeSchema {
+                    TypeSchema::SignedInteger(std::mem::size_of::<$T>() as u32 * 8)
+                }
+                fn decode(sticks: DynRootBranch<'_>, options: &impl DecodeOptions) -> DecodeResult<Self> {
+                    profile_method!(decode);
+                    let lossy = options.lossy_numbers();
+                    match sticks.untagged() {
+                        DynRootBranch::Integer(root_int) => match root_int {
+                            RootInteger::U(v) => <$T>::from_u64(v, lossy),
+                            RootInteger::S(v) => <$T>::from_i64(v, lossy),
+                        },
+                        DynRootBranch::Float(root_float) => match root_float {
+                            RootFloat::F64(v) => <$T>::from_f64(v, lossy),
+                            RootFloat::F32(v) => <$T>::from_f32(v, lossy),
+                            RootFloat::NaN => <$T>::from_f64(f64::NAN, lossy),
+                        },
+                        DynRootBranch::Boolean(v) => <$T>::from_u64(v as u64, lossy),
+                        _ => Err(DecodeError::SchemaMismatch),
+                    }
+                }
+            }
+
+            #[cfg(feature = "decode")]
+            impl InfallibleDecoderArray for IntoIter<$T> {
+                type Decode = $T;
+                fn new_infallible(sticks: DynArrayBranch<'_>, options: &impl DecodeOptions) -> DecodeResult<Self> {
+                    profile_method!(new_infallible);
+
+                    let lossy = options.lossy_numbers();
+                    let sticks = sticks.untagged();
+                    let values: DecodeResult<Vec<$T>> = match sticks {
+                        DynArrayBranch::Float(_) => {
+                            let floats = <IntoIter<f64> as InfallibleDecoderArray>::new_infallible(sticks, options)?;
+                            floats.map(|v| <$T>::from_f64(v, lossy)).collect()
+                        }
+                        // Floats are never written with RLE, so everything else is an unsigned integer,
+                        // including those which were lowered to bool.
+                        other => {
+                            let integers = <IntoIter<u64> as InfallibleDecoderArray>::new_infallible(other, options)?;
+                            integers.map(|v| <$T>::from_u64(v, lossy)).collect()
+                        }
+                    };
+                    Ok(values?.into_iter())
+                }
+                fn decode_next_infallible(&mut self) -> Self::Decode {
+                    self.next().unwrap_or_default()
+                }
+            }
+        )*
+    };
+}
+
+impl_signed_decodable!(i8, i16, i32, i64);
+
 #[cfg(feature = "encode")]
 fn encode_root_uint(value: u64, bytes: &mut Vec<u8>) -> RootTypeId {
     let le = value.to_le_bytes();
@@ -341,7 +459,7 @@ fn encode_root_uint(value: u64, bytes: &mut Vec<u8>) -> RootTypeId {
             bytes.extend_from_slice(&le[..6]);
             RootTypeId::IntU48
         }
-        281474976710656..=72057594037927936 => {
+        281474976710656..=72057594037927935 => {
             bytes.extend_from_slice(&le[..7]);
             RootTypeId::IntU56
         }
@@ -353,11 +471,12 @@ fn encode_root_uint(value: u64, bytes: &mut Vec<u8>) -> RootTypeId {
 }
 
 #[cfg(feature = "encode")]
-fn encode_root_sint(value: i64, bytes: &mut Vec<u8>) -> RootTypeId {
+pub(crate) fn encode_root_sint(value: i64, bytes: &mut Vec<u8>) -> RootTypeId {
     if value >= 0 {
         return encode_root_uint(value as u64, bytes);
     }
-    let value = (value * -1) as u64;
+    // The magnitude of i64::MIN is out of range for i64.
+    let value = value.unsigned_abs();
     let le = value.to_le_bytes();
     match value {
         0 => unsafe { std::hint::unreachable_unchecked() },
@@ -386,7 +505,7 @@ fn encode_root_sint(value: i64, bytes: &mut Vec<u8>) -> RootTypeId {
             bytes.extend_from_slice(&le[..6]);
             RootTypeId::IntS48
         }
-        281474976710656..=72057594037927936 => {
+        281474976710656..=72057594037927935 => {
             bytes.extend_from_slice(&le[..7]);
             RootTypeId::IntS56
         }
@@ -419,7 +538,7 @@ where
 
     within_rle(|| {
         let mut data = data.into_iter();
-        let mut out = Vec::new();
+        let mut out = take_vec();
         // Unwrap is ok because length checked earlier.
         let mut current = data.next().unwrap();
 
@@ -444,7 +563,7 @@ where
     }
     // TODO: Rename? This isn't really in rle
     within_rle(|| {
-        let mut result = Vec::new();
+        let mut result = take_vec();
         let mut current = U::zero();
         for next in data.iter() {
             // See also e394b0c7-d5af-40b8-b944-cb68bac33fe9
@@ -457,19 +576,43 @@ where
     })
 }
 
+impl Compressor<u64> for DeltaZigZagCompressor {
+    fn compress<O: EncodeOptions>(&self, data: &[u64], stream: &mut EncoderStream<'_, O>) -> Result<ArrayTypeId, ()> {
+        let deltas = get_delta_zigs::<u64, i64>(data)?;
+        let _ignore_id = PrefixVarIntCompressor.compress(&deltas, stream);
+        put_vec(deltas);
+        Ok(ArrayTypeId::DeltaZig64)
+    }
+    fn fast_size_for<O: EncodeOptions>(&self, data: &[u64], options: &O) -> Result<usize, ()> {
+        let deltas = get_delta_zigs::<u64, i64>(data)?;
+        let size = PrefixVarIntCompressor.fast_size_for(&deltas, options);
+        put_vec(deltas);
+        size
+    }
+    fn encodings(&self) -> &'static [ArrayTypeId] {
+        &[ArrayTypeId::DeltaZig64]
+    }
+}
+
 impl Compressor<u32> for DeltaZigZagCompressor {
     fn compress<O: EncodeOptions>(&self, data: &[u32], stream: &mut EncoderStream<'_, O>) -> Result<ArrayTypeId, ()> {
         let deltas = get_delta_zigs::<u32, i32>(data)?;
         let _ignore_id = PrefixVarIntCompressor.compress(&deltas, stream);
+        put_vec(deltas);
         Ok(ArrayTypeId::DeltaZig)
     }
     fn fast_size_for<O: EncodeOptions>(&self, data: &[u32], options: &O) -> Result<usize, ()> {
         let deltas = get_delta_zigs::<u32, i32>(data)?;
-        PrefixVarIntCompressor.fast_size_for(&deltas, options)
+        let size = PrefixVarIntCompressor.fast_size_for(&deltas, options);
+        put_vec(deltas);
+        size
+    }
+    fn encodings(&self) -> &'static [ArrayTypeId] {
+        &[ArrayTypeId::DeltaZig]
     }
 }
 
-struct PrefixVarIntCompressor;
+pub(crate) struct PrefixVarIntCompressor;
 
 impl PrefixVarIntCompressor {
     #[inline(always)]
@@ -496,6 +639,9 @@ impl<T: Into<u64> + Copy> Compressor<T> for PrefixVarIntCompressor {
         });
         Ok(ArrayTypeId::IntPrefixVar)
     }
+    fn encodings(&self) -> &'static [ArrayTypeId] {
+        &[ArrayTypeId::IntPrefixVar]
+    }
 }
 
 struct Simple16Compressor<T>(T);
@@ -513,7 +659,7 @@ impl<T: Simple16> Simple16Compressor<T> {
     }
 }
 
-impl<T: Simple16> Compressor<T> for Simple16Compressor<T> {
+impl<T: Simple16 + Sync> Compressor<T> for Simple16Compressor<T> {
     fn compress<O: EncodeOptions>(&self, data: &[T], stream: &mut EncoderStream<'_, O>) -> Result<ArrayTypeId, ()> {
         profile_method!(compress);
 
@@ -533,6 +679,10 @@ impl<T: Simple16> Compressor<T> for Simple16Compressor<T> {
 
         Ok(size)
     }
+
+    fn encodings(&self) -> &'static [ArrayTypeId] {
+        &[ArrayTypeId::IntSimple16]
+    }
 }
 
 struct BytesCompressor;
@@ -553,6 +703,9 @@ impl Compressor<u8> for BytesCompressor {
         let len_size = size_for_varint(data.len() as u64);
         Ok(data.len() + len_size)
     }
+    fn encodings(&self) -> &'static [ArrayTypeId] {
+        &[ArrayTypeId::U8]
+    }
 }
 
 // TODO: Bitpacking https://crates.io/crates/bitpacking
@@ -1,5 +1,6 @@
 pub mod array;
 pub mod array_fixed;
+pub mod blob;
 pub mod boolean;
 pub mod bytes;
 pub mod float;
@@ -7,7 +8,11 @@ pub mod hashmap;
 pub mod ignore;
 pub mod integer;
 pub mod nullable;
+pub mod numeric;
 pub mod string;
 pub mod tuple;
 
-pub use {array::*, array_fixed::*, boolean::*, bytes::*, float::*, hashmap::*, ignore::*, integer::*, nullable::*, string::*, tuple::*};
+pub use {array::*, array_fixed::*, blob::*, boolean::*, bytes::*, float::*, hashmap::*, ignore::*, integer::*, nullable::*, string::*, tuple::*};
+
+#[cfg(feature = "decode")]
+pub(crate) use numeric::FromNumber;
@@ -3,6 +3,9 @@ use crate::prelude::*;
 #[cfg(feature = "encode")]
 impl<T: Encodable> Encodable for Option<T> {
     type EncoderArray = NullableEncoder<T::EncoderArray>;
+    fn encode_schema() -> TypeSchema {
+        TypeSchema::Option(Box::new(T::encode_schema()))
+    }
     fn encode_root<O: EncodeOptions>(&self, stream: &mut EncoderStream<'_, O>) -> RootTypeId {
         if let Some(value) = self {
             T::encode_root(value, stream)
@@ -15,6 +18,9 @@ impl<T: Encodable> Encodable for Option<T> {
 #[cfg(feature = "decode")]
 impl<T: Decodable> Decodable for Option<T> {
     type DecoderArray = Option<NullableDecoder<T::DecoderArray>>;
+    fn decode_schema() -> TypeSchema {
+        TypeSchema::Option(Box::new(T::decode_schema()))
+    }
     fn decode(sticks: DynRootBranch<'_>, options: &impl DecodeOptions) -> DecodeResult<Self> {
         profile_method!(decode);
         match sticks {
@@ -22,6 +28,15 @@ impl<T: Decodable> Decodable for Option<T> {
             _ => Ok(Some(T::decode(sticks, options)?)),
         }
     }
+    fn decode_into(sticks: DynRootBranch<'_>, options: &impl DecodeOptions, into: &mut Self) -> DecodeResult<()> {
+        profile_method!(decode_into);
+        match (sticks, into) {
+            (DynRootBranch::Void, into) => *into = None,
+            (sticks, Some(value)) => T::decode_into(sticks, options, value)?,
+            (sticks, into) => *into = Some(T::decode(sticks, options)?),
+        }
+        Ok(())
+    }
 }
 
 #[cfg(feature = "encode")]
@@ -87,4 +102,17 @@ impl<T: DecoderArray> DecoderArray for Option<NullableDecoder<T>> {
             None
         })
     }
+    fn decode_next_into(&mut self, into: &mut Self::Decode) -> Result<(), Self::Error> {
+        if let Some(inner) = self {
+            if inner.opts.decode_next_infallible() {
+                match into {
+                    Some(value) => inner.values.decode_next_into(value)?,
+                    None => *into = Some(inner.values.decode_next()?),
+                }
+                return Ok(());
+            }
+        }
+        *into = None;
+        Ok(())
+    }
 }
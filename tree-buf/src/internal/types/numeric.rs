@@ -0,0 +1,157 @@
+//! Converts the numbers in a file to the number type being decoded, so that a field may change
+//! between integer and float types of any width. Conversions which keep the value exactly are
+//! always allowed. Eg: 300u64 as u16, 3.0f64 as u8, or 0.5f64 as f32. Conversions which change the
+//! value need the LossyNumbers option, and are otherwise a SchemaMismatch. With the option,
+//! integers are clamped to the range of the type, and floats round to the nearest value.
+//! Since integers are always written in the smallest type which holds them, whether a conversion
+//! is allowed depends on the values in the file rather than the type which wrote them.
+//!
+//! The signed integer types are Decodable but not Encodable, since arrays have no encoding for
+//! negative integers yet. They read the unsigned integers and floats in a file through these
+//! conversions, and the negative integers which are written at the root.
+
+#[cfg(feature = "decode")]
+use crate::prelude::*;
+
+#[cfg(feature = "decode")]
+pub(crate) trait FromNumber: Sized {
+    fn from_u64(value: u64, lossy: bool) -> DecodeResult<Self>;
+    fn from_i64(value: i64, lossy: bool) -> DecodeResult<Self>;
+    fn from_f64(value: f64, lossy: bool) -> DecodeResult<Self>;
+    fn from_f32(value: f32, lossy: bool) -> DecodeResult<Self>;
+}
+
+#[cfg(feature = "decode")]
+fn lossy<T>(value: T, lossy: bool) -> DecodeResult<T> {
+    if lossy {
+        Ok(value)
+    } else {
+        Err(DecodeError::SchemaMismatch)
+    }
+}
+
+macro_rules! impl_unsigned {
+    ($($T:ty),*) => {
+        $(
+            #[cfg(feature = "decode")]
+            impl FromNumber for $T {
+                #[inline]
+                fn from_u64(value: u64, is_lossy: bool) -> DecodeResult<Self> {
+                    match std::convert::TryFrom::try_from(value) {
+                        Ok(value) => Ok(value),
+                        Err(_) => lossy(<$T>::MAX, is_lossy),
+                    }
+                }
+                #[inline]
+                fn from_i64(value: i64, is_lossy: bool) -> DecodeResult<Self> {
+                    if value < 0 {
+                        lossy(0, is_lossy)
+                    } else {
+                        Self::from_u64(value as u64, is_lossy)
+                    }
+                }
+                #[inline]
+                fn from_f64(value: f64, is_lossy: bool) -> DecodeResult<Self> {
+                    // MAX + 1 is a power of 2, so it is exact as a float even when MAX is not.
+                    if value.fract() == 0.0 && value >= 0.0 && value < (<$T>::MAX as f64) + 1.0 {
+                        Ok(value as $T)
+                    } else {
+                        // Saturates, with NaN as 0
+                        lossy(value as $T, is_lossy)
+                    }
+                }
+                #[inline]
+                fn from_f32(value: f32, is_lossy: bool) -> DecodeResult<Self> {
+                    Self::from_f64(value as f64, is_lossy)
+                }
+            }
+        )*
+    };
+}
+
+impl_unsigned!(u8, u16, u32, u64);
+
+macro_rules! impl_signed {
+    ($($T:ty),*) => {
+        $(
+            #[cfg(feature = "decode")]
+            impl FromNumber for $T {
+                #[inline]
+                fn from_u64(value: u64, is_lossy: bool) -> DecodeResult<Self> {
+                    match std::convert::TryFrom::try_from(value) {
+                        Ok(value) => Ok(value),
+                        Err(_) => lossy(<$T>::MAX, is_lossy),
+                    }
+                }
+                #[inline]
+                fn from_i64(value: i64, is_lossy: bool) -> DecodeResult<Self> {
+                    match std::convert::TryFrom::try_from(value) {
+                        Ok(value) => Ok(value),
+                        Err(_) => lossy(if value < 0 { <$T>::MIN } else { <$T>::MAX }, is_lossy),
+                    }
+                }
+                #[inline]
+                fn from_f64(value: f64, is_lossy: bool) -> DecodeResult<Self> {
+                    // MIN and MAX + 1 are powers of 2, so they are exact as floats even when MAX is not.
+                    if value.fract() == 0.0 && value >= (<$T>::MIN as f64) && value < (<$T>::MAX as f64) + 1.0 {
+                        Ok(value as $T)
+                    } else {
+                        // Saturates, with NaN as 0
+                        lossy(value as $T, is_lossy)
+                    }
+                }
+                #[inline]
+                fn from_f32(value: f32, is_lossy: bool) -> DecodeResult<Self> {
+                    Self::from_f64(value as f64, is_lossy)
+                }
+            }
+        )*
+    };
+}
+
+impl_signed!(i8, i16, i32, i64);
+
+macro_rules! impl_float {
+    ($($T:ty),*) => {
+        $(
+            #[cfg(feature = "decode")]
+            impl FromNumber for $T {
+                #[inline]
+                fn from_u64(value: u64, is_lossy: bool) -> DecodeResult<Self> {
+                    let float = value as $T;
+                    // The float may round up to 2^64, which saturates back to u64::MAX
+                    if (float as f64) < 18446744073709551616.0 && float as u64 == value {
+                        Ok(float)
+                    } else {
+                        lossy(float, is_lossy)
+                    }
+                }
+                #[inline]
+                fn from_i64(value: i64, is_lossy: bool) -> DecodeResult<Self> {
+                    let float = value as $T;
+                    if (float as f64) < 9223372036854775808.0 && float as i64 == value {
+                        Ok(float)
+                    } else {
+                        lossy(float, is_lossy)
+                    }
+                }
+                #[inline]
+                fn from_f64(value: f64, is_lossy: bool) -> DecodeResult<Self> {
+                    let float = value as $T;
+                    if float as f64 == value || value.is_nan() {
+                        Ok(float)
+                    } else {
+                        lossy(float, is_lossy)
+                    }
+                }
+                #[inline]
+                fn from_f32(value: f32, _is_lossy: bool) -> DecodeResult<Self> {
+                    // Every f32 is exactly an f64
+                    Ok(value as $T)
+                }
+            }
+        )*
+    };
+}
+
+impl_float!(f32, f64);
@@ -1,11 +1,11 @@
+use crate::internal::encodings::fsst;
 use crate::internal::encodings::varint::*;
 use crate::prelude::*;
 use brotli::enc::BrotliEncoderParams;
-use brotli::{BrotliCompress, BrotliDecompress};
+use brotli::BrotliCompress;
 use std::borrow::Borrow;
-use std::convert::TryInto;
-use std::io::Cursor;
-use std::ops::Deref;
+use std::convert::{TryFrom, TryInto};
+use std::io::{Cursor, Read};
 use std::vec::IntoIter;
 
 // TODO: Consider compressed unicode (SCSU?) for String in general,
@@ -34,6 +34,9 @@ pub fn decode_str<'a>(bytes: &'a [u8], offset: &'_ mut usize) -> DecodeResult<&'
 #[cfg(feature = "encode")]
 impl Encodable for String {
     type EncoderArray = Vec<&'static String>;
+    fn encode_schema() -> TypeSchema {
+        TypeSchema::String
+    }
     fn encode_root<O: EncodeOptions>(&self, stream: &mut EncoderStream<'_, O>) -> RootTypeId {
         let value = self.as_str();
         match value.len() {
@@ -72,14 +75,28 @@ impl EncoderArray<String> for Vec<&'static String> {
         // but when things like threading come into play it's hard to know.
         //
         // TODO: Use extend_lifetime crate
+        take_vec_if_unallocated(self);
         self.push(unsafe { std::mem::transmute(value) });
     }
 
     fn flush<O: EncodeOptions>(self, stream: &mut EncoderStream<'_, O>) -> ArrayTypeId {
         profile_method!(flush);
 
-        let compressors = (Utf8Compressor, RLE::new((Utf8Compressor,)), Dictionary::new((Utf8Compressor,)), BrotliCompressor);
-        compress(&self[..], stream, &compressors)
+        let compressors = (
+            Utf8Compressor,
+            RLE::new((Utf8Compressor,)),
+            Dictionary::new((Utf8Compressor,)),
+            BrotliCompressor,
+            FrontCoding::new((Utf8Compressor, FsstCompressor)),
+            FsstCompressor,
+            HexCompressor,
+            DecimalCompressor,
+        );
+        let options = stream.options;
+        let codecs = options.codecs().map(Codecs::strings).unwrap_or_default();
+        let id = compress(&self[..], stream, &with_codecs(compressors, codecs));
+        put_vec(self);
+        id
     }
 }
 
@@ -87,10 +104,28 @@ impl EncoderArray<String> for Vec<&'static String> {
 impl Decodable for String {
     // TODO: Use lifetimes to make this decode lazy rather than IntoIter
     type DecoderArray = IntoIter<String>;
-    fn decode(sticks: DynRootBranch<'_>, _options: &impl DecodeOptions) -> DecodeResult<Self> {
+    fn decode_schema() -> TypeSchema {
+        TypeSchema::String
+    }
+    fn decode(sticks: DynRootBranch<'_>, options: &impl DecodeOptions) -> DecodeResult<Self> {
         profile_method!(decode);
         match sticks {
-            DynRootBranch::String(s) => Ok(s.to_owned()),
+            DynRootBranch::String(s) => {
+                take_elements(options, s.len())?;
+                Ok(s.to_owned())
+            }
+            _ => Err(DecodeError::SchemaMismatch),
+        }
+    }
+    fn decode_into(sticks: DynRootBranch<'_>, options: &impl DecodeOptions, into: &mut Self) -> DecodeResult<()> {
+        profile_method!(decode_into);
+        match sticks {
+            DynRootBranch::String(s) => {
+                take_elements(options, s.len())?;
+                into.clear();
+                into.push_str(s);
+                Ok(())
+            }
             _ => Err(DecodeError::SchemaMismatch),
         }
     }
@@ -105,15 +140,13 @@ impl InfallibleDecoderArray for IntoIter<String> {
 
         // TODO: Consider when compressing a bloom filter to decide whether to use a dictionary
 
-        match sticks {
+        let all: DecodeResult<Vec<String>> = match sticks {
             DynArrayBranch::BrotliUtf8 { utf8, lens } => {
                 profile_section!(brotli_utf8);
 
                 let (long_str, lens) = parallel(
                     || {
-                        let mut out = Vec::new();
-                        let mut cursor = Cursor::new(utf8.deref());
-                        BrotliDecompress(&mut cursor, &mut out).map_err(|_| DecodeError::InvalidFormat)?;
+                        let out = decompress_brotli(&utf8, options)?;
                         let s = String::from_utf8(out).map_err(|_| DecodeError::InvalidFormat)?;
                         Result::<_, DecodeError>::Ok(s)
                     },
@@ -126,51 +159,134 @@ impl InfallibleDecoderArray for IntoIter<String> {
 
                 // TODO: Support Null for lens to indicate there is exactly 1 item.
                 let long_str = long_str?;
-                let long_str = long_str.as_str();
-
-                // See also c2c4fad7-c231-4fb2-8cf1-50ca1bce7fc6
-                // The last length is implied so we don't have to write it.
-                // Therefore an array with 1 item actually has 2 strings
-                // TODO: NOPE! Nixed the above idea because the length of lens
-                // was not implied, causing us to sometimes take the wrong strings.
-                // Can go back and fix this.
-                let mut all = Vec::with_capacity(lens.len());
-                let mut start: usize = 0;
-                for len in lens {
-                    let len = len.try_into().map_err(|_| DecodeError::InvalidFormat)?;
-                    let end = start.checked_add(len).ok_or(DecodeError::InvalidFormat)?;
-                    let s = long_str.get(start..end).ok_or(DecodeError::InvalidFormat)?;
-                    all.push(s.to_owned());
-                    start = end;
-                }
-                //all.push(long_str[start..].to_owned());
-
-                Ok(all.into_iter())
+                split_by_lens(long_str.as_str(), lens)
+            }
+            DynArrayBranch::FsstUtf8 { fsst, lens } => {
+                profile_section!(fsst_utf8);
+
+                let (long_str, lens) = parallel(
+                    || {
+                        let out = fsst::decompress(&fsst)?;
+                        String::from_utf8(out).map_err(|_| DecodeError::InvalidFormat)
+                    },
+                    || <u64 as Decodable>::DecoderArray::new(*lens, options),
+                    options,
+                );
+                let lens = lens?;
+                let long_str = long_str?;
+                split_by_lens(long_str.as_str(), lens)
+            }
+            DynArrayBranch::HexUtf8 { hex, lens } => {
+                profile_section!(hex_utf8);
+
+                let lens = <u64 as Decodable>::DecoderArray::new(*lens, options)?;
+                decode_hex(&hex, lens)
+            }
+            DynArrayBranch::DecimalUtf8 { mantissas, exponents } => {
+                profile_section!(decimal_utf8);
+
+                let (mantissas, exponents) = parallel(
+                    || <u64 as Decodable>::DecoderArray::new(*mantissas, options),
+                    || <u64 as Decodable>::DecoderArray::new(*exponents, options),
+                    options,
+                );
+                decode_decimal(mantissas?, exponents?, options)
+            }
+            DynArrayBranch::PrefixUtf8 { prefixes, suffixes } => {
+                profile_section!(prefix_utf8);
+
+                let (prefixes, suffixes) = parallel(
+                    || <u64 as Decodable>::DecoderArray::new(*prefixes, options),
+                    || Self::new_infallible(*suffixes, options),
+                    options,
+                );
+                decode_front_coding(prefixes?, suffixes?, options)
             }
             DynArrayBranch::String(bytes) => {
                 profile_section!(str_utf8);
 
-                let strs = decode_all(&bytes, |b, o| decode_str(b, o).and_then(|v| Ok(v.to_owned())))?;
-                Ok(strs.into_iter())
+                decode_all(&bytes, |b, o| decode_str(b, o).and_then(|v| Ok(v.to_owned())))
             }
             DynArrayBranch::RLE { runs, values } => {
                 let rle = RleIterator::new(runs, values, options, |values| Self::new_infallible(values, options))?;
-                let all = rle.collect::<Vec<_>>();
-                Ok(all.into_iter())
+                collect_strings(rle, options)
             }
             DynArrayBranch::Dictionary { indices, values } => {
                 let dict = DictionaryIterator::new(indices, values, options, |values| Self::new_infallible(values, options))?;
-                let all = dict.collect::<Vec<_>>();
-                Ok(all.into_iter())
+                collect_strings(dict, options)
             }
+            DynArrayBranch::Extension { id, bytes } => find_codec(options.codecs(), Codecs::strings, id)?.decode(&bytes),
             _ => Err(DecodeError::SchemaMismatch),
+        };
+        let all = all?;
+
+        let mut total: usize = 0;
+        for s in all.iter() {
+            check_string_len(options, s.len())?;
+            total = total.checked_add(s.len()).ok_or(DecodeError::LimitExceeded)?;
         }
+        take_elements(options, total.saturating_add(all.len()))?;
+
+        Ok(all.into_iter())
     }
     fn decode_next_infallible(&mut self) -> Self::Decode {
         self.next().unwrap_or_default()
     }
 }
 
+/// Collects the strings from encodings which repeat values, like RLE,
+/// stopping early if the copies would be too large.
+#[cfg(feature = "decode")]
+fn collect_strings(strings: impl Iterator<Item = String>, options: &impl DecodeOptions) -> DecodeResult<Vec<String>> {
+    let mut all = Vec::new();
+    let mut total = 0;
+    for s in strings {
+        check_string_total(options, &mut total, s.len())?;
+        all.push(s);
+    }
+    Ok(all)
+}
+
+/// Decompresses Brotli, failing rather than producing more bytes than max_elements allows.
+#[cfg(feature = "decode")]
+pub(crate) fn decompress_brotli(bytes: &[u8], options: &impl DecodeOptions) -> DecodeResult<Vec<u8>> {
+    let limit = remaining_elements(options);
+    let mut out = Vec::new();
+    brotli::Decompressor::new(bytes, 4096)
+        .take(u64::try_from(limit).unwrap_or(u64::MAX).saturating_add(1))
+        .read_to_end(&mut out)
+        .map_err(|_| DecodeError::InvalidFormat)?;
+    if out.len() > limit {
+        return Err(DecodeError::LimitExceeded);
+    }
+    Ok(out)
+}
+
+/// Splits the concatenation of many strings back into the originals.
+#[cfg(feature = "decode")]
+fn split_by_lens(long_str: &str, lens: IntoIter<u64>) -> DecodeResult<Vec<String>> {
+    // See also c2c4fad7-c231-4fb2-8cf1-50ca1bce7fc6
+    // The last length is implied so we don't have to write it.
+    // Therefore an array with 1 item actually has 2 strings
+    // TODO: NOPE! Nixed the above idea because the length of lens
+    // was not implied, causing us to sometimes take the wrong strings.
+    // Can go back and fix this.
+    let mut all = Vec::with_capacity(lens.len());
+    let mut start: usize = 0;
+    for len in lens {
+        let len = len.try_into().map_err(|_| DecodeError::InvalidFormat)?;
+        let end = start.checked_add(len).ok_or(DecodeError::InvalidFormat)?;
+        let s = long_str.get(start..end).ok_or(DecodeError::InvalidFormat)?;
+        all.push(s.to_owned());
+        start = end;
+    }
+    //all.push(long_str[start..].to_owned());
+    if start != long_str.len() {
+        return Err(DecodeError::InvalidFormat);
+    }
+    Ok(all)
+}
+
 #[cfg(feature = "encode")]
 pub(crate) struct BrotliCompressor;
 
@@ -183,7 +299,19 @@ impl<T: Borrow<String>> Compressor<T> for BrotliCompressor {
         // This estimate is totally wrong and it may be nice to have a fast_size_for for Brotli if
         // it's not crazy difficult.
         // See also 9003b01b-83e8-4acc-9f38-d584a37e20c6
-        Ok(data.len().max(10) * 10)
+        let estimate = data.len().max(10) * 10;
+        // Brotli rarely gets below 4 bits per character for strings of hex digits, so without
+        // this long hex strings like 40 digit addresses would always go to Brotli.
+        let mut hex_len = 0;
+        for s in data.iter() {
+            let s = s.borrow();
+            let digits = s.strip_prefix("0x").unwrap_or(s);
+            if !digits.bytes().all(|b| b.is_ascii_hexdigit()) {
+                return Ok(estimate);
+            }
+            hex_len += s.len();
+        }
+        Ok(estimate.max(hex_len / 2))
     }
     fn compress<O: EncodeOptions>(&self, data: &[T], stream: &mut EncoderStream<'_, O>) -> Result<ArrayTypeId, ()> {
         profile_method!(compress);
@@ -240,6 +368,10 @@ impl<T: Borrow<String>> Compressor<T> for BrotliCompressor {
 
         Ok(ArrayTypeId::BrotliUtf8)
     }
+
+    fn encodings(&self) -> &'static [ArrayTypeId] {
+        &[ArrayTypeId::BrotliUtf8]
+    }
 }
 
 #[cfg(feature = "encode")]
@@ -269,4 +401,92 @@ impl<T: Borrow<String>> Compressor<T> for Utf8Compressor {
 
         Ok(ArrayTypeId::Utf8)
     }
+
+    fn encodings(&self) -> &'static [ArrayTypeId] {
+        &[ArrayTypeId::Utf8]
+    }
+}
+
+#[cfg(feature = "encode")]
+pub(crate) struct FsstCompressor;
+
+#[cfg(feature = "encode")]
+impl<T: Borrow<String>> Compressor<T> for FsstCompressor {
+    fn fast_size_for<O: EncodeOptions>(&self, data: &[T], options: &O) -> Result<usize, ()> {
+        profile_method!(fast_size_for);
+
+        // See also c2c4fad7-c231-4fb2-8cf1-50ca1bce7fc6
+        if data.is_empty() {
+            return Err(());
+        }
+
+        let table = fsst::SymbolTable::build(data.iter().map(|s| s.borrow().as_bytes()));
+        let mut size = table.size_for_table();
+        let mut lens = take_vec_with_capacity(data.len());
+        for s in data {
+            let s = s.borrow();
+            size += table.size_for(s.as_bytes());
+            lens.push(s.len() as u64);
+        }
+        let from_lens = Vec::<u64>::fast_size_for_all(&lens[..], options);
+        put_vec(lens);
+        let from_ids = 1;
+
+        Ok(size + size_for_varint(size as u64) + from_lens + from_ids)
+    }
+    fn compress<O: EncodeOptions>(&self, data: &[T], stream: &mut EncoderStream<'_, O>) -> Result<ArrayTypeId, ()> {
+        profile_method!(compress);
+
+        // See also c2c4fad7-c231-4fb2-8cf1-50ca1bce7fc6
+        if data.is_empty() {
+            return Err(());
+        }
+
+        let table = fsst::SymbolTable::build(data.iter().map(|s| s.borrow().as_bytes()));
+        let mut lens = take_vec_with_capacity(data.len());
+
+        stream.encode_with_len(|stream| {
+            table.encode_table(stream.bytes);
+            for s in data {
+                let s = s.borrow();
+                table.encode(s.as_bytes(), stream.bytes);
+                lens.push(s.len() as u64);
+            }
+        });
+
+        stream.encode_with_id(|stream| lens.flush(stream));
+
+        Ok(ArrayTypeId::FsstUtf8)
+    }
+
+    fn encodings(&self) -> &'static [ArrayTypeId] {
+        &[ArrayTypeId::FsstUtf8]
+    }
+}
+
+#[cfg(all(test, feature = "decode"))]
+mod tests {
+    use super::*;
+
+    fn split(long_str: &str, lens: Vec<u64>) -> DecodeResult<Vec<String>> {
+        split_by_lens(long_str, lens.into_iter())
+    }
+
+    #[test]
+    fn splits_exactly() {
+        assert_eq!(split("abc", vec![1, 2]), Ok(vec![String::from("a"), String::from("bc")]));
+        // Zero lens take no bytes, even after the last string
+        assert_eq!(split("ab", vec![2, 0]), Ok(vec![String::from("ab"), String::new()]));
+    }
+
+    #[test]
+    fn rejects_lens_past_the_end() {
+        assert_eq!(split("ab", vec![2, 1]), Err(DecodeError::InvalidFormat));
+        assert_eq!(split("ab", vec![2, 0, 1]), Err(DecodeError::InvalidFormat));
+    }
+
+    #[test]
+    fn rejects_leftover_bytes() {
+        assert_eq!(split("abcd", vec![1, 2]), Err(DecodeError::InvalidFormat));
+    }
 }
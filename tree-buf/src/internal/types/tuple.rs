@@ -62,15 +62,53 @@ macro_rules! parallel_decode {
     };
 }
 
+// Tuples of compressors are the sets to choose from, and a type may need more of them than there are Encodable tuples.
+macro_rules! impl_compressor_set {
+    ($count:expr, $($ts:ident, $ti:tt,)+) => {
+        impl<T, $($ts: Compressor<T>,)+> CompressorSet<T> for ($($ts,)+) {
+            fn len(&self) -> usize {
+                $count
+            }
+            fn name(&self, compressor: usize) -> String {
+                match compressor {
+                    $($ti => tuple_index!(self, $ti).name().to_owned(),)+
+                    _ => unreachable!("No compressor at that index"),
+                }
+            }
+            fn encodings(&self, compressor: usize) -> &'static [ArrayTypeId] {
+                match compressor {
+                    $($ti => tuple_index!(self, $ti).encodings(),)+
+                    _ => unreachable!("No compressor at that index"),
+                }
+            }
+            fn fast_size_for<O: EncodeOptions>(&self, compressor: usize, data: &[T], options: &O) -> Result<usize, ()> {
+                match compressor {
+                    $($ti => tuple_index!(self, $ti).fast_size_for(data, options),)+
+                    _ => unreachable!("No compressor at that index"),
+                }
+            }
+            fn compress<O: EncodeOptions>(&self, compressor: usize, data: &[T], stream: &mut EncoderStream<'_, O>) -> Result<ArrayTypeId, ()> {
+                match compressor {
+                    $($ti => tuple_index!(self, $ti).compress(data, stream),)+
+                    _ => unreachable!("No compressor at that index"),
+                }
+            }
+        }
+    };
+}
+
 macro_rules! impl_tuple {
     ($count:expr, $trid:expr, $taid:expr, $($ts:ident, $ti:tt,)+) => {
         #[cfg(feature = "encode")]
         impl <$($ts: Encodable),+> Encodable for ($($ts),+) {
             type EncoderArray=($($ts::EncoderArray),+);
+            fn encode_schema() -> TypeSchema {
+                TypeSchema::Tuple(vec![$(<$ts as Encodable>::encode_schema()),+])
+            }
             fn encode_root<O: EncodeOptions>(&self, stream: &mut EncoderStream<'_, O>) -> RootTypeId {
                 profile_method!(encode_root);
                 $(
-                    stream.encode_with_id(|stream| tuple_index!(self, $ti).encode_root(stream));
+                    stream.encode_field(stringify!($ti), |stream| stream.encode_with_id(|stream| tuple_index!(self, $ti).encode_root(stream)));
                 )+
                 $trid
             }
@@ -87,9 +125,9 @@ macro_rules! impl_tuple {
                 profile_method!(flush);
                 let ($($ts,)+) = self;
                 $(
-                    stream.encode_with_id(|stream|
+                    stream.encode_field(stringify!($ti), |stream| stream.encode_with_id(|stream|
                         $ts.flush(stream)
-                    );
+                    ));
                 )+
                 $taid
             }
@@ -100,6 +138,9 @@ macro_rules! impl_tuple {
         // Overly verbose because of `?` requiring `From` See also ec4fa3ba-def5-44eb-9065-e80b59530af6
         where $(DecodeError : From<<$ts::DecoderArray as DecoderArray>::Error>),+ {
             type DecoderArray=($($ts::DecoderArray),+);
+            fn decode_schema() -> TypeSchema {
+                TypeSchema::Tuple(vec![$(<$ts as Decodable>::decode_schema()),+])
+            }
             fn decode(sticks: DynRootBranch<'_>, options: &impl DecodeOptions) -> DecodeResult<Self> {
                 profile_method!(decode);
                 match sticks {
@@ -123,6 +164,25 @@ macro_rules! impl_tuple {
                     _ => Err(DecodeError::SchemaMismatch),
                 }
             }
+            fn decode_into(sticks: DynRootBranch<'_>, options: &impl DecodeOptions, into: &mut Self) -> DecodeResult<()> {
+                profile_method!(decode_into);
+                match sticks {
+                    DynRootBranch::Tuple { mut fields } => {
+                        // See also abb368f2-6c99-4c44-8f9f-4b00868adaaf
+                        if fields.len() != $count {
+                            return Err(DecodeError::SchemaMismatch)
+                        }
+                        let mut fields = fields.drain(..);
+
+                        $(
+                            // This unwrap is ok because we verified the len already. See also abb368f2-6c99-4c44-8f9f-4b00868adaaf
+                            $ts::decode_into(fields.next().unwrap(), options, &mut tuple_index!(into, $ti))?;
+                        )+
+                        Ok(())
+                    },
+                    _ => Err(DecodeError::SchemaMismatch),
+                }
+            }
         }
 
         #[cfg(feature = "decode")]
@@ -163,25 +223,15 @@ macro_rules! impl_tuple {
                     tuple_index!(self, $ti).decode_next()?,
                 )+))
             }
-        }
-
-        impl<T, $($ts: Compressor<T>,)+> CompressorSet<T> for ($($ts,)+) {
-            fn len(&self) -> usize {
-                $count
-            }
-            fn fast_size_for<O: EncodeOptions>(&self, compressor: usize, data: &[T], options: &O) -> Result<usize, ()> {
-                match compressor {
-                    $($ti => tuple_index!(self, $ti).fast_size_for(data, options),)+
-                    _ => unreachable!("No compressor at that index"),
-                }
-            }
-            fn compress<O: EncodeOptions>(&self, compressor: usize, data: &[T], stream: &mut EncoderStream<'_, O>) -> Result<ArrayTypeId, ()> {
-                match compressor {
-                    $($ti => tuple_index!(self, $ti).compress(data, stream),)+
-                    _ => unreachable!("No compressor at that index"),
-                }
+            fn decode_next_into(&mut self, into: &mut Self::Decode) -> Result<(), Self::Error> {
+                $(
+                    tuple_index!(self, $ti).decode_next_into(&mut tuple_index!(into, $ti))?;
+                )+
+                Ok(())
             }
         }
+
+        impl_compressor_set!($count, $($ts, $ti,)+);
     };
 }
 
@@ -189,6 +239,18 @@ impl<T, T0: Compressor<T>> CompressorSet<T> for (T0,) {
     fn len(&self) -> usize {
         1
     }
+    fn name(&self, compressor: usize) -> String {
+        match compressor {
+            0 => self.0.name().to_owned(),
+            _ => unreachable!("No compressor at that index"),
+        }
+    }
+    fn encodings(&self, compressor: usize) -> &'static [ArrayTypeId] {
+        match compressor {
+            0 => self.0.encodings(),
+            _ => unreachable!("No compressor at that index"),
+        }
+    }
     fn fast_size_for<O: EncodeOptions>(&self, compressor: usize, data: &[T], options: &O) -> Result<usize, ()> {
         match compressor {
             0 => self.0.fast_size_for(data, options),
@@ -214,5 +276,7 @@ impl_tuple!(3, RootTypeId::Tuple3, ArrayTypeId::Tuple3, T0, 0, T1, 1, T2, 2,);
 impl_tuple!(4, RootTypeId::Tuple4, ArrayTypeId::Tuple4, T0, 0, T1, 1, T2, 2, T3, 3,);
 impl_tuple!(5, RootTypeId::Tuple5, ArrayTypeId::Tuple5, T0, 0, T1, 1, T2, 2, T3, 3, T4, 4,);
 impl_tuple!(6, RootTypeId::Tuple6, ArrayTypeId::Tuple6, T0, 0, T1, 1, T2, 2, T3, 3, T4, 4, T5, 5,);
+impl_compressor_set!(7, T0, 0, T1, 1, T2, 2, T3, 3, T4, 4, T5, 5, T6, 6,);
+impl_compressor_set!(8, T0, 0, T1, 1, T2, 2, T3, 3, T4, 4, T5, 5, T6, 6, T7, 7,);
 
 // TODO: Support tuple structs in the macro
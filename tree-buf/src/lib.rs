@@ -18,7 +18,7 @@ pub mod prelude {
     // This section makes everything interesting available to the rest of the crate
     // without bothering to manage imports.
     pub(crate) use crate::internal::encodings::varint::size_for_varint;
-    pub(crate) use crate::{internal::error::*, internal::*};
+    pub(crate) use crate::{experimental::compat::TypeSchema, internal::error::*, internal::*};
 
     #[cfg(feature = "decode")]
     pub(crate) type DecodeResult<T> = Result<T, DecodeError>;
@@ -39,18 +39,31 @@ pub use crate::prelude::*;
 
 pub use internal::Ignore;
 
+pub use internal::Blob;
+
 // TODO: Take Borrow or AsRef
 pub fn encode<T: Encodable>(value: &T) -> Vec<u8> {
     let options = EncodeOptionsDefault;
     crate::experimental::options::encode_with_options(value, &options)
 }
 
+/// Decodes with no limits other than MAX_DEPTH. For files from untrusted sources,
+/// use decode_with_options with UntrustedInput.
 #[cfg(feature = "decode")]
 pub fn decode<T: Decodable>(bytes: &[u8]) -> DecodeResult<T> {
     let options = DecodeOptionsDefault;
     crate::experimental::options::decode_with_options(bytes, &options)
 }
 
+/// Decodes into an existing value, re-using the allocations it holds where possible.
+/// This is useful when decoding files of the same shape over and over again.
+/// If an error is returned, the value may be partially overwritten.
+#[cfg(feature = "decode")]
+pub fn decode_into<T: Decodable>(bytes: &[u8], into: &mut T) -> DecodeResult<()> {
+    let options = DecodeOptionsDefault;
+    crate::experimental::options::decode_into_with_options(bytes, &options, into)
+}
+
 // TODO: Figure out recursion, at least enough to handle this: https://docs.rs/serde_json/1.0.44/serde_json/value/enum.Value.html
 // TODO: Nullable should be able to handle recursion as well, even if Option doesn't. (Option<Box<T>> could though)
 
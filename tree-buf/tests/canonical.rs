@@ -0,0 +1,81 @@
+use std::collections::HashMap;
+use tree_buf::experimental::options;
+use tree_buf::prelude::*;
+use tree_buf::{encode_options, Encodable};
+
+#[derive(Encode, Decode, Debug, PartialEq, Clone)]
+struct Document {
+    name: String,
+    tags: HashMap<String, u32>,
+    nested: Vec<HashMap<u64, Vec<String>>>,
+    weights: Vec<f64>,
+}
+
+/// Builds equal maps, inserting in either order. Each map also gets its own random hasher.
+fn make_map<K: std::hash::Hash + Eq, V>(entries: impl DoubleEndedIterator<Item = (K, V)>, reverse: bool) -> HashMap<K, V> {
+    if reverse {
+        entries.rev().collect()
+    } else {
+        entries.collect()
+    }
+}
+
+fn make_document(reverse: bool) -> Document {
+    Document {
+        name: "doc".to_owned(),
+        tags: make_map((0..40u32).map(|i| (format!("tag-{}", i), i * 7)), reverse),
+        nested: (0..5u64)
+            .map(|n| make_map((0..n * 10).map(|i| (i * 1000 + n, vec![format!("{}", i); (i % 3) as usize])), !reverse))
+            .collect(),
+        weights: vec![0.5, 1.5, 2.5],
+    }
+}
+
+fn canonical<T: Encodable>(value: &T) -> Vec<u8> {
+    options::encode_with_options(value, &encode_options! { options::Canonical })
+}
+
+#[test]
+fn equal_maps_encode_the_same() {
+    let first = make_document(false);
+    let second = make_document(true);
+    assert_eq!(first, second);
+
+    let bytes = canonical(&first);
+    assert_eq!(bytes, canonical(&second));
+    assert_eq!(canonical(&vec![first.clone(), second.clone()]), canonical(&vec![second.clone(), first.clone()]));
+    assert_eq!(decode::<Document>(&bytes), Ok(first));
+}
+
+#[test]
+fn canonical_encoding_is_the_same_in_parallel() {
+    let data: Vec<_> = (0..10).map(|i| make_document(i % 2 == 0)).collect();
+    let sequential = canonical(&data);
+    let parallel = options::encode_with_options(&data, &encode_options! { options::Canonical, options::EnableParallel });
+    assert_eq!(parallel, sequential);
+    assert_eq!(decode::<Vec<Document>>(&parallel), Ok(data));
+}
+
+#[test]
+fn floats_which_are_equal_encode_the_same() {
+    let other_nan = f64::from_bits(f64::NAN.to_bits() ^ 1);
+    assert!(other_nan.is_nan());
+
+    assert_eq!(canonical(&-0.0f64), canonical(&0.0f64));
+    assert_eq!(canonical(&-0.0f32), canonical(&0.0f32));
+    assert_eq!(canonical(&other_nan), canonical(&f64::NAN));
+    assert_eq!(canonical(&vec![1.0, -0.0, other_nan, 4.0]), canonical(&vec![1.0, 0.0, f64::NAN, 4.0]));
+    assert_eq!(canonical(&vec![-0.0f32, f32::NAN, -f32::NAN]), canonical(&vec![0.0f32, f32::NAN, f32::NAN]));
+
+    // Without the option, floats still round-trip bit-for-bit.
+    let values = vec![1.0, -0.0, other_nan, 4.0];
+    let decoded = decode::<Vec<f64>>(&encode(&values)).unwrap();
+    assert_eq!(
+        decoded.iter().map(|f| f.to_bits()).collect::<Vec<_>>(),
+        values.iter().map(|f| f.to_bits()).collect::<Vec<_>>()
+    );
+
+    let decoded = decode::<Vec<f64>>(&canonical(&values)).unwrap();
+    assert_eq!(decoded[1].to_bits(), 0.0f64.to_bits());
+    assert_eq!(decoded[2].to_bits(), f64::NAN.to_bits());
+}
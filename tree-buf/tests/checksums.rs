@@ -0,0 +1,68 @@
+use tree_buf::experimental::options;
+use tree_buf::prelude::*;
+use tree_buf::{encode_options, DecodeError, Encodable};
+
+#[derive(Encode, Decode, Debug, PartialEq, Clone)]
+struct Reading {
+    sensor: String,
+    values: Vec<u64>,
+}
+
+fn make_data() -> Vec<Reading> {
+    (0..20)
+        .map(|i| Reading {
+            sensor: format!("sensor-{}", i % 3),
+            values: (0..i).map(|v| v * 1000).collect(),
+        })
+        .collect()
+}
+
+fn encode_with_checksums<T: Encodable>(value: &T) -> Vec<u8> {
+    let options = encode_options! { options::Checksums };
+    options::encode_with_options(value, &options)
+}
+
+#[test]
+fn round_trips() {
+    let data = make_data();
+    let bytes = encode_with_checksums(&data);
+    assert_eq!(decode::<Vec<Reading>>(&bytes), Ok(data.clone()));
+
+    // A header byte, a whole-file checksum, and a checksum for each branch.
+    assert_eq!(encode(&data).len(), 439);
+    assert_eq!(bytes.len(), 464);
+
+    // Root values have no branches, but still get a whole-file checksum.
+    let bytes = encode_with_checksums(&1u32);
+    assert_eq!(bytes.len(), 6);
+    assert_eq!(decode::<u32>(&bytes), Ok(1));
+}
+
+#[test]
+fn detects_corruption() {
+    let bytes = encode_with_checksums(&make_data());
+
+    // Skip the header, since without it the checksums are not known to exist.
+    for i in 1..bytes.len() {
+        for bit in 0..8 {
+            let mut corrupt = bytes.clone();
+            corrupt[i] ^= 1 << bit;
+            assert_eq!(decode::<Vec<Reading>>(&corrupt), Err(DecodeError::ChecksumMismatch));
+        }
+    }
+}
+
+#[test]
+fn detects_corruption_in_branch() {
+    let data: Vec<u64> = (0..100).map(|i| i * 1000).collect();
+    let mut bytes = encode_with_checksums(&data);
+
+    // Corrupt the data, then patch up the whole-file checksum
+    // so that only the branch checksum can catch the problem.
+    bytes[4] ^= 1;
+    let end = bytes.len() - 4;
+    let checksum = crc32c::crc32c(&bytes[..end]);
+    bytes[end..].copy_from_slice(&checksum.to_le_bytes());
+
+    assert_eq!(decode::<Vec<u64>>(&bytes), Err(DecodeError::ChecksumMismatch));
+}
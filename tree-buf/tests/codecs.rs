@@ -0,0 +1,201 @@
+use std::convert::TryInto;
+use tree_buf::experimental::codecs::*;
+use tree_buf::experimental::options::{self, ArrayTypeId, EncodingRules};
+use tree_buf::experimental::stats::size_breakdown;
+use tree_buf::internal::encodings::varint::{decode_prefix_varint, encode_prefix_varint};
+use tree_buf::prelude::*;
+use tree_buf::{decode_options, encode_options, Blob, DecodeError};
+
+/// Integers in an arithmetic sequence, written as the start, step, and count.
+struct SequenceCodec;
+
+impl IntegerCodec for SequenceCodec {
+    fn id(&self) -> u64 {
+        1
+    }
+    fn encode(&self, values: &[u64], bytes: &mut Vec<u8>) -> Result<(), Unsupported> {
+        let start = *values.first().ok_or(Unsupported)?;
+        let step = values.get(1).map(|second| second.wrapping_sub(start)).unwrap_or(0);
+        if values.iter().enumerate().any(|(i, &value)| value != start.wrapping_add(step.wrapping_mul(i as u64))) {
+            return Err(Unsupported);
+        }
+        encode_prefix_varint(start, bytes);
+        encode_prefix_varint(step, bytes);
+        encode_prefix_varint(values.len() as u64, bytes);
+        Ok(())
+    }
+    fn decode(&self, bytes: &[u8]) -> Result<Vec<u64>, DecodeError> {
+        let mut offset = 0;
+        let start = decode_prefix_varint(bytes, &mut offset)?;
+        let step = decode_prefix_varint(bytes, &mut offset)?;
+        let count = decode_prefix_varint(bytes, &mut offset)?;
+        if count > 1 << 20 {
+            return Err(DecodeError::InvalidFormat);
+        }
+        Ok((0..count).map(|i| start.wrapping_add(step.wrapping_mul(i))).collect())
+    }
+}
+
+/// Floats which are all the same value.
+struct ConstantFloatCodec;
+
+impl FloatCodec for ConstantFloatCodec {
+    fn id(&self) -> u64 {
+        2
+    }
+    fn encode(&self, values: &[f64], bytes: &mut Vec<u8>) -> Result<(), Unsupported> {
+        let first = *values.first().ok_or(Unsupported)?;
+        if values.iter().any(|&value| value.to_bits() != first.to_bits()) {
+            return Err(Unsupported);
+        }
+        bytes.extend_from_slice(&first.to_le_bytes());
+        encode_prefix_varint(values.len() as u64, bytes);
+        Ok(())
+    }
+    fn decode(&self, bytes: &[u8]) -> Result<Vec<f64>, DecodeError> {
+        let value = f64::from_le_bytes(bytes.get(..8).ok_or(DecodeError::InvalidFormat)?.try_into().unwrap());
+        let mut offset = 8;
+        let count = decode_prefix_varint(bytes, &mut offset)?;
+        Ok(vec![value; count as usize])
+    }
+}
+
+/// Strings like "user-0", "user-1", ...
+struct NumberedStringCodec;
+
+impl StringCodec for NumberedStringCodec {
+    fn id(&self) -> u64 {
+        3
+    }
+    fn encode(&self, values: &[&str], bytes: &mut Vec<u8>) -> Result<(), Unsupported> {
+        let prefix = values.first().ok_or(Unsupported)?.trim_end_matches(|c: char| c.is_ascii_digit());
+        if values.iter().enumerate().any(|(i, value)| *value != format!("{}{}", prefix, i)) {
+            return Err(Unsupported);
+        }
+        encode_prefix_varint(prefix.len() as u64, bytes);
+        bytes.extend_from_slice(prefix.as_bytes());
+        encode_prefix_varint(values.len() as u64, bytes);
+        Ok(())
+    }
+    fn decode(&self, bytes: &[u8]) -> Result<Vec<String>, DecodeError> {
+        let mut offset = 0;
+        let len = decode_prefix_varint(bytes, &mut offset)? as usize;
+        let prefix = bytes.get(offset..offset + len).ok_or(DecodeError::InvalidFormat)?;
+        let prefix = std::str::from_utf8(prefix).map_err(|_| DecodeError::InvalidFormat)?;
+        offset += len;
+        let count = decode_prefix_varint(bytes, &mut offset)?;
+        Ok((0..count).map(|i| format!("{}{}", prefix, i)).collect())
+    }
+}
+
+/// Byte buffers which are all the same.
+struct RepeatedBytesCodec;
+
+impl BytesCodec for RepeatedBytesCodec {
+    fn id(&self) -> u64 {
+        4
+    }
+    fn encode(&self, values: &[&[u8]], bytes: &mut Vec<u8>) -> Result<(), Unsupported> {
+        let first = *values.first().ok_or(Unsupported)?;
+        if values.iter().any(|value| *value != first) {
+            return Err(Unsupported);
+        }
+        encode_prefix_varint(values.len() as u64, bytes);
+        bytes.extend_from_slice(first);
+        Ok(())
+    }
+    fn decode(&self, bytes: &[u8]) -> Result<Vec<Vec<u8>>, DecodeError> {
+        let mut offset = 0;
+        let count = decode_prefix_varint(bytes, &mut offset)?;
+        Ok(vec![bytes[offset..].to_vec(); count as usize])
+    }
+}
+
+fn codecs() -> Codecs {
+    Codecs::new()
+        .with_integer(SequenceCodec)
+        .with_float(ConstantFloatCodec)
+        .with_string(NumberedStringCodec)
+        .with_bytes(RepeatedBytesCodec)
+}
+
+#[derive(Encode, Decode, Debug, PartialEq, Clone)]
+struct Record {
+    id: u64,
+    small: u16,
+    weight: f64,
+    ratio: f32,
+    name: String,
+    payload: Blob,
+}
+
+fn make_data(n: u64) -> Vec<Record> {
+    (0..n)
+        .map(|i| Record {
+            id: 1_000_000 + i * 37,
+            small: (i * 3) as u16,
+            weight: 9.81,
+            ratio: 0.5,
+            name: format!("user-{}", i),
+            payload: Blob(vec![1, 2, 3, 4, 5, 6, 7, 8]),
+        })
+        .collect()
+}
+
+#[test]
+fn codecs_are_used_and_round_trip() {
+    let codecs = codecs();
+    let data = make_data(500);
+    let bytes = options::encode_with_options(&data, &encode_options! { &codecs });
+    assert!(bytes.len() < encode(&data).len() / 10);
+
+    let breakdown = size_breakdown(&bytes).unwrap();
+    for field in ["id", "small", "weight", "ratio", "name", "payload"].iter() {
+        assert!(breakdown.contains(&format!("[500].{}\n\t   Array.Object.Extension", field)), "{}: {}", field, breakdown);
+    }
+
+    let decoded = options::decode_with_options::<Vec<Record>>(&bytes, &decode_options! { &codecs });
+    assert_eq!(decoded, Ok(data));
+}
+
+#[test]
+fn decoding_requires_the_codec() {
+    let codecs = codecs();
+    let data = make_data(100);
+    let bytes = options::encode_with_options(&data, &encode_options! { &codecs });
+    assert_eq!(decode::<Vec<Record>>(&bytes), Err(DecodeError::UnknownCodec));
+
+    let other = Codecs::new().with_integer(SequenceCodec);
+    assert_eq!(
+        options::decode_with_options::<Vec<Record>>(&bytes, &decode_options! { &other }),
+        Err(DecodeError::UnknownCodec)
+    );
+}
+
+#[test]
+fn codecs_which_fail_are_not_used() {
+    let codecs = codecs();
+    let data: Vec<_> = make_data(100)
+        .into_iter()
+        .map(|mut record| {
+            record.id *= record.id;
+            record.name.push('!');
+            record
+        })
+        .collect();
+    let bytes = options::encode_with_options(&data, &encode_options! { &codecs });
+    let breakdown = size_breakdown(&bytes).unwrap();
+    assert!(!breakdown.contains(".id\n\t   Array.Object.Extension"), "{}", breakdown);
+    assert!(!breakdown.contains(".name\n\t   Array.Object.Extension"), "{}", breakdown);
+    assert!(breakdown.contains(".small\n\t   Array.Object.Extension"), "{}", breakdown);
+    assert_eq!(options::decode_with_options(&bytes, &decode_options! { &codecs }), Ok(data));
+}
+
+#[test]
+fn codecs_can_be_denied() {
+    let codecs = codecs();
+    let rules = EncodingRules::new().deny("", &[ArrayTypeId::Extension]);
+    let data = make_data(100);
+    let bytes = options::encode_with_options(&data, &encode_options! { &codecs, &rules });
+    assert_eq!(decode(&bytes), Ok(data));
+}
@@ -0,0 +1,211 @@
+use std::collections::HashMap;
+use tree_buf::experimental::codegen::rust_source;
+use tree_buf::prelude::*;
+
+// The expected output is compiled here too, which checks that it can decode the file.
+#[path = "codegen/generated.rs"]
+mod generated;
+
+mod source {
+    use super::*;
+
+    #[derive(Encode, Decode, Debug, PartialEq, Clone)]
+    pub enum Status {
+        Active,
+        Retired(String),
+    }
+
+    #[derive(Encode, Decode, Debug, PartialEq, Clone)]
+    pub struct Reading {
+        pub sensor_id: u64,
+        pub value: f32,
+    }
+
+    #[derive(Encode, Decode, Debug, PartialEq, Clone)]
+    pub struct Station {
+        pub station_name: String,
+        pub elevation: u64,
+        pub nickname: Option<String>,
+        pub status: Status,
+        pub readings: Vec<Reading>,
+        pub totals: HashMap<String, u32>,
+        pub bounds: (u64, u64),
+    }
+}
+
+fn make_stations() -> Vec<source::Station> {
+    (0..30u64)
+        .map(|i| source::Station {
+            station_name: format!("station-{}", i),
+            elevation: i * 100,
+            nickname: if i % 3 == 0 { Some(format!("nick-{}", i)) } else { None },
+            status: if i % 2 == 0 {
+                source::Status::Active
+            } else {
+                source::Status::Retired(format!("{}", 1990 + i))
+            },
+            readings: (0..i % 4)
+                .map(|j| source::Reading {
+                    sensor_id: 70_000 + j,
+                    value: j as f32 * 0.5,
+                })
+                .collect(),
+            totals: (0..i % 3).map(|j| (format!("total-{}", j), (j * 5) as u32)).collect(),
+            bounds: (i + 2, 100_000 + i),
+        })
+        .collect()
+}
+
+#[test]
+fn generates_types_which_decode_the_file() {
+    let bytes = encode(&make_stations());
+    let source = rust_source(&bytes, "Stations").unwrap();
+    assert_eq!(source, include_str!("codegen/generated.rs"));
+
+    let decoded = decode::<generated::Stations>(&bytes).unwrap();
+    assert_eq!(decoded.len(), 30);
+    assert_eq!(decoded[3].station_name, "station-3");
+    assert_eq!(decoded[3].elevation, 300);
+    assert_eq!(decoded[3].nickname, Some("nick-3".to_owned()));
+    assert_eq!(decoded[3].status, generated::Status::Retired("1993".to_owned()));
+    assert_eq!(decoded[3].readings[2].sensor_id, 70_002);
+    assert_eq!(decoded[3].bounds, (5, 100_003));
+}
+
+#[test]
+fn integers_get_the_narrowest_type() {
+    #[derive(Encode, Decode, Debug, PartialEq, Clone)]
+    struct Sizes {
+        small: u64,
+        medium: u64,
+        large: u64,
+        none: Vec<u64>,
+    }
+    let value = Sizes {
+        small: 200,
+        medium: 60_000,
+        large: 5_000_000_000,
+        none: Vec::new(),
+    };
+    let source = rust_source(&encode(&value), "Sizes").unwrap();
+    let expected = "use tree_buf::prelude::*;
+
+#[derive(Encode, Decode, Debug, Clone, PartialEq)]
+pub struct Sizes {
+    pub large: u64,
+    pub medium: u16,
+    pub none: Vec<tree_buf::Ignore>,
+    pub small: u8,
+}
+";
+    assert_eq!(source, expected);
+}
+
+mod keywords_source {
+    use super::*;
+
+    #[derive(Encode, Decode, Debug, PartialEq, Clone)]
+    pub enum Kind {
+        #[tree_buf(rename = "self")]
+        Itself,
+        Other(u64),
+    }
+
+    #[derive(Encode, Decode, Debug, PartialEq, Clone)]
+    pub struct Token {
+        pub r#type: String,
+        #[tree_buf(rename = "self")]
+        pub itself: u64,
+        #[tree_buf(rename = "crate")]
+        pub krate: bool,
+        pub r#match: Vec<bool>,
+        pub kind: Kind,
+    }
+}
+
+#[path = "codegen/keywords.rs"]
+mod keywords;
+
+#[test]
+fn escapes_keywords() {
+    let token = keywords_source::Token {
+        r#type: "ident".to_owned(),
+        itself: 3,
+        krate: true,
+        r#match: vec![true, false, true],
+        kind: keywords_source::Kind::Itself,
+    };
+    let bytes = encode(&token);
+    let source = rust_source(&bytes, "Token").unwrap();
+    assert_eq!(source, include_str!("codegen/keywords.rs"));
+
+    let decoded = decode::<keywords::Token>(&bytes).unwrap();
+    assert_eq!(decoded.r#type, "ident");
+    assert_eq!(decoded.self_, 3);
+    assert!(decoded.crate_);
+    // Booleans in arrays may have been integers, so they are generated as u8
+    assert_eq!(decoded.r#match, vec![1, 0, 1]);
+    assert_eq!(decoded.kind, keywords::Kind::Self_);
+}
+
+#[test]
+fn booleans_in_arrays_are_u8() {
+    // Which could have been written by either of these
+    let bools = vec![true, false, false, true];
+    let ints = vec![1u64, 0, 0, 1];
+    assert_eq!(encode(&bools), encode(&ints));
+
+    let expected = "use tree_buf::prelude::*;\n\npub type Flags = Vec<u8>;\n";
+    assert_eq!(rust_source(&encode(&bools), "Flags").unwrap(), expected);
+    assert_eq!(decode::<Vec<u8>>(&encode(&bools)), Ok(vec![1, 0, 0, 1]));
+    // A single item is written at the root, where it is still a bool
+    assert_eq!(rust_source(&encode(&vec![true]), "Flags").unwrap(), expected);
+    assert_eq!(decode::<Vec<u8>>(&encode(&vec![true])), Ok(vec![1]));
+
+    // Outside of arrays, booleans are never integers
+    assert_eq!(rust_source(&encode(&true), "Flag").unwrap(), "use tree_buf::prelude::*;\n\npub type Flag = bool;\n");
+}
+
+mod signed_source {
+    use super::*;
+    use std::time::SystemTime;
+
+    #[derive(Encode, Decode, Debug, PartialEq, Clone)]
+    pub struct Window {
+        pub start: SystemTime,
+        pub end: SystemTime,
+    }
+
+    #[derive(Encode, Decode, Debug, PartialEq, Clone)]
+    pub struct Event {
+        pub name: String,
+        pub window: Window,
+        pub tags: Vec<String>,
+    }
+}
+
+#[path = "codegen/signed.rs"]
+mod signed;
+
+#[test]
+fn negative_integers_are_signed() {
+    use std::time::{Duration, UNIX_EPOCH};
+
+    // Times before 1970 are negative integers at the root
+    let event = signed_source::Event {
+        name: "moon landing".to_owned(),
+        window: signed_source::Window {
+            start: UNIX_EPOCH - Duration::from_secs(14_182_940),
+            end: UNIX_EPOCH + Duration::from_secs(100),
+        },
+        tags: vec!["apollo".to_owned()],
+    };
+    let bytes = encode(&event);
+    let source = rust_source(&bytes, "Event").unwrap();
+    assert_eq!(source, include_str!("codegen/signed.rs"));
+
+    let decoded = decode::<signed::Event>(&bytes).unwrap();
+    assert_eq!(decoded.name, "moon landing");
+    assert_eq!(decoded.window.start, -14_182_940);
+    assert_eq!(decoded.window.end, 100);
+}
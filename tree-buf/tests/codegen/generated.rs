@@ -0,0 +1,27 @@
+use std::collections::HashMap;
+use tree_buf::prelude::*;
+
+pub type Stations = Vec<StationsItem>;
+
+#[derive(Encode, Decode, Debug, Clone, PartialEq)]
+pub struct StationsItem {
+    pub bounds: (u8, u32),
+    pub elevation: u16,
+    pub nickname: Option<String>,
+    pub readings: Vec<Readings>,
+    pub station_name: String,
+    pub status: Status,
+    pub totals: HashMap<String, u8>,
+}
+
+#[derive(Encode, Decode, Debug, Clone, PartialEq)]
+pub struct Readings {
+    pub sensor_id: u32,
+    pub value: f64,
+}
+
+#[derive(Encode, Decode, Debug, Clone, PartialEq)]
+pub enum Status {
+    Active,
+    Retired(String),
+}
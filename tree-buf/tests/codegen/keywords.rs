@@ -0,0 +1,18 @@
+use tree_buf::prelude::*;
+
+#[derive(Encode, Decode, Debug, Clone, PartialEq)]
+pub struct Token {
+    #[tree_buf(rename = "crate")]
+    pub crate_: bool,
+    pub kind: Kind,
+    pub r#match: Vec<u8>,
+    #[tree_buf(rename = "self")]
+    pub self_: u8,
+    pub r#type: String,
+}
+
+#[derive(Encode, Decode, Debug, Clone, PartialEq)]
+pub enum Kind {
+    #[tree_buf(rename = "self")]
+    Self_,
+}
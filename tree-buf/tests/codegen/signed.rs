@@ -0,0 +1,14 @@
+use tree_buf::prelude::*;
+
+#[derive(Decode, Debug, Clone, PartialEq)]
+pub struct Event {
+    pub name: String,
+    pub tags: Vec<String>,
+    pub window: Window,
+}
+
+#[derive(Decode, Debug, Clone, PartialEq)]
+pub struct Window {
+    pub end: u8,
+    pub start: i32,
+}
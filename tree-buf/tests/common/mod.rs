@@ -29,7 +29,12 @@ where
     let bytes = encode(i);
     let result = decode(&bytes);
     match result {
-        Ok(parsed) => assert_eq!(o, &parsed),
+        Ok(mut parsed) => {
+            assert_eq!(o, &parsed);
+            // Decoding again into the same value re-uses its allocations
+            tree_buf::decode_into(&bytes, &mut parsed).unwrap();
+            assert_eq!(o, &parsed);
+        }
         Err(e) => assert!(false, "{}", e),
     }
     if let Some(size) = size.into() {
@@ -0,0 +1,284 @@
+use std::collections::HashMap;
+use tree_buf::experimental::compat::*;
+use tree_buf::prelude::*;
+
+fn difference(path: &str, change: Change) -> Difference {
+    Difference { path: path.to_owned(), change }
+}
+
+fn widened(writer: &str, reader: &str) -> Change {
+    Change::Widened {
+        writer: writer.to_owned(),
+        reader: reader.to_owned(),
+    }
+}
+
+fn mismatched(writer: &str, reader: &str) -> Change {
+    Change::Mismatched {
+        writer: writer.to_owned(),
+        reader: reader.to_owned(),
+    }
+}
+
+mod v1 {
+    use super::*;
+
+    #[derive(Encode, Decode, Debug, PartialEq, Clone)]
+    pub enum Status {
+        Active,
+        Retired(String),
+    }
+
+    #[derive(Encode, Decode, Debug, PartialEq, Clone)]
+    pub struct Reading {
+        pub sensor: u32,
+        pub value: f32,
+    }
+
+    #[derive(Encode, Decode, Debug, PartialEq, Clone)]
+    pub struct Station {
+        pub name: String,
+        pub status: Status,
+        pub readings: Vec<Reading>,
+        pub totals: HashMap<String, u64>,
+        pub corners: [u8; 4],
+        pub removed: bool,
+    }
+}
+
+mod v2 {
+    use super::*;
+
+    #[derive(Encode, Decode, Debug, PartialEq, Clone)]
+    pub enum Status {
+        Active,
+        Retired(String),
+        Moved(String),
+    }
+
+    #[derive(Encode, Decode, Debug, PartialEq, Clone)]
+    pub struct Reading {
+        pub sensor: u64,
+        pub value: f64,
+        pub unit: Option<String>,
+    }
+
+    #[derive(Encode, Decode, Debug, PartialEq, Clone)]
+    pub struct Station {
+        pub name: String,
+        pub status: Status,
+        pub readings: Vec<Reading>,
+        pub totals: HashMap<String, u64>,
+        pub corners: Vec<u8>,
+    }
+}
+
+#[test]
+fn same_type_has_no_differences() {
+    let compatibility = check_compatible::<v1::Station, v1::Station>();
+    assert_eq!(compatibility.differences, vec![]);
+    assert!(compatibility.is_compatible());
+}
+
+#[test]
+fn new_release_reads_old_files() {
+    let compatibility = check_compatible::<v1::Station, v2::Station>();
+    assert_eq!(
+        compatibility.differences,
+        vec![
+            difference("readings.sensor", widened("u32", "u64")),
+            difference("readings.value", widened("f32", "f64")),
+            difference("readings.unit", Change::Defaulted),
+        ]
+    );
+    assert!(compatibility.is_compatible());
+
+    // Check that against the real thing
+    let old = v1::Station {
+        name: "a".to_owned(),
+        status: v1::Status::Retired("1990".to_owned()),
+        readings: vec![v1::Reading { sensor: 1, value: 1.5 }, v1::Reading { sensor: 2, value: 2.5 }],
+        totals: HashMap::new(),
+        corners: [1, 2, 3, 4],
+        removed: true,
+    };
+    let new = decode::<v2::Station>(&encode(&old)).unwrap();
+    assert_eq!(new.readings[1].sensor, 2);
+    assert_eq!(new.readings[1].unit, None);
+    assert_eq!(new.corners, vec![1, 2, 3, 4]);
+}
+
+#[test]
+fn old_release_reads_new_files() {
+    let compatibility = check_compatible::<v2::Station, v1::Station>();
+    assert_eq!(
+        compatibility.differences,
+        vec![
+            difference("status.moved", Change::Missing),
+            difference(
+                "readings.sensor",
+                Change::Narrowed {
+                    writer: "u64".to_owned(),
+                    reader: "u32".to_owned()
+                }
+            ),
+            difference(
+                "readings.value",
+                Change::Narrowed {
+                    writer: "f64".to_owned(),
+                    reader: "f32".to_owned()
+                }
+            ),
+            difference("corners", mismatched("Vec<u8>", "[u8; 4]")),
+            difference("removed", Change::Missing),
+        ]
+    );
+    assert!(!compatibility.is_compatible());
+    assert_eq!(
+        format!("{}", compatibility),
+        "status.moved: missing
+readings.sensor: narrowed from u64 to u32
+readings.value: narrowed from f64 to f32
+corners: mismatched Vec<u8> and [u8; 4]
+removed: missing
+"
+    );
+}
+
+#[test]
+fn mismatched_types() {
+    #[derive(Encode, Decode)]
+    struct Writer {
+        id: String,
+        pair: (u8, u8),
+        lookup: HashMap<u64, String>,
+        maybe: Option<u32>,
+    }
+
+    #[derive(Encode, Decode)]
+    struct Reader {
+        id: u64,
+        pair: (u8, bool),
+        lookup: HashMap<String, String>,
+        maybe: u32,
+    }
+
+    let compatibility = check_compatible::<Writer, Reader>();
+    assert_eq!(
+        compatibility.differences,
+        vec![
+            difference("id", mismatched("String", "u64")),
+            difference("pair.1", mismatched("u8", "bool")),
+            difference("lookup.{key}", mismatched("u64", "String")),
+            difference("maybe", mismatched("Option<u32>", "u32")),
+        ]
+    );
+}
+
+#[test]
+fn ignore_reads_anything() {
+    #[derive(Encode, Decode)]
+    struct Writer {
+        a: Vec<String>,
+    }
+
+    #[derive(Encode, Decode)]
+    struct Reader {
+        a: tree_buf::Ignore,
+        b: tree_buf::Ignore,
+    }
+
+    assert_eq!(check_compatible::<Writer, Reader>().differences, vec![]);
+}
+
+#[test]
+fn integers_and_floats() {
+    #[derive(Encode, Decode)]
+    struct Writer {
+        small: u16,
+        medium: u32,
+        large: u64,
+        ratio: f32,
+    }
+
+    #[derive(Encode, Decode)]
+    struct Reader {
+        small: f32,
+        medium: f64,
+        large: f64,
+        ratio: u8,
+    }
+
+    assert_eq!(
+        format!("{}", check_compatible::<Writer, Reader>()),
+        "small: widened from u16 to f32
+medium: widened from u32 to f64
+large: narrowed from u64 to f64
+ratio: narrowed from f32 to u8
+"
+    );
+}
+
+#[test]
+fn signed_readers() {
+    #[derive(Encode)]
+    struct Writer {
+        small: u8,
+        medium: u32,
+        ratio: f64,
+    }
+
+    // Signed integers can be read, but not written
+    #[derive(Decode)]
+    struct Reader {
+        small: i64,
+        medium: i32,
+        ratio: i16,
+    }
+
+    assert_eq!(
+        format!("{}", check_compatible::<Writer, Reader>()),
+        "small: widened from u8 to i64
+medium: narrowed from u32 to i32
+ratio: narrowed from f64 to i16
+"
+    );
+}
+
+#[test]
+fn booleans_read_as_integers() {
+    #[derive(Encode, Decode, Debug, PartialEq)]
+    struct Writer {
+        flag: bool,
+        flags: Vec<bool>,
+    }
+
+    #[derive(Encode, Decode, Debug, PartialEq)]
+    struct Reader {
+        flag: u8,
+        flags: Vec<u64>,
+    }
+
+    let compatibility = check_compatible::<Writer, Reader>();
+    assert!(compatibility.is_compatible());
+    assert_eq!(
+        compatibility.differences,
+        vec![difference("flag", widened("bool", "u8")), difference("flags", widened("bool", "u64"))]
+    );
+
+    let writer = Writer {
+        flag: true,
+        flags: vec![false, true, true],
+    };
+    assert_eq!(decode::<Reader>(&encode(&writer)), Ok(Reader { flag: 1, flags: vec![0, 1, 1] }));
+}
+
+#[test]
+fn schemas_of_a_type() {
+    use tree_buf::{Decodable, Encodable};
+    // With both traits in scope, either schema can be named without qualifying the trait
+    let written = v1::Reading::encode_schema();
+    let read = v1::Reading::decode_schema();
+    assert_eq!(written, read);
+    assert!(compare_schemas(&written, &read).is_compatible());
+}
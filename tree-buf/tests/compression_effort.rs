@@ -0,0 +1,87 @@
+use tree_buf::experimental::options::{self, CompressionEffort, Sampling};
+use tree_buf::prelude::*;
+use tree_buf::{encode_options, Decodable, Encodable};
+
+#[derive(Encode, Decode, Debug, PartialEq, Clone)]
+struct Item {
+    id: u64,
+    name: String,
+    value: f64,
+    on: bool,
+}
+
+const EFFORTS: [CompressionEffort; 6] = [
+    CompressionEffort::DEFAULT,
+    CompressionEffort::Sample(0, Sampling::Strided),
+    CompressionEffort::Sample(10, Sampling::Head),
+    CompressionEffort::Sample(100, Sampling::Strided),
+    CompressionEffort::Sample(100, Sampling::Random(7)),
+    CompressionEffort::Exhaustive,
+];
+
+fn encode_with_effort<T: Encodable>(value: &T, effort: CompressionEffort) -> Vec<u8> {
+    options::encode_with_options(value, &encode_options! { effort })
+}
+
+fn assert_round_trips<T: Encodable + Decodable + PartialEq + std::fmt::Debug>(value: &T) {
+    for &effort in EFFORTS.iter() {
+        let bytes = encode_with_effort(value, effort);
+        assert_eq!(&decode::<T>(&bytes).unwrap(), value, "{:?}", effort);
+    }
+}
+
+// The head of the data is all the same, but the rest isn't.
+fn changing_data() -> Vec<u64> {
+    (0..5000u64).map(|i| if i < 300 { 0 } else { i * 3 }).collect()
+}
+
+#[test]
+fn all_efforts_round_trip() {
+    let items: Vec<_> = (0..2000u64)
+        .map(|i| Item {
+            id: if i < 500 { 1 } else { i * i },
+            name: format!("name-{}", i % 13),
+            value: i as f64 * 0.125,
+            on: i % 3 == 1,
+        })
+        .collect();
+    assert_round_trips(&items);
+    assert_round_trips(&changing_data());
+    assert_round_trips(&Vec::<Item>::new());
+    assert_round_trips(&vec![1u32]);
+}
+
+#[test]
+fn sampling_throughout_finds_better_compression() {
+    let data = changing_data();
+    let head = encode_with_effort(&data, CompressionEffort::DEFAULT).len();
+    let strided = encode_with_effort(&data, CompressionEffort::Sample(256, Sampling::Strided)).len();
+    let random = encode_with_effort(&data, CompressionEffort::Sample(256, Sampling::Random(1))).len();
+    assert!(strided < head, "strided: {}, head: {}", strided, head);
+    assert!(random < head, "random: {}, head: {}", random, head);
+}
+
+#[test]
+fn exhaustive_is_smallest() {
+    let strings: Vec<_> = (0..3000).map(|i| if i < 1000 { "same".to_owned() } else { format!("{}", i * 7919 % 1000) }).collect();
+    let floats: Vec<_> = (0..3000).map(|i| if i < 400 { 0.0 } else { (i as f64).sqrt() }).collect();
+
+    fn check<T: Encodable>(value: &T) {
+        let exhaustive = encode_with_effort(value, CompressionEffort::Exhaustive).len();
+        for &effort in EFFORTS.iter() {
+            let size = encode_with_effort(value, effort).len();
+            assert!(exhaustive <= size, "exhaustive: {}, {:?}: {}", exhaustive, effort, size);
+        }
+    }
+    check(&changing_data());
+    check(&strings);
+    check(&floats);
+}
+
+#[test]
+fn exhaustive_is_deterministic_in_parallel() {
+    let data = changing_data();
+    let sequential = encode_with_effort(&data, CompressionEffort::Exhaustive);
+    let parallel = options::encode_with_options(&data, &encode_options! { CompressionEffort::Exhaustive, options::EnableParallel });
+    assert_eq!(sequential, parallel);
+}
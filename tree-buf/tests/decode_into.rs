@@ -0,0 +1,74 @@
+use std::collections::HashMap;
+use tree_buf::prelude::*;
+use tree_buf::{decode_into, DecodeError};
+
+#[derive(Encode, Decode, Debug, PartialEq, Clone, Default)]
+struct Sample {
+    name: String,
+    values: Vec<u64>,
+    nested: Vec<Vec<f64>>,
+    labels: HashMap<String, u32>,
+    extra: Option<Vec<bool>>,
+    points: Vec<Point>,
+}
+
+#[derive(Encode, Decode, Debug, PartialEq, Clone, Default)]
+struct Point {
+    x: u32,
+    tags: Vec<String>,
+}
+
+fn sample(n: usize) -> Sample {
+    Sample {
+        name: format!("sample-{}", n),
+        values: (0..n as u64 * 3).collect(),
+        nested: (0..n).map(|i| (0..i).map(|v| v as f64 / 2.0).collect()).collect(),
+        labels: (0..n).map(|i| (format!("label-{}", i), i as u32)).collect(),
+        extra: if n % 2 == 1 { Some((0..n).map(|i| i % 3 == 1).collect()) } else { None },
+        points: (0..n)
+            .map(|i| Point {
+                x: i as u32 * 2,
+                tags: (0..i % 3).map(|t| t.to_string()).collect(),
+            })
+            .collect(),
+    }
+}
+
+#[test]
+fn decode_into_matches_decode() {
+    let mut into = Sample::default();
+    // Grow, shrink, and switch the Option back and forth.
+    for &n in &[5, 2, 9, 0, 1, 7, 3] {
+        let value = sample(n);
+        let bytes = encode(&value);
+        decode_into(&bytes, &mut into).unwrap();
+        assert_eq!(into, value);
+
+        let mut array = vec![sample(n + 1); n % 4];
+        let expected = vec![value.clone(); 3];
+        decode_into(&encode(&expected), &mut array).unwrap();
+        assert_eq!(array, expected);
+    }
+}
+
+#[test]
+fn decode_into_reuses_capacity() {
+    let mut into = sample(50);
+    let values_ptr = into.values.as_ptr();
+    let points_ptr = into.points.as_ptr();
+    let first_tags_ptr = into.points[2].tags.as_ptr();
+
+    let value = sample(20);
+    decode_into(&encode(&value), &mut into).unwrap();
+    assert_eq!(into, value);
+    assert_eq!(into.values.as_ptr(), values_ptr);
+    assert_eq!(into.points.as_ptr(), points_ptr);
+    assert_eq!(into.points[2].tags.as_ptr(), first_tags_ptr);
+}
+
+#[test]
+fn decode_into_schema_mismatch() {
+    let mut into = vec![1u64, 2, 3];
+    let bytes = encode(&vec![String::from("a"), String::from("b")]);
+    assert_eq!(decode_into(&bytes, &mut into), Err(DecodeError::SchemaMismatch));
+}
@@ -0,0 +1,134 @@
+use tree_buf::encode_options;
+use tree_buf::experimental::explain::encode_with_report;
+use tree_buf::experimental::options::{self, ArrayTypeId, CompressionEffort, EncodingRules};
+use tree_buf::experimental::stats::size_breakdown;
+use tree_buf::prelude::*;
+
+#[derive(Encode, Decode, Debug, PartialEq, Clone)]
+struct Row {
+    kind: String,
+    label: String,
+    text: String,
+    count: u64,
+    pair: (u64, String),
+}
+
+fn make_data() -> Vec<Row> {
+    (0..1000u64)
+        .map(|i| Row {
+            kind: ["alpha", "beta", "gamma"][(i % 3) as usize].to_owned(),
+            label: ["one", "two"][(i % 2) as usize].to_owned(),
+            text: format!("The quick brown fox number {} jumps over the lazy dog number {}", i * 7 % 13, i),
+            count: i * 1000,
+            pair: (i % 4, format!("{}", i % 5)),
+        })
+        .collect()
+}
+
+fn encode_with_rules(data: &Vec<Row>, rules: &EncodingRules) -> String {
+    let bytes = options::encode_with_options(data, &encode_options! { rules });
+    assert_eq!(&decode::<Vec<Row>>(&bytes).unwrap(), data);
+    size_breakdown(&bytes).unwrap()
+}
+
+#[test]
+fn deny_everywhere() {
+    let data = make_data();
+    let breakdown = size_breakdown(&encode(&data)).unwrap();
+    assert!(breakdown.contains("Dictionary"), "{}", breakdown);
+    assert!(breakdown.contains("FsstUtf8"), "{}", breakdown);
+
+    let rules = EncodingRules::new().deny("", &[ArrayTypeId::Dictionary, ArrayTypeId::FsstUtf8]);
+    let breakdown = encode_with_rules(&data, &rules);
+    assert!(!breakdown.contains("Dictionary"), "{}", breakdown);
+    assert!(!breakdown.contains("FsstUtf8"), "{}", breakdown);
+}
+
+#[test]
+fn deny_at_path() {
+    let data = make_data();
+    let rules = EncodingRules::new().deny("kind", &[ArrayTypeId::Dictionary]);
+    let breakdown = encode_with_rules(&data, &rules);
+    assert!(!breakdown.contains("kind.indices"), "{}", breakdown);
+    assert!(breakdown.contains("label.indices"), "{}", breakdown);
+}
+
+#[test]
+fn force_at_path() {
+    let data = make_data();
+    let rules = EncodingRules::new().force("count", &[ArrayTypeId::IntSimple16]).force("pair.1", &[ArrayTypeId::Utf8]);
+    let breakdown = encode_with_rules(&data, &rules);
+    assert!(breakdown.contains("[1000].count\n\t   Array.Object.Simple16"), "{}", breakdown);
+    assert!(breakdown.contains("[1000].pair.1\n\t   Array.Object.Tuple.UTF-8"), "{}", breakdown);
+    // Forcing an encoding for one type doesn't affect other types beneath the path.
+    let rules = EncodingRules::new().force("", &[ArrayTypeId::Utf8]);
+    let breakdown = encode_with_rules(&data, &rules);
+    assert!(breakdown.contains("DeltaZig"), "{}", breakdown);
+}
+
+#[test]
+fn forced_pass_only_tries_forced_encodings() {
+    let mut data = make_data();
+    data.truncate(100);
+    let rules = EncodingRules::new().force("", &[ArrayTypeId::Utf8]);
+    let (bytes, report) = encode_with_report(&data, &encode_options! { &rules });
+    assert_eq!(decode::<Vec<Row>>(&bytes), Ok(data));
+    // Compressors which can't write Utf8 aren't tried until the forced pass is over,
+    // so the first of them to succeed is written.
+    let report = report.to_string();
+    assert!(!report.contains("rejected"), "{}", report);
+}
+
+#[test]
+fn allow_only_plain() {
+    let data = make_data();
+    let plain = [
+        ArrayTypeId::IntPrefixVar,
+        ArrayTypeId::IntSimple16,
+        ArrayTypeId::Utf8,
+        ArrayTypeId::PackedBool,
+        ArrayTypeId::F64,
+    ];
+    let breakdown = encode_with_rules(&data, &EncodingRules::new().allow_only("", &plain));
+    for encoding in ["Dictionary", "RLE", "DeltaZig", "Fsst", "Prefix.", "Decimal", "Brotli"].iter() {
+        assert!(!breakdown.contains(encoding), "{}", breakdown);
+    }
+}
+
+#[test]
+fn denying_everything_still_encodes() {
+    let data = make_data();
+    let all = [
+        ArrayTypeId::IntSimple16,
+        ArrayTypeId::IntPrefixVar,
+        ArrayTypeId::U8,
+        ArrayTypeId::DeltaZig,
+        ArrayTypeId::DeltaZig64,
+        ArrayTypeId::RLE,
+        ArrayTypeId::Dictionary,
+        ArrayTypeId::Utf8,
+        ArrayTypeId::BrotliUtf8,
+        ArrayTypeId::PrefixUtf8,
+        ArrayTypeId::FsstUtf8,
+        ArrayTypeId::HexUtf8,
+        ArrayTypeId::DecimalUtf8,
+    ];
+    encode_with_rules(&data, &EncodingRules::new().deny("", &all));
+}
+
+#[test]
+fn rules_apply_to_exhaustive_and_parallel() {
+    let mut data = make_data();
+    data.truncate(100);
+    let rules = EncodingRules::new()
+        .deny("", &[ArrayTypeId::FsstUtf8, ArrayTypeId::Dictionary])
+        .force("count", &[ArrayTypeId::IntPrefixVar]);
+    let sequential = options::encode_with_options(&data, &encode_options! { &rules, CompressionEffort::Exhaustive });
+    let parallel = options::encode_with_options(&data, &encode_options! { &rules, CompressionEffort::Exhaustive, options::EnableParallel });
+    assert_eq!(sequential, parallel);
+    assert_eq!(decode::<Vec<Row>>(&sequential), Ok(data));
+    let breakdown = size_breakdown(&sequential).unwrap();
+    assert!(!breakdown.contains("FsstUtf8"), "{}", breakdown);
+    assert!(!breakdown.contains("Dictionary"), "{}", breakdown);
+    assert!(breakdown.contains("[100].count\n\t   Array.Object.Prefix Varint"), "{}", breakdown);
+}
@@ -78,6 +78,46 @@ fn mixed_void_and_single() {
     round_trip(&vec![Mixed::Ex, Mixed::One(2), Mixed::One(2), Mixed::One(3), Mixed::Ex], 21, 24);
 }
 
+#[derive(Encode, Decode, Debug, PartialEq, Clone, Copy)]
+#[tree_buf(integer)]
+#[repr(u8)]
+enum Color {
+    Red = 1,
+    Green = 2,
+    Blue = 40,
+}
+
+#[test]
+fn integer_enum() {
+    round_trip(&Color::Blue, 2, 6);
+    round_trip(&vec![Color::Red, Color::Red, Color::Green, Color::Blue, Color::Red], 8, 14);
+
+    // Written as the discriminant, so it is interchangeable with integers
+    assert_eq!(encode(&Color::Blue), encode(&40u8));
+    let colors = vec![Color::Green, Color::Blue, Color::Red];
+    assert_eq!(decode::<Vec<u32>>(&encode(&colors)), Ok(vec![2, 40, 1]));
+    assert_eq!(decode::<Vec<Color>>(&encode(&vec![2u64, 40, 1])), Ok(colors));
+}
+
+#[test]
+fn integer_enum_unknown_discriminant() {
+    #[derive(Decode, Debug, PartialEq, Clone, Copy)]
+    #[tree_buf(integer)]
+    enum Fallback {
+        Red = 1,
+        #[tree_buf(other)]
+        Unknown = 0,
+    }
+
+    assert_eq!(decode::<Color>(&encode(&3u8)), Err(tree_buf::DecodeError::SchemaMismatch));
+    assert_eq!(decode::<Vec<Color>>(&encode(&vec![1u8, 3])), Err(tree_buf::DecodeError::SchemaMismatch));
+    assert_eq!(decode::<Fallback>(&encode(&Color::Blue)), Ok(Fallback::Unknown));
+    assert_eq!(
+        decode::<Vec<Fallback>>(&encode(&vec![Color::Red, Color::Green, Color::Red])),
+        Ok(vec![Fallback::Red, Fallback::Unknown, Fallback::Red])
+    );
+}
+
 /*
 // TODO: Enable test
 #[test]
@@ -0,0 +1,130 @@
+use tree_buf::encode_options;
+use tree_buf::experimental::explain::*;
+use tree_buf::experimental::options::{self, ArrayTypeId, CompressionEffort, EncodingRules};
+use tree_buf::prelude::*;
+
+#[derive(Encode, Decode, Debug, PartialEq, Clone)]
+struct Row {
+    id: u64,
+    kind: String,
+    values: Vec<u32>,
+}
+
+fn make_data() -> Vec<Row> {
+    (0..300u64)
+        .map(|i| Row {
+            id: i * 3,
+            kind: ["red", "green", "blue"][(i % 3) as usize].to_owned(),
+            values: (0..i % 4).map(|v| v as u32 * 100).collect(),
+        })
+        .collect()
+}
+
+fn find<'a>(report: &'a EncodeReport, path: &str, value_type: &str) -> &'a CompressDecision {
+    report
+        .decisions
+        .iter()
+        .find(|d| d.path == path && d.value_type.ends_with(value_type))
+        .unwrap_or_else(|| panic!("{} {} in\n{}", path, value_type, report))
+}
+
+fn written(decision: &CompressDecision) -> &Candidate {
+    let written: Vec<_> = decision.candidates.iter().filter(|c| matches!(c.outcome, Outcome::Written { .. })).collect();
+    assert_eq!(written.len(), 1, "{:?}", decision);
+    assert!(std::ptr::eq(written[0], &decision.candidates[decision.chosen]));
+    written[0]
+}
+
+#[test]
+fn report_does_not_change_the_bytes() {
+    let data = make_data();
+    let (bytes, report) = encode_with_report(&data, &encode_options! {});
+    assert_eq!(bytes, encode(&data));
+    assert!(!report.decisions.is_empty());
+
+    let exhaustive = encode_options! { CompressionEffort::Exhaustive, options::EnableParallel };
+    let (bytes, _) = encode_with_report(&data, &exhaustive);
+    assert_eq!(bytes, options::encode_with_options(&data, &exhaustive));
+
+    // Nothing is recorded when not asked for
+    let (_, report) = encode_with_report(&Vec::<u64>::new(), &encode_options! {});
+    assert!(report.decisions.is_empty());
+}
+
+#[test]
+fn report_records_estimates_and_sizes() {
+    let (_, report) = encode_with_report(&make_data(), &encode_options! {});
+
+    let id = find(&report, "id", "u16");
+    assert_eq!(id.len, 300);
+    assert_eq!(id.sample_len, Some(256));
+    let chosen = written(id);
+    assert!(chosen.estimate.is_some());
+    assert!(chosen.outcome.size().unwrap() > 0);
+    // The written candidate had the smallest estimate, and nothing after it was tried.
+    let estimates: Vec<_> = id.candidates.iter().filter_map(|c| c.estimate).collect();
+    assert_eq!(chosen.estimate, estimates.iter().min().copied());
+    assert!(id.candidates.iter().any(|c| c.outcome == Outcome::NotTried));
+
+    // The strings are written with a dictionary, whose indices and values have their own decisions.
+    let kind = find(&report, "kind", "String");
+    let chosen = written(kind);
+    assert_eq!(chosen.compressor, "Dictionary");
+    assert!(matches!(
+        chosen.outcome,
+        Outcome::Written {
+            encoding: ArrayTypeId::Dictionary,
+            ..
+        }
+    ));
+    assert_eq!(chosen.nested.len(), 2);
+    assert_eq!(chosen.nested[0].len, 3);
+    assert_eq!(chosen.nested[1].len, 300);
+    assert!(report.all().len() > report.decisions.len());
+
+    let display = report.to_string();
+    assert!(display.contains("* Dictionary: estimate "), "{}", display);
+}
+
+#[test]
+fn exhaustive_report_has_every_size() {
+    let (_, report) = encode_with_report(&make_data(), &encode_options! { CompressionEffort::Exhaustive });
+    for decision in report.all() {
+        assert_eq!(decision.sample_len, None);
+        assert!(decision.candidates.iter().all(|c| c.outcome != Outcome::NotTried), "{:?}", decision);
+        let size = written(decision).outcome.size().unwrap();
+        assert!(decision.candidates.iter().filter_map(|c| c.outcome.size()).all(|other| size <= other), "{:?}", decision);
+    }
+}
+
+#[test]
+fn report_records_rejected_and_failed() {
+    let rules = EncodingRules::new().deny("id", &[ArrayTypeId::IntSimple16]);
+    let (_, report) = encode_with_report(&make_data(), &encode_options! { &rules });
+    let id = find(&report, "id", "u16");
+    let simple16 = id.candidates.iter().find(|c| c.compressor.starts_with("Simple16")).unwrap();
+    assert!(
+        matches!(
+            simple16.outcome,
+            Outcome::Rejected {
+                encoding: ArrayTypeId::IntSimple16,
+                ..
+            }
+        ),
+        "{:?}",
+        id
+    );
+    assert!(!matches!(
+        written(id).outcome,
+        Outcome::Written {
+            encoding: ArrayTypeId::IntSimple16,
+            ..
+        }
+    ));
+
+    // Every candidate is tried when exhaustive, and Hex can't encode these strings.
+    let (_, report) = encode_with_report(&make_data(), &encode_options! { CompressionEffort::Exhaustive });
+    let kind = find(&report, "kind", "String");
+    let hex = kind.candidates.iter().find(|c| c.compressor.starts_with("Hex")).unwrap();
+    assert_eq!(hex.outcome, Outcome::Failed);
+}
@@ -0,0 +1,115 @@
+mod common;
+
+use common::*;
+use tree_buf::experimental::compat::check_compatible;
+use tree_buf::experimental::schema::{schema_of, Schema};
+use tree_buf::prelude::*;
+
+#[derive(Encode, Decode, Debug, PartialEq, Clone, Default)]
+pub struct Audit {
+    created_at: u64,
+    created_by: String,
+}
+
+#[derive(Encode, Decode, Debug, PartialEq, Clone, Default)]
+pub struct Record {
+    id: u32,
+    #[tree_buf(flatten)]
+    audit: Audit,
+    tags: Vec<String>,
+}
+
+/// The same file schema as Record, without the shared struct.
+#[derive(Encode, Decode, Debug, PartialEq, Clone, Default)]
+pub struct FlatRecord {
+    id: u32,
+    created_at: u64,
+    created_by: String,
+    tags: Vec<String>,
+}
+
+fn record(n: u32) -> Record {
+    Record {
+        id: n,
+        audit: Audit {
+            created_at: 1_600_000_000 + n as u64,
+            created_by: format!("user-{}", n % 3),
+        },
+        tags: (0..n).map(|i| format!("tag-{}", i)).collect(),
+    }
+}
+
+fn flat_record(n: u32) -> FlatRecord {
+    let Record { id, audit, tags } = record(n);
+    FlatRecord {
+        id,
+        created_at: audit.created_at,
+        created_by: audit.created_by,
+        tags,
+    }
+}
+
+#[test]
+fn same_bytes_as_flat_struct() {
+    assert_eq!(encode(&record(2)), encode(&flat_record(2)));
+
+    let records: Vec<_> = (0..10).map(record).collect();
+    let flat_records: Vec<_> = (0..10).map(flat_record).collect();
+    assert_eq!(encode(&records), encode(&flat_records));
+}
+
+#[test]
+fn reads_and_writes_flat_struct() {
+    serialize_eq(&record(3), &flat_record(3), None);
+    serialize_eq(&flat_record(3), &record(3), None);
+
+    let records: Vec<_> = (0..10).map(record).collect();
+    let flat_records: Vec<_> = (0..10).map(flat_record).collect();
+    serialize_eq(&records, &flat_records, None);
+    serialize_eq(&flat_records, &records, None);
+}
+
+#[test]
+fn nested_and_only_flattened() {
+    #[derive(Encode, Decode, Debug, PartialEq, Clone, Default)]
+    pub struct Outer {
+        #[tree_buf(flatten)]
+        record: Record,
+        #[tree_buf(flatten)]
+        more: More,
+    }
+
+    // Enough fields in total to need a count written for the object
+    #[derive(Encode, Decode, Debug, PartialEq, Clone, Default)]
+    pub struct More {
+        a: u8,
+        b: u8,
+        c: u8,
+        d: bool,
+        e: Option<String>,
+    }
+
+    let outer = Outer {
+        record: record(4),
+        more: More {
+            a: 1,
+            b: 2,
+            c: 3,
+            d: true,
+            e: Some("e".to_owned()),
+        },
+    };
+    round_trip(&outer, None, None);
+    round_trip(&Outer::default(), None, None);
+
+    match schema_of(&encode(&outer)).unwrap() {
+        Schema::Object(fields) => assert_eq!(fields.len(), 9),
+        schema => panic!("Expected an object, got {}", schema),
+    }
+}
+
+#[test]
+fn compatible_with_flat_struct() {
+    assert_eq!(check_compatible::<Record, FlatRecord>().differences, vec![]);
+    assert_eq!(check_compatible::<FlatRecord, Record>().differences, vec![]);
+}
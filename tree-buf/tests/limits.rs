@@ -0,0 +1,273 @@
+use std::collections::HashMap;
+use tree_buf::experimental::options;
+use tree_buf::internal::encodings::varint::encode_prefix_varint;
+use tree_buf::internal::{ArrayTypeId, RootTypeId};
+use tree_buf::prelude::*;
+use tree_buf::{decode_options, Blob, Decodable, DecodeError};
+
+#[derive(Encode, Decode, Debug, PartialEq, Clone)]
+struct Record {
+    name: String,
+    tags: Vec<String>,
+    values: Vec<u64>,
+}
+
+fn make_data() -> Vec<Record> {
+    (0..50)
+        .map(|i| Record {
+            name: format!("record-{}", i),
+            tags: (0..i % 4).map(|t| format!("tag-{}", t)).collect(),
+            values: (0..i).collect(),
+        })
+        .collect()
+}
+
+fn decode_with<T: Decodable>(bytes: &[u8], options: &impl options::DecodeOptions) -> Result<T, DecodeError> {
+    options::decode_with_options(bytes, options)
+}
+
+#[test]
+fn generous_limits_decode() {
+    let data = make_data();
+    let bytes = encode(&data);
+    let options = decode_options! { options::MaxDepth(8), options::MaxElements(10_000), options::MaxStringLen(16), options::MaxCollectionLen(64) };
+    assert_eq!(decode_with::<Vec<Record>>(&bytes, &options), Ok(data));
+}
+
+#[test]
+fn max_depth() {
+    let data = vec![vec![vec![1u32, 2], vec![3]], vec![vec![4]]];
+    let bytes = encode(&data);
+    assert_eq!(
+        decode_with::<Vec<Vec<Vec<u32>>>>(&bytes, &decode_options! { options::MaxDepth(2) }),
+        Err(DecodeError::LimitExceeded)
+    );
+    assert_eq!(decode_with::<Vec<Vec<Vec<u32>>>>(&bytes, &decode_options! { options::MaxDepth(4) }), Ok(data));
+}
+
+/// Nested single item arrays, with a void at the bottom
+fn nested_arrays(depth: usize) -> Vec<u8> {
+    let mut bytes = vec![RootTypeId::Array1.into(); depth - 1];
+    bytes.push(RootTypeId::Void.into());
+    bytes
+}
+
+#[test]
+fn depth_is_limited_for_untrusted_input() {
+    let bytes = nested_arrays(100);
+    assert_eq!(decode::<Vec<u32>>(&bytes), Err(DecodeError::SchemaMismatch));
+    assert_eq!(
+        decode_with::<Vec<u32>>(&bytes, &decode_options! { options::UntrustedInput }),
+        Err(DecodeError::LimitExceeded)
+    );
+    assert_eq!(
+        decode_with::<Vec<u32>>(&nested_arrays(64), &decode_options! { options::UntrustedInput }),
+        Err(DecodeError::SchemaMismatch)
+    );
+    // Later options take precedence
+    let options = decode_options! { options::UntrustedInput, options::MaxDepth(100) };
+    assert_eq!(decode_with::<Vec<u32>>(&bytes, &options), Err(DecodeError::SchemaMismatch));
+}
+
+#[test]
+fn untrusted_input_limits_sizes() {
+    let options = decode_options! { options::UntrustedInput };
+
+    // 2^10 fixed length arrays of 2^20 items each
+    let mut bytes = vec![RootTypeId::ArrayN.into()];
+    encode_prefix_varint(1 << 10, &mut bytes);
+    bytes.push(ArrayTypeId::ArrayFixed.into());
+    encode_prefix_varint(1 << 20, &mut bytes);
+    bytes.push(ArrayTypeId::Void.into());
+    assert_eq!(decode_with::<Vec<Vec<u32>>>(&bytes, &options), Err(DecodeError::LimitExceeded));
+
+    let bytes = encode(&"a".repeat((1 << 24) + 1));
+    assert_eq!(decode_with::<String>(&bytes, &options), Err(DecodeError::LimitExceeded));
+    let options = decode_options! { options::UntrustedInput, options::MaxStringLen(1 << 25) };
+    assert!(decode_with::<String>(&bytes, &options).is_ok());
+}
+
+#[test]
+fn default_depth_prevents_stack_overflow() {
+    // Deeply nested single-item arrays, one per byte.
+    let bytes = vec![2u8; 1_000_000];
+    assert_eq!(decode::<Vec<u32>>(&bytes), Err(DecodeError::LimitExceeded));
+}
+
+#[test]
+fn deep_input_without_depth_limit() {
+    // Even without a limit in the options, these stop at MAX_DEPTH
+    // rather than overflowing the call stack.
+    let options = decode_options! { options::MaxDepth(usize::MAX) };
+    let bytes = vec![2u8; 1_000_000];
+    assert_eq!(decode_with::<Vec<u32>>(&bytes, &options), Err(DecodeError::LimitExceeded));
+
+    // An array of deeply nested nullable values
+    let mut bytes = vec![3u8, 2];
+    bytes.resize(1_000_002, 1);
+    assert_eq!(decode_with::<Vec<Option<u32>>>(&bytes, &options), Err(DecodeError::LimitExceeded));
+}
+
+#[test]
+fn deepest_input_is_dropped() {
+    // Decoding reads the whole tree of branches before finding that it doesn't match the type.
+    // Then the tree is dropped, which is recursive.
+    let options = decode_options! { options::MaxDepth(usize::MAX) };
+
+    assert_eq!(decode_with::<Vec<u32>>(&nested_arrays(options::MAX_DEPTH), &options), Err(DecodeError::SchemaMismatch));
+    assert_eq!(decode_with::<Vec<u32>>(&nested_arrays(options::MAX_DEPTH + 1), &options), Err(DecodeError::LimitExceeded));
+
+    // Likewise, nested fixed length arrays in an array
+    let nested = |depth: usize| {
+        let mut bytes = vec![RootTypeId::ArrayN.into()];
+        encode_prefix_varint(2, &mut bytes);
+        for _ in 0..depth - 2 {
+            bytes.push(ArrayTypeId::ArrayFixed.into());
+            encode_prefix_varint(1, &mut bytes);
+        }
+        bytes.push(ArrayTypeId::Void.into());
+        bytes
+    };
+    assert_eq!(decode_with::<Vec<u32>>(&nested(options::MAX_DEPTH), &options), Err(DecodeError::SchemaMismatch));
+    assert_eq!(decode_with::<Vec<u32>>(&nested(options::MAX_DEPTH + 1), &options), Err(DecodeError::LimitExceeded));
+}
+
+#[test]
+fn tools_decode_without_limits() {
+    use tree_buf::experimental::{codegen, json, schema};
+
+    // Deeper than UntrustedInput allows
+    let bytes = nested_arrays(100);
+    assert!(json::to_json(&bytes).is_ok());
+    assert!(schema::schema_of(&bytes).is_ok());
+    assert!(codegen::rust_source(&bytes, "Root").is_ok());
+
+    let options = decode_options! { options::UntrustedInput };
+    assert_eq!(json::to_json_with_options(&bytes, &options), Err(DecodeError::LimitExceeded));
+    assert_eq!(schema::schema_of_with_options(&bytes, &options), Err(DecodeError::LimitExceeded));
+    assert_eq!(codegen::rust_source_with_options(&bytes, "Root", &options), Err(DecodeError::LimitExceeded));
+
+    // An array of 2^34 voids, in 7 bytes
+    let mut voids = vec![RootTypeId::ArrayN.into()];
+    encode_prefix_varint(1 << 34, &mut voids);
+    voids.push(ArrayTypeId::Void.into());
+    assert_eq!(voids.len(), 7);
+    assert_eq!(json::to_json_with_options(&voids, &options), Err(DecodeError::LimitExceeded));
+    assert_eq!(
+        json::to_json_with_options(&voids, &decode_options! { options::MaxElements(1 << 20) }),
+        Err(DecodeError::LimitExceeded)
+    );
+
+    let strings = encode(&vec!["long enough".to_owned(); 10]);
+    assert_eq!(
+        json::to_json_with_options(&strings, &decode_options! { options::MaxElements(20) }),
+        Err(DecodeError::LimitExceeded)
+    );
+}
+
+#[test]
+fn max_elements() {
+    let data: Vec<u32> = (0..100).collect();
+    let bytes = encode(&data);
+    assert_eq!(
+        decode_with::<Vec<u32>>(&bytes, &decode_options! { options::MaxElements(50) }),
+        Err(DecodeError::LimitExceeded)
+    );
+    assert_eq!(decode_with::<Vec<u32>>(&bytes, &decode_options! { options::MaxElements(1000) }), Ok(data));
+
+    // The total applies across the whole decode, not each array.
+    let data = make_data();
+    let bytes = encode(&data);
+    assert_eq!(
+        decode_with::<Vec<Record>>(&bytes, &decode_options! { options::MaxElements(1000) }),
+        Err(DecodeError::LimitExceeded)
+    );
+}
+
+#[test]
+fn max_elements_bounds_expansion() {
+    // Compresses very well, so the limit must be checked before expanding the runs.
+    let data = vec![7u64; 100_000];
+    let bytes = encode(&data);
+    assert!(bytes.len() < 20);
+    assert_eq!(
+        decode_with::<Vec<u64>>(&bytes, &decode_options! { options::MaxElements(1000) }),
+        Err(DecodeError::LimitExceeded)
+    );
+
+    let data = vec!["compressible".to_owned(); 10_000];
+    let bytes = encode(&data);
+    assert_eq!(
+        decode_with::<Vec<String>>(&bytes, &decode_options! { options::MaxElements(1000) }),
+        Err(DecodeError::LimitExceeded)
+    );
+
+    let data = Blob(vec![0; 100_000]);
+    let bytes = encode(&data);
+    assert_eq!(
+        decode_with::<Blob>(&bytes, &decode_options! { options::MaxElements(1000) }),
+        Err(DecodeError::LimitExceeded)
+    );
+}
+
+#[test]
+fn max_elements_bounds_fixed_arrays() {
+    // Fixed length arrays nested within each other, with no values. Each
+    // level multiplies how many default values would be allocated.
+    let mut bytes = vec![RootTypeId::ArrayN.into()];
+    encode_prefix_varint(100, &mut bytes);
+    for _ in 0..2 {
+        bytes.push(ArrayTypeId::ArrayFixed.into());
+        encode_prefix_varint(100, &mut bytes);
+    }
+    bytes.push(ArrayTypeId::Void.into());
+
+    assert_eq!(
+        decode_with::<Vec<Vec<Vec<u32>>>>(&bytes, &decode_options! { options::MaxElements(10_000) }),
+        Err(DecodeError::LimitExceeded)
+    );
+    let decoded = decode_with::<Vec<Vec<Vec<u32>>>>(&bytes, &decode_options! { options::MaxElements(1_100_000) }).unwrap();
+    assert_eq!(decoded.len(), 100);
+}
+
+#[test]
+fn max_string_len() {
+    let options = decode_options! { options::MaxStringLen(5) };
+
+    let bytes = encode(&"hello world".to_owned());
+    assert_eq!(decode_with::<String>(&bytes, &options), Err(DecodeError::LimitExceeded));
+
+    let data = vec!["hello".to_owned(), "world".to_owned(), "!".to_owned()];
+    let bytes = encode(&data);
+    assert_eq!(decode_with::<Vec<String>>(&bytes, &options), Ok(data));
+
+    let bytes = encode(&vec!["hello".to_owned(), "world!".to_owned()]);
+    assert_eq!(decode_with::<Vec<String>>(&bytes, &options), Err(DecodeError::LimitExceeded));
+
+    let bytes = encode(&Blob(vec![1, 2, 3, 4, 5, 6]));
+    assert_eq!(decode_with::<Blob>(&bytes, &options), Err(DecodeError::LimitExceeded));
+}
+
+#[test]
+fn max_collection_len() {
+    let options = decode_options! { options::MaxCollectionLen(10) };
+
+    let data: Vec<u32> = (0..11).collect();
+    let bytes = encode(&data);
+    assert_eq!(decode_with::<Vec<u32>>(&bytes, &options), Err(DecodeError::LimitExceeded));
+
+    let data: Vec<Vec<u32>> = vec![(0..10).collect(), (0..11).collect()];
+    let bytes = encode(&data);
+    assert_eq!(decode_with::<Vec<Vec<u32>>>(&bytes, &options), Err(DecodeError::LimitExceeded));
+
+    let data: HashMap<u32, u32> = (0..11).map(|i| (i, i)).collect();
+    let bytes = encode(&data);
+    assert_eq!(decode_with::<HashMap<u32, u32>>(&bytes, &options), Err(DecodeError::LimitExceeded));
+
+    // Also applies to the number of fields in an object
+    let bytes = encode(&make_data()[3]);
+    assert_eq!(
+        decode_with::<Record>(&bytes, &decode_options! { options::MaxCollectionLen(2) }),
+        Err(DecodeError::LimitExceeded)
+    );
+}
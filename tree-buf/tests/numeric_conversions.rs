@@ -0,0 +1,159 @@
+use tree_buf::experimental::options;
+use tree_buf::prelude::*;
+use tree_buf::{decode_options, Decodable, DecodeError};
+
+fn lossy<T: Decodable>(bytes: &[u8]) -> Result<T, DecodeError> {
+    options::decode_with_options(bytes, &decode_options! { options::LossyNumbers })
+}
+
+/// Checks decoding values written as $W into $R, at the root and in arrays.
+/// $lossless is whether every value of $W is exactly a value of $R.
+macro_rules! pair {
+    ($W:ty, $R:ty, $lossless:expr) => {{
+        // Small whole numbers are exact in every type, whatever the encoding.
+        let values: Vec<$W> = vec![0 as $W, 1 as $W, 7 as $W, 100 as $W, 127 as $W, 3 as $W];
+        let expected: Vec<$R> = values.iter().map(|&v| v as $R).collect();
+        assert_eq!(decode::<Vec<$R>>(&encode(&values)), Ok(expected.clone()), "{} to {}", stringify!($W), stringify!($R));
+        for (value, expected) in values.iter().zip(expected.iter()) {
+            assert_eq!(decode::<$R>(&encode(value)), Ok(*expected), "{} to {}", stringify!($W), stringify!($R));
+        }
+
+        // The largest value needs a type which holds it, unless lossy.
+        let max = <$W>::MAX;
+        let values: Vec<$W> = vec![max, 2 as $W, max, 5 as $W];
+        // Through f64, `as` clamps or rounds the same way as LossyNumbers, where
+        // an integer to integer `as` would wrap.
+        let expected: Vec<$R> = values.iter().map(|&v| v as f64 as $R).collect();
+        let root = encode(&max);
+        let array = encode(&values);
+        if $lossless {
+            assert_eq!(decode::<$R>(&root), Ok(max as $R), "{} to {}", stringify!($W), stringify!($R));
+            assert_eq!(decode::<Vec<$R>>(&array), Ok(expected.clone()), "{} to {}", stringify!($W), stringify!($R));
+        } else {
+            assert_eq!(decode::<$R>(&root), Err(DecodeError::SchemaMismatch), "{} to {}", stringify!($W), stringify!($R));
+            assert_eq!(decode::<Vec<$R>>(&array), Err(DecodeError::SchemaMismatch), "{} to {}", stringify!($W), stringify!($R));
+        }
+        assert_eq!(lossy::<$R>(&root), Ok(max as f64 as $R), "{} to {}", stringify!($W), stringify!($R));
+        assert_eq!(lossy::<Vec<$R>>(&array), Ok(expected), "{} to {}", stringify!($W), stringify!($R));
+    }};
+}
+
+#[test]
+fn every_pair() {
+    pair!(u8, u8, true);
+    pair!(u8, u16, true);
+    pair!(u8, u32, true);
+    pair!(u8, u64, true);
+    pair!(u8, f32, true);
+    pair!(u8, f64, true);
+    pair!(u8, i8, false);
+    pair!(u8, i16, true);
+    pair!(u8, i32, true);
+    pair!(u8, i64, true);
+
+    pair!(u16, u8, false);
+    pair!(u16, u16, true);
+    pair!(u16, u32, true);
+    pair!(u16, u64, true);
+    pair!(u16, f32, true);
+    pair!(u16, f64, true);
+    pair!(u16, i8, false);
+    pair!(u16, i16, false);
+    pair!(u16, i32, true);
+    pair!(u16, i64, true);
+
+    pair!(u32, u8, false);
+    pair!(u32, u16, false);
+    pair!(u32, u32, true);
+    pair!(u32, u64, true);
+    pair!(u32, f32, false);
+    pair!(u32, f64, true);
+    pair!(u32, i8, false);
+    pair!(u32, i16, false);
+    pair!(u32, i32, false);
+    pair!(u32, i64, true);
+
+    pair!(u64, u8, false);
+    pair!(u64, u16, false);
+    pair!(u64, u32, false);
+    pair!(u64, u64, true);
+    pair!(u64, f32, false);
+    pair!(u64, f64, false);
+    pair!(u64, i8, false);
+    pair!(u64, i16, false);
+    pair!(u64, i32, false);
+    pair!(u64, i64, false);
+
+    pair!(f32, u8, false);
+    pair!(f32, u16, false);
+    pair!(f32, u32, false);
+    pair!(f32, u64, false);
+    pair!(f32, f32, true);
+    pair!(f32, f64, true);
+    pair!(f32, i8, false);
+    pair!(f32, i16, false);
+    pair!(f32, i32, false);
+    pair!(f32, i64, false);
+
+    pair!(f64, u8, false);
+    pair!(f64, u16, false);
+    pair!(f64, u32, false);
+    pair!(f64, u64, false);
+    pair!(f64, f32, false);
+    pair!(f64, f64, true);
+    pair!(f64, i8, false);
+    pair!(f64, i16, false);
+    pair!(f64, i32, false);
+    pair!(f64, i64, false);
+}
+
+#[test]
+fn fractions_are_not_integers() {
+    let values = vec![2.5f64, 3.0, -1.0, 4.0];
+    let bytes = encode(&values);
+    assert_eq!(decode::<Vec<u32>>(&bytes), Err(DecodeError::SchemaMismatch));
+    assert_eq!(lossy::<Vec<u32>>(&bytes), Ok(vec![2, 3, 0, 4]));
+
+    assert_eq!(decode::<u8>(&encode(&2.5f32)), Err(DecodeError::SchemaMismatch));
+    assert_eq!(decode::<u8>(&encode(&-1.0f64)), Err(DecodeError::SchemaMismatch));
+    assert_eq!(lossy::<u8>(&encode(&-1.0f64)), Ok(0));
+    assert_eq!(decode::<u64>(&encode(&f64::NAN)), Err(DecodeError::SchemaMismatch));
+    assert_eq!(lossy::<u64>(&encode(&f64::NAN)), Ok(0));
+}
+
+#[test]
+fn floats_which_fit_convert() {
+    // These are exactly representable as f32
+    let values = vec![0.5f64, -2.25, 1e10, f64::INFINITY];
+    assert_eq!(decode::<Vec<f32>>(&encode(&values)), Ok(vec![0.5f32, -2.25, 1e10, f32::INFINITY]));
+    assert!(decode::<f32>(&encode(&f64::NAN)).unwrap().is_nan());
+
+    // But these are not
+    assert_eq!(decode::<f32>(&encode(&1.1f64)), Err(DecodeError::SchemaMismatch));
+    assert_eq!(lossy::<f32>(&encode(&1.1f64)), Ok(1.1f32));
+
+    // 2^53 + 1 is the smallest integer which an f64 rounds
+    let big = (1u64 << 53) + 1;
+    assert_eq!(decode::<f64>(&encode(&(1u64 << 53))), Ok((1u64 << 53) as f64));
+    assert_eq!(decode::<Vec<f64>>(&encode(&vec![big, 1, 2])), Err(DecodeError::SchemaMismatch));
+    assert_eq!(lossy::<Vec<f64>>(&encode(&vec![big, 1, 2])), Ok(vec![big as f64, 1.0, 2.0]));
+}
+
+#[test]
+fn negative_roots() {
+    use std::time::{Duration, UNIX_EPOCH};
+
+    // A time before 1970 is a negative integer at the root, which only the signed types hold.
+    let bytes = encode(&(UNIX_EPOCH - Duration::from_secs(5)));
+    assert_eq!(decode::<i64>(&bytes), Ok(-5));
+    assert_eq!(decode::<i8>(&bytes), Ok(-5));
+    assert_eq!(decode::<u64>(&bytes), Err(DecodeError::SchemaMismatch));
+
+    let bytes = encode(&(UNIX_EPOCH - Duration::from_secs(1000)));
+    assert_eq!(decode::<i16>(&bytes), Ok(-1000));
+    assert_eq!(decode::<i8>(&bytes), Err(DecodeError::SchemaMismatch));
+    assert_eq!(lossy::<i8>(&bytes), Ok(i8::MIN));
+
+    assert_eq!(decode::<i32>(&encode(&-2.0f64)), Ok(-2));
+    assert_eq!(decode::<Vec<i32>>(&encode(&vec![-2.0f64, 3.0, -4.0])), Ok(vec![-2, 3, -4]));
+}
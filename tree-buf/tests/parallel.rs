@@ -0,0 +1,61 @@
+use std::collections::HashMap;
+use tree_buf::experimental::options;
+use tree_buf::prelude::*;
+use tree_buf::{encode_options, Decodable, Encodable};
+
+#[derive(Encode, Decode, Debug, PartialEq, Clone)]
+struct Inner {
+    name: String,
+    values: Vec<u64>,
+    ratio: f64,
+}
+
+#[derive(Encode, Decode, Debug, PartialEq, Clone)]
+struct Outer {
+    id: u32,
+    inner: Vec<Inner>,
+    flags: Vec<bool>,
+    lookup: HashMap<String, Option<u32>>,
+    blob: Vec<u8>,
+}
+
+fn make_data() -> Vec<Outer> {
+    (0..50u32)
+        .map(|i| Outer {
+            id: i,
+            inner: (0..i % 7)
+                .map(|j| Inner {
+                    name: format!("inner-{}", j % 3),
+                    values: (0..j).map(|v| v as u64 * 1000 + i as u64).collect(),
+                    ratio: j as f64 / 3.0,
+                })
+                .collect(),
+            flags: (0..i).map(|f| f % 5 == 1).collect(),
+            lookup: (0..i % 4).map(|k| (format!("k{}", k), if k == 1 { None } else { Some(k * i) })).collect(),
+            blob: (0..i as u8).collect(),
+        })
+        .collect()
+}
+
+fn assert_parallel_matches<T: Encodable + Decodable + PartialEq + std::fmt::Debug>(value: &T) {
+    let parallel = options::encode_with_options(value, &encode_options! { options::EnableParallel });
+    assert_eq!(parallel, encode(value));
+    assert_eq!(&decode::<T>(&parallel).unwrap(), value);
+}
+
+#[test]
+fn parallel_matches_sequential() {
+    assert_parallel_matches(&make_data());
+    assert_parallel_matches(&make_data()[3]);
+    assert_parallel_matches(&Vec::<Outer>::new());
+    assert_parallel_matches(&(1u32, String::from("tuple"), vec![1.5f64, 2.5]));
+}
+
+#[test]
+fn parallel_with_checksums() {
+    let data = make_data();
+    let sequential = options::encode_with_options(&data, &encode_options! { options::Checksums });
+    let parallel = options::encode_with_options(&data, &encode_options! { options::Checksums, options::EnableParallel });
+    assert_eq!(parallel, sequential);
+    assert_eq!(decode::<Vec<Outer>>(&parallel), Ok(data));
+}
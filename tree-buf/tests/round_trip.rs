@@ -5,6 +5,7 @@ use common::*;
 use std::collections::HashMap;
 use tree_buf::encode_options;
 use tree_buf::experimental::options;
+use tree_buf::Blob;
 
 // Create this namespace to hide the prelude. This is a check that the hygenics do not require any types from tree_buf to be imported
 mod hide_namespace {
@@ -87,6 +88,29 @@ fn ints_root() {
     }
 }
 
+#[test]
+fn large_ints_root() {
+    round_trip(&((1u64 << 56) - 1), 8, 14);
+    round_trip(&(1u64 << 56), 9, 14);
+    round_trip(&u64::MAX, 9, 6);
+}
+
+// Found by fuzzing. The lens are RLE bools, which must not be confused by padding.
+#[test]
+fn mostly_empty_strings() {
+    let mut data = vec![String::new(); 34];
+    data[4] = "0".to_owned();
+    data[5] = "@".to_owned();
+    round_trip(&data, 15, 23);
+
+    // Lens of only 0 and 1 are written as RLE bools. Whatever the number of runs, decoding
+    // must not find more lens than were written.
+    for len in 25..100 {
+        let data: Vec<String> = (0..len).map(|i| if i % 3 == 0 { String::from("x") } else { String::new() }).collect();
+        round_trip(&data, None, None);
+    }
+}
+
 // Special case for 1 element array encodes root object
 #[test]
 fn array1() {
@@ -480,6 +504,15 @@ fn delta_prefix_var() {
     round_trip(&data, 49, 96);
 }
 
+#[test]
+fn delta_prefix_var_64() {
+    // Millisecond timestamps, which don't fit in u32
+    let data: Vec<u64> = (0..20).map(|i| 1_600_000_000_000 + i * 1_000 + i % 3).collect();
+    round_trip(&data, 48, 91);
+    // Deltas wrap around
+    round_trip(&vec![u64::MAX, 0, u64::MAX, 1, u64::MAX - 2], 9, 16);
+}
+
 #[test]
 fn big_brotli_str() {
     let data = owned_vec(vec![
@@ -539,6 +572,74 @@ fn big_brotli_str() {
     round_trip(&data, 2347, 2405);
 }
 
+#[test]
+fn sorted_strings_using_front_coding() {
+    let data: Vec<_> = (0..100).map(|i| format!("https://example.com/api/v1/items/{}", i * 7)).collect();
+
+    // Brotli is smaller here, but is much slower to decode. See also 9003b01b-83e8-4acc-9f38-d584a37e20c6
+    //round_trip(&data, 209, 221);
+
+    round_trip(&data, 392, 740);
+}
+
+#[test]
+fn short_strings_using_fsst() {
+    let data: Vec<_> = (0..100).map(|i| format!("{}:{}", i * 37 % 100, ["red", "green", "blue", "black"][i % 4])).collect();
+
+    // Without FSST:
+    //round_trip(&data, 820, 1010);
+
+    round_trip(&data, 423, 1010);
+}
+
+#[test]
+fn hex_strings() {
+    let data: Vec<_> = (1..=50u64).map(|i| format!("0x{:016x}", i.wrapping_mul(0x9E3779B97F4A7C15))).collect();
+    round_trip(&data, 413, 815);
+
+    // 40 digit addresses, where Brotli must not win on its estimate alone
+    let data: Vec<_> = (1..=50u64)
+        .map(|i| {
+            let a = i.wrapping_mul(0x9E3779B97F4A7C15);
+            let b = a.wrapping_mul(0xBF58476D1CE4E5B9);
+            format!("0x{:016x}{:016x}{:08x}", a, b, b >> 32)
+        })
+        .collect();
+    assert!(tree_buf::experimental::stats::size_breakdown(&encode(&data)).unwrap().contains("HexUtf8"));
+    round_trip(&data, 1013, 2015);
+}
+
+#[test]
+fn decimal_strings() {
+    let data: Vec<_> = (0..50).map(|i| if i == 0 { "0".to_owned() } else { format!("{}000000000000000000", i * 25) }).collect();
+    round_trip(&data, 119, 234);
+}
+
+#[test]
+fn blobs() {
+    use rand::{Rng, SeedableRng};
+    let mut rng = rand::rngs::StdRng::seed_from_u64(0);
+
+    // Compressible payloads
+    let text: Vec<u8> = (0..200).flat_map(|i| format!("{{\"id\":{},\"name\":\"item\"}}", i).into_bytes()).collect();
+    round_trip(&Blob(text.clone()), 266, 280);
+
+    // Incompressible payloads are stored as-is
+    let noise: Vec<u8> = (0..1000).map(|_| rng.gen()).collect();
+    round_trip(&Blob(noise.clone()), 1003, 1028);
+
+    let data = vec![Blob(text), Blob(Vec::new()), Blob(noise)];
+    round_trip(&data, 1289, 1305);
+}
+
+#[cfg(feature = "bytes")]
+#[test]
+fn bytes_crate() {
+    let data = bytes::Bytes::from_static(b"Encoded the same as a Blob");
+    round_trip(&data, 23, 36);
+    assert_eq!(encode(&data), encode(&Blob(data.to_vec())));
+}
+
 // TODO: Use coverage marks to ensure all types are used
 // https://ferrous-systems.com/blog/coverage-marks/
 
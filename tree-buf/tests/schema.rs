@@ -0,0 +1,103 @@
+use std::collections::HashMap;
+use tree_buf::experimental::schema::*;
+use tree_buf::prelude::*;
+
+#[derive(Encode, Decode, Debug, PartialEq, Clone)]
+enum Shape {
+    Empty,
+    Circle(f64),
+    Named(String),
+}
+
+#[derive(Encode, Decode, Debug, PartialEq, Clone)]
+struct Point {
+    x: u64,
+    y: u64,
+}
+
+#[derive(Encode, Decode, Debug, PartialEq, Clone)]
+struct Item {
+    id: u32,
+    name: String,
+    label: Option<String>,
+    shape: Shape,
+    pair: (bool, Vec<u8>),
+    points: Vec<Point>,
+    attrs: HashMap<String, u64>,
+}
+
+fn make_items(count: u32, repetitive: bool) -> Vec<Item> {
+    (0..count)
+        .map(|i| {
+            let n = if repetitive { 1 } else { i };
+            Item {
+                id: n * 1000,
+                name: format!("item-{}", n),
+                label: if i % 2 == 0 { Some(format!("label-{}", n * 7)) } else { None },
+                shape: match i % 3 {
+                    0 => Shape::Empty,
+                    1 => Shape::Circle(n as f64 * 1.5),
+                    _ => Shape::Named(format!("shape-{}", n)),
+                },
+                pair: (i % 2 == 0, vec![n as u8 + 5; 3]),
+                points: (0..i % 4)
+                    .map(|j| Point {
+                        x: (n + j) as u64,
+                        y: (n * j) as u64,
+                    })
+                    .collect(),
+                attrs: (0..i % 3).map(|j| (format!("key-{}", j), (n + j) as u64)).collect(),
+            }
+        })
+        .collect()
+}
+
+#[test]
+fn renders_like_a_type_declaration() {
+    let schema = schema_of(&encode(&make_items(50, false))).unwrap();
+    let expected = "Vec<{
+    attrs: HashMap<String, uint>,
+    id: uint,
+    label: Option<String>,
+    name: String,
+    pair: (bool, Vec<uint>),
+    points: Vec<{
+        x: uint,
+        y: uint,
+    }>,
+    shape: enum {
+        circle(float),
+        empty,
+        named(String),
+    },
+}>";
+    assert_eq!(format!("{}", schema), expected);
+}
+
+#[test]
+fn encoding_does_not_change_the_schema() {
+    // Repetitive data is written with different encodings (Eg: RLE and Dictionary) than varied data.
+    let varied = schema_of(&encode(&make_items(200, false))).unwrap();
+    let repetitive = schema_of(&encode(&make_items(200, true))).unwrap();
+    assert_eq!(varied, repetitive);
+}
+
+#[test]
+fn root_values() {
+    assert_eq!(schema_of(&encode(&500u32)), Ok(Schema::Integer(IntegerKind::Unsigned)));
+    assert_eq!(schema_of(&encode(&1.25f64)), Ok(Schema::Float(FloatKind::F64)));
+    assert_eq!(schema_of(&encode(&"text".to_owned())), Ok(Schema::String));
+    assert_eq!(schema_of(&encode(&Vec::<u64>::new())), Ok(Schema::Array(Box::new(Schema::Void))));
+
+    let schema = schema_of(&encode(&Shape::Named("name".to_owned()))).unwrap();
+    assert_eq!(
+        schema,
+        Schema::Enum(vec![Field {
+            name: "named".to_owned(),
+            schema: Schema::String
+        }])
+    );
+
+    let point = schema_of(&encode(&Point { x: 1, y: 2 })).unwrap();
+    assert_eq!(format!("{}", point), "{\n    x: uint,\n    y: uint,\n}");
+}
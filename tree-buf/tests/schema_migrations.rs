@@ -52,3 +52,83 @@ fn fixed_array_to_vec() {
     let vec: Vec<Vec<_>> = fixed.iter().map(|f| f.iter().copied().collect()).collect();
     serialize_eq(&fixed, &vec, 14);
 }
+
+mod unknown_variants {
+    use super::*;
+
+    #[derive(Encode, Debug, PartialEq, Clone)]
+    pub enum Writer {
+        Circle(f64),
+        Square(u32),
+        Point,
+    }
+
+    #[derive(Decode, Debug, PartialEq, Clone)]
+    pub enum UnitOther {
+        Circle(f64),
+        #[tree_buf(other)]
+        Unknown,
+    }
+
+    #[derive(Encode, Decode, Debug, PartialEq, Clone)]
+    pub enum NamedOther {
+        Circle(f64),
+        #[tree_buf(other)]
+        Unknown(String),
+    }
+
+    #[derive(Decode, Debug, PartialEq, Clone)]
+    pub enum NoOther {
+        Circle(f64),
+    }
+
+    #[test]
+    fn decode_as_other() {
+        assert_eq!(decode::<UnitOther>(&encode(&Writer::Square(2))), Ok(UnitOther::Unknown));
+        assert_eq!(decode::<UnitOther>(&encode(&Writer::Circle(1.5))), Ok(UnitOther::Circle(1.5)));
+        assert_eq!(decode::<NamedOther>(&encode(&Writer::Point)), Ok(NamedOther::Unknown("point".to_owned())));
+        assert_eq!(decode::<NoOther>(&encode(&Writer::Point)), Err(tree_buf::DecodeError::SchemaMismatch));
+
+        let shapes = vec![Writer::Circle(1.5), Writer::Square(2), Writer::Point, Writer::Square(3), Writer::Circle(2.5)];
+        let bytes = encode(&shapes);
+        assert_eq!(
+            decode::<Vec<UnitOther>>(&bytes),
+            Ok(vec![
+                UnitOther::Circle(1.5),
+                UnitOther::Unknown,
+                UnitOther::Unknown,
+                UnitOther::Unknown,
+                UnitOther::Circle(2.5)
+            ])
+        );
+        assert_eq!(
+            decode::<Vec<NamedOther>>(&bytes),
+            Ok(vec![
+                NamedOther::Circle(1.5),
+                NamedOther::Unknown("square".to_owned()),
+                NamedOther::Unknown("point".to_owned()),
+                NamedOther::Unknown("square".to_owned()),
+                NamedOther::Circle(2.5),
+            ])
+        );
+        assert_eq!(decode::<Vec<NoOther>>(&bytes), Err(tree_buf::DecodeError::SchemaMismatch));
+    }
+
+    #[test]
+    fn other_round_trips() {
+        // The other variant is written like any other, so a reader that was written with it decodes the same.
+        let values = vec![NamedOther::Unknown("square".to_owned()), NamedOther::Circle(1.0)];
+        assert_eq!(decode::<Vec<NamedOther>>(&encode(&values)), Ok(values.clone()));
+        assert_eq!(decode::<NamedOther>(&encode(&values[0])), Ok(values[0].clone()));
+    }
+
+    #[test]
+    fn compatibility() {
+        use tree_buf::experimental::compat::*;
+
+        let compatibility = check_compatible::<Writer, NamedOther>();
+        assert!(compatibility.is_compatible());
+        assert_eq!(format!("{}", compatibility), "square: read as unknown\npoint: read as unknown\n");
+        assert!(!check_compatible::<Writer, NoOther>().is_compatible());
+    }
+}
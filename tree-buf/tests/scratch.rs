@@ -0,0 +1,113 @@
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+use tree_buf::experimental::scratch::{encode_into_with_scratch, scratch};
+use tree_buf::prelude::*;
+
+struct CountingAllocator;
+
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let _ = ALLOCATIONS.try_with(|count| count.set(count.get() + 1));
+        System.alloc(layout)
+    }
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+fn count_allocations(f: impl FnOnce()) -> usize {
+    let before = ALLOCATIONS.with(Cell::get);
+    f();
+    ALLOCATIONS.with(Cell::get) - before
+}
+
+#[derive(Encode, Decode, Debug, PartialEq, Clone)]
+struct Message {
+    id: u64,
+    name: String,
+    readings: Vec<f64>,
+    flags: Vec<bool>,
+    tags: Option<Vec<String>>,
+}
+
+fn make_message(i: u64) -> Message {
+    Message {
+        id: i,
+        name: format!("message-{}", i % 3),
+        readings: (0..i).map(|r| r as f64 * 0.5).collect(),
+        flags: (0..i * 10).map(|f| f % 7 == 0).collect(),
+        tags: if i % 2 == 1 { Some(vec!["a".to_owned(), "b".to_owned()]) } else { None },
+    }
+}
+
+#[test]
+fn scratch_matches_encode() {
+    let mut scratch = scratch::<Vec<Message>>();
+    let mut bytes = Vec::new();
+    for i in 0..20 {
+        let value: Vec<_> = (0..i).map(make_message).collect();
+        bytes.clear();
+        encode_into_with_scratch(&value, &mut scratch, &mut bytes);
+        assert_eq!(bytes, encode(&value));
+        assert_eq!(decode::<Vec<Message>>(&bytes), Ok(value));
+    }
+}
+
+#[test]
+fn scratch_appends() {
+    let mut scratch = scratch::<Message>();
+    let mut bytes = vec![1, 2, 3];
+    let value = make_message(5);
+    encode_into_with_scratch(&value, &mut scratch, &mut bytes);
+    assert_eq!(&bytes[..3], &[1, 2, 3]);
+    assert_eq!(decode::<Message>(&bytes[3..]), Ok(value));
+}
+
+#[test]
+fn scratch_is_reusable_across_types() {
+    let mut scratch = scratch::<Vec<u32>>();
+    let mut bytes = Vec::new();
+    let ints: Vec<u32> = (0..1000).map(|i| i % 17).collect();
+    encode_into_with_scratch(&ints, &mut scratch, &mut bytes);
+    assert_eq!(decode::<Vec<u32>>(&bytes), Ok(ints));
+
+    bytes.clear();
+    let strings: Vec<String> = (0..1000).map(|i| format!("{}", i % 5)).collect();
+    encode_into_with_scratch(&strings, &mut scratch, &mut bytes);
+    assert_eq!(decode::<Vec<String>>(&bytes), Ok(strings));
+}
+
+#[derive(Encode, Decode, Debug, PartialEq, Clone)]
+struct Reading {
+    time: u64,
+    values: Vec<f64>,
+    valid: Vec<bool>,
+}
+
+#[test]
+fn scratch_reduces_allocations() {
+    let value: Vec<_> = (0..100u64)
+        .map(|i| Reading {
+            time: i * 1000,
+            values: (0..i % 10).map(|v| v as f64 * 0.25).collect(),
+            valid: (0..i % 10).map(|v| v % 3 != 0).collect(),
+        })
+        .collect();
+    let without = count_allocations(|| drop(encode(&value)));
+
+    let mut scratch = scratch::<Vec<Reading>>();
+    let mut bytes = Vec::new();
+    encode_into_with_scratch(&value, &mut scratch, &mut bytes);
+    let with = count_allocations(|| {
+        bytes.clear();
+        encode_into_with_scratch(&value, &mut scratch, &mut bytes);
+    });
+    assert!(with < without / 2, "{} allocations with scratch, {} without", with, without);
+}
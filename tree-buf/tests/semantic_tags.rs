@@ -0,0 +1,192 @@
+mod common;
+
+use common::*;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::vec::IntoIter;
+use tree_buf::experimental::json::to_json;
+use tree_buf::experimental::schema::{schema_of, IntegerKind, Schema};
+use tree_buf::experimental::semantic::{SemanticTag, TimeUnit};
+use tree_buf::internal::{
+    encode_tagged_root, flush_tagged, ArrayTypeId, DecodeOptions, DynArrayBranch, DynRootBranch, EncodeOptions, EncoderArray, EncoderStream, InfallibleDecoderArray, RootTypeId,
+};
+use tree_buf::prelude::*;
+use tree_buf::{Blob, Decodable, DecodeError, Encodable};
+
+pub struct Decoder<T>(IntoIter<T>);
+
+/// A newtype which writes its value with a tag, the way a type outside of this crate would.
+macro_rules! tagged {
+    ($name:ident($inner:ty), $tag:expr) => {
+        #[derive(Clone, Debug, Default, PartialEq)]
+        pub struct $name($inner);
+
+        impl Encodable for $name {
+            type EncoderArray = Vec<$name>;
+            fn encode_root<O: EncodeOptions>(&self, stream: &mut EncoderStream<'_, O>) -> RootTypeId {
+                encode_tagged_root($tag, stream, |stream| self.0.encode_root(stream))
+            }
+        }
+
+        impl EncoderArray<$name> for Vec<$name> {
+            fn buffer_one<'a, 'b: 'a>(&'a mut self, value: &'b $name) {
+                self.push(value.clone());
+            }
+            fn flush<O: EncodeOptions>(self, stream: &mut EncoderStream<'_, O>) -> ArrayTypeId {
+                let values: Vec<$inner> = self.into_iter().map(|value| value.0).collect();
+                flush_tagged($tag, stream, |stream| <$inner as Encodable>::EncoderArray::encode_all(&values, stream))
+            }
+        }
+
+        impl Decodable for $name {
+            type DecoderArray = Decoder<$name>;
+            fn decode(sticks: DynRootBranch<'_>, options: &impl DecodeOptions) -> Result<Self, DecodeError> {
+                Ok($name(<$inner>::decode(sticks.expect_tag(|tag| tag == $tag)?, options)?))
+            }
+        }
+
+        impl InfallibleDecoderArray for Decoder<$name> {
+            type Decode = $name;
+            fn new_infallible(sticks: DynArrayBranch<'_>, options: &impl DecodeOptions) -> Result<Self, DecodeError> {
+                let values = IntoIter::<$inner>::new_infallible(sticks.expect_tag(|tag| tag == $tag)?, options)?;
+                Ok(Decoder(values.map($name).collect::<Vec<_>>().into_iter()))
+            }
+            fn decode_next_infallible(&mut self) -> $name {
+                self.0.next().unwrap_or_default()
+            }
+        }
+    };
+}
+
+tagged!(Millis(u64), SemanticTag::Timestamp(TimeUnit::Millis));
+tagged!(Seconds(u64), SemanticTag::Timestamp(TimeUnit::Seconds));
+tagged!(Elapsed(u64), SemanticTag::Duration(TimeUnit::Nanos));
+tagged!(Uuid(Blob), SemanticTag::Uuid);
+tagged!(Cents(u64), SemanticTag::Decimal { scale: 2 });
+tagged!(FromLaterVersion(u64), SemanticTag::Unknown { kind: 99, param: 1 });
+
+#[test]
+fn ip_addresses() {
+    let v4 = Ipv4Addr::new(192, 168, 0, 1);
+    let v6 = Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1);
+    round_trip(&v4, None, None);
+    round_trip(&v6, None, None);
+    round_trip(&IpAddr::V4(v4), None, None);
+    round_trip(&vec![IpAddr::V4(v4), IpAddr::V6(v6)], None, None);
+
+    serialize_eq(&v4, &IpAddr::V4(v4), None);
+    serialize_eq(&IpAddr::V6(v6), &v6, None);
+    assert_eq!(decode::<Ipv4Addr>(&encode(&v6)), Err(DecodeError::SchemaMismatch));
+    assert_eq!(decode::<Vec<Ipv6Addr>>(&encode(&vec![v4])), Err(DecodeError::SchemaMismatch));
+}
+
+#[test]
+fn tags_are_skipped_by_untagged_types() {
+    serialize_eq(&Millis(1_600_000_000_000), &1_600_000_000_000u64, None);
+    serialize_eq(&vec![Cents(5), Cents(12345)], &vec![5u64, 12345], None);
+    serialize_eq(&Ipv4Addr::new(10, 0, 0, 1), &Blob(vec![10, 0, 0, 1]), None);
+    serialize_eq(&FromLaterVersion(3), &3.0f64, None);
+
+    // And values without a tag may be read by tagged types
+    serialize_eq(&1_600_000_000_000u64, &Millis(1_600_000_000_000), None);
+    serialize_eq(&Blob(vec![10, 0, 0, 1]), &Ipv4Addr::new(10, 0, 0, 1), None);
+}
+
+#[test]
+fn tagged_types_check_the_tag() {
+    round_trip(&Millis(1_600_000_000_000), None, None);
+    round_trip(&Uuid(Blob((0..16).collect())), None, None);
+
+    assert_eq!(decode::<Millis>(&encode(&Seconds(1_600_000_000))), Err(DecodeError::SchemaMismatch));
+    assert_eq!(decode::<Vec<Millis>>(&encode(&vec![Elapsed(1), Elapsed(2)])), Err(DecodeError::SchemaMismatch));
+    assert_eq!(decode::<Ipv4Addr>(&encode(&Uuid(Blob(vec![1, 2, 3, 4])))), Err(DecodeError::SchemaMismatch));
+}
+
+#[test]
+fn schema() {
+    let tagged = |tag, schema| Schema::Tagged(tag, Box::new(schema));
+    assert_eq!(
+        schema_of(&encode(&Millis(5))),
+        Ok(tagged(SemanticTag::Timestamp(TimeUnit::Millis), Schema::Integer(IntegerKind::Unsigned)))
+    );
+    assert_eq!(schema_of(&encode(&IpAddr::V4(Ipv4Addr::LOCALHOST))), Ok(tagged(SemanticTag::IpAddr, Schema::Blob)));
+    assert_eq!(
+        schema_of(&encode(&vec![FromLaterVersion(1), FromLaterVersion(2)])),
+        Ok(Schema::Array(Box::new(tagged(
+            SemanticTag::Unknown { kind: 99, param: 1 },
+            Schema::Integer(IntegerKind::Unsigned)
+        ))))
+    );
+
+    let schema = schema_of(&encode(&vec![event(0), event(1)])).unwrap();
+    let expected = "Vec<{
+    at: uint /* timestamp(ms) */,
+    id: Blob /* uuid */,
+    ip: Blob /* ip */,
+    kind: enum {
+        done(uint),
+        started,
+    },
+    price: uint /* decimal(2) */,
+    retries: Option<uint>,
+    took: uint /* duration(ns) */,
+}>";
+    assert_eq!(schema.to_string(), expected);
+}
+
+#[derive(Encode, Decode, Clone, Debug, PartialEq)]
+pub enum Kind {
+    Started,
+    Done(u32),
+}
+
+#[derive(Encode, Decode, Clone, Debug, PartialEq)]
+pub struct Event {
+    at: Millis,
+    took: Elapsed,
+    ip: IpAddr,
+    id: Uuid,
+    price: Cents,
+    kind: Kind,
+    retries: Option<u8>,
+}
+
+fn event(n: u8) -> Event {
+    Event {
+        at: Millis(1_600_000_000_500 + n as u64 * 1000),
+        took: Elapsed(1_500_000_000 * (n as u64 + 1)),
+        ip: if n == 0 {
+            IpAddr::V4(Ipv4Addr::new(192, 168, 0, 1))
+        } else {
+            IpAddr::V6(Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, n as u16))
+        },
+        id: Uuid(Blob((n..n + 16).collect())),
+        price: Cents(12345 + n as u64 * 10000),
+        kind: if n == 0 { Kind::Started } else { Kind::Done(n as u32 + 8) },
+        retries: if n == 0 { None } else { Some(n + 6) },
+    }
+}
+
+#[test]
+fn json() {
+    let first = r#"{"at":"2020-09-13T12:26:40.5Z","id":"00010203-0405-0607-0809-0a0b0c0d0e0f","ip":"192.168.0.1","kind":"started","price":123.45,"retries":null,"took":"PT1.5S"}"#;
+    let second = r#"{"at":"2020-09-13T12:26:41.5Z","id":"01020304-0506-0708-090a-0b0c0d0e0f10","ip":"2001:db8::1","kind":{"done":9},"price":223.45,"retries":7,"took":"PT3S"}"#;
+
+    assert_eq!(to_json(&encode(&event(0))).unwrap(), first);
+    assert_eq!(to_json(&encode(&event(1))).unwrap(), second);
+    assert_eq!(to_json(&encode(&vec![event(0), event(1)])).unwrap(), format!("[{},{}]", first, second));
+
+    assert_eq!(to_json(&encode(&Seconds(0))).unwrap(), r#""1970-01-01T00:00:00Z""#);
+    assert_eq!(to_json(&encode(&Cents(5))).unwrap(), "0.05");
+    assert_eq!(to_json(&encode(&FromLaterVersion(5))).unwrap(), "5");
+}
+
+#[test]
+fn json_without_tags() {
+    // A map with one entry, since the order of the entries isn't known
+    let mut map = std::collections::HashMap::new();
+    map.insert(5u32, vec!["a\"b".to_owned(), String::new()]);
+    assert_eq!(to_json(&encode(&map)).unwrap(), r#"{"5":["a\"b",""]}"#);
+    assert_eq!(to_json(&encode(&vec![map.clone(), map])).unwrap(), r#"[{"5":["a\"b",""]},{"5":["a\"b",""]}]"#);
+    assert_eq!(to_json(&encode(&(vec![2.5f64, 3.0], true, Blob(vec![255, 0])))).unwrap(), r#"[[2.5,3],true,"ff00"]"#);
+    assert_eq!(to_json(&encode(&vec![Some(vec![5u32, 6]), None])).unwrap(), "[[5,6],null]");
+}
@@ -0,0 +1,125 @@
+use tree_buf::encode_options;
+use tree_buf::experimental::options;
+use tree_buf::experimental::stats::{size_breakdown, size_report, size_report_with_options};
+use tree_buf::internal::encodings::varint::{encode_prefix_varint, encode_suffix_varint};
+use tree_buf::internal::{ArrayTypeId, RootTypeId};
+use tree_buf::prelude::*;
+use tree_buf::{decode_options, DecodeError};
+
+#[derive(Encode, Decode, Debug, PartialEq, Clone)]
+struct Row {
+    id: u64,
+    name: String,
+    scores: Vec<u32>,
+    note: Option<String>,
+}
+
+fn make_data() -> Vec<Row> {
+    (0..100u64)
+        .map(|i| Row {
+            id: i * 1000,
+            name: format!("row-{}", i % 7),
+            scores: (0..i % 5).map(|s| s as u32 * 3).collect(),
+            note: if i % 4 == 0 { Some(format!("note {}", i)) } else { None },
+        })
+        .collect()
+}
+
+#[test]
+fn report_accounts_for_every_byte() {
+    let bytes = encode(&make_data());
+    let report = size_report(&bytes).unwrap();
+
+    assert_eq!(report.total, bytes.len());
+    let data: usize = report.paths.iter().map(|p| p.size).sum();
+    assert_eq!(data + report.overhead.total(), report.total);
+    assert_eq!(report.by_type.iter().map(|t| t.size).sum::<usize>(), data);
+    // The only thing left over is the len of the root array.
+    assert_eq!(report.overhead.other, 1);
+    assert!(report.overhead.type_ids > 0);
+    assert_eq!(report.overhead.idents, ["id", "name", "scores", "note"].iter().map(|s| s.len() + 1).sum::<usize>());
+
+    // The same holds with checksums after each len
+    let bytes = options::encode_with_options(&make_data(), &encode_options! { options::Checksums });
+    let report = size_report(&bytes).unwrap();
+    // Plus the leading type id and trailing checksum of the file.
+    assert_eq!(report.overhead.other, 1 + 1 + 4);
+}
+
+#[test]
+fn report_counts_values_and_parents() {
+    let bytes = encode(&make_data());
+    let report = size_report(&bytes).unwrap();
+
+    let path = |name: &str| report.paths.iter().find(|p| p.path == name).unwrap_or_else(|| panic!("{} in {:?}", name, report));
+    assert_eq!(path("[100].id").count, Some(100));
+    assert_eq!(path("[100].id").parent, "[100]");
+    assert_eq!(path("[100].scores.values").count, Some((0..100).map(|i| i % 5).sum()));
+    assert_eq!(path("[100].scores.values").parent, "[100].scores");
+    assert!(path("[100].scores.values").types.ends_with(&path("[100].scores.values").encoding));
+    assert_eq!(path("[100].note.opt").count, Some(100));
+    // The notes share a prefix, and the count of suffixes isn't known without decoding them.
+    assert_eq!(path("[100].note.values.prefixes").count, Some(25));
+    assert_eq!(path("[100].note.values.suffixes").count, None);
+
+    // Largest first
+    assert!(report.paths.windows(2).all(|w| w[0].size >= w[1].size));
+}
+
+#[test]
+fn report_formats() {
+    let bytes = encode(&make_data());
+    let report = size_report(&bytes).unwrap();
+
+    assert_eq!(report.to_string(), size_breakdown(&bytes).unwrap());
+    assert!(report.to_string().contains(&format!("Other: {}\nTotal: {}\n", report.overhead.total(), bytes.len())));
+
+    let csv = report.to_csv();
+    let mut lines = csv.lines();
+    assert_eq!(lines.next(), Some("path,parent,types,encoding,size,count"));
+    let first = &report.paths[0];
+    assert_eq!(
+        lines.next().unwrap(),
+        format!("{},{},{},{},{},{}", first.path, first.parent, first.types, first.encoding, first.size, first.count.unwrap())
+    );
+    assert_eq!(csv.lines().count(), report.paths.len() + 1);
+
+    let json = report.to_json();
+    assert!(json.starts_with(&format!("{{\"total\":{},\"overhead\":{{\"type_ids\":{},", report.total, report.overhead.type_ids)));
+    assert!(json.contains(&format!(
+        "{{\"path\":\"{}\",\"parent\":\"{}\",\"types\":\"{}\",\"encoding\":\"{}\",\"size\":{},\"count\":{}}}",
+        first.path,
+        first.parent,
+        first.types,
+        first.encoding,
+        first.size,
+        first.count.unwrap()
+    )));
+    assert!(json.ends_with("]}"));
+}
+
+#[test]
+fn counts_are_bounded() {
+    // One array, whose len is RLE with a single run of 2^36 fives.
+    let mut bytes = vec![RootTypeId::ArrayN.into()];
+    encode_prefix_varint(1, &mut bytes);
+    bytes.push(ArrayTypeId::ArrayVar.into());
+    bytes.push(ArrayTypeId::RLE.into());
+    bytes.push(ArrayTypeId::U8.into());
+    bytes.push(5);
+    bytes.push(ArrayTypeId::IntPrefixVar.into());
+    let mut runs = Vec::new();
+    encode_prefix_varint(1 << 36, &mut runs);
+    bytes.extend_from_slice(&runs);
+    bytes.push(ArrayTypeId::Void.into());
+    encode_suffix_varint(runs.len() as u64, &mut bytes);
+    encode_suffix_varint(1, &mut bytes);
+    assert_eq!(bytes.len(), 16);
+
+    let report = size_report(&bytes).unwrap();
+    assert_eq!(report.total, bytes.len());
+    assert!(report.paths.iter().all(|path| path.count.is_none() || path.count == Some(1)));
+
+    let options = decode_options! { options::MaxCollectionLen(0) };
+    assert_eq!(size_report_with_options(&bytes, &options).map(|_| ()), Err(DecodeError::LimitExceeded));
+}
@@ -0,0 +1,148 @@
+mod common;
+
+use common::*;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tree_buf::experimental::json::to_json;
+use tree_buf::experimental::schema::{schema_of, IntegerKind, Schema};
+use tree_buf::experimental::semantic::{SemanticTag, TimeUnit};
+use tree_buf::experimental::stats::size_breakdown;
+use tree_buf::prelude::*;
+use tree_buf::DecodeError;
+
+fn at(millis: u64) -> SystemTime {
+    UNIX_EPOCH + Duration::from_millis(millis)
+}
+
+#[derive(Encode, Decode, Clone, Debug, PartialEq)]
+pub struct Sample {
+    at: SystemTime,
+    took: Duration,
+    finished: Option<SystemTime>,
+}
+
+#[test]
+fn durations() {
+    round_trip(&Duration::from_secs(0), None, None);
+    round_trip(&Duration::from_millis(1500), None, None);
+    round_trip(&Duration::new(3, 7), None, None);
+    round_trip(&Duration::from_secs(u64::MAX), None, None);
+    round_trip(&vec![Duration::from_secs(1), Duration::from_micros(5), Duration::from_nanos(u64::MAX)], None, None);
+}
+
+#[test]
+fn system_times() {
+    round_trip(&UNIX_EPOCH, None, None);
+    round_trip(&at(1_600_000_000_123), None, None);
+    round_trip(&(UNIX_EPOCH + Duration::new(1_600_000_000, 1)), None, None);
+    // Before 1970
+    round_trip(&(UNIX_EPOCH - Duration::from_secs(86_400)), None, None);
+    round_trip(&vec![at(1_600_000_000_000), UNIX_EPOCH - Duration::from_millis(1)], None, None);
+
+    let sample = Sample {
+        at: at(1_600_000_000_000),
+        took: Duration::from_millis(250),
+        finished: Some(at(1_600_000_000_250)),
+    };
+    round_trip(&sample, None, None);
+    round_trip(&Sample { finished: None, ..sample }, None, None);
+}
+
+#[test]
+fn signed_and_large_values_are_exact() {
+    let before = UNIX_EPOCH - Duration::new(1_000_000_000, 123_456_789);
+    let after = UNIX_EPOCH + Duration::new(1_700_000_000, 123_456_789);
+    round_trip(&before, None, None);
+    round_trip(&vec![before, after], None, None);
+    round_trip(&vec![after, UNIX_EPOCH, before, after], None, None);
+    round_trip(&(UNIX_EPOCH - Duration::new(1 << 40, 1)), None, None);
+    // Beyond a u64 of nanoseconds
+    round_trip(&Duration::new(u64::MAX, 1), None, None);
+    round_trip(&vec![Duration::new(u64::MAX, 999_999_999), Duration::from_nanos(1)], None, None);
+
+    let nanos = |schema| Schema::Tagged(SemanticTag::Timestamp(TimeUnit::Nanos), Box::new(schema));
+    assert_eq!(schema_of(&encode(&before)), Ok(nanos(Schema::Integer(IntegerKind::Signed))));
+    // Arrays have no signed integers, so each value is its sign and magnitude
+    let parts = Schema::Tuple(vec![Schema::Boolean, Schema::Integer(IntegerKind::Unsigned), Schema::Integer(IntegerKind::Unsigned)]);
+    assert_eq!(schema_of(&encode(&vec![before, after])), Ok(Schema::Array(Box::new(nanos(parts)))));
+    assert_eq!(
+        to_json(&encode(&vec![before, after])).unwrap(),
+        r#"["1938-04-24T22:13:19.876543211Z","2023-11-14T22:13:20.123456789Z"]"#
+    );
+
+    assert_eq!(TimeUnit::Millis.to_parts(-1_500), (true, 1, 500));
+    assert_eq!(TimeUnit::Millis.from_parts(true, 1, 500), Some(-1_500));
+    assert_eq!(TimeUnit::Millis.from_parts(false, 1, 1_000), None);
+}
+
+#[test]
+fn written_as_integers_with_a_tag() {
+    // The unit is the largest which holds the value exactly
+    assert_eq!(
+        schema_of(&encode(&at(1_600_000_000_123))),
+        Ok(Schema::Tagged(SemanticTag::Timestamp(TimeUnit::Millis), Box::new(Schema::Integer(IntegerKind::Unsigned))))
+    );
+    assert_eq!(
+        schema_of(&encode(&vec![Duration::from_secs(1), Duration::from_secs(2)])),
+        Ok(Schema::Array(Box::new(Schema::Tagged(
+            SemanticTag::Duration(TimeUnit::Seconds),
+            Box::new(Schema::Integer(IntegerKind::Unsigned))
+        ))))
+    );
+
+    serialize_eq(&at(1_600_000_000_123), &1_600_000_000_123u64, None);
+    serialize_eq(&vec![Duration::from_micros(1), Duration::from_micros(2)], &vec![1u64, 2], None);
+    assert_eq!(to_json(&encode(&at(1_600_000_000_123))).unwrap(), r#""2020-09-13T12:26:40.123Z""#);
+    assert_eq!(to_json(&encode(&(UNIX_EPOCH - Duration::from_secs(86_400)))).unwrap(), r#""1969-12-31T00:00:00Z""#);
+}
+
+#[test]
+fn delta_encoded() {
+    let times: Vec<_> = (0..1000u64).map(|i| at(1_600_000_000_000 + i * 1000 + i % 7)).collect();
+    let bytes = encode(&times);
+    let breakdown = size_breakdown(&bytes).unwrap();
+    assert!(breakdown.contains("DeltaZig"), "{}", breakdown);
+    assert!(bytes.len() < 2100, "{}", bytes.len());
+}
+
+#[test]
+fn tags_are_checked() {
+    assert_eq!(decode::<Duration>(&encode(&at(5))), Err(DecodeError::SchemaMismatch));
+    assert_eq!(decode::<Vec<SystemTime>>(&encode(&vec![Duration::from_secs(1)])), Err(DecodeError::SchemaMismatch));
+    // Without a tag, the unit isn't known
+    assert_eq!(decode::<SystemTime>(&encode(&5u64)), Err(DecodeError::SchemaMismatch));
+}
+
+#[cfg(feature = "chrono")]
+#[test]
+fn chrono() {
+    use chrono::{DateTime, TimeDelta, Utc};
+
+    let time = DateTime::<Utc>::from_timestamp(1_600_000_000, 123_000_000).unwrap();
+    round_trip(&time, None, None);
+    round_trip(&DateTime::<Utc>::from_timestamp(-86_400, 5).unwrap(), None, None);
+    assert_eq!(encode(&time), encode(&at(1_600_000_000_123)));
+    serialize_eq(&time, &at(1_600_000_000_123), None);
+    serialize_eq(&time.naive_utc(), &time, None);
+    round_trip(&DateTime::UNIX_EPOCH.naive_utc(), None, None);
+
+    round_trip(&TimeDelta::milliseconds(-1500), None, None);
+    round_trip(&vec![TimeDelta::seconds(5), TimeDelta::nanoseconds(-3)], None, None);
+    serialize_eq(&Duration::from_millis(1500), &TimeDelta::milliseconds(1500), None);
+}
+
+#[cfg(feature = "time")]
+#[test]
+fn time_crate() {
+    use time::{OffsetDateTime, PrimitiveDateTime, UtcOffset};
+
+    let time = OffsetDateTime::from_unix_timestamp_nanos(1_600_000_000_123_000_000).unwrap();
+    round_trip(&time, None, None);
+    assert_eq!(encode(&time), encode(&at(1_600_000_000_123)));
+    // The offset isn't written, only the instant
+    serialize_eq(&time.to_offset(UtcOffset::from_hms(2, 0, 0).unwrap()), &time, None);
+    serialize_eq(&PrimitiveDateTime::new(time.date(), time.time()), &time, None);
+
+    round_trip(&time::Duration::milliseconds(-1500), None, None);
+    round_trip(&vec![time::Duration::seconds(5), time::Duration::nanoseconds(-3)], None, None);
+    serialize_eq(&Duration::from_millis(1500), &time::Duration::milliseconds(1500), None);
+}